@@ -2,6 +2,9 @@ use std::path::PathBuf;
 
 fn main() {
     println!("cargo:rerun-if-env-changed=PKG_CONFIG_PATH");
+    if std::env::var_os("CARGO_FEATURE_APE").is_some() {
+        link_monkeys_audio();
+    }
 
     let wavpack = pkg_config::Config::new()
         .atleast_version("5.9.0")
@@ -45,3 +48,16 @@ and ensure wavpack.pc is visible in PKG_CONFIG_PATH."
         .write_to_file(out_dir.join("wavpack_bindings.rs"))
         .expect("failed to write WavPack bindings");
 }
+
+fn link_monkeys_audio() {
+    // The Monkey's Audio SDK installs no pkg-config file, so the library is
+    // linked by name; MAC_LIB_DIR points at a non-standard install.
+    println!("cargo:rerun-if-env-changed=MAC_LIB_DIR");
+    if let Some(dir) = std::env::var_os("MAC_LIB_DIR") {
+        println!(
+            "cargo:rustc-link-search=native={}",
+            PathBuf::from(dir).display()
+        );
+    }
+    println!("cargo:rustc-link-lib=MAC");
+}
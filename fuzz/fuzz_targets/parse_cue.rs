@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The first byte picks the encoding, so autodetection and both explicit
+    // decoders see the same inputs.
+    let Some((selector, contents)) = data.split_first() else {
+        return;
+    };
+    let encoding = match selector % 3 {
+        0 => None,
+        1 => Some("utf-8"),
+        _ => Some("windows-1251"),
+    };
+    if let Ok((disc, warnings)) = flac_cue_split::parse_cue(contents, encoding) {
+        let _ = format!("{:?} {:?}", disc, warnings);
+    }
+});
@@ -0,0 +1,275 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::Result;
+use crate::decoder::{AudioBlock, ByteOrder, Decoder, DecoderMetadata, PcmBlockIter, PcmFormat};
+use crate::id3::merge_id3_tags;
+use crate::iff::{MAX_TAG_CHUNK, decode_text, read_body, read_id3_chunk, walk_chunks};
+use crate::metadata::find_tag;
+use crate::types::InputMetadata;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct AiffLayout {
+    format: PcmFormat,
+    sample_rate: u32,
+    total_samples: u64,
+    data_offset: u64,
+    data_len: u64,
+    text_tags: Vec<(String, String)>,
+    id3_tags: Vec<(String, String)>,
+}
+
+pub(crate) struct AiffDecoder {
+    path: PathBuf,
+}
+
+impl AiffDecoder {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn open(&self) -> Result<(BufReader<File>, AiffLayout)> {
+        let file = File::open(&self.path)
+            .map_err(|err| format!("failed to open {}: {}", self.path.display(), err))?;
+        let file_len = file
+            .metadata()
+            .map_err(|err| format!("failed to read {}: {}", self.path.display(), err))?
+            .len();
+        let mut reader = BufReader::new(file);
+        let layout = read_layout(&mut reader, file_len)
+            .map_err(|err| format!("{}: {}", self.path.display(), err))?;
+        Ok((reader, layout))
+    }
+}
+
+impl Decoder for AiffDecoder {
+    fn read_metadata(&mut self) -> Result<DecoderMetadata> {
+        let (_, layout) = self.open()?;
+        let mut input_meta = InputMetadata::new();
+        input_meta.sample_rate = layout.sample_rate;
+        input_meta.channels = layout.format.channels;
+        input_meta.bits_per_sample = layout.format.bits_per_sample;
+        input_meta.total_samples = layout.total_samples;
+        // The NAME/AUTH/ANNO text chunks only fill what the ID3 chunk lacks.
+        input_meta.comments = layout.id3_tags.clone();
+        merge_id3_tags(&mut input_meta.comments, &layout.text_tags);
+        input_meta.embedded_cue = find_tag(&input_meta.comments, "CUESHEET").map(str::to_string);
+
+        Ok(DecoderMetadata {
+            input_meta,
+            picture_names: Vec::new(),
+        })
+    }
+
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        let (mut reader, layout) = self.open()?;
+        reader
+            .seek(SeekFrom::Start(layout.data_offset))
+            .map_err(|err| format!("failed to read {}: {}", self.path.display(), err))?;
+        Ok(Box::new(PcmBlockIter::new(
+            reader,
+            layout.format,
+            layout.total_samples,
+            block_size,
+        )))
+    }
+}
+
+fn read_layout<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<AiffLayout> {
+    let io_error = |err: std::io::Error| err.to_string();
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header).map_err(io_error)?;
+    let aifc = match (&header[..4], &header[8..]) {
+        (b"FORM", b"AIFF") => false,
+        (b"FORM", b"AIFC") => true,
+        _ => return Err("not an AIFF file".to_string()),
+    };
+
+    let mut layout = AiffLayout::default();
+    let mut found_comm = false;
+    let mut found_ssnd = false;
+    walk_chunks(reader, file_len, ByteOrder::Big, |reader, chunk| {
+        let size = u64::from(chunk.size);
+        match &chunk.id {
+            b"COMM" => {
+                parse_comm(&read_body(reader, size)?, aifc, &mut layout)?;
+                found_comm = true;
+            }
+            b"SSND" => {
+                let mut offsets = [0u8; 8];
+                reader.read_exact(&mut offsets).map_err(io_error)?;
+                let offset = u64::from(u32::from_be_bytes([
+                    offsets[0], offsets[1], offsets[2], offsets[3],
+                ]));
+                layout.data_offset = chunk.start + 8 + offset;
+                // A sound chunk cut short by a failed copy still holds audio.
+                layout.data_len = size
+                    .saturating_sub(8 + offset)
+                    .min(file_len.saturating_sub(layout.data_offset));
+                found_ssnd = true;
+            }
+            b"ID3 " | b"id3 " if size <= MAX_TAG_CHUNK => {
+                layout.id3_tags.extend(read_id3_chunk(reader, size)?);
+            }
+            b"NAME" | b"AUTH" | b"ANNO" | b"(c) " if size <= MAX_TAG_CHUNK => {
+                if let Some(value) = decode_text(&read_body(reader, size)?) {
+                    layout
+                        .text_tags
+                        .push((text_key(&chunk.id).to_string(), value));
+                }
+            }
+            _ => {}
+        }
+        Ok(size)
+    })?;
+
+    if !found_comm {
+        return Err("AIFF file has no COMM chunk".to_string());
+    }
+    if !found_ssnd {
+        return Err("AIFF file has no SSND chunk".to_string());
+    }
+    let available = layout.data_len / layout.format.block_align() as u64;
+    layout.total_samples = layout.total_samples.min(available);
+    Ok(layout)
+}
+
+fn parse_comm(body: &[u8], aifc: bool, layout: &mut AiffLayout) -> Result<()> {
+    if body.len() < 18 || (aifc && body.len() < 22) {
+        return Err("AIFF COMM chunk is too short".to_string());
+    }
+    let channels = u32::from(u16::from_be_bytes([body[0], body[1]]));
+    let bits_per_sample = u32::from(u16::from_be_bytes([body[6], body[7]]));
+    layout.total_samples = u64::from(u32::from_be_bytes([body[2], body[3], body[4], body[5]]));
+    layout.sample_rate = extended_to_u32(&body[8..18]);
+
+    let mut byte_order = ByteOrder::Big;
+    if aifc {
+        match &body[18..22] {
+            b"sowt" if bits_per_sample > 8 => byte_order = ByteOrder::Little,
+            // 8-bit `sowt` samples are single bytes either way.
+            b"NONE" | b"twos" | b"in24" | b"in32" | b"sowt" => {}
+            b"fl32" | b"FL32" | b"fl64" | b"FL64" => {
+                return Err("floating-point AIFC files are not supported".to_string());
+            }
+            compression => {
+                return Err(format!(
+                    "compressed AIFC files ({}) are not supported",
+                    String::from_utf8_lossy(compression)
+                ));
+            }
+        }
+    }
+    if channels == 0 {
+        return Err("AIFF channel count is zero".to_string());
+    }
+    if layout.sample_rate == 0 {
+        return Err("AIFF sample rate is zero".to_string());
+    }
+    if !(1..=32).contains(&bits_per_sample) {
+        return Err(format!(
+            "unsupported AIFF sample size of {} bits",
+            bits_per_sample
+        ));
+    }
+    layout.format = PcmFormat {
+        channels,
+        bits_per_sample,
+        bytes_per_sample: bits_per_sample.div_ceil(8) as usize,
+        byte_order,
+    };
+    Ok(())
+}
+
+// The sample rate is an 80-bit IEEE extended float; only whole rates are kept.
+fn extended_to_u32(bytes: &[u8]) -> u32 {
+    let exponent = i32::from(u16::from_be_bytes([bytes[0], bytes[1]]) & 0x7FFF) - 16383;
+    let mantissa = bytes[2..10]
+        .iter()
+        .fold(0u64, |acc, &byte| (acc << 8) | u64::from(byte));
+    if bytes[0] & 0x80 != 0 || !(0..=31).contains(&exponent) {
+        return 0;
+    }
+    (mantissa >> (63 - exponent)) as u32
+}
+
+fn text_key(id: &[u8]) -> &'static str {
+    match id {
+        b"NAME" => "TITLE",
+        b"AUTH" => "ARTIST",
+        b"(c) " => "COPYRIGHT",
+        _ => "COMMENT",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_layout;
+    use crate::decoder::{ByteOrder, PcmBlockIter};
+    use crate::iff::chunk;
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    #[test]
+    fn aiff_files_are_read_with_their_id3_tags() {
+        let samples: [i32; 4] = [1, -1, i16::MAX as i32, i16::MIN as i32];
+        let mut ssnd = vec![0u8; 8];
+        ssnd.extend(
+            samples
+                .iter()
+                .flat_map(|&sample| (sample as i16).to_be_bytes()),
+        );
+
+        let mut comm = Vec::new();
+        comm.extend_from_slice(&2u16.to_be_bytes());
+        comm.extend_from_slice(&2u32.to_be_bytes());
+        comm.extend_from_slice(&16u16.to_be_bytes());
+        // 44100 as an 80-bit extended float.
+        comm.extend_from_slice(&[0x40, 0x0E, 0xAC, 0x44, 0, 0, 0, 0, 0, 0]);
+
+        let mut frame = b"TIT2".to_vec();
+        frame.extend_from_slice(&6u32.to_be_bytes());
+        frame.extend_from_slice(&[0, 0, 3]);
+        frame.extend_from_slice(b"Album");
+        let mut id3 = b"ID3\x03\x00\x00\x00\x00\x00".to_vec();
+        id3.push(frame.len() as u8);
+        id3.extend(frame);
+
+        let mut file = b"FORM\0\0\0\0AIFF".to_vec();
+        file.extend(chunk(b"COMM", &comm, ByteOrder::Big));
+        file.extend(chunk(b"NAME", b"Name", ByteOrder::Big));
+        file.extend(chunk(b"AUTH", b"Artist", ByteOrder::Big));
+        file.extend(chunk(b"SSND", &ssnd, ByteOrder::Big));
+        file.extend(chunk(b"ID3 ", &id3, ByteOrder::Big));
+
+        let mut reader = Cursor::new(file);
+        let len = reader.get_ref().len() as u64;
+        let layout = read_layout(&mut reader, len).unwrap();
+        assert_eq!(
+            (
+                layout.format.channels,
+                layout.sample_rate,
+                layout.format.bits_per_sample
+            ),
+            (2, 44_100, 16)
+        );
+        assert_eq!(layout.total_samples, 2);
+        assert_eq!(
+            layout.id3_tags,
+            vec![("TITLE".to_string(), "Album".to_string())]
+        );
+        assert_eq!(
+            layout.text_tags[1],
+            ("ARTIST".to_string(), "Artist".to_string())
+        );
+
+        reader.seek(SeekFrom::Start(layout.data_offset)).unwrap();
+        let decoded: Vec<i32> = PcmBlockIter::new(reader, layout.format, layout.total_samples, 1)
+            .flat_map(|block| block.unwrap().interleaved)
+            .collect();
+        assert_eq!(decoded, samples);
+    }
+}
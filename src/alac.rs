@@ -0,0 +1,375 @@
+use std::fs::File;
+use std::path::PathBuf;
+
+use symphonia::core::audio::{AudioBufferRef, Signal};
+use symphonia::core::codecs::{CODEC_TYPE_ALAC, CodecParameters, DecoderOptions};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, FormatReader};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::{MetadataRevision, StandardTagKey, Value};
+use symphonia::default::codecs::AlacDecoder as AlacCodec;
+use symphonia::default::formats::IsoMp4Reader;
+
+use crate::Result;
+use crate::decoder::{AudioBlock, Decoder, DecoderMetadata};
+use crate::metadata::find_tag;
+use crate::picture::build_picture_metadata_from_data;
+use crate::types::InputMetadata;
+
+pub(crate) struct AlacDecoder {
+    path: PathBuf,
+}
+
+struct AlacTrack {
+    reader: IsoMp4Reader,
+    track_id: u32,
+    params: CodecParameters,
+    bits_per_sample: u32,
+}
+
+impl AlacDecoder {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn open(&self) -> Result<AlacTrack> {
+        let error = |err: &dyn std::fmt::Display| {
+            format!("failed to read {}: {}", self.path.display(), err)
+        };
+        let file = File::open(&self.path).map_err(|err| error(&err))?;
+        let stream = MediaSourceStream::new(Box::new(file), Default::default());
+        let reader =
+            IsoMp4Reader::try_new(stream, &FormatOptions::default()).map_err(|err| error(&err))?;
+        let Some(track) = reader
+            .tracks()
+            .iter()
+            .find(|track| track.codec_params.codec == CODEC_TYPE_ALAC)
+        else {
+            return Err(format!(
+                "{} has no Apple Lossless track (AAC files are lossy and cannot be split)",
+                self.path.display()
+            ));
+        };
+        let params = track.codec_params.clone();
+        let bits_per_sample = params
+            .extra_data
+            .as_deref()
+            .and_then(alac_bit_depth)
+            .ok_or_else(|| format!("{}: invalid ALAC configuration", self.path.display()))?;
+        Ok(AlacTrack {
+            track_id: track.id,
+            reader,
+            params,
+            bits_per_sample,
+        })
+    }
+}
+
+impl Decoder for AlacDecoder {
+    fn read_metadata(&mut self) -> Result<DecoderMetadata> {
+        let mut track = self.open()?;
+        let mut input_meta = InputMetadata::new();
+        input_meta.sample_rate = track.params.sample_rate.unwrap_or(0);
+        input_meta.channels = track
+            .params
+            .channels
+            .map_or(0, |channels| channels.count() as u32);
+        input_meta.bits_per_sample = track.bits_per_sample;
+        // The track duration is counted in the media time scale, which is
+        // normally the sample rate.
+        input_meta.total_samples = match (track.params.n_frames, track.params.time_base) {
+            (Some(frames), Some(base))
+                if base.denom != input_meta.sample_rate && base.denom > 0 =>
+            {
+                frames * u64::from(input_meta.sample_rate) / u64::from(base.denom)
+            }
+            (Some(frames), _) => frames,
+            (None, _) => 0,
+        };
+        if input_meta.sample_rate == 0 {
+            return Err("ALAC sample rate is zero".to_string());
+        }
+        if input_meta.channels == 0 {
+            return Err("ALAC channel count is zero".to_string());
+        }
+
+        if let Some(revision) = track.reader.metadata().skip_to_latest() {
+            read_mp4_tags(revision, &mut input_meta)?;
+        }
+        input_meta.embedded_cue = find_tag(&input_meta.comments, "CUESHEET").map(str::to_string);
+
+        Ok(DecoderMetadata {
+            input_meta,
+            picture_names: Vec::new(),
+        })
+    }
+
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        let track = self.open()?;
+        let codec = <AlacCodec as symphonia::core::codecs::Decoder>::try_new(
+            &track.params,
+            &DecoderOptions::default(),
+        )
+        .map_err(|err| format!("failed to start the ALAC decoder: {}", err))?;
+        let channels = track.params.channels.map_or(0, |channels| channels.count());
+        if channels == 0 {
+            return Err("ALAC channel count is zero".to_string());
+        }
+        Ok(Box::new(AlacBlockIter {
+            reader: track.reader,
+            codec,
+            track_id: track.track_id,
+            channels,
+            shift: 32 - track.bits_per_sample,
+            block_len: block_size.max(1) * channels,
+            pending: Vec::new(),
+            sample_index: 0,
+            done: false,
+        }))
+    }
+}
+
+struct AlacBlockIter {
+    reader: IsoMp4Reader,
+    codec: AlacCodec,
+    track_id: u32,
+    channels: usize,
+    shift: u32,
+    block_len: usize,
+    pending: Vec<i32>,
+    sample_index: u64,
+    done: bool,
+}
+
+impl AlacBlockIter {
+    // Decodes the next packet of the track into `pending`; false at the end.
+    fn decode_packet(&mut self) -> Result<bool> {
+        use symphonia::core::codecs::Decoder as _;
+
+        let packet = loop {
+            match self.reader.next_packet() {
+                Ok(packet) if packet.track_id() == self.track_id => break packet,
+                Ok(_) => {}
+                Err(SymphoniaError::IoError(err))
+                    if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+                {
+                    return Ok(false);
+                }
+                Err(err) => return Err(format!("failed to read ALAC packet: {}", err)),
+            }
+        };
+        let decoded = self
+            .codec
+            .decode(&packet)
+            .map_err(|err| format!("ALAC decoding failed: {}", err))?;
+        let AudioBufferRef::S32(buffer) = decoded else {
+            return Err("ALAC decoder returned an unexpected sample format".to_string());
+        };
+        // The decoder scales every sample up to 32 bits.
+        let planes = buffer.planes();
+        let planes = planes.planes();
+        if planes.len() != self.channels {
+            return Err(format!(
+                "ALAC packet has {} channels, expected {}",
+                planes.len(),
+                self.channels
+            ));
+        }
+        self.pending.reserve(buffer.frames() * self.channels);
+        for frame in 0..buffer.frames() {
+            for plane in planes {
+                self.pending.push(plane[frame] >> self.shift);
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl Iterator for AlacBlockIter {
+    type Item = Result<AudioBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        while self.pending.len() < self.block_len {
+            match self.decode_packet() {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+        if self.pending.is_empty() {
+            self.done = true;
+            return None;
+        }
+
+        let take = self.block_len.min(self.pending.len());
+        let interleaved: Vec<i32> = self.pending.drain(..take).collect();
+        let block = AudioBlock {
+            sample_index: self.sample_index,
+            channels: self.channels as u32,
+            interleaved,
+        };
+        self.sample_index += block.sample_count() as u64;
+        Some(Ok(block))
+    }
+}
+
+// The ALAC magic cookie starts with the frame length (u32) and the
+// compatible version (u8), followed by the bit depth.
+fn alac_bit_depth(cookie: &[u8]) -> Option<u32> {
+    let depth = u32::from(*cookie.get(5)?);
+    (8..=32).contains(&depth).then_some(depth)
+}
+
+// iTunes-style `ilst` items become Vorbis comments; `covr` images become
+// pictures.
+fn read_mp4_tags(revision: &MetadataRevision, input_meta: &mut InputMetadata) -> Result<()> {
+    for tag in revision.tags() {
+        let key = match tag.std_key.and_then(vorbis_key) {
+            Some(key) => key.to_string(),
+            // Freeform `----` items are named "mean:name", e.g.
+            // "com.apple.iTunes:CUESHEET".
+            None => match tag.key.rsplit_once(':') {
+                Some((_, name)) if !name.is_empty() => name.to_ascii_uppercase(),
+                _ => continue,
+            },
+        };
+        let value = match &tag.value {
+            Value::Binary(_) => continue,
+            Value::Flag => "1".to_string(),
+            Value::Boolean(enabled) => u8::from(*enabled).to_string(),
+            value => value.to_string(),
+        };
+        // trkn and disk items report an unknown number or total as zero.
+        if value.is_empty() || (is_number_key(&key) && value == "0") {
+            continue;
+        }
+        input_meta.comments.push((key, value));
+    }
+    for visual in revision.visuals() {
+        if visual.data.is_empty() {
+            continue;
+        }
+        input_meta
+            .pictures
+            .push(build_picture_metadata_from_data(&visual.data, None)?);
+    }
+    Ok(())
+}
+
+fn is_number_key(key: &str) -> bool {
+    matches!(
+        key,
+        "TRACKNUMBER" | "TRACKTOTAL" | "DISCNUMBER" | "DISCTOTAL"
+    )
+}
+
+fn vorbis_key(key: StandardTagKey) -> Option<&'static str> {
+    Some(match key {
+        StandardTagKey::Album => "ALBUM",
+        StandardTagKey::AlbumArtist => "ALBUMARTIST",
+        StandardTagKey::Artist => "ARTIST",
+        StandardTagKey::Bpm => "BPM",
+        StandardTagKey::Comment => "COMMENT",
+        StandardTagKey::Compilation => "COMPILATION",
+        StandardTagKey::Composer => "COMPOSER",
+        StandardTagKey::Conductor => "CONDUCTOR",
+        StandardTagKey::Copyright => "COPYRIGHT",
+        StandardTagKey::Date => "DATE",
+        StandardTagKey::Description => "DESCRIPTION",
+        StandardTagKey::DiscNumber => "DISCNUMBER",
+        StandardTagKey::DiscSubtitle => "DISCSUBTITLE",
+        StandardTagKey::DiscTotal => "DISCTOTAL",
+        StandardTagKey::EncodedBy => "ENCODEDBY",
+        StandardTagKey::Encoder => "ENCODER",
+        StandardTagKey::Genre => "GENRE",
+        StandardTagKey::IdentBarcode => "BARCODE",
+        StandardTagKey::IdentCatalogNumber => "CATALOGNUMBER",
+        StandardTagKey::IdentIsrc => "ISRC",
+        StandardTagKey::Label => "LABEL",
+        StandardTagKey::Lyricist => "LYRICIST",
+        StandardTagKey::Lyrics => "LYRICS",
+        StandardTagKey::MusicBrainzAlbumArtistId => "MUSICBRAINZ_ALBUMARTISTID",
+        StandardTagKey::MusicBrainzAlbumId => "MUSICBRAINZ_ALBUMID",
+        StandardTagKey::MusicBrainzArtistId => "MUSICBRAINZ_ARTISTID",
+        StandardTagKey::MusicBrainzReleaseGroupId => "MUSICBRAINZ_RELEASEGROUPID",
+        StandardTagKey::MusicBrainzReleaseTrackId => "MUSICBRAINZ_RELEASETRACKID",
+        StandardTagKey::MusicBrainzTrackId => "MUSICBRAINZ_TRACKID",
+        StandardTagKey::OriginalDate => "ORIGINALDATE",
+        StandardTagKey::Performer => "PERFORMER",
+        StandardTagKey::ReleaseCountry => "RELEASECOUNTRY",
+        StandardTagKey::ReplayGainAlbumGain => "REPLAYGAIN_ALBUM_GAIN",
+        StandardTagKey::ReplayGainAlbumPeak => "REPLAYGAIN_ALBUM_PEAK",
+        StandardTagKey::ReplayGainTrackGain => "REPLAYGAIN_TRACK_GAIN",
+        StandardTagKey::ReplayGainTrackPeak => "REPLAYGAIN_TRACK_PEAK",
+        StandardTagKey::SortAlbum => "ALBUMSORT",
+        StandardTagKey::SortAlbumArtist => "ALBUMARTISTSORT",
+        StandardTagKey::SortArtist => "ARTISTSORT",
+        StandardTagKey::SortComposer => "COMPOSERSORT",
+        StandardTagKey::SortTrackTitle => "TITLESORT",
+        StandardTagKey::TrackNumber => "TRACKNUMBER",
+        StandardTagKey::TrackTitle => "TITLE",
+        StandardTagKey::TrackTotal => "TRACKTOTAL",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alac_bit_depth, read_mp4_tags};
+    use crate::metadata::find_tag;
+    use crate::types::InputMetadata;
+    use symphonia::core::meta::{MetadataBuilder, StandardTagKey, Tag, Value};
+
+    #[test]
+    fn mp4_items_become_vorbis_comments() {
+        let mut builder = MetadataBuilder::new();
+        builder.add_tag(Tag::new(
+            Some(StandardTagKey::TrackTitle),
+            "",
+            Value::from("Intro"),
+        ));
+        builder.add_tag(Tag::new(
+            Some(StandardTagKey::TrackNumber),
+            "",
+            Value::from(3u8),
+        ));
+        builder.add_tag(Tag::new(
+            Some(StandardTagKey::TrackTotal),
+            "",
+            Value::from(0u8),
+        ));
+        builder.add_tag(Tag::new(Some(StandardTagKey::Compilation), "", Value::Flag));
+        builder.add_tag(Tag::new(
+            None,
+            "com.apple.iTunes:cuesheet",
+            Value::from("FILE \"Album.m4a\" WAVE"),
+        ));
+        builder.add_tag(Tag::new(None, "", Value::from("unnamed")));
+        let mut meta = InputMetadata::new();
+        read_mp4_tags(&builder.metadata(), &mut meta).unwrap();
+
+        assert_eq!(find_tag(&meta.comments, "TITLE"), Some("Intro"));
+        assert_eq!(find_tag(&meta.comments, "TRACKNUMBER"), Some("3"));
+        assert_eq!(find_tag(&meta.comments, "TRACKTOTAL"), None);
+        assert_eq!(find_tag(&meta.comments, "COMPILATION"), Some("1"));
+        assert_eq!(
+            find_tag(&meta.comments, "CUESHEET"),
+            Some("FILE \"Album.m4a\" WAVE")
+        );
+        assert_eq!(meta.comments.len(), 4);
+
+        let mut cookie = [0u8; 24];
+        cookie[5] = 24;
+        assert_eq!(alac_bit_depth(&cookie), Some(24));
+        assert_eq!(alac_bit_depth(&cookie[..4]), None);
+    }
+}
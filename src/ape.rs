@@ -0,0 +1,207 @@
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::apetag::read_ape_tags;
+use crate::decoder::{AudioBlock, Decoder, DecoderMetadata, decode_pcm_le};
+use crate::flac::path_to_cstring;
+use crate::metadata::find_tag;
+use crate::types::InputMetadata;
+
+// The SDK headers are C++, so the plain C entry points that MACDll exports are
+// declared by hand. Field numbers are IAPEDecompress::APE_DECOMPRESS_FIELDS.
+mod mac_bindings {
+    use std::os::raw::{c_char, c_int, c_uchar, c_void};
+
+    pub(super) type ApeDecompressHandle = *mut c_void;
+
+    pub(super) const APE_INFO_SAMPLE_RATE: c_int = 1003;
+    pub(super) const APE_INFO_BITS_PER_SAMPLE: c_int = 1004;
+    pub(super) const APE_INFO_BYTES_PER_SAMPLE: c_int = 1005;
+    pub(super) const APE_INFO_CHANNELS: c_int = 1006;
+    pub(super) const APE_INFO_TOTAL_BLOCKS: c_int = 1016;
+
+    unsafe extern "C" {
+        pub(super) fn c_APEDecompress_Create(
+            filename: *const c_char,
+            error_code: *mut c_int,
+        ) -> ApeDecompressHandle;
+        pub(super) fn c_APEDecompress_Destroy(handle: ApeDecompressHandle);
+        pub(super) fn c_APEDecompress_GetData(
+            handle: ApeDecompressHandle,
+            buffer: *mut c_uchar,
+            blocks: i64,
+            blocks_retrieved: *mut i64,
+        ) -> c_int;
+        pub(super) fn c_APEDecompress_GetInfo(
+            handle: ApeDecompressHandle,
+            field: c_int,
+            param1: i64,
+            param2: i64,
+        ) -> i64;
+    }
+}
+
+pub(crate) struct ApeDecoder {
+    path: PathBuf,
+}
+
+impl ApeDecoder {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn read_metadata_internal(&self) -> Result<DecoderMetadata> {
+        let handle = ApeHandle::open(&self.path)?;
+        let mut input_meta = InputMetadata::new();
+        input_meta.sample_rate = handle.info(mac_bindings::APE_INFO_SAMPLE_RATE) as u32;
+        input_meta.channels = handle.info(mac_bindings::APE_INFO_CHANNELS) as u32;
+        input_meta.bits_per_sample = handle.info(mac_bindings::APE_INFO_BITS_PER_SAMPLE) as u32;
+        input_meta.total_samples =
+            u64::try_from(handle.info(mac_bindings::APE_INFO_TOTAL_BLOCKS)).unwrap_or(0);
+        if input_meta.sample_rate == 0 {
+            return Err("Monkey's Audio sample rate is zero".to_string());
+        }
+        if input_meta.channels == 0 {
+            return Err("Monkey's Audio channel count is zero".to_string());
+        }
+
+        let mut picture_names = Vec::new();
+        read_ape_tags(&self.path, &mut input_meta, &mut picture_names)?;
+        input_meta.embedded_cue = find_tag(&input_meta.comments, "CUESHEET").map(str::to_string);
+
+        Ok(DecoderMetadata {
+            input_meta,
+            picture_names,
+        })
+    }
+}
+
+impl Decoder for ApeDecoder {
+    fn read_metadata(&mut self) -> Result<DecoderMetadata> {
+        self.read_metadata_internal()
+    }
+
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        Ok(Box::new(ApeBlockIter::new(&self.path, block_size)?))
+    }
+}
+
+struct ApeHandle {
+    handle: mac_bindings::ApeDecompressHandle,
+}
+
+impl ApeHandle {
+    fn open(path: &Path) -> Result<Self> {
+        let path_c = path_to_cstring(path)?;
+        let mut error_code = 0;
+        let handle =
+            unsafe { mac_bindings::c_APEDecompress_Create(path_c.as_ptr(), &mut error_code) };
+        if handle.is_null() {
+            return Err(format!(
+                "failed to open Monkey's Audio file {} (error {})",
+                path.display(),
+                error_code
+            ));
+        }
+        Ok(Self { handle })
+    }
+
+    fn info(&self, field: std::os::raw::c_int) -> i64 {
+        unsafe { mac_bindings::c_APEDecompress_GetInfo(self.handle, field, 0, 0) }
+    }
+
+    fn read(&self, buffer: &mut [u8], blocks: usize) -> Result<usize> {
+        let mut retrieved = 0i64;
+        let code = unsafe {
+            mac_bindings::c_APEDecompress_GetData(
+                self.handle,
+                buffer.as_mut_ptr(),
+                blocks as i64,
+                &mut retrieved,
+            )
+        };
+        if code != 0 {
+            return Err(format!("Monkey's Audio decoding failed (error {})", code));
+        }
+        Ok(usize::try_from(retrieved).unwrap_or(0))
+    }
+}
+
+impl Drop for ApeHandle {
+    fn drop(&mut self) {
+        unsafe {
+            mac_bindings::c_APEDecompress_Destroy(self.handle);
+        }
+    }
+}
+
+struct ApeBlockIter {
+    handle: ApeHandle,
+    channels: usize,
+    bytes_per_sample: usize,
+    block_size: usize,
+    buffer: Vec<u8>,
+    position: u64,
+    done: bool,
+}
+
+impl ApeBlockIter {
+    fn new(path: &Path, block_size: usize) -> Result<Self> {
+        let handle = ApeHandle::open(path)?;
+        let channels = handle.info(mac_bindings::APE_INFO_CHANNELS) as usize;
+        let bytes_per_sample = handle.info(mac_bindings::APE_INFO_BYTES_PER_SAMPLE) as usize;
+        if channels == 0 {
+            return Err("Monkey's Audio channel count is zero".to_string());
+        }
+        if !(1..=4).contains(&bytes_per_sample) {
+            return Err(format!(
+                "unsupported Monkey's Audio sample size of {} bytes",
+                bytes_per_sample
+            ));
+        }
+        let block_size = block_size.max(1);
+        Ok(Self {
+            handle,
+            channels,
+            bytes_per_sample,
+            block_size,
+            buffer: vec![0u8; block_size * channels * bytes_per_sample],
+            position: 0,
+            done: false,
+        })
+    }
+}
+
+impl Iterator for ApeBlockIter {
+    type Item = Result<AudioBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let blocks = match self.handle.read(&mut self.buffer, self.block_size) {
+            Ok(blocks) => blocks.min(self.block_size),
+            Err(err) => {
+                self.done = true;
+                return Some(Err(err));
+            }
+        };
+        if blocks == 0 {
+            self.done = true;
+            return None;
+        }
+
+        let used = blocks * self.channels * self.bytes_per_sample;
+        let sample_index = self.position;
+        self.position += blocks as u64;
+        Some(Ok(AudioBlock {
+            sample_index,
+            channels: self.channels as u32,
+            interleaved: decode_pcm_le(&self.buffer[..used], self.bytes_per_sample),
+        }))
+    }
+}
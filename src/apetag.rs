@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::Result;
+use crate::picture::build_picture_metadata_from_data;
+use crate::types::InputMetadata;
+use crate::wavpack::{decode_lossy_bytes, split_picture_blob};
+
+const APE_TAG_FOOTER_LEN: usize = 32;
+const APE_ITEM_TYPE_MASK: u32 = 0b110;
+const APE_ITEM_BINARY: u32 = 0b010;
+
+// APEv2 tags, as written at the end of Monkey's Audio and TTA images: text
+// items become comments and "Cover Art" items pictures.
+pub(crate) fn read_ape_tags(
+    path: &Path,
+    input_meta: &mut InputMetadata,
+    picture_names: &mut Vec<String>,
+) -> Result<()> {
+    for item in read_ape_tag(path)? {
+        match item.value {
+            ApeTagValue::Text(values) => {
+                for value in values {
+                    input_meta
+                        .comments
+                        .push((item.key.to_ascii_uppercase(), value));
+                }
+            }
+            ApeTagValue::Binary(data) => {
+                if !item.key.to_ascii_lowercase().starts_with("cover art") {
+                    continue;
+                }
+                let (name, data) = split_picture_blob(&data);
+                if data.is_empty() {
+                    continue;
+                }
+                input_meta
+                    .pictures
+                    .push(build_picture_metadata_from_data(data, name.as_deref())?);
+                picture_names.extend(name);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ApeTagValue {
+    Text(Vec<String>),
+    Binary(Vec<u8>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ApeTagItem {
+    key: String,
+    value: ApeTagValue,
+}
+
+fn read_ape_tag(path: &Path) -> Result<Vec<ApeTagItem>> {
+    let error = |err: std::io::Error| format!("failed to read tags of {}: {}", path.display(), err);
+    let mut file = File::open(path).map_err(error)?;
+    let len = file.seek(SeekFrom::End(0)).map_err(error)?;
+    // The APEv2 tag sits at the end of the file, in front of an ID3v1 tag if
+    // there is one.
+    let mut end = len;
+    if len >= 128 {
+        let mut magic = [0u8; 3];
+        file.seek(SeekFrom::Start(len - 128)).map_err(error)?;
+        file.read_exact(&mut magic).map_err(error)?;
+        if &magic == b"TAG" {
+            end = len - 128;
+        }
+    }
+    if end < APE_TAG_FOOTER_LEN as u64 {
+        return Ok(Vec::new());
+    }
+    let mut footer = [0u8; APE_TAG_FOOTER_LEN];
+    file.seek(SeekFrom::Start(end - APE_TAG_FOOTER_LEN as u64))
+        .map_err(error)?;
+    file.read_exact(&mut footer).map_err(error)?;
+    let Some((size, count)) = parse_ape_footer(&footer) else {
+        return Ok(Vec::new());
+    };
+    if size < APE_TAG_FOOTER_LEN || size as u64 > end {
+        return Ok(Vec::new());
+    }
+    let mut items = vec![0u8; size - APE_TAG_FOOTER_LEN];
+    file.seek(SeekFrom::Start(end - size as u64))
+        .map_err(error)?;
+    file.read_exact(&mut items).map_err(error)?;
+    Ok(parse_ape_items(&items, count))
+}
+
+fn parse_ape_footer(footer: &[u8]) -> Option<(usize, usize)> {
+    if footer.len() < APE_TAG_FOOTER_LEN || &footer[..8] != b"APETAGEX" {
+        return None;
+    }
+    let size = read_u32_le(footer, 12)? as usize;
+    let count = read_u32_le(footer, 16)? as usize;
+    Some((size, count))
+}
+
+fn parse_ape_items(bytes: &[u8], count: usize) -> Vec<ApeTagItem> {
+    let mut items = Vec::new();
+    let mut offset = 0;
+    for _ in 0..count {
+        let (Some(size), Some(flags)) =
+            (read_u32_le(bytes, offset), read_u32_le(bytes, offset + 4))
+        else {
+            break;
+        };
+        let key_start = offset + 8;
+        let Some(key_len) = bytes
+            .get(key_start..)
+            .and_then(|rest| rest.iter().position(|byte| *byte == 0))
+        else {
+            break;
+        };
+        let value_start = key_start + key_len + 1;
+        let Some(value) = bytes.get(value_start..value_start + size as usize) else {
+            break;
+        };
+        offset = value_start + size as usize;
+        let Some(key) = decode_lossy_bytes(&bytes[key_start..key_start + key_len]) else {
+            continue;
+        };
+        let value = if flags & APE_ITEM_TYPE_MASK == APE_ITEM_BINARY {
+            ApeTagValue::Binary(value.to_vec())
+        } else {
+            // Text items hold one or more UTF-8 values separated by NUL.
+            ApeTagValue::Text(
+                value
+                    .split(|byte| *byte == 0)
+                    .filter_map(decode_lossy_bytes)
+                    .collect(),
+            )
+        };
+        items.push(ApeTagItem { key, value });
+    }
+    items
+}
+
+fn read_u32_le(bytes: &[u8], offset: usize) -> Option<u32> {
+    let slice = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([slice[0], slice[1], slice[2], slice[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApeTagItem, ApeTagValue, parse_ape_footer, parse_ape_items};
+
+    fn item(key: &str, flags: u32, value: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend((value.len() as u32).to_le_bytes());
+        bytes.extend(flags.to_le_bytes());
+        bytes.extend(key.as_bytes());
+        bytes.push(0);
+        bytes.extend(value);
+        bytes
+    }
+
+    #[test]
+    fn ape_tag_items_are_read() {
+        let mut footer = b"APETAGEX".to_vec();
+        footer.extend(2000u32.to_le_bytes());
+        footer.extend(120u32.to_le_bytes());
+        footer.extend(3u32.to_le_bytes());
+        footer.extend([0u8; 12]);
+        assert_eq!(parse_ape_footer(&footer), Some((120, 3)));
+        assert_eq!(parse_ape_footer(&[0u8; 32]), None);
+
+        let mut items = item("Title", 0, b"Song");
+        items.extend(item("Artist", 0, b"One\0Two"));
+        items.extend(item("Cover Art (Front)", 2, b"cover.png\0PNG"));
+        assert_eq!(
+            parse_ape_items(&items, 3),
+            vec![
+                ApeTagItem {
+                    key: "Title".to_string(),
+                    value: ApeTagValue::Text(vec!["Song".to_string()]),
+                },
+                ApeTagItem {
+                    key: "Artist".to_string(),
+                    value: ApeTagValue::Text(vec!["One".to_string(), "Two".to_string()]),
+                },
+                ApeTagItem {
+                    key: "Cover Art (Front)".to_string(),
+                    value: ApeTagValue::Binary(b"cover.png\0PNG".to_vec()),
+                },
+            ]
+        );
+        // A truncated item ends the list instead of reading past the tag.
+        assert_eq!(parse_ape_items(&items[..items.len() - 1], 3).len(), 2);
+    }
+}
@@ -0,0 +1,274 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::apetag::read_ape_tags;
+use crate::decoder::{AudioBlock, Decoder, DecoderMetadata, create_decoder};
+use crate::types::InputMetadata;
+
+pub(crate) const APL_EXTENSION: &str = "apl";
+const APL_HEADER: &str = "[Monkey's Audio Image Link File]";
+// The header is followed by an APEv2 tag, which is binary.
+const APL_TAG_MARKER: &str = "----- APE TAG";
+
+// An .apl link file stands for the blocks (samples per channel) `start` to
+// `finish` of a Monkey's Audio image; `finish` is exclusive.
+#[derive(Debug, PartialEq, Eq)]
+struct AplLink {
+    image: PathBuf,
+    start: u64,
+    finish: u64,
+}
+
+// Decodes the span of the image that an .apl link names, as if it were an
+// image of its own.
+pub(crate) struct AplDecoder {
+    path: PathBuf,
+    image: Box<dyn Decoder>,
+    start: u64,
+    finish: u64,
+}
+
+impl AplDecoder {
+    pub(crate) fn open(path: PathBuf) -> Result<Self> {
+        let data =
+            fs::read(&path).map_err(|err| format!("failed to read {}: {}", path.display(), err))?;
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let link = parse_apl_link(&String::from_utf8_lossy(&data), dir)
+            .map_err(|err| format!("invalid .apl link file {}: {}", path.display(), err))?;
+        Ok(Self {
+            image: create_decoder(&link.image)?,
+            path,
+            start: link.start,
+            finish: link.finish,
+        })
+    }
+}
+
+impl Decoder for AplDecoder {
+    fn read_metadata(&mut self) -> Result<DecoderMetadata> {
+        let mut decoded = self.image.read_metadata()?;
+        clamp_metadata(&mut decoded.input_meta, self.start, self.finish)?;
+
+        // Tags written into the link describe the span better than the image's.
+        let mut link_meta = InputMetadata::new();
+        let mut picture_names = Vec::new();
+        read_ape_tags(&self.path, &mut link_meta, &mut picture_names)?;
+        if !link_meta.comments.is_empty() {
+            decoded.input_meta.comments = link_meta.comments;
+        }
+        if !link_meta.pictures.is_empty() {
+            decoded.input_meta.pictures = link_meta.pictures;
+            decoded.picture_names = picture_names;
+        }
+        Ok(decoded)
+    }
+
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        let blocks = self.image.into_blocks(block_size)?;
+        Ok(clamp_blocks(blocks, self.start, self.finish))
+    }
+}
+
+fn parse_apl_link(text: &str, dir: &Path) -> Result<AplLink> {
+    let header = text.split(APL_TAG_MARKER).next().unwrap_or_default();
+    let mut lines = header.lines().map(str::trim);
+    if !lines.any(|line| line == APL_HEADER) {
+        return Err(format!("missing {} header", APL_HEADER));
+    }
+
+    let mut image = None;
+    let mut start = None;
+    let mut finish = None;
+    for line in lines {
+        // The link section ends where the next one starts.
+        if line.starts_with('[') {
+            break;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Image File" => image = Some(value),
+            "Start Block" => start = Some(parse_block(key, value)?),
+            "Finish Block" => finish = Some(parse_block(key, value)?),
+            _ => {}
+        }
+    }
+
+    let image = image
+        .filter(|image| !image.is_empty())
+        .ok_or("missing Image File")?;
+    let start = start.ok_or("missing Start Block")?;
+    let finish = finish.ok_or("missing Finish Block")?;
+    if start >= finish {
+        return Err(format!("empty block range {}-{}", start, finish));
+    }
+    Ok(AplLink {
+        image: resolve_image(image, dir),
+        start,
+        finish,
+    })
+}
+
+fn parse_block(key: &str, value: &str) -> Result<u64> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {} {:?}", key.trim(), value))
+}
+
+// Links are usually written on Windows, often with the image's absolute path.
+// As libMAC does, an image that is not where the link says is looked for next
+// to the link.
+fn resolve_image(name: &str, dir: &Path) -> PathBuf {
+    let name = name.replace('\\', "/");
+    let path = dir.join(&name);
+    if path.exists() {
+        return path;
+    }
+    match Path::new(&name).file_name() {
+        Some(file) => dir.join(file),
+        None => path,
+    }
+}
+
+fn clamp_metadata(meta: &mut InputMetadata, start: u64, finish: u64) -> Result<()> {
+    let end = if meta.total_samples == 0 {
+        finish
+    } else {
+        finish.min(meta.total_samples)
+    };
+    if start >= end {
+        return Err(format!(
+            "the link starts at block {} but the image has only {} blocks",
+            start, meta.total_samples
+        ));
+    }
+    meta.total_samples = end - start;
+    // The checksum, frame layout and cue sheet of the image cover all of it,
+    // not the span.
+    meta.md5 = None;
+    meta.block_layout.clear();
+    meta.embedded_cue = None;
+    Ok(())
+}
+
+fn clamp_blocks(
+    blocks: Box<dyn Iterator<Item = Result<AudioBlock>>>,
+    start: u64,
+    finish: u64,
+) -> Box<dyn Iterator<Item = Result<AudioBlock>>> {
+    Box::new(
+        blocks
+            .take_while(move |block| !matches!(block, Ok(block) if block.sample_index >= finish))
+            .filter_map(move |block| {
+                let mut block = match block {
+                    Ok(block) => block,
+                    Err(err) => return Some(Err(err)),
+                };
+                let block_end = block.sample_index + block.sample_count() as u64;
+                let first = block.sample_index.max(start);
+                let last = block_end.min(finish);
+                if first >= last {
+                    return None;
+                }
+                let channels = block.channels as usize;
+                block
+                    .interleaved
+                    .truncate((last - block.sample_index) as usize * channels);
+                block
+                    .interleaved
+                    .drain(..(first - block.sample_index) as usize * channels);
+                block.sample_index = first - start;
+                Some(Ok(block))
+            }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AplLink, clamp_blocks, clamp_metadata, parse_apl_link};
+    use crate::decoder::AudioBlock;
+    use crate::types::InputMetadata;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn apl_link_header_is_parsed() {
+        let text = "[Monkey's Audio Image Link File]\r\n\
+                    Image File=C:\\Rips\\CD.ape\r\n\
+                    Start Block=44100\r\n\
+                    Finish Block=88200\r\n\
+                    \r\n\
+                    [Monkey's Audio]\r\n\
+                    Image File=ignored.ape\r\n\
+                    ----- APE TAG (DO NOT TOUCH!!!) -----\r\n\
+                    APETAGEX\0\0";
+        assert_eq!(
+            parse_apl_link(text, Path::new("/music")),
+            Ok(AplLink {
+                image: PathBuf::from("/music/CD.ape"),
+                start: 44100,
+                finish: 88200,
+            })
+        );
+
+        let link = |body: &str| {
+            parse_apl_link(
+                &format!("[Monkey's Audio Image Link File]\n{}", body),
+                Path::new("/music"),
+            )
+        };
+        assert!(link("Image File=CD.ape\nStart Block=10\nFinish Block=10").is_err());
+        assert!(link("Image File=CD.ape\nStart Block=x\nFinish Block=10").is_err());
+        assert!(link("Start Block=0\nFinish Block=10").is_err());
+        assert!(parse_apl_link("Image File=CD.ape", Path::new("/music")).is_err());
+    }
+
+    #[test]
+    fn apl_span_is_clamped_to_the_image() {
+        let mut meta = InputMetadata::new();
+        meta.total_samples = 100;
+        meta.md5 = Some([1; 16]);
+        meta.embedded_cue = Some("FILE \"CD.ape\" WAVE".to_string());
+        clamp_metadata(&mut meta, 60, 200).unwrap();
+        assert_eq!(meta.total_samples, 40);
+        assert_eq!(meta.md5, None);
+        assert_eq!(meta.embedded_cue, None);
+
+        let mut meta = InputMetadata::new();
+        meta.total_samples = 100;
+        assert!(clamp_metadata(&mut meta, 100, 200).is_err());
+
+        // Stereo blocks of four samples whose values are their sample index.
+        let blocks = (0..5u64).map(|block| {
+            Ok(AudioBlock {
+                sample_index: block * 4,
+                channels: 2,
+                interleaved: (block * 4..block * 4 + 4)
+                    .flat_map(|sample| [sample as i32; 2])
+                    .collect(),
+            })
+        });
+        let clamped: Vec<AudioBlock> = clamp_blocks(Box::new(blocks), 6, 13)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            clamped
+                .iter()
+                .map(|block| block.sample_index)
+                .collect::<Vec<_>>(),
+            vec![0, 2, 6]
+        );
+        assert_eq!(
+            clamped
+                .iter()
+                .flat_map(|block| block.interleaved.iter().copied())
+                .collect::<Vec<_>>(),
+            (6..13).flat_map(|sample| [sample; 2]).collect::<Vec<_>>()
+        );
+    }
+}
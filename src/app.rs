@@ -1,32 +1,328 @@
-use clap::Parser;
-use dialoguer::Input;
+use clap::{CommandFactory, FromArgMatches};
+use dialoguer::{Input, MultiSelect, Select};
+use encoding_rs::Encoding;
 use owo_colors::OwoColorize;
-use std::collections::HashSet;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsString;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 use crate::Result;
-use crate::cli::{Args, InputPair, resolve_input_pairs};
+use crate::archive::{ExtractedArchive, extract_archive, is_archive};
+use crate::cli::{
+    Args, CUE_STRING_DISPLAY, Cli, Command, CueCandidate, ExecuteArgs, InputPair, InputPath,
+    PlanArgs, SPLIT_POINTS_DISPLAY, display_path, resolve_cueless_pair, resolve_input_pairs,
+};
+use crate::config::print_config;
 use crate::cue::report_cue_warnings;
-use crate::cue::resolve_encoding;
-use crate::split::{SplitOptions, prepare_split, sanitize_filename};
-use crate::ui::{ConfirmAction, confirm_or_exit, print_plan};
+use crate::cue::{CueStyle, load_sample_offsets, resolve_encoding};
+use crate::diff::run_diff;
+use crate::doctor::run_doctor;
+use crate::events::EventSink;
+use crate::extract::run_extract_cue;
+use crate::fade::Fade;
+use crate::flac::{EncoderOptions, Preset};
+use crate::genre::{GenreMap, load_genre_map};
+use crate::i18n::{Msg, set_lang, tr, tr_with};
+use crate::inspect::run_inspect;
+use crate::join::run_join;
+use crate::lengths::{TrackLengths, load_track_lengths};
+use crate::manifest::load_manifest;
+use crate::plan_file::{load_plan_file, write_plan_file};
+use crate::priority::lower_priority;
+use crate::profile::apply_profile;
+use crate::remote::is_url;
+use crate::rename::run_rename;
+use crate::retag::run_retag;
+use crate::rules::{TagRules, load_tag_rules};
+use crate::serve::run_serve;
+use crate::split::{
+    PathTags, Plan, SourceAction, SplitOptions, archived_path, encoder_threads, execute_plans,
+    parallel_albums, prepare_split, sanitize_filename,
+};
+use crate::tags::{TagOverride, load_tag_overrides, stack_tag_overrides};
+use crate::tracklist::run_make_cue;
+use crate::ui::{
+    ConfirmAction, PairChoice, collect_warnings, confirm_or_exit, print_plan, print_warning_recap,
+    prompt_pair_choice, set_time_format,
+};
+use crate::verify::run_verify;
 
 pub fn run() -> Result<()> {
-    let args = Args::parse();
-    let encoding = match args.cue_encoding {
-        Some(label) => Some(resolve_encoding(&label)?),
+    let argv: Vec<OsString> = std::env::args_os().collect();
+    let mut matches = Cli::command().get_matches_from(&argv);
+    let profile = apply_profile(&argv, &matches)?;
+    if let Some(profile) = &profile {
+        matches = Cli::command().get_matches_from(&profile.argv);
+    }
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    set_lang(cli.lang);
+    set_time_format(cli.time_format);
+    let config_args = match &cli.command {
+        Some(Command::Split(args)) => Some(args),
+        Some(Command::Plan(args)) => Some(&args.split),
+        None => Some(&cli.args),
+        _ => None,
+    };
+    if let Some(args) = config_args.filter(|args| args.print_config) {
+        return print_config(args, &matches, profile.as_ref());
+    }
+    match cli.command {
+        Some(Command::Split(args)) => run_split(args),
+        Some(Command::Plan(args)) => run_plan(args),
+        Some(Command::Execute(args)) => run_execute(args),
+        Some(Command::Join(args)) => run_join(args),
+        Some(Command::Retag(args)) => run_retag(args),
+        Some(Command::Rename(args)) => run_rename(args),
+        Some(Command::Inspect(args)) => run_inspect(args),
+        Some(Command::ExtractCue(args)) => run_extract_cue(args),
+        Some(Command::MakeCue(args)) => match run_make_cue(args)? {
+            Some(split) => run_split(split),
+            None => Ok(()),
+        },
+        Some(Command::Verify(args)) => run_verify(args),
+        Some(Command::Diff(args)) => run_diff(args),
+        Some(Command::Doctor) => run_doctor(),
+        Some(Command::Serve(args)) => run_serve(args),
+        None => run_split(cli.args),
+    }
+}
+
+const DISC_KEYWORDS: [&str; 3] = ["cd", "disk", "volume"];
+const DISC_NUMBER_KEYWORDS: [&str; 5] = ["cd", "disc", "disk", "vol", "volume"];
+
+struct SplitInputs {
+    encoding: Option<&'static Encoding>,
+    base_dir_abs: PathBuf,
+    display_base_abs: Option<PathBuf>,
+    picture_path: Option<PathBuf>,
+    tag_overrides: Vec<TagOverride>,
+    genre_map: GenreMap,
+    tag_rules: TagRules,
+    sample_offsets: Vec<(u32, u64)>,
+    track_lengths: Option<TrackLengths>,
+    source_action: SourceAction,
+    mirror_root: Option<PathBuf>,
+    pairs: Vec<InputPair>,
+    pair_settings: Vec<PairSettings>,
+    archive: Option<ExtractedArchive>,
+}
+
+#[derive(Clone, Default)]
+struct PairSettings {
+    encoding: Option<&'static Encoding>,
+    subdir: Option<PathBuf>,
+    compression_level: Option<u8>,
+    track_compression: BTreeMap<u32, u8>,
+    tag_overrides: Vec<TagOverride>,
+}
+
+fn run_split(args: Args) -> Result<()> {
+    lower_priority(&args.priority)?;
+    let events = EventSink::open(&args.progress)?;
+    let mut inputs = resolve_split_inputs(&args)?;
+    let mut output_subdirs = initial_output_subdirs(&inputs)?;
+    if args.select {
+        select_pairs(&mut inputs, &mut output_subdirs)?;
+        if inputs.pairs.is_empty() {
+            println!("{}", tr(Msg::NothingToSplit).green().bold());
+            return Ok(());
+        }
+    }
+    let total = inputs.pairs.len();
+    if args.stdout && total > 1 {
+        return Err(tr_with(Msg::StdoutSingleAlbum, &[&total]));
+    }
+    loop {
+        let prepared_jobs = prepare_jobs(&args, &inputs, &output_subdirs)?;
+        if prepared_jobs.is_empty() {
+            report_status(&args, tr(Msg::NothingToSplit).green().bold().to_string());
+            return Ok(());
+        }
+        check_stdin_jobs(&prepared_jobs, args.yes)?;
+        if args.print0 {
+            // Keep stdout for the path list; warnings still go to stderr.
+            for prepared in &prepared_jobs {
+                report_cue_warnings(prepared.warnings());
+            }
+        } else if !args.stdout {
+            print_jobs(&prepared_jobs, args.jobs)?;
+        }
+
+        match confirm_or_exit(args.yes, total > 1)? {
+            ConfirmAction::Proceed => {
+                let written: Vec<PathBuf> = if args.print0 {
+                    prepared_jobs
+                        .iter()
+                        .flat_map(Plan::written_track_paths)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                let recap = collect_warnings(&prepared_jobs);
+                let result = execute_plans(prepared_jobs, args.jobs, events.as_ref());
+                print_warning_recap(&recap);
+                result?;
+                return print_nul_separated(&written);
+            }
+            ConfirmAction::Cancel => return Err(tr(Msg::AbortedByUser).to_string()),
+            ConfirmAction::EditSubdirs => {
+                output_subdirs = prompt_output_subdirs(&inputs.pairs, &output_subdirs)?;
+            }
+            ConfirmAction::PerPair => {
+                let selected = select_plans_per_pair(prepared_jobs)?;
+                if selected.is_empty() {
+                    println!("{}", tr(Msg::NothingToSplit).green().bold());
+                    return Ok(());
+                }
+                let recap = collect_warnings(&selected);
+                let result = execute_plans(selected, args.jobs, events.as_ref());
+                print_warning_recap(&recap);
+                return result;
+            }
+        }
+    }
+}
+
+fn select_plans_per_pair(plans: Vec<Plan>) -> Result<Vec<Plan>> {
+    println!();
+    let total = plans.len();
+    let mut selected = Vec::with_capacity(total);
+    let mut rest = None;
+    for (index, plan) in plans.into_iter().enumerate() {
+        let take = match rest {
+            Some(take) => take,
+            None => match prompt_pair_choice(index + 1, total, plan.flac_display())? {
+                PairChoice::Yes => true,
+                PairChoice::No => false,
+                PairChoice::All => {
+                    rest = Some(true);
+                    true
+                }
+                PairChoice::Skip => {
+                    rest = Some(false);
+                    false
+                }
+                PairChoice::Quit => return Err(tr(Msg::AbortedByUser).to_string()),
+            },
+        };
+        if take {
+            selected.push(plan);
+        }
+    }
+    Ok(selected)
+}
+
+pub(crate) fn plan_split_jobs(args: &Args) -> Result<Vec<Plan>> {
+    let inputs = resolve_split_inputs(args)?;
+    if let Some(archive) = &inputs.archive {
+        // The extracted members are gone once this returns.
+        return Err(tr_with(
+            Msg::ArchiveNotQueued,
+            &[&archive.archive().display()],
+        ));
+    }
+    let output_subdirs = initial_output_subdirs(&inputs)?;
+    let prepared_jobs = prepare_jobs(args, &inputs, &output_subdirs)?;
+    check_stdin_jobs(&prepared_jobs, args.yes)?;
+    Ok(prepared_jobs)
+}
+
+fn run_plan(args: PlanArgs) -> Result<()> {
+    let mut inputs = resolve_split_inputs(&args.split)?;
+    let mut output_subdirs = initial_output_subdirs(&inputs)?;
+    if args.split.select {
+        select_pairs(&mut inputs, &mut output_subdirs)?;
+        if inputs.pairs.is_empty() {
+            println!("{}", tr(Msg::NothingToSplit).green().bold());
+            return Ok(());
+        }
+    }
+    let prepared_jobs = prepare_jobs(&args.split, &inputs, &output_subdirs)?;
+    print_jobs(&prepared_jobs, args.split.jobs)?;
+
+    if let Some(out) = args.out.as_ref() {
+        if let Some(archive) = &inputs.archive {
+            return Err(tr_with(
+                Msg::ArchivePlanNotSaved,
+                &[&archive.archive().display()],
+            ));
+        }
+        let cwd = std::env::current_dir().map_err(|err| tr_with(Msg::NoCurrentDir, &[&err]))?;
+        let out_abs = if out.is_absolute() {
+            out.clone()
+        } else {
+            cwd.join(out)
+        };
+        write_plan_file(&out_abs, &prepared_jobs)?;
+        println!(
+            "{} {}",
+            tr(Msg::SavedPlan).green().bold(),
+            display_path(Some(cwd.as_path()), &out_abs)
+                .display()
+                .to_string()
+                .bold()
+        );
+    }
+    Ok(())
+}
+
+fn run_execute(args: ExecuteArgs) -> Result<()> {
+    lower_priority(&args.priority)?;
+    let events = EventSink::open(&args.progress)?;
+    let cwd = std::env::current_dir().map_err(|err| tr_with(Msg::NoCurrentDir, &[&err]))?;
+    let plan_abs = if args.plan.is_absolute() {
+        args.plan.clone()
+    } else {
+        cwd.join(&args.plan)
+    };
+
+    let prepared_jobs = load_plan_file(
+        &plan_abs,
+        Some(cwd.as_path()),
+        args.overwrite,
+        args.allow_truncated,
+    )?;
+    check_stdin_jobs(&prepared_jobs, args.yes)?;
+    print_jobs(&prepared_jobs, args.jobs)?;
+
+    match confirm_or_exit(args.yes, false)? {
+        ConfirmAction::Proceed => {
+            let recap = collect_warnings(&prepared_jobs);
+            let result = execute_plans(prepared_jobs, args.jobs, events.as_ref());
+            print_warning_recap(&recap);
+            result
+        }
+        _ => Err(tr(Msg::AbortedByUser).to_string()),
+    }
+}
+
+fn resolve_split_inputs(args: &Args) -> Result<SplitInputs> {
+    let encoding = match args.cue_encoding.as_deref() {
+        Some(label) => Some(resolve_encoding(label)?),
         None => None,
     };
 
-    let cwd = std::env::current_dir()
-        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let cwd = std::env::current_dir().map_err(|err| tr_with(Msg::NoCurrentDir, &[&err]))?;
     let (base_dir_abs, display_base_abs) = match args.dir.as_ref() {
         Some(dir) if dir.is_absolute() => (dir.clone(), None),
         Some(dir) => (cwd.join(dir), Some(cwd.clone())),
         None => (cwd.clone(), Some(cwd)),
     };
+    // An archive is extracted to a staging directory that then stands in for DIR.
+    let archive = if is_archive(&base_dir_abs) {
+        Some(extract_archive(&base_dir_abs)?)
+    } else {
+        None
+    };
+    let (base_dir_abs, display_base_abs) = match &archive {
+        Some(archive) => (
+            archive.root().to_path_buf(),
+            Some(archive.root().to_path_buf()),
+        ),
+        None => (base_dir_abs, display_base_abs),
+    };
 
-    let picture_enabled = !args.no_picture;
     let picture_path = if let Some(path) = args.picture.as_ref() {
         let abs = if path.is_absolute() {
             path.clone()
@@ -34,68 +330,372 @@ pub fn run() -> Result<()> {
             base_dir_abs.join(path)
         };
         if !abs.is_file() {
-            return Err(format!("picture file not found: {}", abs.display()));
+            return Err(tr_with(Msg::PictureNotFound, &[&abs.display()]));
         }
         Some(abs)
     } else {
         None
     };
 
-    let pairs = resolve_input_pairs(
-        &base_dir_abs,
-        display_base_abs.as_deref(),
-        args.flac.as_ref(),
-        args.cue.as_ref(),
-    )?;
+    let tag_overrides = match args.tags_from.as_ref() {
+        Some(path) => {
+            let abs = if path.is_absolute() {
+                path.clone()
+            } else {
+                base_dir_abs.join(path)
+            };
+            load_tag_overrides(&abs)?
+        }
+        None => Vec::new(),
+    };
+    let genre_map = match args.genre_map.as_ref() {
+        Some(path) if path.is_absolute() => load_genre_map(path)?,
+        Some(path) => load_genre_map(&base_dir_abs.join(path))?,
+        None => GenreMap::default(),
+    };
+    let tag_rules = match args.tag_rules.as_ref() {
+        Some(path) if path.is_absolute() => load_tag_rules(path)?,
+        Some(path) => load_tag_rules(&base_dir_abs.join(path))?,
+        None => TagRules::default(),
+    };
+    let sample_offsets = match args.sample_offsets.as_ref() {
+        Some(path) if path.is_absolute() => load_sample_offsets(path)?,
+        Some(path) => load_sample_offsets(&base_dir_abs.join(path))?,
+        None => Vec::new(),
+    };
+    let track_lengths = match args.track_lengths.as_ref() {
+        Some(path) if path.is_absolute() => Some(load_track_lengths(path)?),
+        Some(path) => Some(load_track_lengths(&base_dir_abs.join(path))?),
+        None => None,
+    };
+
+    let source_action = if let Some(dir) = args.archive_original.as_ref() {
+        SourceAction::Archive {
+            dir: if dir.is_absolute() {
+                dir.clone()
+            } else {
+                cwd.join(dir)
+            },
+            base: base_dir_abs.clone(),
+        }
+    } else if args.trash_original {
+        SourceAction::Trash
+    } else if args.delete_original {
+        SourceAction::Delete
+    } else if args.rename_original {
+        let template = match args.rename_suffix.as_deref() {
+            Some(suffix) => format!("{{file}}{}", suffix.replace('{', "{{").replace('}', "}}")),
+            None => args.rename_template.clone(),
+        };
+        SourceAction::Rename { template }
+    } else {
+        SourceAction::Keep
+    };
+
+    let mirror_root = args.mirror_root.as_ref().map(|root| {
+        if root.is_absolute() {
+            root.clone()
+        } else {
+            cwd.join(root)
+        }
+    });
 
-    let mut output_subdirs = derive_output_subdirs(&pairs)?;
-    let total = pairs.len();
+    let cueless_display = if args.split_at.is_some() {
+        Some(SPLIT_POINTS_DISPLAY)
+    } else if args.cue_string.is_some() {
+        Some(CUE_STRING_DISPLAY)
+    } else {
+        None
+    };
+    let mut pair_settings = Vec::new();
+    let pairs = if let Some(path) = args.manifest.as_ref() {
+        let abs = if path.is_absolute() {
+            path.clone()
+        } else {
+            base_dir_abs.join(path)
+        };
+        let jobs = load_manifest(&abs, display_base_abs.as_deref())?;
+        let mut pairs = Vec::with_capacity(jobs.len());
+        for job in jobs {
+            pairs.push(job.pair);
+            pair_settings.push(PairSettings {
+                encoding: job.encoding,
+                subdir: job.subdir,
+                compression_level: job.compression_level,
+                track_compression: job.track_compression,
+                tag_overrides: job.tag_overrides,
+            });
+        }
+        pairs
+    } else if let Some(cue_display) = cueless_display {
+        vec![resolve_cueless_pair(
+            &base_dir_abs,
+            display_base_abs.as_deref(),
+            args.flac.as_ref(),
+            cue_display,
+        )?]
+    } else {
+        resolve_input_pairs(
+            &base_dir_abs,
+            display_base_abs.as_deref(),
+            args.flac.as_ref(),
+            args.cue.as_ref(),
+            &|flac: &InputPath, candidates: &[CueCandidate]| {
+                choose_cue(flac, candidates, args.prefer_cue_style, args.yes)
+            },
+        )?
+    };
+    let remote = pairs
+        .iter()
+        .any(|pair| is_url(&pair.flac.abs) || is_url(&pair.cue.abs));
+    if remote && source_action != SourceAction::Keep {
+        return Err(
+            "the source cannot be deleted, renamed or moved when it is read from a URL".to_string(),
+        );
+    }
+    if archive.is_some() && source_action != SourceAction::Keep {
+        return Err(
+            "the source cannot be deleted, renamed or moved when it is read from an archive"
+                .to_string(),
+        );
+    }
+
+    pair_settings.resize(pairs.len(), PairSettings::default());
+    Ok(SplitInputs {
+        encoding,
+        base_dir_abs,
+        display_base_abs,
+        picture_path,
+        tag_overrides,
+        genre_map,
+        tag_rules,
+        sample_offsets,
+        track_lengths,
+        source_action,
+        mirror_root,
+        pairs,
+        pair_settings,
+        archive,
+    })
+}
+
+fn prepare_jobs(
+    args: &Args,
+    inputs: &SplitInputs,
+    output_subdirs: &[Option<PathBuf>],
+) -> Result<Vec<Plan>> {
+    let total = inputs.pairs.len();
     let enforce_cue_filename_match = total > 1;
-    loop {
-        let mut prepared_jobs = Vec::with_capacity(total);
-        for (pair, output_subdir) in pairs.iter().cloned().zip(output_subdirs.iter().cloned()) {
-            let prepared = prepare_split(SplitOptions {
-                flac_input: pair.flac,
-                cue_input: pair.cue,
-                display_base_abs: display_base_abs.clone(),
-                cue_encoding: encoding,
-                overwrite: args.overwrite,
-                compression_level: args.compression_level,
-                search_dir: base_dir_abs.clone(),
-                picture_enabled,
-                picture_path: picture_path.clone(),
-                delete_original: args.delete_original,
-                rename_original: args.rename_original,
-                output_subdir,
-                enforce_cue_filename_match,
-            })?;
-            prepared_jobs.push(prepared);
-        }
-
-        for (index, prepared) in prepared_jobs.iter().enumerate() {
-            if total > 1 {
-                if index > 0 {
-                    println!();
-                }
-                println!("{}", format!("Pair {}/{}", index + 1, total).bold().blue());
-            }
-            report_cue_warnings(prepared.warnings());
-            print_plan(prepared)?;
+    let disc_numbers: Vec<Option<u32>> = if args.no_disc_number {
+        vec![None; total]
+    } else {
+        inputs
+            .pairs
+            .iter()
+            .map(|pair| {
+                pair.flac
+                    .abs
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(disc_number_from_name)
+            })
+            .collect()
+    };
+    let disc_total = disc_total(&disc_numbers);
+    let mut prepared_jobs = Vec::with_capacity(total);
+    for (((pair, settings), output_subdir), disc_number) in inputs
+        .pairs
+        .iter()
+        .cloned()
+        .zip(&inputs.pair_settings)
+        .zip(output_subdirs.iter().cloned())
+        .zip(disc_numbers)
+    {
+        let output_root = if let Some(archive) = &inputs.archive {
+            let root = archive.output_root();
+            Some(match &inputs.mirror_root {
+                Some(mirror) => mirror.join(root.file_name().unwrap_or_default()),
+                None => root,
+            })
+        } else if is_url(&pair.flac.abs) {
+            Some(
+                inputs
+                    .mirror_root
+                    .clone()
+                    .unwrap_or_else(|| inputs.base_dir_abs.clone()),
+            )
+        } else {
+            inputs.mirror_root.as_ref().map(|root| {
+                let source_dir = pair.flac.abs.parent().unwrap_or(Path::new("."));
+                archived_path(source_dir, root, &inputs.base_dir_abs)
+            })
+        };
+        let path_tags = (args.tags_from_path && !is_url(&pair.flac.abs))
+            .then(|| path_tags_from_dirs(&pair.flac.abs));
+        let prepared = prepare_split(SplitOptions {
+            flac_input: pair.flac,
+            cue_input: pair.cue,
+            cue_text: args.cue_string.clone(),
+            split_points: args.split_at.clone(),
+            display_base_abs: inputs.display_base_abs.clone(),
+            cue_encoding: settings.encoding.or(inputs.encoding),
+            overwrite: args.overwrite || args.update || args.resume,
+            encoder: encoder_options(args, settings.compression_level),
+            search_dir: inputs.base_dir_abs.clone(),
+            picture_enabled: !args.no_picture,
+            picture_path: inputs.picture_path.clone(),
+            cover_mode: args.cover,
+            art_strategy: args.art_strategy,
+            write_cue: args.write_cue,
+            source_action: inputs.source_action.clone(),
+            include_sidecars: args.include_sidecars,
+            allow_truncated: args.allow_truncated,
+            clamp_to_audio: args.clamp_to_audio,
+            length_tolerance: args.length_tolerance,
+            strict_cdda: args.strict_cdda,
+            read_offset: args.offset,
+            trim_silence: args.trim_silence,
+            silence_threshold_db: args.silence_threshold,
+            downmix: args.downmix,
+            gain: args.gain.clone(),
+            fade: Fade {
+                fade_in: args.fade_in.unwrap_or_default(),
+                fade_out: args.fade_out.unwrap_or_default(),
+            },
+            audio_md5: args.audio_md5,
+            keep_replaygain: args.keep_replaygain,
+            update: args.update,
+            resume: args.resume,
+            split_indexes: args.split_indexes,
+            check_lossy: args.check_lossy,
+            check_tags: args.check_tags || args.strict,
+            strict: args.strict,
+            normalize_case: args.normalize_case,
+            genre_map: inputs.genre_map.clone(),
+            tag_rules: inputs.tag_rules.clone(),
+            sample_offsets: inputs.sample_offsets.clone(),
+            track_lengths: inputs.track_lengths.clone(),
+            block_size: args.block_size,
+            selected_tracks: args.tracks.clone(),
+            skipped_tracks: args.skip_tracks.clone(),
+            track_numbering: args.skip_numbering,
+            to_stdout: args.stdout,
+            status_to_stderr: args.print0,
+            permissions: args.permissions.clone(),
+            output_root,
+            output_subdir,
+            enforce_cue_filename_match,
+            tag_overrides: stack_tag_overrides(
+                stack_tag_overrides(
+                    disc_tag_overrides(disc_number, disc_total),
+                    &inputs.tag_overrides,
+                ),
+                &settings.tag_overrides,
+            ),
+            track_compression: settings.track_compression.clone(),
+            path_tags,
+            also_encode: args.also_encode.clone(),
+        })?;
+        if prepared.up_to_date() {
+            report_status(
+                args,
+                format!(
+                    "{} {}",
+                    tr(Msg::UpToDate).green().bold(),
+                    prepared.flac_display().display()
+                ),
+            );
+            continue;
         }
+        prepared_jobs.push(prepared);
+    }
+    Ok(prepared_jobs)
+}
 
-        match confirm_or_exit(args.yes, total > 1)? {
-            ConfirmAction::Proceed => {
-                for prepared in prepared_jobs {
-                    prepared.execute()?;
-                }
-                return Ok(());
-            }
-            ConfirmAction::Cancel => return Err("aborted by user".to_string()),
-            ConfirmAction::EditSubdirs => {
-                output_subdirs = prompt_output_subdirs(&pairs, &output_subdirs)?;
+pub(crate) fn encoder_options(args: &Args, compression_level: Option<u8>) -> EncoderOptions {
+    let preset = args.preset.map(Preset::options).unwrap_or_default();
+    EncoderOptions {
+        compression_level: compression_level
+            .or(args.compression_level)
+            .unwrap_or(preset.compression_level),
+        format: args.output_format.unwrap_or(preset.format),
+        verify: args.verify_encoding || preset.verify,
+        seektable: args.seektable || preset.seektable,
+        padding: args.padding.unwrap_or(preset.padding),
+        keep_block_layout: args.keep_block_layout,
+        threads: 1,
+    }
+}
+
+fn check_stdin_jobs(prepared_jobs: &[Plan], yes: bool) -> Result<()> {
+    let Some(plan) = prepared_jobs.iter().find(|plan| plan.reads_stdin()) else {
+        return Ok(());
+    };
+    if !yes {
+        return Err(tr(Msg::StdinNeedsYes).to_string());
+    }
+    if *plan.source_action() != SourceAction::Keep {
+        return Err(
+            "the source cannot be deleted, renamed or moved when it is read from stdin".to_string(),
+        );
+    }
+    Ok(())
+}
+
+fn report_status(args: &Args, line: String) {
+    if args.print0 {
+        eprintln!("{}", line);
+    } else {
+        println!("{}", line);
+    }
+}
+
+fn print_nul_separated(paths: &[PathBuf]) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    // A truncated album keeps only its complete tracks.
+    for path in paths.iter().filter(|path| path.exists()) {
+        stdout
+            .write_all(path.as_os_str().as_encoded_bytes())
+            .and_then(|()| stdout.write_all(b"\0"))
+            .map_err(|err| tr_with(Msg::StdoutWriteFailed, &[&err]))?;
+    }
+    stdout
+        .flush()
+        .map_err(|err| tr_with(Msg::StdoutWriteFailed, &[&err]))
+}
+
+fn print_jobs(prepared_jobs: &[Plan], jobs: usize) -> Result<()> {
+    let total = prepared_jobs.len();
+    for (index, prepared) in prepared_jobs.iter().enumerate() {
+        if total > 1 {
+            if index > 0 {
+                println!();
             }
+            println!(
+                "{}",
+                tr_with(Msg::PairOf, &[&(index + 1), &total]).bold().blue()
+            );
         }
+        report_cue_warnings(prepared.warnings());
+        print_plan(prepared)?;
+    }
+    if total > 1 {
+        println!();
+        println!(
+            "{} {}",
+            tr(Msg::Jobs).cyan(),
+            tr_with(
+                Msg::JobsDetail,
+                &[
+                    &jobs,
+                    &parallel_albums(total, jobs),
+                    &encoder_threads(total, jobs)
+                ]
+            )
+        );
+        print_warning_recap(&collect_warnings(prepared_jobs));
     }
+    Ok(())
 }
 
 fn prompt_output_subdirs(
@@ -103,7 +703,7 @@ fn prompt_output_subdirs(
     current_subdirs: &[Option<PathBuf>],
 ) -> Result<Vec<Option<PathBuf>>> {
     println!();
-    println!("{}", "Configure output subdirectories".bold());
+    println!("{}", tr(Msg::ConfigureSubdirs).bold());
 
     let mut seen = HashSet::new();
     let mut edited = Vec::with_capacity(pairs.len());
@@ -114,25 +714,22 @@ fn prompt_output_subdirs(
             .and_then(|value| value.as_ref())
             .map(|value| value.to_string_lossy().to_string())
             .filter(|value| !value.is_empty())
-            .ok_or_else(|| format!("missing derived output subdirectory for pair {}", index + 1))?;
+            .ok_or_else(|| tr_with(Msg::MissingSubdir, &[&(index + 1)]))?;
 
-        println!("{} {}", "Pair:".cyan(), pair_name(pair).bold());
+        println!("{} {}", tr(Msg::Pair).cyan(), pair_name(pair).bold());
 
         let edited_subdir: String = Input::new()
-            .with_prompt("Subdir")
+            .with_prompt(tr(Msg::Subdir))
             .with_initial_text(default_subdir)
             .interact_text()
-            .map_err(|err| format!("failed to read subdir name: {}", err))?;
+            .map_err(|err| tr_with(Msg::SubdirReadFailed, &[&err]))?;
 
         let normalized = sanitize_filename(edited_subdir.trim());
         if normalized.is_empty() {
-            return Err("subdir name cannot be empty".to_string());
+            return Err(tr(Msg::EmptySubdir).to_string());
         }
         if !seen.insert(normalized.clone()) {
-            return Err(format!(
-                "duplicate output subdirectory name: {}",
-                normalized
-            ));
+            return Err(tr_with(Msg::DuplicateSubdir, &[&normalized]));
         }
         edited.push(Some(PathBuf::from(normalized)));
     }
@@ -140,6 +737,91 @@ fn prompt_output_subdirs(
     Ok(edited)
 }
 
+fn select_pairs(inputs: &mut SplitInputs, output_subdirs: &mut Vec<Option<PathBuf>>) -> Result<()> {
+    if inputs.pairs.len() < 2 {
+        return Ok(());
+    }
+    let labels: Vec<String> = inputs
+        .pairs
+        .iter()
+        .map(|pair| pair.flac.display.display().to_string())
+        .collect();
+    let picked: HashSet<usize> = MultiSelect::new()
+        .with_prompt(tr(Msg::SelectPairs))
+        .items(&labels)
+        .defaults(&vec![true; labels.len()])
+        .interact()
+        .map_err(|err| tr_with(Msg::PairSelectionFailed, &[&err]))?
+        .into_iter()
+        .collect();
+
+    let (pairs, subdirs) = inputs
+        .pairs
+        .drain(..)
+        .zip(output_subdirs.drain(..))
+        .enumerate()
+        .filter(|(index, _)| picked.contains(index))
+        .map(|(_, pair)| pair)
+        .unzip();
+    inputs.pairs = pairs;
+    *output_subdirs = subdirs;
+    let mut index = 0;
+    inputs.pair_settings.retain(|_| {
+        index += 1;
+        picked.contains(&(index - 1))
+    });
+    Ok(())
+}
+
+fn initial_output_subdirs(inputs: &SplitInputs) -> Result<Vec<Option<PathBuf>>> {
+    let mut subdirs = derive_output_subdirs(&inputs.pairs)?;
+    for (subdir, settings) in subdirs.iter_mut().zip(&inputs.pair_settings) {
+        if let Some(chosen) = &settings.subdir {
+            *subdir = Some(chosen.clone());
+        }
+    }
+    Ok(subdirs)
+}
+
+fn choose_cue(
+    flac: &InputPath,
+    candidates: &[CueCandidate],
+    prefer: Option<CueStyle>,
+    yes: bool,
+) -> Result<usize> {
+    if let Some(style) = prefer {
+        let preferred: Vec<usize> = (0..candidates.len())
+            .filter(|index| candidates[*index].style == style)
+            .collect();
+        if let [index] = preferred[..] {
+            return Ok(index);
+        }
+    }
+
+    let labels: Vec<String> = candidates
+        .iter()
+        .map(|candidate| {
+            format!(
+                "{} ({})",
+                candidate.cue.display.display(),
+                candidate.style.label()
+            )
+        })
+        .collect();
+    if yes {
+        return Err(tr_with(
+            Msg::SeveralCues,
+            &[&flac.display.display(), &labels.join("\n  ")],
+        ));
+    }
+    Select::new()
+        .with_prompt(tr_with(Msg::ChooseCue, &[&flac.display.display()]))
+        .items(&labels)
+        .default(0)
+        .interact()
+        .map_err(|err| tr_with(Msg::CueChoiceFailed, &[&err]))
+}
+
 fn pair_name(pair: &InputPair) -> String {
     pair.flac
         .display
@@ -197,13 +879,10 @@ fn derive_output_subdirs(pairs: &[InputPair]) -> Result<Vec<Option<PathBuf>>> {
             candidate
         };
         if name.is_empty() {
-            return Err("failed to derive output subdirectory name".to_string());
+            return Err(tr(Msg::SubdirNotDerived).to_string());
         }
         if !seen.insert(name.clone()) {
-            return Err(format!(
-                "derived duplicate output subdirectory name: {}",
-                name
-            ));
+            return Err(tr_with(Msg::DerivedDuplicateSubdir, &[&name]));
         }
 
         out.push(Some(PathBuf::from(name)));
@@ -227,6 +906,135 @@ fn longest_common_prefix_len(values: &[&str]) -> usize {
     prefix_len
 }
 
+fn disc_total(disc_numbers: &[Option<u32>]) -> Option<u32> {
+    if disc_numbers.len() < 2 || disc_numbers.iter().any(Option::is_none) {
+        return None;
+    }
+    // A set split in one run has as many discs as pairs, unless the names show
+    // a higher disc that was left out of this run.
+    let highest = disc_numbers.iter().flatten().copied().max()?;
+    Some(highest.max(disc_numbers.len() as u32))
+}
+
+fn disc_tag_overrides(disc_number: Option<u32>, disc_total: Option<u32>) -> Vec<TagOverride> {
+    let Some(number) = disc_number else {
+        return Vec::new();
+    };
+    let mut tags = vec![("DISCNUMBER", number)];
+    if let Some(total) = disc_total {
+        tags.push(("DISCTOTAL", total));
+        tags.push(("TOTALDISCS", total));
+    }
+    tags.into_iter()
+        .map(|(key, value)| TagOverride {
+            track: None,
+            key: key.to_string(),
+            value: value.to_string(),
+        })
+        .collect()
+}
+
+fn path_tags_from_dirs(flac_abs: &Path) -> PathTags {
+    let mut dirs = flac_abs
+        .ancestors()
+        .skip(1)
+        .filter_map(|dir| dir.file_name())
+        .filter_map(|name| name.to_str());
+    let mut album_dir = dirs.next();
+    if album_dir.is_some_and(is_disc_dir_name) {
+        album_dir = dirs.next();
+    }
+    let artist_dir = dirs.next();
+    PathTags {
+        artist: artist_dir
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string),
+        album: album_dir
+            .map(album_from_dir_name)
+            .filter(|name| !name.is_empty())
+            .map(str::to_string),
+    }
+}
+
+fn is_disc_dir_name(name: &str) -> bool {
+    let lowered = name.trim().to_ascii_lowercase();
+    DISC_NUMBER_KEYWORDS.iter().any(|keyword| {
+        lowered.strip_prefix(keyword).is_some_and(|rest| {
+            let digits = rest.trim_start_matches([' ', '.', '_', '-', '#']);
+            !digits.is_empty() && digits.bytes().all(|byte| byte.is_ascii_digit())
+        })
+    })
+}
+
+fn album_from_dir_name(name: &str) -> &str {
+    let name = name.trim();
+    let (bracketed, rest) = match name.strip_prefix(['(', '[']) {
+        Some(rest) => (true, rest),
+        None => (false, name),
+    };
+    if rest.len() > 4 && rest.as_bytes()[..4].iter().all(u8::is_ascii_digit) {
+        let mut after = &rest[4..];
+        if bracketed {
+            after = after.strip_prefix([')', ']']).unwrap_or("");
+        }
+        let album = after.trim_start_matches([' ', '-', '.', '_', '–']);
+        if !album.is_empty() && album.len() < after.len() {
+            return album;
+        }
+    }
+    if let Some(stripped) = name.strip_suffix([')', ']'])
+        && stripped.len() > 5
+        && let Some(album) = stripped[..stripped.len() - 4].strip_suffix(['(', '['])
+        && stripped.as_bytes()[stripped.len() - 4..]
+            .iter()
+            .all(u8::is_ascii_digit)
+    {
+        let album = album.trim_end_matches([' ', '-', '.', '_', '–']);
+        if !album.is_empty() {
+            return album;
+        }
+    }
+    name
+}
+
+fn disc_number_from_name(name: &str) -> Option<u32> {
+    let lowered = name.to_ascii_lowercase();
+    for keyword in DISC_NUMBER_KEYWORDS {
+        let mut offset = 0usize;
+        while let Some(rel) = lowered[offset..].find(keyword) {
+            let start = offset + rel;
+            let end = start + keyword.len();
+            offset = start + 1;
+
+            let before_ok = lowered[..start]
+                .chars()
+                .next_back()
+                .is_none_or(|ch| !ch.is_alphanumeric());
+            if !before_ok {
+                continue;
+            }
+            let rest = lowered[end..].trim_start_matches([' ', '.', '_', '-', '#']);
+            let digits_len = rest
+                .find(|ch: char| !ch.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let after_ok = rest[digits_len..]
+                .chars()
+                .next()
+                .is_none_or(|ch| !ch.is_alphanumeric());
+            if digits_len == 0 || !after_ok {
+                continue;
+            }
+            if let Ok(number) = rest[..digits_len].parse::<u32>()
+                && number > 0
+            {
+                return Some(number);
+            }
+        }
+    }
+    None
+}
+
 fn adjust_common_prefix_len(values: &[&str], prefix_len: usize) -> usize {
     if values.is_empty() || prefix_len == 0 {
         return prefix_len;
@@ -234,7 +1042,7 @@ fn adjust_common_prefix_len(values: &[&str], prefix_len: usize) -> usize {
 
     let prefix = &values[0][..prefix_len];
     let mut best = None;
-    for keyword in ["cd", "disk", "volume"] {
+    for keyword in DISC_KEYWORDS {
         if let Some(start) = keyword_start_in_prefix(prefix, keyword) {
             best = Some(best.map_or(start, |current: usize| current.max(start)));
         }
@@ -327,10 +1135,15 @@ fn common_suffix_len(a: &str, b: &str) -> usize {
 #[cfg(test)]
 mod tests {
     use super::{
-        derive_output_subdirs, keyword_start_in_prefix, longest_common_prefix_len,
-        longest_common_suffix_len,
+        album_from_dir_name, derive_output_subdirs, disc_number_from_name, disc_total,
+        encoder_options, keyword_start_in_prefix, longest_common_prefix_len,
+        longest_common_suffix_len, path_tags_from_dirs,
     };
-    use crate::cli::{InputPair, InputPath};
+    use crate::cli::{Cli, InputPair, InputPath};
+    use crate::flac::{EncoderOptions, OutputFormat};
+    use crate::split::PathTags;
+    use clap::Parser;
+    use std::path::Path;
     use std::path::PathBuf;
 
     fn pair(stem: &str) -> InputPair {
@@ -412,6 +1225,59 @@ mod tests {
         );
     }
 
+    #[test]
+    fn disc_number_is_parsed_from_disc_tokens() {
+        assert_eq!(disc_number_from_name("Artist - Album CD 2"), Some(2));
+        assert_eq!(disc_number_from_name("Artist - Album CD2"), Some(2));
+        assert_eq!(disc_number_from_name("Artist - Album [Disc 3]"), Some(3));
+        assert_eq!(disc_number_from_name("Artist - Vol. 12"), Some(12));
+        assert_eq!(disc_number_from_name("Artist - Volume 1"), Some(1));
+        assert_eq!(disc_number_from_name("Artist - Disk_04"), Some(4));
+        assert_eq!(disc_number_from_name("ABCD 2"), None);
+        assert_eq!(disc_number_from_name("Artist - CD 2000s"), None);
+        assert_eq!(disc_number_from_name("Artist - CD 0"), None);
+        assert_eq!(disc_number_from_name("Artist - Album"), None);
+    }
+
+    #[test]
+    fn disc_total_counts_the_pairs_of_the_run() {
+        assert_eq!(disc_total(&[Some(1), Some(2), Some(3)]), Some(3));
+        assert_eq!(disc_total(&[Some(2), Some(1)]), Some(2));
+        assert_eq!(disc_total(&[Some(1), Some(4)]), Some(4));
+        assert_eq!(disc_total(&[Some(1), None]), None);
+        assert_eq!(disc_total(&[Some(2)]), None);
+    }
+
+    #[test]
+    fn album_dir_names_drop_the_year() {
+        assert_eq!(album_from_dir_name("1999 - Album"), "Album");
+        assert_eq!(album_from_dir_name("1999. Album"), "Album");
+        assert_eq!(album_from_dir_name("(1999) Album"), "Album");
+        assert_eq!(album_from_dir_name("[1999] Album"), "Album");
+        assert_eq!(album_from_dir_name("Album (1999)"), "Album");
+        assert_eq!(album_from_dir_name("1999"), "1999");
+        assert_eq!(album_from_dir_name("1999AD"), "1999AD");
+        assert_eq!(album_from_dir_name("Album"), "Album");
+    }
+
+    #[test]
+    fn path_tags_skip_disc_folders() {
+        assert_eq!(
+            path_tags_from_dirs(Path::new("/music/Artist/1999 - Album/Album.flac")),
+            PathTags {
+                artist: Some("Artist".to_string()),
+                album: Some("Album".to_string()),
+            }
+        );
+        assert_eq!(
+            path_tags_from_dirs(Path::new("/music/Artist/Album/CD2/Album CD2.flac")),
+            PathTags {
+                artist: Some("Artist".to_string()),
+                album: Some("Album".to_string()),
+            }
+        );
+    }
+
     #[test]
     fn keyword_detection_requires_boundary_and_whitespace() {
         assert_eq!(keyword_start_in_prefix("Artist Scd ", "cd"), None);
@@ -423,4 +1289,37 @@ mod tests {
             Some(9)
         );
     }
+
+    #[test]
+    fn explicit_encoder_options_override_the_preset() {
+        let parse = |extra: &[&str]| {
+            let argv = ["flac-cue-split"].iter().chain(extra).chain(&["Album"]);
+            Cli::try_parse_from(argv).unwrap().args
+        };
+        assert_eq!(
+            encoder_options(&parse(&[]), None),
+            EncoderOptions::default()
+        );
+
+        let archive = encoder_options(&parse(&["--preset", "archive", "-c", "6"]), None);
+        assert_eq!(archive.compression_level, 6);
+        assert!(archive.verify && archive.seektable);
+        assert_eq!(archive.padding, 8192);
+
+        let fast = encoder_options(
+            &parse(&[
+                "--preset",
+                "fast",
+                "--padding",
+                "512",
+                "--output-format",
+                "oggflac",
+            ]),
+            Some(8),
+        );
+        assert_eq!(fast.compression_level, 8);
+        assert_eq!(fast.format, OutputFormat::Oggflac);
+        assert_eq!(fast.padding, 512);
+        assert!(!fast.seektable);
+    }
 }
@@ -0,0 +1,253 @@
+use std::fs::{self, File};
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::SystemTime;
+
+use crate::Result;
+
+const UNRAR: &str = "unrar";
+const STAGING_ATTEMPTS: usize = 16;
+
+pub(crate) struct ExtractedArchive {
+    archive: PathBuf,
+    staging: PathBuf,
+    root: PathBuf,
+}
+
+impl ExtractedArchive {
+    pub(crate) fn archive(&self) -> &Path {
+        &self.archive
+    }
+
+    // The directory to scan for the image and cue: the archive itself, or the
+    // single folder everything in it is wrapped in.
+    pub(crate) fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub(crate) fn output_root(&self) -> PathBuf {
+        let stem = self
+            .archive
+            .file_stem()
+            .map(|stem| stem.to_os_string())
+            .unwrap_or_else(|| "archive".into());
+        self.archive.parent().unwrap_or(Path::new(".")).join(stem)
+    }
+}
+
+impl Drop for ExtractedArchive {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.staging);
+    }
+}
+
+pub(crate) fn is_archive(path: &Path) -> bool {
+    path.is_file() && archive_kind(path).is_some()
+}
+
+fn archive_kind(path: &Path) -> Option<&'static str> {
+    let ext = path.extension()?.to_str()?.to_ascii_lowercase();
+    match ext.as_str() {
+        "zip" => Some("zip"),
+        "7z" => Some("7z"),
+        "rar" => Some("rar"),
+        _ => None,
+    }
+}
+
+pub(crate) fn extract_archive(path: &Path) -> Result<ExtractedArchive> {
+    let Some(kind) = archive_kind(path) else {
+        return Err(format!("not an archive: {}", path.display()));
+    };
+    let staging = create_staging_dir()?;
+    // Dropping the guard removes the staging directory again, also when the
+    // extraction fails half way.
+    let mut extracted = ExtractedArchive {
+        archive: path.to_path_buf(),
+        staging: staging.clone(),
+        root: staging.clone(),
+    };
+    match kind {
+        "zip" => extract_zip(path, &staging)?,
+        "7z" => extract_7z(path, &staging)?,
+        _ => extract_rar(path, &staging)?,
+    }
+    extracted.root = single_folder(&staging)?;
+    Ok(extracted)
+}
+
+// The directory is new and only readable by us, so a path that already exists,
+// perhaps planted by another user, is never reused or removed.
+fn create_staging_dir() -> Result<PathBuf> {
+    let temp = std::env::temp_dir();
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    for _ in 0..STAGING_ATTEMPTS {
+        // Each RandomState is seeded with fresh random keys.
+        let suffix = RandomState::new().hash_one(SystemTime::now());
+        let staging = temp.join(format!(
+            "flac-cue-split-archive-{}-{:016x}",
+            std::process::id(),
+            suffix
+        ));
+        match builder.create(&staging) {
+            Ok(()) => return Ok(staging),
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => continue,
+            Err(err) => {
+                return Err(format!("failed to create {}: {}", staging.display(), err));
+            }
+        }
+    }
+    Err(format!(
+        "failed to create a staging directory in {}",
+        temp.display()
+    ))
+}
+
+fn extract_zip(path: &Path, staging: &Path) -> Result<()> {
+    let error = |err: &dyn std::fmt::Display| format!("failed to read {}: {}", path.display(), err);
+    let file = File::open(path).map_err(|err| error(&err))?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|err| error(&err))?;
+    for index in 0..archive.len() {
+        let mut member = archive.by_index(index).map_err(|err| error(&err))?;
+        if member.is_dir() {
+            continue;
+        }
+        let name = member.name().to_string();
+        write_member(path, staging, &name, &mut member)?;
+    }
+    Ok(())
+}
+
+fn extract_7z(path: &Path, staging: &Path) -> Result<()> {
+    let error = |err: &dyn std::fmt::Display| format!("failed to read {}: {}", path.display(), err);
+    let mut archive = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+        .map_err(|err| error(&err))?;
+    let mut failure = None;
+    archive
+        .for_each_entries(|entry, reader| {
+            if entry.is_directory() {
+                return Ok(true);
+            }
+            match write_member(path, staging, entry.name(), reader) {
+                Ok(()) => Ok(true),
+                Err(err) => {
+                    failure = Some(err);
+                    Ok(false)
+                }
+            }
+        })
+        .map_err(|err| error(&err))?;
+    match failure {
+        Some(err) => Err(err),
+        None => Ok(()),
+    }
+}
+
+// No RAR decoder is built in; `unrar` refuses members that would leave the
+// destination directory.
+fn extract_rar(path: &Path, staging: &Path) -> Result<()> {
+    let output = Command::new(UNRAR)
+        .args(["x", "-idq", "-o-", "-y", "--"])
+        .arg(path)
+        // unrar takes the destination as a directory only with a trailing
+        // separator.
+        .arg(staging.join(""))
+        .output()
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => format!(
+                "{} was not found; install it to split RAR archives, or extract {} first",
+                UNRAR,
+                path.display()
+            ),
+            _ => format!("failed to start {}: {}", UNRAR, err),
+        })?;
+    if !output.status.success() {
+        return Err(format!(
+            "{} failed for {} ({}): {}",
+            UNRAR,
+            path.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+fn write_member(archive: &Path, staging: &Path, name: &str, reader: &mut dyn Read) -> Result<()> {
+    let relative = member_path(name).ok_or_else(|| {
+        format!(
+            "refusing to extract {:?} from {}: the path leaves the archive",
+            name,
+            archive.display()
+        )
+    })?;
+    let target = staging.join(relative);
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|err| format!("failed to create {}: {}", parent.display(), err))?;
+    }
+    let mut file = File::create(&target)
+        .map_err(|err| format!("failed to create {}: {}", target.display(), err))?;
+    io::copy(reader, &mut file).map_err(|err| {
+        format!(
+            "failed to extract {:?} from {}: {}",
+            name,
+            archive.display(),
+            err
+        )
+    })?;
+    Ok(())
+}
+
+fn member_path(name: &str) -> Option<PathBuf> {
+    // Members are only ever written below the staging directory.
+    let mut path = PathBuf::new();
+    for component in Path::new(&name.replace('\\', "/")).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!path.as_os_str().is_empty()).then_some(path)
+}
+
+fn single_folder(dir: &Path) -> Result<PathBuf> {
+    let mut current = dir.to_path_buf();
+    loop {
+        let entries: Vec<PathBuf> = fs::read_dir(&current)
+            .map_err(|err| format!("failed to read directory {}: {}", current.display(), err))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect();
+        match entries.as_slice() {
+            [only] if only.is_dir() => current = only.clone(),
+            _ => return Ok(current),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::member_path;
+    use std::path::PathBuf;
+
+    #[test]
+    fn member_paths_stay_inside_the_archive() {
+        assert_eq!(
+            member_path("Album/Album.cue"),
+            Some(PathBuf::from("Album/Album.cue"))
+        );
+        assert_eq!(
+            member_path("./Album\\CD1.flac"),
+            Some(PathBuf::from("Album/CD1.flac"))
+        );
+        assert_eq!(member_path("../evil.flac"), None);
+        assert_eq!(member_path("/etc/passwd"), None);
+        assert_eq!(member_path("Album/../../evil"), None);
+        assert_eq!(member_path(""), None);
+    }
+}
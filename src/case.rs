@@ -0,0 +1,158 @@
+use clap::ValueEnum;
+
+use crate::types::CueDisc;
+
+const MINOR_WORDS: &[&str] = &[
+    "a", "an", "and", "as", "at", "but", "by", "for", "from", "in", "nor", "of", "on", "or", "the",
+    "to", "with",
+];
+const LOWERCASE_WORDS: &[&str] = &["feat", "ft", "vs"];
+const ACRONYMS: &[&str] = &[
+    "AC/DC", "BBC", "CD", "DJ", "DVD", "EP", "LP", "MC", "MTV", "NYC", "OK", "R&B", "TV", "UFO",
+    "UK", "USA",
+];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum CaseStyle {
+    Title,
+    Sentence,
+    #[default]
+    Keep,
+}
+
+pub(crate) fn normalize_cue_case(cue: &mut CueDisc, style: CaseStyle) {
+    if style == CaseStyle::Keep {
+        return;
+    }
+    // Names read wrong in sentence case, so performers are always title-cased.
+    recase_field(&mut cue.title, style);
+    recase_field(&mut cue.performer, CaseStyle::Title);
+    for track in &mut cue.tracks {
+        recase_field(&mut track.title, style);
+        recase_field(&mut track.performer, CaseStyle::Title);
+    }
+}
+
+fn recase_field(field: &mut Option<String>, style: CaseStyle) {
+    if let Some(value) = field.as_mut() {
+        *value = normalize_case(value, style);
+    }
+}
+
+pub(crate) fn normalize_case(text: &str, style: CaseStyle) -> String {
+    // Only shouting values are touched; mixed case is assumed to be deliberate.
+    if style == CaseStyle::Keep
+        || text.chars().any(char::is_lowercase)
+        || !text.chars().any(char::is_uppercase)
+    {
+        return text.to_string();
+    }
+
+    let words: Vec<&str> = text.split(' ').collect();
+    let last = words.len() - 1;
+    let mut phrase_start = true;
+    let mut out = Vec::with_capacity(words.len());
+    for (index, word) in words.iter().enumerate() {
+        let start = word.find(char::is_alphanumeric).unwrap_or(word.len());
+        let end = word
+            .char_indices()
+            .rev()
+            .find(|(_, ch)| ch.is_alphanumeric())
+            .map_or(start, |(pos, ch)| pos + ch.len_utf8());
+        let (prefix, core, suffix) = (&word[..start], &word[start..end], &word[end..]);
+        if core.is_empty() {
+            out.push(word.to_string());
+            phrase_start |= !word.is_empty();
+            continue;
+        }
+
+        let starts_phrase = phrase_start || prefix.contains(['(', '[', '"']);
+        let lower = core.to_lowercase();
+        let cased = if is_roman_numeral(core) || is_acronym(core) {
+            core.to_string()
+        } else if LOWERCASE_WORDS.contains(&lower.as_str()) {
+            lower
+        } else {
+            match style {
+                CaseStyle::Title
+                    if !starts_phrase && index != last && MINOR_WORDS.contains(&lower.as_str()) =>
+                {
+                    lower
+                }
+                CaseStyle::Title => capitalize(&lower, true),
+                _ if starts_phrase => capitalize(&lower, false),
+                _ => lower,
+            }
+        };
+        out.push(format!("{}{}{}", prefix, cased, suffix));
+        phrase_start = suffix.ends_with([':', '.', '!', '?']);
+    }
+    out.join(" ")
+}
+
+fn capitalize(word: &str, after_hyphens: bool) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut upper_next = true;
+    for ch in word.chars() {
+        if upper_next && ch.is_alphabetic() {
+            out.extend(ch.to_uppercase());
+        } else {
+            out.push(ch);
+        }
+        upper_next = after_hyphens && ch == '-';
+    }
+    out
+}
+
+fn is_acronym(word: &str) -> bool {
+    ACRONYMS.contains(&word)
+        || (word.contains('.')
+            && word
+                .split('.')
+                .all(|part| part.chars().count() == 1 && part.chars().all(char::is_alphabetic)))
+}
+
+fn is_roman_numeral(word: &str) -> bool {
+    // Only I, V and X (up to 39) are kept; words like MIX or CIVIL are too
+    // likely to be real words.
+    let tens = word.len() - word.trim_start_matches('X').len();
+    let units = &word[tens..];
+    tens <= 3
+        && !word.is_empty()
+        && matches!(
+            units,
+            "" | "I" | "II" | "III" | "IV" | "V" | "VI" | "VII" | "VIII" | "IX"
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaseStyle, normalize_case};
+
+    #[test]
+    fn normalize_case_recases_only_shouting_values() {
+        let title = |text: &str| normalize_case(text, CaseStyle::Title);
+        let sentence = |text: &str| normalize_case(text, CaseStyle::Sentence);
+
+        assert_eq!(
+            title("THE DARK SIDE OF THE MOON"),
+            "The Dark Side of the Moon"
+        );
+        assert_eq!(
+            title("SYMPHONY NO. IX (LIVE AT THE BBC)"),
+            "Symphony No. IX (Live at the BBC)"
+        );
+        assert_eq!(
+            title("ROCK-N-ROLL FEAT. DJ SHADOW"),
+            "Rock-N-Roll feat. DJ Shadow"
+        );
+        assert_eq!(title("WHAT IT'S FOR"), "What It's For");
+        assert_eq!(title("LIVE IN THE U.S.A."), "Live in the U.S.A.");
+        assert_eq!(sentence("PART II: THE MIX"), "Part II: The mix");
+        assert_eq!(sentence("DON'T STOP ME NOW"), "Don't stop me now");
+        assert_eq!(sentence("2ND TAKE"), "2nd take");
+        assert_eq!(title("Already Mixed CASE"), "Already Mixed CASE");
+        assert_eq!(normalize_case("LOUD", CaseStyle::Keep), "LOUD");
+        assert_eq!(title("1999"), "1999");
+    }
+}
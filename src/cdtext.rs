@@ -0,0 +1,230 @@
+use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::types::CueDisc;
+
+const PACK_SIZE: usize = 18;
+const PACK_TITLE: u8 = 0x80;
+const PACK_PERFORMER: u8 = 0x81;
+const PACK_SONGWRITER: u8 = 0x82;
+const PACK_COMPOSER: u8 = 0x83;
+const PACK_MESSAGE: u8 = 0x85;
+const PACK_CODE: u8 = 0x8E;
+const PACK_SIZE_INFO: u8 = 0x8F;
+const CHARSET_MS_JIS: u8 = 0x80;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct CdTextEntry {
+    pub(crate) title: Option<String>,
+    pub(crate) performer: Option<String>,
+    pub(crate) songwriter: Option<String>,
+    pub(crate) composer: Option<String>,
+    pub(crate) message: Option<String>,
+    pub(crate) code: Option<String>,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct CdText {
+    pub(crate) disc: CdTextEntry,
+    pub(crate) tracks: BTreeMap<u32, CdTextEntry>,
+}
+
+pub(crate) fn find_cdtext_file(cue_path: &Path, cue_text: &str) -> Option<PathBuf> {
+    let dir = cue_path.parent()?;
+    let referenced = cue_text.lines().find_map(|line| {
+        let name = line
+            .trim()
+            .strip_prefix("CDTEXTFILE")?
+            .trim()
+            .trim_matches('"');
+        (!name.is_empty()).then(|| dir.join(name))
+    });
+    referenced
+        .into_iter()
+        .chain(Some(cue_path.with_extension("cdt")))
+        .find(|path| path.is_file())
+}
+
+pub(crate) fn parse_cdtext(data: &[u8]) -> Result<CdText> {
+    // Dumps written by drive tools carry a 4-byte size header and sometimes a
+    // trailing NUL around the raw 18-byte packs.
+    let packs = match data.len() % PACK_SIZE {
+        0 => data,
+        4 | 5 => &data[4..],
+        _ => return Err("not a CD-TEXT file".to_string()),
+    };
+    let packs = &packs[..packs.len() / PACK_SIZE * PACK_SIZE];
+    if packs.is_empty() {
+        return Err("CD-TEXT file has no packs".to_string());
+    }
+
+    // Per pack type: the first track, whether the packs hold double-byte
+    // text, and the concatenated text.
+    let mut streams: BTreeMap<u8, (u32, bool, Vec<u8>)> = BTreeMap::new();
+    let mut charset = 0u8;
+    for pack in packs.chunks_exact(PACK_SIZE) {
+        let kind = pack[0];
+        if !(0x80..=0x8F).contains(&kind) {
+            return Err(format!("unexpected CD-TEXT pack type 0x{:02X}", kind));
+        }
+        let block = (pack[3] >> 4) & 0x07;
+        if block != 0 {
+            continue;
+        }
+        if kind == PACK_SIZE_INFO {
+            if pack[1] == 0 {
+                charset = pack[4];
+            }
+            continue;
+        }
+        let stream = streams
+            .entry(kind)
+            .or_insert_with(|| (u32::from(pack[1] & 0x7F), false, Vec::new()));
+        stream.1 |= pack[3] & 0x80 != 0;
+        stream.2.extend_from_slice(&pack[4..16]);
+    }
+
+    let encoding = if charset == CHARSET_MS_JIS {
+        SHIFT_JIS
+    } else {
+        WINDOWS_1252
+    };
+    let mut cdtext = CdText::default();
+    for (kind, (first_track, double_byte, bytes)) in streams {
+        // MS-JIS strings end in two NULs and repeat the previous one with `\t\t`.
+        let double_byte = double_byte || charset == CHARSET_MS_JIS;
+        let repeat: &[u8] = if double_byte { b"\t\t" } else { b"\t" };
+        let mut previous: Option<String> = None;
+        for (index, raw) in split_strings(&bytes, double_byte).into_iter().enumerate() {
+            let text = if raw == repeat {
+                previous.clone()
+            } else {
+                let (decoded, _, _) = encoding.decode(raw);
+                Some(decoded.trim().to_string()).filter(|text| !text.is_empty())
+            };
+            let Some(text) = text else {
+                continue;
+            };
+            let track = first_track + index as u32;
+            let entry = if track == 0 {
+                &mut cdtext.disc
+            } else {
+                cdtext.tracks.entry(track).or_default()
+            };
+            let slot = match kind {
+                PACK_TITLE => &mut entry.title,
+                PACK_PERFORMER => &mut entry.performer,
+                PACK_SONGWRITER => &mut entry.songwriter,
+                PACK_COMPOSER => &mut entry.composer,
+                PACK_MESSAGE => &mut entry.message,
+                PACK_CODE => &mut entry.code,
+                _ => continue,
+            };
+            *slot = Some(text.clone());
+            previous = Some(text);
+        }
+    }
+    Ok(cdtext)
+}
+
+fn split_strings(bytes: &[u8], double_byte: bool) -> Vec<&[u8]> {
+    if !double_byte {
+        return bytes.split(|byte| *byte == 0).collect();
+    }
+    let mut strings = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos + 1 < bytes.len() {
+        if bytes[pos] == 0 && bytes[pos + 1] == 0 {
+            strings.push(&bytes[start..pos]);
+            start = pos + 2;
+        }
+        pos += 2;
+    }
+    strings.push(&bytes[start..]);
+    strings
+}
+
+pub(crate) fn merge_cdtext(disc: &mut CueDisc, cdtext: &CdText) {
+    fill(&mut disc.title, &cdtext.disc.title);
+    fill(&mut disc.performer, &cdtext.disc.performer);
+    fill(&mut disc.songwriter, &cdtext.disc.songwriter);
+    fill(&mut disc.composer, &cdtext.disc.composer);
+    fill(&mut disc.message, &cdtext.disc.message);
+    for track in &mut disc.tracks {
+        let Some(entry) = cdtext.tracks.get(&track.number) else {
+            continue;
+        };
+        fill(&mut track.title, &entry.title);
+        fill(&mut track.performer, &entry.performer);
+        fill(&mut track.songwriter, &entry.songwriter);
+        fill(&mut track.composer, &entry.composer);
+        fill(&mut track.isrc, &entry.code);
+    }
+}
+
+fn fill(slot: &mut Option<String>, value: &Option<String>) {
+    if slot.is_none() {
+        slot.clone_from(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_cdtext;
+    use encoding_rs::SHIFT_JIS;
+
+    fn packs(kind: u8, first_track: u8, text: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        for (seq, chunk) in text.chunks(12).enumerate() {
+            let mut pack = vec![kind, first_track, seq as u8, 0];
+            pack.extend_from_slice(chunk);
+            pack.resize(16, 0);
+            pack.extend_from_slice(&[0, 0]);
+            out.extend(pack);
+        }
+        out
+    }
+
+    #[test]
+    fn parse_cdtext_splits_strings_per_track() {
+        let mut data = vec![0x00, 0x00, 0x00, 0x00];
+        data.extend(packs(0x80, 0, b"Album\0One\0Two\0"));
+        data.extend(packs(0x81, 0, b"Artist\0\t\0Guest\0"));
+        let cdtext = parse_cdtext(&data).unwrap();
+
+        assert_eq!(cdtext.disc.title.as_deref(), Some("Album"));
+        assert_eq!(cdtext.disc.performer.as_deref(), Some("Artist"));
+        assert_eq!(cdtext.tracks[&1].title.as_deref(), Some("One"));
+        assert_eq!(cdtext.tracks[&1].performer.as_deref(), Some("Artist"));
+        assert_eq!(cdtext.tracks[&2].performer.as_deref(), Some("Guest"));
+        assert!(parse_cdtext(b"not cd-text").is_err());
+    }
+
+    #[test]
+    fn parse_cdtext_reads_ms_jis_strings() {
+        let mut text = Vec::new();
+        for title in ["アルバム", "一", "二"] {
+            text.extend_from_slice(&SHIFT_JIS.encode(title).0);
+            text.extend_from_slice(&[0, 0]);
+        }
+        let mut data = packs(0x80, 0, &text);
+        let mut performers = SHIFT_JIS.encode("歌手").0.into_owned();
+        performers.extend_from_slice(b"\0\0\t\t\0\0");
+        data.extend(packs(0x81, 0, &performers));
+        for pack in data.chunks_mut(18) {
+            pack[3] |= 0x80;
+        }
+        let mut size_info = vec![0x8F, 0, 0, 0, 0x80];
+        size_info.resize(18, 0);
+        data.extend(size_info);
+
+        let cdtext = parse_cdtext(&data).unwrap();
+        assert_eq!(cdtext.disc.title.as_deref(), Some("アルバム"));
+        assert_eq!(cdtext.tracks[&1].title.as_deref(), Some("一"));
+        assert_eq!(cdtext.tracks[&2].title.as_deref(), Some("二"));
+        assert_eq!(cdtext.tracks[&1].performer.as_deref(), Some("歌手"));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::Result;
+
+pub(crate) const CHANNEL_MASK_TAG: &str = "WAVEFORMATEXTENSIBLE_CHANNEL_MASK";
+
+const HALF_POWER: f64 = std::f64::consts::FRAC_1_SQRT_2;
+
+pub(crate) struct Speaker {
+    pub(crate) name: &'static str,
+    pub(crate) left: f64,
+    pub(crate) right: f64,
+}
+
+const SPEAKERS: [Speaker; 18] = [
+    speaker("FL", 1.0, 0.0),
+    speaker("FR", 0.0, 1.0),
+    speaker("FC", HALF_POWER, HALF_POWER),
+    speaker("LFE", 0.0, 0.0),
+    speaker("BL", HALF_POWER, 0.0),
+    speaker("BR", 0.0, HALF_POWER),
+    speaker("FLC", 1.0, 0.0),
+    speaker("FRC", 0.0, 1.0),
+    speaker("BC", 0.5, 0.5),
+    speaker("SL", HALF_POWER, 0.0),
+    speaker("SR", 0.0, HALF_POWER),
+    speaker("TC", 0.5, 0.5),
+    speaker("TFL", HALF_POWER, 0.0),
+    speaker("TFC", 0.5, 0.5),
+    speaker("TFR", 0.0, HALF_POWER),
+    speaker("TBL", HALF_POWER, 0.0),
+    speaker("TBC", 0.5, 0.5),
+    speaker("TBR", 0.0, HALF_POWER),
+];
+
+const fn speaker(name: &'static str, left: f64, right: f64) -> Speaker {
+    Speaker { name, left, right }
+}
+
+pub(crate) fn default_channel_mask(channels: u32) -> Option<u32> {
+    match channels {
+        1 => Some(0x0004),
+        2 => Some(0x0003),
+        3 => Some(0x0007),
+        4 => Some(0x0033),
+        5 => Some(0x0037),
+        6 => Some(0x003F),
+        7 => Some(0x070F),
+        8 => Some(0x063F),
+        _ => None,
+    }
+}
+
+pub(crate) fn parse_channel_mask(value: &str) -> Option<u32> {
+    let value = value.trim();
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    }
+}
+
+pub(crate) fn format_channel_mask(mask: u32) -> String {
+    format!("0x{:04X}", mask)
+}
+
+pub(crate) fn speaker_layout(channels: u32, mask: Option<u32>) -> Result<Vec<&'static Speaker>> {
+    let mask = mask
+        .or_else(|| default_channel_mask(channels))
+        .ok_or_else(|| format!("no default channel layout for {} channels", channels))?;
+    let speakers: Vec<&Speaker> = SPEAKERS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| mask & (1 << bit) != 0)
+        .map(|(_, speaker)| speaker)
+        .collect();
+    if speakers.len() != channels as usize {
+        return Err(format!(
+            "channel mask {} does not describe {} channels",
+            format_channel_mask(mask),
+            channels
+        ));
+    }
+    Ok(speakers)
+}
+
+pub(crate) fn describe_channel_layout(channels: u32, mask: Option<u32>) -> String {
+    match speaker_layout(channels, mask) {
+        Ok(speakers) => {
+            let names: Vec<&str> = speakers.iter().map(|speaker| speaker.name).collect();
+            format!("{} ch ({})", channels, names.join(" "))
+        }
+        Err(_) => match mask {
+            Some(mask) => format!("{} ch (mask {})", channels, format_channel_mask(mask)),
+            None => format!("{} ch", channels),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{describe_channel_layout, parse_channel_mask, speaker_layout};
+
+    #[test]
+    fn parse_channel_mask_accepts_hex_and_decimal() {
+        assert_eq!(parse_channel_mask("0x003F"), Some(0x3F));
+        assert_eq!(parse_channel_mask("0X060F"), Some(0x60F));
+        assert_eq!(parse_channel_mask("63"), Some(63));
+        assert_eq!(parse_channel_mask("surround"), None);
+    }
+
+    #[test]
+    fn layout_follows_mask_or_flac_default() {
+        assert_eq!(
+            describe_channel_layout(6, None),
+            "6 ch (FL FR FC LFE BL BR)"
+        );
+        assert_eq!(
+            describe_channel_layout(6, Some(0x60F)),
+            "6 ch (FL FR FC LFE SL SR)"
+        );
+        assert_eq!(describe_channel_layout(6, Some(0x3)), "6 ch (mask 0x0003)");
+        assert!(speaker_layout(9, None).is_err());
+    }
+}
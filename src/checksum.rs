@@ -0,0 +1,59 @@
+pub(crate) const AUDIO_MD5_TAG: &str = "AUDIOMD5";
+pub(crate) const SOURCE_MD5_TAG: &str = "SOURCEMD5";
+
+pub(crate) struct PcmHasher {
+    context: md5::Context,
+    bytes_per_sample: usize,
+    buffer: Vec<u8>,
+}
+
+impl PcmHasher {
+    pub(crate) fn new(bits_per_sample: u32) -> Self {
+        Self {
+            context: md5::Context::new(),
+            bytes_per_sample: bits_per_sample.div_ceil(8).clamp(1, 4) as usize,
+            buffer: Vec::new(),
+        }
+    }
+
+    pub(crate) fn update(&mut self, interleaved: &[i32]) {
+        self.buffer.clear();
+        self.buffer
+            .reserve(interleaved.len() * self.bytes_per_sample);
+        for sample in interleaved {
+            let bytes = sample.to_le_bytes();
+            self.buffer
+                .extend_from_slice(&bytes[..self.bytes_per_sample]);
+        }
+        self.context.consume(&self.buffer);
+    }
+
+    pub(crate) fn finish(self) -> [u8; 16] {
+        self.context.compute().0
+    }
+}
+
+pub(crate) fn format_md5(digest: &[u8; 16]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PcmHasher, format_md5};
+
+    #[test]
+    fn pcm_hasher_uses_little_endian_sample_width() {
+        let mut hasher = PcmHasher::new(16);
+        hasher.update(&[1, -1]);
+        let expected = md5::compute([0x01u8, 0x00, 0xff, 0xff]).0;
+        assert_eq!(hasher.finish(), expected);
+    }
+
+    #[test]
+    fn format_md5_is_lowercase_hex() {
+        let mut digest = [0u8; 16];
+        digest[0] = 0xab;
+        digest[15] = 0x01;
+        assert_eq!(format_md5(&digest), "ab000000000000000000000000000001");
+    }
+}
@@ -1,34 +1,531 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use crate::Result;
+use crate::apl::APL_EXTENSION;
+use crate::case::CaseStyle;
+use crate::cue::{CueStyle, cue_referenced_files, detect_cue_style};
+use crate::decoder::{
+    AUDIO_EXTENSIONS, DEFAULT_BLOCK_SIZE, STDIN_DISPLAY, STDIN_NAME, parse_block_size,
+    supported_audio_exts_label,
+};
+use crate::downmix::Downmix;
+use crate::fade::parse_fade_length;
+use crate::flac::{OutputFormat, Preset};
+use crate::gain::{GainSetting, parse_gain};
+use crate::i18n::{Lang, Msg, tr_with};
+use crate::jobs::{default_jobs, parse_jobs};
+use crate::lossy::{AlsoEncode, parse_also_encode};
+use crate::permissions::{Owner, parse_mode, parse_owner};
+use crate::picture::{ArtStrategy, CoverMode};
+use crate::priority::IoClass;
+use crate::remote::{is_url, url_extension};
+use crate::silence::{DEFAULT_SILENCE_THRESHOLD_DB, TrimMode, parse_silence_threshold};
+use crate::split::TrackNumbering;
+use crate::template::{DEFAULT_RENAME_TEMPLATE, DEFAULT_TRACK_TEMPLATE};
+use crate::ui::TimeFormat;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about)]
+#[command(author, version, about, args_conflicts_with_subcommands = true)]
+pub(crate) struct Cli {
+    #[command(subcommand)]
+    pub(crate) command: Option<Command>,
+    #[command(flatten)]
+    pub(crate) args: Args,
+    #[arg(long, global = true, value_enum, value_name = "LANG")]
+    pub(crate) lang: Option<Lang>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = TimeFormat::Msf,
+        value_name = "FORMAT"
+    )]
+    pub(crate) time_format: TimeFormat,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Command {
+    /// Split a cue + audio image into per-track FLAC files (default when no subcommand is given)
+    Split(Args),
+    /// Print the split plan without asking for confirmation or writing anything
+    Plan(PlanArgs),
+    /// Run a plan previously saved with `plan --out`
+    Execute(ExecuteArgs),
+    /// Join per-track FLAC files back into a single image with a cue sheet
+    Join(JoinArgs),
+    /// Rewrite tags and artwork of already split tracks from a cue sheet
+    Retag(RetagArgs),
+    /// Rename already split tracks from their tags using a filename template
+    Rename(RenameArgs),
+    /// Print stream info, tags, pictures and the track layout of an input file
+    Inspect(InspectArgs),
+    /// Write the cue sheet embedded in an audio file to a .cue file
+    ExtractCue(ExtractCueArgs),
+    /// Write a cue sheet for an image from a plain tracklist of titles and durations
+    MakeCue(MakeCueArgs),
+    /// Check already split tracks against a cue sheet and optionally the original image
+    Verify(VerifyArgs),
+    /// Compare two directories of split tracks: audio MD5s, lengths and tags
+    Diff(DiffArgs),
+    /// Report library versions, self-checks and settings for bug reports
+    Doctor,
+    /// Run a local HTTP server with a JSON API to submit and follow splits
+    Serve(ServeArgs),
+}
+
+#[derive(clap::Args, Debug)]
 pub(crate) struct Args {
     #[arg(long)]
     pub(crate) flac: Option<PathBuf>,
     #[arg(long)]
     pub(crate) cue: Option<PathBuf>,
+    #[arg(long, value_name = "TEXT", conflicts_with = "cue")]
+    pub(crate) cue_string: Option<String>,
+    #[arg(
+        long,
+        value_name = "POINTS",
+        value_parser = parse_split_points,
+        conflicts_with_all = ["cue", "cue_string"]
+    )]
+    pub(crate) split_at: Option<SplitPoints>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = ["flac", "cue", "cue_string", "split_at", "select"]
+    )]
+    pub(crate) manifest: Option<PathBuf>,
     #[arg(long, value_name = "ENCODING")]
     pub(crate) cue_encoding: Option<String>,
     #[arg(short = 'y', long)]
     pub(crate) yes: bool,
+    #[arg(long, conflicts_with_all = ["yes", "flac", "cue", "cue_string", "split_at"])]
+    pub(crate) select: bool,
     #[arg(short = 'o', long)]
     pub(crate) overwrite: bool,
-    #[arg(short = 'c', long, default_value_t = 5, value_parser = parse_compression_level)]
-    pub(crate) compression_level: u8,
+    #[arg(long, value_enum, value_name = "PRESET")]
+    pub(crate) preset: Option<Preset>,
+    #[arg(long, value_name = "NAME")]
+    pub(crate) profile: Option<String>,
+    #[arg(short = 'c', long, value_parser = parse_compression_level)]
+    pub(crate) compression_level: Option<u8>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = ["audio_md5", "update", "resume"]
+    )]
+    pub(crate) output_format: Option<OutputFormat>,
+    #[arg(long)]
+    pub(crate) verify_encoding: bool,
+    #[arg(long)]
+    pub(crate) seektable: bool,
+    #[arg(long, value_name = "BYTES")]
+    pub(crate) padding: Option<u32>,
+    #[arg(long, conflicts_with = "padding")]
+    pub(crate) keep_block_layout: bool,
+    #[arg(
+        long,
+        value_name = "FORMAT:DIR",
+        value_parser = parse_also_encode,
+        conflicts_with = "stdout"
+    )]
+    pub(crate) also_encode: Option<AlsoEncode>,
+    /// Albums split at the same time; spare jobs become encoder threads
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        default_value_t = default_jobs(),
+        value_parser = parse_jobs
+    )]
+    pub(crate) jobs: usize,
+    #[command(flatten)]
+    pub(crate) priority: PriorityArgs,
+    #[command(flatten)]
+    pub(crate) permissions: PermissionArgs,
+    #[command(flatten)]
+    pub(crate) progress: ProgressArgs,
+    #[arg(
+        long,
+        value_name = "SAMPLES",
+        default_value_t = DEFAULT_BLOCK_SIZE,
+        value_parser = parse_block_size
+    )]
+    pub(crate) block_size: usize,
     #[arg(value_name = "DIR")]
     pub(crate) dir: Option<PathBuf>,
     #[arg(long, value_name = "FILE")]
     pub(crate) picture: Option<PathBuf>,
     #[arg(long, conflicts_with = "picture")]
     pub(crate) no_picture: bool,
-    #[arg(long, conflicts_with = "rename_original")]
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        default_value_t = CoverMode::Embed,
+        conflicts_with = "no_picture"
+    )]
+    pub(crate) cover: CoverMode,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STRATEGY",
+        default_value_t = ArtStrategy::Embed,
+        conflicts_with_all = ["no_picture", "cover", "stdout"]
+    )]
+    pub(crate) art_strategy: ArtStrategy,
+    #[arg(long, conflicts_with = "stdout")]
+    pub(crate) write_cue: bool,
+    #[arg(long, group = "source_action")]
     pub(crate) delete_original: bool,
-    #[arg(short = 'r', long, conflicts_with = "delete_original")]
+    #[arg(short = 'r', long, group = "source_action")]
     pub(crate) rename_original: bool,
+    #[arg(
+        long,
+        value_name = "TEMPLATE",
+        default_value = DEFAULT_RENAME_TEMPLATE,
+        requires = "rename_original"
+    )]
+    pub(crate) rename_template: String,
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        requires = "rename_original",
+        conflicts_with = "rename_template"
+    )]
+    pub(crate) rename_suffix: Option<String>,
+    #[arg(long, group = "source_action")]
+    pub(crate) trash_original: bool,
+    #[arg(long, value_name = "DIR", group = "source_action")]
+    pub(crate) archive_original: Option<PathBuf>,
+    #[arg(long)]
+    pub(crate) include_sidecars: bool,
+    #[arg(long, value_name = "DIR")]
+    pub(crate) mirror_root: Option<PathBuf>,
+    #[arg(long)]
+    pub(crate) update: bool,
+    #[arg(long)]
+    pub(crate) resume: bool,
+    #[arg(long, conflicts_with_all = ["resume", "write_cue"])]
+    pub(crate) split_indexes: bool,
+    #[arg(long)]
+    pub(crate) check_lossy: bool,
+    #[arg(long)]
+    pub(crate) check_tags: bool,
+    #[arg(long)]
+    pub(crate) strict: bool,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        default_value_t = CaseStyle::Keep
+    )]
+    pub(crate) normalize_case: CaseStyle,
+    #[arg(long)]
+    pub(crate) no_disc_number: bool,
+    #[arg(long)]
+    pub(crate) tags_from_path: bool,
+    #[arg(long)]
+    pub(crate) keep_replaygain: bool,
+    #[arg(long, value_enum, value_name = "STYLE")]
+    pub(crate) prefer_cue_style: Option<CueStyle>,
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_parser = parse_track_selection,
+        conflicts_with = "source_action"
+    )]
+    pub(crate) tracks: Option<TrackSelection>,
+    #[arg(
+        long,
+        value_name = "LIST",
+        value_parser = parse_track_selection,
+        conflicts_with = "source_action"
+    )]
+    pub(crate) skip_tracks: Option<TrackSelection>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "MODE",
+        default_value_t = TrackNumbering::Original,
+        requires = "skip_tracks"
+    )]
+    pub(crate) skip_numbering: TrackNumbering,
+    #[arg(
+        long,
+        requires_all = ["tracks", "yes"],
+        conflicts_with_all = ["source_action", "audio_md5", "update", "resume"]
+    )]
+    pub(crate) stdout: bool,
+    #[arg(long, requires = "yes", conflicts_with_all = ["stdout", "select"])]
+    pub(crate) print0: bool,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) tags_from: Option<PathBuf>,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) genre_map: Option<PathBuf>,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) tag_rules: Option<PathBuf>,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) sample_offsets: Option<PathBuf>,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) track_lengths: Option<PathBuf>,
+    #[arg(long)]
+    pub(crate) allow_truncated: bool,
+    #[arg(long)]
+    pub(crate) clamp_to_audio: bool,
+    #[arg(long, value_name = "FRAMES", value_parser = parse_length_tolerance)]
+    pub(crate) length_tolerance: Option<u64>,
+    #[arg(long)]
+    pub(crate) strict_cdda: bool,
+    #[arg(
+        long,
+        value_name = "SAMPLES",
+        default_value_t = 0,
+        allow_hyphen_values = true
+    )]
+    pub(crate) offset: i64,
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, default_missing_value = "both")]
+    pub(crate) trim_silence: Option<TrimMode>,
+    #[arg(
+        long,
+        value_name = "DB",
+        default_value_t = DEFAULT_SILENCE_THRESHOLD_DB,
+        allow_hyphen_values = true,
+        value_parser = parse_silence_threshold
+    )]
+    pub(crate) silence_threshold: f64,
+    #[arg(long, value_enum, value_name = "LAYOUT")]
+    pub(crate) downmix: Option<Downmix>,
+    #[arg(
+        long,
+        value_name = "[TRACK:]GAIN",
+        allow_hyphen_values = true,
+        value_parser = parse_gain
+    )]
+    pub(crate) gain: Vec<GainSetting>,
+    #[arg(long, value_name = "DURATION", value_parser = parse_fade_length)]
+    pub(crate) fade_in: Option<Duration>,
+    #[arg(long, value_name = "DURATION", value_parser = parse_fade_length)]
+    pub(crate) fade_out: Option<Duration>,
+    #[arg(long)]
+    pub(crate) audio_md5: bool,
+    #[arg(long)]
+    pub(crate) print_config: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct PlanArgs {
+    #[command(flatten)]
+    pub(crate) split: Args,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) out: Option<PathBuf>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ExecuteArgs {
+    #[arg(value_name = "PLAN")]
+    pub(crate) plan: PathBuf,
+    #[arg(short = 'y', long)]
+    pub(crate) yes: bool,
+    #[arg(short = 'o', long)]
+    pub(crate) overwrite: bool,
+    #[arg(long)]
+    pub(crate) allow_truncated: bool,
+    /// Albums split at the same time; spare jobs become encoder threads
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        default_value_t = default_jobs(),
+        value_parser = parse_jobs
+    )]
+    pub(crate) jobs: usize,
+    #[command(flatten)]
+    pub(crate) priority: PriorityArgs,
+    #[command(flatten)]
+    pub(crate) progress: ProgressArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct RetagArgs {
+    #[arg(value_name = "DIR")]
+    pub(crate) dir: PathBuf,
+    #[arg(long)]
+    pub(crate) cue: Option<PathBuf>,
+    #[arg(long, value_name = "ENCODING")]
+    pub(crate) cue_encoding: Option<String>,
+    #[arg(short = 'y', long)]
+    pub(crate) yes: bool,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) picture: Option<PathBuf>,
+    #[arg(long, conflicts_with = "picture")]
+    pub(crate) no_picture: bool,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) tags_from: Option<PathBuf>,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) genre_map: Option<PathBuf>,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) tag_rules: Option<PathBuf>,
+    #[arg(
+        long,
+        value_enum,
+        value_name = "STYLE",
+        default_value_t = CaseStyle::Keep
+    )]
+    pub(crate) normalize_case: CaseStyle,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct RenameArgs {
+    #[arg(value_name = "DIR")]
+    pub(crate) dir: PathBuf,
+    #[arg(short = 't', long, default_value = DEFAULT_TRACK_TEMPLATE)]
+    pub(crate) template: String,
+    #[arg(short = 'y', long)]
+    pub(crate) yes: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct InspectArgs {
+    #[arg(value_name = "FILE")]
+    pub(crate) file: PathBuf,
+    #[arg(long)]
+    pub(crate) cue: Option<PathBuf>,
+    #[arg(long, value_name = "ENCODING")]
+    pub(crate) cue_encoding: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ExtractCueArgs {
+    #[arg(value_name = "FILE")]
+    pub(crate) file: PathBuf,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) out: Option<PathBuf>,
+    #[arg(short = 'o', long)]
+    pub(crate) overwrite: bool,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct MakeCueArgs {
+    #[arg(value_name = "TRACKLIST")]
+    pub(crate) tracklist: PathBuf,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) flac: PathBuf,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) out: Option<PathBuf>,
+    #[arg(long)]
+    pub(crate) performer: Option<String>,
+    #[arg(long)]
+    pub(crate) title: Option<String>,
+    #[arg(long)]
+    pub(crate) date: Option<String>,
+    #[arg(long)]
+    pub(crate) genre: Option<String>,
+    #[arg(short = 'o', long)]
+    pub(crate) overwrite: bool,
+    #[arg(long)]
+    pub(crate) split: bool,
+    #[arg(last = true, value_name = "SPLIT_OPTIONS", requires = "split")]
+    pub(crate) split_options: Vec<OsString>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct VerifyArgs {
+    #[arg(value_name = "DIR")]
+    pub(crate) dir: PathBuf,
+    #[arg(long)]
+    pub(crate) cue: Option<PathBuf>,
+    #[arg(long, value_name = "ENCODING")]
+    pub(crate) cue_encoding: Option<String>,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) flac: Option<PathBuf>,
+    #[arg(long)]
+    pub(crate) md5: bool,
+    #[arg(long, value_name = "FRAMES", default_value_t = 0)]
+    pub(crate) tolerance: u64,
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        default_value_t = default_jobs(),
+        value_parser = parse_jobs
+    )]
+    pub(crate) jobs: usize,
+    #[command(flatten)]
+    pub(crate) priority: PriorityArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct DiffArgs {
+    #[arg(value_name = "OLD")]
+    pub(crate) old: PathBuf,
+    #[arg(value_name = "NEW")]
+    pub(crate) new: PathBuf,
+    #[arg(long)]
+    pub(crate) json: bool,
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        default_value_t = default_jobs(),
+        value_parser = parse_jobs
+    )]
+    pub(crate) jobs: usize,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ServeArgs {
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8717")]
+    pub(crate) listen: SocketAddr,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct JoinArgs {
+    #[arg(value_name = "DIR")]
+    pub(crate) dir: PathBuf,
+    #[arg(long, value_name = "FILE")]
+    pub(crate) out: Option<PathBuf>,
+    #[arg(short = 'y', long)]
+    pub(crate) yes: bool,
+    #[arg(short = 'o', long)]
+    pub(crate) overwrite: bool,
+    #[arg(short = 'c', long, default_value_t = 5, value_parser = parse_compression_level)]
+    pub(crate) compression_level: u8,
+    #[command(flatten)]
+    pub(crate) priority: PriorityArgs,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct PriorityArgs {
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(i32).range(0..=19))]
+    pub(crate) nice: Option<i32>,
+    #[arg(long, value_enum, value_name = "CLASS")]
+    pub(crate) ionice: Option<IoClass>,
+    #[arg(long)]
+    pub(crate) background: bool,
+}
+
+#[derive(clap::Args, Clone, Debug, Default)]
+pub(crate) struct PermissionArgs {
+    #[arg(long, value_name = "USER:GROUP", value_parser = parse_owner)]
+    pub(crate) owner: Option<Owner>,
+    #[arg(long, value_name = "MODE", value_parser = parse_mode)]
+    pub(crate) file_mode: Option<u32>,
+    #[arg(long, value_name = "MODE", value_parser = parse_mode)]
+    pub(crate) dir_mode: Option<u32>,
+}
+
+#[derive(clap::Args, Debug)]
+pub(crate) struct ProgressArgs {
+    #[arg(long, value_name = "FD", conflicts_with = "progress_socket")]
+    pub(crate) progress_fd: Option<i32>,
+    #[arg(long, value_name = "PATH")]
+    pub(crate) progress_socket: Option<PathBuf>,
 }
 
 #[derive(Clone)]
@@ -43,6 +540,137 @@ pub(crate) struct InputPair {
     pub(crate) cue: InputPath,
 }
 
+pub(crate) const CUE_STRING_DISPLAY: &str = "<cue string>";
+pub(crate) const SPLIT_POINTS_DISPLAY: &str = "<split points>";
+
+pub(crate) struct CueCandidate {
+    pub(crate) cue: InputPath,
+    pub(crate) style: CueStyle,
+}
+
+pub(crate) type CueChooser = dyn Fn(&InputPath, &[CueCandidate]) -> Result<usize>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct TrackSelection(Vec<(u32, u32)>);
+
+impl TrackSelection {
+    pub(crate) fn contains(&self, number: u32) -> bool {
+        self.0
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&number))
+    }
+}
+
+pub(crate) fn parse_track_selection(value: &str) -> Result<TrackSelection> {
+    let parse_number = |text: &str| {
+        text.trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|number| *number > 0)
+            .ok_or_else(|| format!("invalid track number {:?} in {:?}", text.trim(), value))
+    };
+    let mut ranges = Vec::new();
+    for part in value.split(',') {
+        let range = match part.split_once('-') {
+            Some((first, last)) => (parse_number(first)?, parse_number(last)?),
+            None => {
+                let number = parse_number(part)?;
+                (number, number)
+            }
+        };
+        if range.0 > range.1 {
+            return Err(format!("invalid track range {:?}", part.trim()));
+        }
+        ranges.push(range);
+    }
+    Ok(TrackSelection(ranges))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SplitPoint {
+    Millis(u64),
+    Sample(u64),
+}
+
+impl std::fmt::Display for SplitPoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            SplitPoint::Millis(millis) => write!(
+                f,
+                "{}:{:02}.{:03}",
+                millis / 60_000,
+                millis / 1000 % 60,
+                millis % 1000
+            ),
+            SplitPoint::Sample(sample) => write!(f, "sample {}", sample),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SplitPoints(Vec<SplitPoint>);
+
+impl SplitPoints {
+    pub(crate) fn points(&self) -> &[SplitPoint] {
+        &self.0
+    }
+}
+
+pub(crate) fn parse_split_points(value: &str) -> Result<SplitPoints> {
+    value
+        .split(',')
+        .map(parse_split_point)
+        .collect::<Result<_>>()
+        .map(SplitPoints)
+}
+
+fn parse_split_point(text: &str) -> Result<SplitPoint> {
+    let text = text.trim();
+    let invalid = || {
+        format!(
+            "invalid split point {:?} (expected M:SS[.mmm] or a sample)",
+            text
+        )
+    };
+    if !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit()) {
+        return text.parse().map(SplitPoint::Sample).map_err(|_| invalid());
+    }
+
+    let parts: Vec<&str> = text.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return Err(invalid());
+    }
+    let number = |part: &str| {
+        (!part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()))
+            .then(|| part.parse::<u64>().ok())
+            .flatten()
+            .ok_or_else(invalid)
+    };
+    let (seconds, fraction) = parts[parts.len() - 1]
+        .split_once('.')
+        .unwrap_or((parts[parts.len() - 1], ""));
+    if seconds.len() != 2 || fraction.len() > 3 {
+        return Err(invalid());
+    }
+    let seconds = number(seconds)?;
+    if seconds >= 60 {
+        return Err(invalid());
+    }
+    let millis = if fraction.is_empty() {
+        0
+    } else {
+        number(fraction)? * 10u64.pow(3 - fraction.len() as u32)
+    };
+    let mut minutes = number(parts[parts.len() - 2])?;
+    if parts.len() == 3 {
+        if minutes >= 60 {
+            return Err(invalid());
+        }
+        minutes += number(parts[0])? * 60;
+    }
+    Ok(SplitPoint::Millis((minutes * 60 + seconds) * 1000 + millis))
+}
+
 pub(crate) fn parse_compression_level(value: &str) -> Result<u8> {
     let trimmed = value.trim();
     if trimmed.eq_ignore_ascii_case("max") {
@@ -57,12 +685,28 @@ pub(crate) fn parse_compression_level(value: &str) -> Result<u8> {
     Ok(level)
 }
 
-fn is_supported_audio_ext(ext: &str) -> bool {
-    matches!(ext, "flac" | "wv")
+// An hour of CD frames; a larger difference means the cue belongs to another
+// image.
+const MAX_LENGTH_TOLERANCE: u64 = 75 * 60 * 60;
+
+pub(crate) fn parse_length_tolerance(value: &str) -> Result<u64> {
+    let frames: u64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid length tolerance {:?} (expected CD frames)", value))?;
+    if frames > MAX_LENGTH_TOLERANCE {
+        return Err(format!(
+            "length tolerance must be at most {} frames (one hour)",
+            MAX_LENGTH_TOLERANCE
+        ));
+    }
+    Ok(frames)
 }
 
-fn supported_audio_exts_label() -> &'static str {
-    ".flac/.wv"
+// .apl link files are split when named, but not picked up by the scan, which
+// would find the image they point to as well.
+fn is_supported_audio_ext(ext: &str) -> bool {
+    AUDIO_EXTENSIONS.contains(&ext) || ext == APL_EXTENSION
 }
 
 pub(crate) fn resolve_input_path(
@@ -72,13 +716,16 @@ pub(crate) fn resolve_input_path(
     extension: &str,
 ) -> Result<InputPath> {
     if let Some(path) = provided {
+        if is_url(path) {
+            return Ok(url_input_path(path));
+        }
         let abs = if path.is_absolute() {
             path.clone()
         } else {
             base_dir_abs.join(path)
         };
         if !abs.exists() {
-            return Err(format!("file not found: {}", abs.display()));
+            return Err(tr_with(Msg::FileNotFound, &[&abs.display()]));
         }
         let display = display_path(display_base_abs, &abs);
         return Ok(InputPath { abs, display });
@@ -89,19 +736,56 @@ pub(crate) fn resolve_input_path(
     Ok(InputPath { abs, display })
 }
 
+pub(crate) fn resolve_cueless_pair(
+    base_dir_abs: &Path,
+    display_base_abs: Option<&Path>,
+    flac: Option<&PathBuf>,
+    cue_display: &str,
+) -> Result<InputPair> {
+    Ok(InputPair {
+        flac: resolve_audio_input_path(base_dir_abs, display_base_abs, flac)?,
+        cue: InputPath {
+            abs: base_dir_abs.join(cue_display),
+            display: PathBuf::from(cue_display),
+        },
+    })
+}
+
+fn url_input_path(url: &Path) -> InputPath {
+    InputPath {
+        abs: url.to_path_buf(),
+        display: url.to_path_buf(),
+    }
+}
+
 fn resolve_audio_input_path(
     base_dir_abs: &Path,
     display_base_abs: Option<&Path>,
     provided: Option<&PathBuf>,
 ) -> Result<InputPath> {
     if let Some(path) = provided {
+        if path.as_os_str() == STDIN_NAME {
+            return Ok(InputPath {
+                abs: base_dir_abs.join(STDIN_NAME),
+                display: PathBuf::from(STDIN_DISPLAY),
+            });
+        }
+        if is_url(path) {
+            if url_extension(&path.to_string_lossy()) != "flac" {
+                return Err(format!(
+                    "unsupported audio URL {} (only .flac can be streamed)",
+                    path.display()
+                ));
+            }
+            return Ok(url_input_path(path));
+        }
         let abs = if path.is_absolute() {
             path.clone()
         } else {
             base_dir_abs.join(path)
         };
         if !abs.exists() {
-            return Err(format!("file not found: {}", abs.display()));
+            return Err(tr_with(Msg::FileNotFound, &[&abs.display()]));
         }
         let ext = abs
             .extension()
@@ -109,32 +793,33 @@ fn resolve_audio_input_path(
             .map(|ext| ext.to_ascii_lowercase())
             .unwrap_or_default();
         if !is_supported_audio_ext(&ext) {
-            return Err(format!(
-                "unsupported audio input {} (expected {})",
-                abs.display(),
-                supported_audio_exts_label()
+            return Err(tr_with(
+                Msg::UnsupportedAudio,
+                &[&abs.display(), &supported_audio_exts_label()],
             ));
         }
         let display = display_path(display_base_abs, &abs);
         return Ok(InputPath { abs, display });
     }
 
-    let matches = find_files_with_extensions(base_dir_abs, &["flac", "wv"])?;
+    let matches = find_files_with_extensions(base_dir_abs, AUDIO_EXTENSIONS)?;
     match matches.len() {
-        0 => Err(format!(
-            "no {} file found in {}",
-            supported_audio_exts_label(),
-            base_dir_abs.display()
+        0 => Err(tr_with(
+            Msg::NoFileFound,
+            &[&supported_audio_exts_label(), &base_dir_abs.display()],
         )),
         1 => {
             let abs = matches[0].clone();
             let display = display_path(display_base_abs, &abs);
             Ok(InputPath { abs, display })
         }
-        _ => Err(format!(
-            "multiple {} files found in {}, please specify --flac",
-            supported_audio_exts_label(),
-            base_dir_abs.display()
+        _ => Err(tr_with(
+            Msg::MultipleFilesFound,
+            &[
+                &supported_audio_exts_label(),
+                &base_dir_abs.display(),
+                &"--flac",
+            ],
         )),
     }
 }
@@ -163,7 +848,7 @@ fn resolve_or_find_file(
             base_dir.join(path)
         };
         if !resolved.exists() {
-            return Err(format!("file not found: {}", resolved.display()));
+            return Err(tr_with(Msg::FileNotFound, &[&resolved.display()]));
         }
         return Ok(resolved);
     }
@@ -187,17 +872,18 @@ fn resolve_or_find_file(
     }
 
     match matches.len() {
-        0 => Err(format!(
-            "no .{} file found in {}",
-            extension,
-            base_dir.display()
+        0 => Err(tr_with(
+            Msg::NoFileFound,
+            &[&format!(".{}", extension), &base_dir.display()],
         )),
         1 => Ok(matches.remove(0)),
-        _ => Err(format!(
-            "multiple .{} files found in {}, please specify --{}",
-            extension,
-            base_dir.display(),
-            extension
+        _ => Err(tr_with(
+            Msg::MultipleFilesFound,
+            &[
+                &format!(".{}", extension),
+                &base_dir.display(),
+                &format!("--{}", extension),
+            ],
         )),
     }
 }
@@ -205,6 +891,7 @@ fn resolve_or_find_file(
 pub(crate) fn resolve_matching_pairs(
     base_dir_abs: &Path,
     display_base_abs: Option<&Path>,
+    choose_cue: &CueChooser,
 ) -> Result<Vec<InputPair>> {
     let read_dir = std::fs::read_dir(base_dir_abs).map_err(|err| {
         format!(
@@ -215,7 +902,7 @@ pub(crate) fn resolve_matching_pairs(
     })?;
 
     let mut audio_by_stem = BTreeMap::<String, PathBuf>::new();
-    let mut cue_by_stem = BTreeMap::<String, PathBuf>::new();
+    let mut cues = Vec::<(String, PathBuf)>::new();
 
     for entry in read_dir {
         let entry = entry.map_err(|err| format!("failed to read directory entry: {}", err))?;
@@ -228,18 +915,17 @@ pub(crate) fn resolve_matching_pairs(
             Some(ext) => ext.to_ascii_lowercase(),
             None => continue,
         };
-        if !is_supported_audio_ext(&ext) && ext != "cue" {
+        if !AUDIO_EXTENSIONS.contains(&ext.as_str()) && ext != "cue" {
             continue;
         }
 
         let stem = pairing_stem_for_extension(&path, &ext)?;
 
-        let target = if is_supported_audio_ext(&ext) {
-            &mut audio_by_stem
-        } else {
-            &mut cue_by_stem
-        };
-        if let Some(existing) = target.insert(stem.clone(), path.clone()) {
+        if ext == "cue" {
+            cues.push((stem, path));
+            continue;
+        }
+        if let Some(existing) = audio_by_stem.insert(stem.clone(), path.clone()) {
             return Err(format!(
                 "multiple {} files with basename {:?}: {} and {}",
                 ext,
@@ -251,65 +937,101 @@ pub(crate) fn resolve_matching_pairs(
     }
 
     if audio_by_stem.is_empty() {
-        return Err(format!(
-            "no {} file found in {}",
-            supported_audio_exts_label(),
-            base_dir_abs.display()
+        return Err(tr_with(
+            Msg::NoFileFound,
+            &[&supported_audio_exts_label(), &base_dir_abs.display()],
+        ));
+    }
+    if cues.is_empty() {
+        return Err(tr_with(
+            Msg::NoFileFound,
+            &[&".cue", &base_dir_abs.display()],
         ));
     }
-    if cue_by_stem.is_empty() {
-        return Err(format!("no .cue file found in {}", base_dir_abs.display()));
+
+    // A cue is paired by basename, or else with the image its FILE line names,
+    // so "Album.cue" and "Album (gaps).cue" can both belong to "Album.flac".
+    let single_audio = audio_by_stem
+        .keys()
+        .next()
+        .filter(|_| audio_by_stem.len() == 1);
+    let mut cues_by_stem = BTreeMap::<String, Vec<PathBuf>>::new();
+    let mut unmatched_cues = Vec::new();
+    let cue_count = cues.len();
+    for (stem, path) in cues {
+        let target = if audio_by_stem.contains_key(&stem) {
+            Some(stem.clone())
+        } else {
+            single_audio
+                .cloned()
+                .or_else(|| referenced_audio_stem(&path, &audio_by_stem))
+        };
+        match target {
+            Some(target) => cues_by_stem.entry(target).or_default().push(path),
+            None => unmatched_cues.push(stem),
+        }
     }
-    if audio_by_stem.len() != cue_by_stem.len() {
+
+    let missing_cue: Vec<&str> = audio_by_stem
+        .keys()
+        .filter(|stem| !cues_by_stem.contains_key(*stem))
+        .map(String::as_str)
+        .collect();
+    if (!missing_cue.is_empty() || !unmatched_cues.is_empty()) && audio_by_stem.len() != cue_count {
         return Err(format!(
             "found {} {} files but {} .cue files in {}; counts must match",
             audio_by_stem.len(),
             supported_audio_exts_label(),
-            cue_by_stem.len(),
+            cue_count,
             base_dir_abs.display()
         ));
     }
-
-    let missing_cue: Vec<&str> = audio_by_stem
-        .keys()
-        .filter(|stem| !cue_by_stem.contains_key(*stem))
-        .map(String::as_str)
-        .collect();
     if !missing_cue.is_empty() {
         return Err(format!(
             "missing .cue file(s) for basename(s): {}",
             missing_cue.join(", ")
         ));
     }
-
-    let missing_audio: Vec<&str> = cue_by_stem
-        .keys()
-        .filter(|stem| !audio_by_stem.contains_key(*stem))
-        .map(String::as_str)
-        .collect();
-    if !missing_audio.is_empty() {
+    if !unmatched_cues.is_empty() {
         return Err(format!(
             "missing {} file(s) for basename(s): {}",
             supported_audio_exts_label(),
-            missing_audio.join(", ")
+            unmatched_cues.join(", ")
         ));
     }
 
+    let input_path = |abs: PathBuf| InputPath {
+        display: display_path(display_base_abs, &abs),
+        abs,
+    };
     let mut pairs = Vec::with_capacity(audio_by_stem.len());
     for (stem, flac_abs) in audio_by_stem {
-        let cue_abs = cue_by_stem
-            .get(&stem)
-            .ok_or_else(|| format!("missing .cue file for basename {}", stem))?
-            .clone();
+        let mut cue_paths = cues_by_stem
+            .remove(&stem)
+            .ok_or_else(|| format!("missing .cue file for basename {}", stem))?;
+        let flac = input_path(flac_abs);
+        let cue_abs = if cue_paths.len() == 1 {
+            cue_paths.remove(0)
+        } else {
+            cue_paths.sort();
+            let candidates: Vec<CueCandidate> = cue_paths
+                .into_iter()
+                .map(|path| CueCandidate {
+                    style: detect_cue_style(&read_cue_text(&path)),
+                    cue: input_path(path),
+                })
+                .collect();
+            let index = choose_cue(&flac, &candidates)?;
+            candidates
+                .into_iter()
+                .nth(index)
+                .ok_or_else(|| format!("no cue sheet chosen for {}", flac.display.display()))?
+                .cue
+                .abs
+        };
         pairs.push(InputPair {
-            flac: InputPath {
-                display: display_path(display_base_abs, &flac_abs),
-                abs: flac_abs,
-            },
-            cue: InputPath {
-                display: display_path(display_base_abs, &cue_abs),
-                abs: cue_abs,
-            },
+            flac,
+            cue: input_path(cue_abs),
         });
     }
 
@@ -317,6 +1039,30 @@ pub(crate) fn resolve_matching_pairs(
     Ok(pairs)
 }
 
+fn read_cue_text(path: &Path) -> String {
+    std::fs::read(path)
+        .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        .unwrap_or_default()
+}
+
+fn referenced_audio_stem(
+    cue_path: &Path,
+    audio_by_stem: &BTreeMap<String, PathBuf>,
+) -> Option<String> {
+    let referenced = cue_referenced_files(&read_cue_text(cue_path));
+    audio_by_stem
+        .iter()
+        .find(|(_, audio)| {
+            let Some(name) = audio.file_name().and_then(|name| name.to_str()) else {
+                return false;
+            };
+            referenced
+                .iter()
+                .any(|candidate| candidate.eq_ignore_ascii_case(name))
+        })
+        .map(|(stem, _)| stem.clone())
+}
+
 fn sort_pairs_by_audio_file_name(pairs: &mut [InputPair]) {
     pairs.sort_by_cached_key(|pair| {
         let file_name = pair
@@ -334,6 +1080,7 @@ pub(crate) fn resolve_input_pairs(
     display_base_abs: Option<&Path>,
     flac: Option<&PathBuf>,
     cue: Option<&PathBuf>,
+    choose_cue: &CueChooser,
 ) -> Result<Vec<InputPair>> {
     if flac.is_some() || cue.is_some() {
         return Ok(vec![InputPair {
@@ -342,7 +1089,7 @@ pub(crate) fn resolve_input_pairs(
         }]);
     }
 
-    let flacs = find_files_with_extensions(base_dir_abs, &["flac", "wv"])?;
+    let flacs = find_files_with_extensions(base_dir_abs, AUDIO_EXTENSIONS)?;
     let cues = find_files_with_extension(base_dir_abs, "cue")?;
     if flacs.len() == 1 && cues.len() == 1 {
         let flac_abs = flacs[0].clone();
@@ -359,13 +1106,25 @@ pub(crate) fn resolve_input_pairs(
         }]);
     }
 
-    resolve_matching_pairs(base_dir_abs, display_base_abs)
+    resolve_matching_pairs(base_dir_abs, display_base_abs, choose_cue)
 }
 
 fn find_files_with_extension(base_dir_abs: &Path, extension: &str) -> Result<Vec<PathBuf>> {
     find_files_with_extensions(base_dir_abs, &[extension])
 }
 
+pub(crate) fn find_track_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut paths = find_files_with_extension(dir, "flac")?;
+    paths.sort_by_cached_key(|path| {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        (name.to_ascii_lowercase(), name)
+    });
+    Ok(paths)
+}
+
 fn find_files_with_extensions(base_dir_abs: &Path, extensions: &[&str]) -> Result<Vec<PathBuf>> {
     let mut matches = Vec::new();
     let read_dir = std::fs::read_dir(base_dir_abs).map_err(|err| {
@@ -408,7 +1167,7 @@ fn pairing_stem_for_extension(path: &Path, extension: &str) -> Result<String> {
 
 fn strip_known_audio_suffix(stem: &str) -> &str {
     const KNOWN_AUDIO_EXTS: &[&str] = &[
-        "flac", "wv", "ape", "wav", "tta", "alac", "aiff", "aif", "m4a", "mp3", "ogg",
+        "flac", "wv", "ape", "wav", "tta", "alac", "aiff", "aif", "aifc", "m4a", "mp3", "ogg",
     ];
     let Some((base, suffix)) = stem.rsplit_once('.') else {
         return stem;
@@ -423,13 +1182,109 @@ fn strip_known_audio_suffix(stem: &str) -> &str {
 #[cfg(test)]
 mod tests {
     use super::{
-        InputPair, InputPath, resolve_input_pairs, sort_pairs_by_audio_file_name,
-        strip_known_audio_suffix,
+        Cli, Command, CueCandidate, InputPair, InputPath, SplitPoint, parse_length_tolerance,
+        parse_split_points, parse_track_selection, resolve_input_pairs,
+        sort_pairs_by_audio_file_name, strip_known_audio_suffix,
     };
+    use crate::Result;
+    use crate::cue::CueStyle;
+    use clap::Parser;
     use std::fs;
     use std::path::PathBuf;
     use std::time::{SystemTime, UNIX_EPOCH};
 
+    #[test]
+    fn bare_dir_and_split_subcommand_parse_the_same_args() {
+        let bare = Cli::try_parse_from(["flac-cue-split", "-y", "Album"]).unwrap();
+        assert!(bare.command.is_none());
+        assert_eq!(bare.args.dir, Some(PathBuf::from("Album")));
+        assert!(bare.args.yes);
+
+        let split = Cli::try_parse_from(["flac-cue-split", "split", "-y", "Album"]).unwrap();
+        match split.command {
+            Some(Command::Split(args)) => {
+                assert_eq!(args.dir, Some(PathBuf::from("Album")));
+                assert!(args.yes);
+            }
+            other => panic!("unexpected command: {:?}", other),
+        }
+
+        let plan = Cli::try_parse_from(["flac-cue-split", "plan", "Album"]).unwrap();
+        assert!(matches!(plan.command, Some(Command::Plan(_))));
+    }
+
+    #[test]
+    fn split_points_accept_timestamps_and_samples() {
+        assert_eq!(
+            parse_split_points("0:00, 4:31.2,1:02:03.045,44100")
+                .unwrap()
+                .points(),
+            [
+                SplitPoint::Millis(0),
+                SplitPoint::Millis(271_200),
+                SplitPoint::Millis(3_723_045),
+                SplitPoint::Sample(44100),
+            ]
+        );
+        assert_eq!(SplitPoint::Millis(271_200).to_string(), "4:31.200");
+        assert!(parse_split_points("4:61").is_err());
+        assert!(parse_split_points("4:3").is_err());
+        assert!(parse_split_points("1:60:00").is_err());
+        assert!(parse_split_points("0:00,").is_err());
+        assert!(parse_split_points("-5").is_err());
+    }
+
+    #[test]
+    fn track_selection_accepts_numbers_and_ranges() {
+        let selection = parse_track_selection("2,5-7").unwrap();
+        assert!(selection.contains(2));
+        assert!(selection.contains(6));
+        assert!(!selection.contains(3));
+        assert!(parse_track_selection("0").is_err());
+        assert!(parse_track_selection("7-5").is_err());
+        assert!(parse_track_selection("two").is_err());
+    }
+
+    #[test]
+    fn length_tolerance_rejects_absurd_values() {
+        assert_eq!(parse_length_tolerance("75"), Ok(75));
+        assert_eq!(parse_length_tolerance("270000"), Ok(270_000));
+        assert!(parse_length_tolerance("270001").is_err());
+        assert!(parse_length_tolerance("18446744073709551615").is_err());
+        assert!(parse_length_tolerance("-1").is_err());
+    }
+
+    #[test]
+    fn stdout_needs_a_track_selection_and_yes() {
+        assert!(Cli::try_parse_from(["flac-cue-split", "--stdout", "-y", "Album"]).is_err());
+        assert!(
+            Cli::try_parse_from(["flac-cue-split", "--stdout", "--tracks", "3", "Album"]).is_err()
+        );
+        let cli =
+            Cli::try_parse_from(["flac-cue-split", "--stdout", "--tracks", "3", "-y", "Album"])
+                .unwrap();
+        assert!(cli.args.stdout);
+    }
+
+    #[test]
+    fn print0_needs_yes_and_excludes_stdout() {
+        assert!(Cli::try_parse_from(["flac-cue-split", "--print0", "Album"]).is_err());
+        assert!(
+            Cli::try_parse_from([
+                "flac-cue-split",
+                "--print0",
+                "--stdout",
+                "--tracks",
+                "3",
+                "-y",
+                "Album"
+            ])
+            .is_err()
+        );
+        let cli = Cli::try_parse_from(["flac-cue-split", "--print0", "-y", "Album"]).unwrap();
+        assert!(cli.args.print0);
+    }
+
     #[test]
     fn strip_known_audio_suffix_for_cue_basename() {
         assert_eq!(strip_known_audio_suffix("Album"), "Album");
@@ -446,7 +1301,7 @@ mod tests {
         fs::write(dir.join("One Name.flac"), b"").unwrap();
         fs::write(dir.join("Different Name.wv.cue"), b"").unwrap();
 
-        let pairs = resolve_input_pairs(&dir, Some(&dir), None, None).unwrap();
+        let pairs = resolve_input_pairs(&dir, Some(&dir), None, None, &first_cue).unwrap();
         assert_eq!(pairs.len(), 1);
         assert_eq!(
             pairs[0].flac.abs.file_name().unwrap().to_string_lossy(),
@@ -467,7 +1322,7 @@ mod tests {
         fs::write(dir.join("Album.wv"), b"").unwrap();
         fs::write(dir.join("Album.cue"), b"").unwrap();
 
-        let pairs = resolve_input_pairs(&dir, Some(&dir), None, None).unwrap();
+        let pairs = resolve_input_pairs(&dir, Some(&dir), None, None, &first_cue).unwrap();
         assert_eq!(pairs.len(), 1);
         assert_eq!(
             pairs[0].flac.abs.file_name().unwrap().to_string_lossy(),
@@ -518,6 +1373,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_input_pairs_lets_caller_choose_between_cues_for_one_image() {
+        let dir = unique_test_dir();
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("Album.flac"), b"").unwrap();
+        fs::write(dir.join("Other.flac"), b"").unwrap();
+        fs::write(dir.join("Other.cue"), b"FILE \"Other.flac\" WAVE\n").unwrap();
+        fs::write(
+            dir.join("Album.cue"),
+            b"FILE \"Album.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.join("Album (gaps).cue"),
+            b"FILE \"D:\\Rips\\ALBUM.FLAC\" WAVE\n  TRACK 01 AUDIO\n    INDEX 00 00:00:00\n    INDEX 01 00:02:00\n",
+        )
+        .unwrap();
+
+        let pick_gaps = |_: &InputPath, candidates: &[CueCandidate]| -> Result<usize> {
+            let styles: Vec<CueStyle> =
+                candidates.iter().map(|candidate| candidate.style).collect();
+            assert_eq!(styles, vec![CueStyle::Gaps, CueStyle::Plain]);
+            Ok(0)
+        };
+        let pairs = resolve_input_pairs(&dir, Some(&dir), None, None, &pick_gaps).unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(
+            pairs[0].cue.abs.file_name().unwrap().to_string_lossy(),
+            "Album (gaps).cue"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn first_cue(_: &InputPath, _: &[CueCandidate]) -> Result<usize> {
+        Ok(0)
+    }
+
     fn unique_test_dir() -> PathBuf {
         let stamp = SystemTime::now()
             .duration_since(UNIX_EPOCH)
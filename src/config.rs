@@ -0,0 +1,190 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, ValueEnum};
+use owo_colors::OwoColorize;
+
+use crate::Result;
+use crate::app::encoder_options;
+use crate::cli::{Args, Cli};
+use crate::i18n::{current_lang, locale_var};
+use crate::profile::AppliedProfile;
+
+#[derive(Debug, PartialEq, Eq)]
+struct ConfigEntry {
+    name: String,
+    value: String,
+    source: String,
+}
+
+pub(crate) fn print_config(
+    args: &Args,
+    matches: &ArgMatches,
+    profile: Option<&AppliedProfile>,
+) -> Result<()> {
+    println!("{}", "Configuration".bold());
+    let entries = config_entries(args, matches, profile);
+    let width = entries
+        .iter()
+        .map(|entry| entry.name.len())
+        .max()
+        .unwrap_or(0);
+    for entry in entries {
+        println!(
+            "  {} {} {}",
+            format!("{:width$}", entry.name, width = width).cyan(),
+            entry.value,
+            format!("({})", entry.source).dimmed()
+        );
+    }
+    Ok(())
+}
+
+fn config_entries(
+    args: &Args,
+    matches: &ArgMatches,
+    profile: Option<&AppliedProfile>,
+) -> Vec<ConfigEntry> {
+    let mut root = Cli::command();
+    root.build();
+    let (command, matches) = match matches.subcommand() {
+        Some((name, sub_matches)) => match root.find_subcommand(name) {
+            Some(command) => (command.clone(), sub_matches),
+            None => (root, matches),
+        },
+        None => (root, matches),
+    };
+
+    let preset = args
+        .preset
+        .and_then(|preset| preset.to_possible_value())
+        .map(|value| format!("preset {}", value.get_name()));
+    let encoder = encoder_options(args, None);
+
+    let mut entries = Vec::new();
+    for arg in command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if matches!(id, "help" | "version" | "print_config") || arg.is_hide_set() {
+            continue;
+        }
+        let name = match arg.get_long() {
+            Some(long) => format!("--{}", long),
+            None => arg
+                .get_value_names()
+                .and_then(|names| names.first())
+                .map_or_else(|| id.to_uppercase(), |name| name.to_string()),
+        };
+        let explicit = matches.value_source(id) == Some(ValueSource::CommandLine);
+        let mut value = matches
+            .get_raw(id)
+            .map(|values| {
+                values
+                    .map(|value| value.to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            })
+            .unwrap_or_else(|| "(not set)".to_string());
+        let mut source = match matches.value_source(id) {
+            Some(ValueSource::CommandLine) => match profile {
+                Some(profile)
+                    if arg.get_long().is_some_and(|long| {
+                        profile.options.iter().any(|option| option == long)
+                    }) =>
+                {
+                    format!("profile {}", profile.name)
+                }
+                _ => "command line".to_string(),
+            },
+            Some(ValueSource::EnvVariable) => "environment".to_string(),
+            _ => "default".to_string(),
+        };
+
+        // The preset fills in the encoder settings that were not given explicitly.
+        if !explicit {
+            let effective = match id {
+                "compression_level" => Some(encoder.compression_level.to_string()),
+                "output_format" => encoder
+                    .format
+                    .to_possible_value()
+                    .map(|value| value.get_name().to_string()),
+                "verify_encoding" => Some(encoder.verify.to_string()),
+                "seektable" => Some(encoder.seektable.to_string()),
+                "padding" => Some(encoder.padding.to_string()),
+                _ => None,
+            };
+            if let Some(effective) = effective {
+                value = effective;
+                if let Some(preset) = &preset {
+                    source = preset.clone();
+                }
+            }
+            if id == "lang" {
+                value = format!("{:?}", current_lang()).to_lowercase();
+                if let Some((key, locale)) = locale_var() {
+                    source = format!("locale {}={}", key, locale);
+                }
+            }
+        }
+        entries.push(ConfigEntry {
+            name,
+            value,
+            source,
+        });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigEntry, config_entries};
+    use crate::cli::{Cli, Command};
+    use clap::{CommandFactory, FromArgMatches};
+
+    fn entry<'a>(entries: &'a [ConfigEntry], name: &str) -> &'a ConfigEntry {
+        entries.iter().find(|entry| entry.name == name).unwrap()
+    }
+
+    #[test]
+    fn config_reports_values_with_their_source() {
+        let matches = Cli::command()
+            .try_get_matches_from([
+                "flac-cue-split",
+                "plan",
+                "--preset",
+                "archive",
+                "-c",
+                "6",
+                "--print-config",
+                "Album",
+            ])
+            .unwrap();
+        let cli = Cli::from_arg_matches(&matches).unwrap();
+        let Some(Command::Plan(plan)) = cli.command else {
+            panic!("expected the plan command");
+        };
+        let entries = config_entries(&plan.split, &matches, None);
+
+        let level = entry(&entries, "--compression-level");
+        assert_eq!(
+            (level.value.as_str(), level.source.as_str()),
+            ("6", "command line")
+        );
+        let padding = entry(&entries, "--padding");
+        assert_eq!(
+            (padding.value.as_str(), padding.source.as_str()),
+            ("8192", "preset archive")
+        );
+        let seektable = entry(&entries, "--seektable");
+        assert_eq!(seektable.value, "true");
+        let cover = entry(&entries, "--cover");
+        assert_eq!(
+            (cover.value.as_str(), cover.source.as_str()),
+            ("embed", "default")
+        );
+        let dir = entry(&entries, "DIR");
+        assert_eq!(
+            (dir.value.as_str(), dir.source.as_str()),
+            ("Album", "command line")
+        );
+        assert_eq!(entry(&entries, "--out").value, "(not set)");
+        assert!(entries.iter().all(|entry| entry.name != "--print-config"));
+    }
+}
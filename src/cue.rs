@@ -1,3 +1,4 @@
+use clap::ValueEnum;
 use cue_sys as cue;
 use encoding_rs::{Encoding, UTF_8, WINDOWS_1251};
 use libc::{c_int, c_void as libc_void};
@@ -5,9 +6,14 @@ use owo_colors::OwoColorize;
 use std::ffi::{CStr, CString};
 use std::fs;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::thread::JoinHandle;
 
 use crate::Result;
+use crate::cdtext::{find_cdtext_file, merge_cdtext, parse_cdtext};
+use crate::remote::{fetch_url, is_url};
 use crate::types::{CueDisc, CueRem, CueTrack};
+use crate::ui::format_msf;
 
 const REM_DATE: u32 = 0;
 const REM_REPLAYGAIN_ALBUM_GAIN: u32 = 1;
@@ -15,6 +21,64 @@ const REM_REPLAYGAIN_ALBUM_PEAK: u32 = 2;
 const REM_REPLAYGAIN_TRACK_GAIN: u32 = 3;
 const REM_REPLAYGAIN_TRACK_PEAK: u32 = 4;
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum CueStyle {
+    Gaps,
+    Plain,
+    Noncompliant,
+}
+
+impl CueStyle {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            CueStyle::Gaps => "with gaps",
+            CueStyle::Plain => "without gaps",
+            CueStyle::Noncompliant => "noncompliant, one FILE per track",
+        }
+    }
+}
+
+pub(crate) fn detect_cue_style(text: &str) -> CueStyle {
+    let mut files = 0;
+    let mut gaps = false;
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next().map(str::to_ascii_uppercase).as_deref() {
+            Some("FILE") => files += 1,
+            Some("PREGAP") => gaps = true,
+            Some("INDEX") if words.next() == Some("00") => gaps = true,
+            _ => {}
+        }
+    }
+    if files > 1 {
+        CueStyle::Noncompliant
+    } else if gaps {
+        CueStyle::Gaps
+    } else {
+        CueStyle::Plain
+    }
+}
+
+pub(crate) fn cue_referenced_files(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let keyword = line.get(..4)?;
+            if !keyword.eq_ignore_ascii_case("FILE") {
+                return None;
+            }
+            let rest = line[4..].trim_start();
+            let name = match rest.strip_prefix('"') {
+                Some(quoted) => quoted.split('"').next()?,
+                None => rest
+                    .rsplit_once(char::is_whitespace)
+                    .map_or(rest, |(name, _)| name),
+            };
+            Some(cue_file_name(name.trim()).to_string()).filter(|name| !name.is_empty())
+        })
+        .collect()
+}
+
 pub(crate) fn resolve_encoding(label: &str) -> Result<&'static Encoding> {
     Encoding::for_label(label.as_bytes())
         .ok_or_else(|| format!("unsupported cue encoding: {}", label))
@@ -24,17 +88,49 @@ pub(crate) fn parse_cue_file(
     path: &Path,
     encoding: Option<&'static Encoding>,
 ) -> Result<(CueDisc, Vec<String>, &'static Encoding, bool)> {
-    let contents = fs::read(path)
-        .map_err(|err| format!("failed to read cue file {}: {}", path.display(), err))?;
+    let contents = if is_url(path) {
+        fetch_url(&path.to_string_lossy())?
+    } else {
+        fs::read(path)
+            .map_err(|err| format!("failed to read cue file {}: {}", path.display(), err))?
+    };
+    let (mut disc, mut warnings, used, autodetected) = parse_cue_contents(&contents, encoding)?;
+    if !is_url(path)
+        && let Some(cdt_path) = find_cdtext_file(path, &used.decode(&contents).0)
+    {
+        let cdtext = fs::read(&cdt_path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| parse_cdtext(&data));
+        match cdtext {
+            Ok(cdtext) => merge_cdtext(&mut disc, &cdtext),
+            Err(err) => warnings.push(format!(
+                "ignoring CD-TEXT file {}: {}",
+                cdt_path.display(),
+                err
+            )),
+        }
+    }
+    Ok((disc, warnings, used, autodetected))
+}
+
+pub(crate) fn parse_cue_contents(
+    contents: &[u8],
+    encoding: Option<&'static Encoding>,
+) -> Result<(CueDisc, Vec<String>, &'static Encoding, bool)> {
     let (encoding, autodetected) = match encoding {
         Some(enc) => (enc, false),
-        None => (detect_cue_encoding(&contents), true),
+        None => (detect_cue_encoding(contents), true),
     };
-    parse_cue_from_bytes(&contents, encoding)
+    parse_cue_from_bytes(contents, encoding)
         .map(|(disc, warnings, used)| (disc, warnings, used, autodetected))
 }
 
-#[cfg(test)]
+pub fn parse_cue(contents: &[u8], encoding: Option<&str>) -> Result<(CueDisc, Vec<String>)> {
+    let encoding = encoding.map(resolve_encoding).transpose()?;
+    let (disc, warnings, _, _) = parse_cue_contents(contents, encoding)?;
+    Ok((disc, warnings))
+}
+
 pub(crate) fn parse_cue_from_str(contents: &str) -> Result<CueDisc> {
     let (disc, _, _) = parse_cue_from_bytes(contents.as_bytes(), UTF_8)?;
     Ok(disc)
@@ -48,7 +144,7 @@ fn parse_cue_from_bytes(
     let capture = StderrCapture::start()?;
     let cd = unsafe { cue::cue_parse_string(cue_cstr.as_ptr()) };
     let stderr = capture.finish()?;
-    let warnings = parse_cue_warnings(&stderr, contents, encoding);
+    let mut warnings = parse_cue_warnings(&stderr, contents, encoding);
     if cd.is_null() {
         let mut message = "failed to parse cue file".to_string();
         let warning_text = format_cue_warnings(&warnings);
@@ -63,7 +159,267 @@ fn parse_cue_from_bytes(
     unsafe {
         cue::cd_delete(cd);
     }
-    result.map(|disc| (disc, warnings, encoding))
+    let mut disc = result?;
+    fill_indexes(&mut disc, &encoding.decode(contents).0);
+    if disc.tracks.is_empty() {
+        return Err("cue sheet has no audio tracks".to_string());
+    }
+    warnings.extend(disc.mode_issues.iter().cloned());
+    Ok((disc, warnings, encoding))
+}
+
+#[derive(Default)]
+struct TrackSection {
+    number: Option<u32>,
+    disc: Option<u32>,
+    pregap: Option<i64>,
+    indexes: Vec<(u32, i64)>,
+    sample_offset: Option<u64>,
+}
+
+fn fill_indexes(disc: &mut CueDisc, text: &str) {
+    // libcue only reports where INDEX 01 starts and drops unknown REM lines, so
+    // the pregap, later indexes, sample offsets and disc markers are read from
+    // the text; its track numbers count every TRACK line in order.
+    let mut sections: Vec<TrackSection> = Vec::new();
+    let mut disc_marker = None;
+    for line in text.lines() {
+        let mut words = line.split_whitespace();
+        match words.next().map(str::to_ascii_uppercase).as_deref() {
+            Some("TRACK") => sections.push(TrackSection {
+                number: words.next().and_then(|value| value.parse::<u32>().ok()),
+                disc: disc_marker.take(),
+                ..TrackSection::default()
+            }),
+            Some("REM") => {
+                let key = words.next().map(str::to_ascii_uppercase);
+                let value = words.next().and_then(|value| value.parse::<u64>().ok());
+                match (key.as_deref(), value) {
+                    (Some("DISC" | "DISCNUMBER"), Some(value)) if value > 0 => {
+                        disc_marker = u32::try_from(value).ok();
+                    }
+                    (Some("SAMPLE_OFFSET"), Some(value)) => {
+                        if let Some(section) = sections.last_mut() {
+                            section.sample_offset = Some(value);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Some("INDEX") => {
+                let number = words.next().and_then(|value| value.parse::<u32>().ok());
+                let frames = words.next().and_then(parse_msf);
+                let (Some(section), Some(number), Some(frames)) =
+                    (sections.last_mut(), number, frames)
+                else {
+                    continue;
+                };
+                match number {
+                    0 => section.pregap = Some(frames),
+                    1 => {}
+                    _ => section.indexes.push((number, frames)),
+                }
+            }
+            _ => {}
+        }
+    }
+    for track in &mut disc.tracks {
+        if let Some(section) = sections.get(track.number as usize - 1) {
+            track.pregap_start = section.pregap;
+            track.sub_indexes = section.indexes.clone();
+            track.rem.sample_offset = section.sample_offset;
+        }
+    }
+    disc.discs = disc_layout(&sections);
+}
+
+fn disc_layout(sections: &[TrackSection]) -> Vec<u32> {
+    // Merged multi-disc sheets mark each disc with REM DISC/DISCNUMBER or start
+    // the track numbers over at 1. A sheet that stays on one disc has no layout.
+    let marked = sections.iter().any(|section| section.disc.is_some());
+    let mut discs = Vec::with_capacity(sections.len());
+    let mut current = 1;
+    for (index, section) in sections.iter().enumerate() {
+        if marked {
+            current = section.disc.unwrap_or(current);
+        } else if index > 0 && section.number == Some(1) {
+            current += 1;
+        }
+        discs.push(current);
+    }
+    if discs.windows(2).all(|pair| pair[0] == pair[1]) {
+        return Vec::new();
+    }
+    discs
+}
+
+pub(crate) fn track_disc(disc: &CueDisc, number: u32) -> Option<(u32, u32)> {
+    let index = (number as usize).checked_sub(1)?;
+    let track_disc = *disc.discs.get(index)?;
+    let on_disc = disc.discs[..=index]
+        .iter()
+        .filter(|&&value| value == track_disc)
+        .count();
+    Some((track_disc, on_disc as u32))
+}
+
+pub(crate) fn disc_track_count(disc: &CueDisc, number: u32) -> usize {
+    disc.discs.iter().filter(|&&value| value == number).count()
+}
+
+fn parse_msf(value: &str) -> Option<i64> {
+    let mut parts = value.split(':').map(|part| part.parse::<i64>().ok());
+    let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames)), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    Some((minutes * 60 + seconds) * 75 + frames)
+}
+
+pub(crate) fn cue_file_name(name: &str) -> &str {
+    // FILE entries often keep the ripper's path, e.g. `D:\RIPS\ALBUM.WAV`.
+    let name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    match name.as_bytes() {
+        [drive, b':', ..] if drive.is_ascii_alphabetic() => &name[2..],
+        _ => name,
+    }
+}
+
+pub(crate) fn render_cue_sheet(disc: &CueDisc) -> String {
+    let mut out = String::new();
+    if let Some(genre) = &disc.genre {
+        out.push_str(&format!("REM GENRE {}\n", quote_cue_value(genre)));
+    }
+    if let Some(date) = &disc.rem.date {
+        out.push_str(&format!("REM DATE {}\n", date));
+    }
+    if let Some(disc_id) = &disc.disc_id {
+        out.push_str(&format!("REM DISCID {}\n", disc_id));
+    }
+    if let Some(message) = &disc.message {
+        out.push_str(&format!("REM COMMENT {}\n", quote_cue_value(message)));
+    }
+    if let Some(gain) = &disc.rem.replaygain_album_gain {
+        out.push_str(&format!("REM REPLAYGAIN_ALBUM_GAIN {}\n", gain));
+    }
+    if let Some(peak) = &disc.rem.replaygain_album_peak {
+        out.push_str(&format!("REM REPLAYGAIN_ALBUM_PEAK {}\n", peak));
+    }
+    push_cue_field(&mut out, "", "PERFORMER", disc.performer.as_deref());
+    push_cue_field(&mut out, "", "SONGWRITER", disc.songwriter.as_deref());
+    push_cue_field(&mut out, "", "COMPOSER", disc.composer.as_deref());
+    push_cue_field(&mut out, "", "TITLE", disc.title.as_deref());
+
+    let mut current_file: Option<&str> = None;
+    for track in &disc.tracks {
+        if let Some(name) = track.filename.as_deref()
+            && current_file != Some(name)
+        {
+            out.push_str(&format!("FILE {} WAVE\n", quote_cue_value(name)));
+            current_file = Some(name);
+        }
+        out.push_str(&format!("  TRACK {:02} AUDIO\n", track.number));
+        push_cue_field(&mut out, "    ", "TITLE", track.title.as_deref());
+        push_cue_field(&mut out, "    ", "PERFORMER", track.performer.as_deref());
+        push_cue_field(&mut out, "    ", "SONGWRITER", track.songwriter.as_deref());
+        push_cue_field(&mut out, "    ", "COMPOSER", track.composer.as_deref());
+        if let Some(isrc) = &track.isrc {
+            out.push_str(&format!("    ISRC {}\n", isrc));
+        }
+        if let Some(date) = &track.rem.date {
+            out.push_str(&format!("    REM DATE {}\n", date));
+        }
+        if let Some(gain) = &track.rem.replaygain_track_gain {
+            out.push_str(&format!("    REM REPLAYGAIN_TRACK_GAIN {}\n", gain));
+        }
+        if let Some(peak) = &track.rem.replaygain_track_peak {
+            out.push_str(&format!("    REM REPLAYGAIN_TRACK_PEAK {}\n", peak));
+        }
+        if let Some(offset) = track.rem.sample_offset {
+            out.push_str(&format!("    REM SAMPLE_OFFSET {}\n", offset));
+        }
+        if let Some(pregap) = track.pregap_start {
+            let pregap = u64::try_from(pregap).unwrap_or(0);
+            out.push_str(&format!("    INDEX 00 {}\n", format_msf(pregap)));
+        }
+        let start = u64::try_from(track.start_frames).unwrap_or(0);
+        out.push_str(&format!("    INDEX 01 {}\n", format_msf(start)));
+        for (number, frames) in &track.sub_indexes {
+            let frames = u64::try_from(*frames).unwrap_or(0);
+            out.push_str(&format!("    INDEX {:02} {}\n", number, format_msf(frames)));
+        }
+    }
+
+    out
+}
+
+fn push_cue_field(out: &mut String, indent: &str, keyword: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        out.push_str(&format!(
+            "{}{} {}\n",
+            indent,
+            keyword,
+            quote_cue_value(value)
+        ));
+    }
+}
+
+fn quote_cue_value(value: &str) -> String {
+    let cleaned: String = value
+        .chars()
+        .map(|ch| match ch {
+            '"' => '\'',
+            '\r' | '\n' => ' ',
+            other => other,
+        })
+        .collect();
+    format!("\"{}\"", cleaned)
+}
+
+pub(crate) fn load_sample_offsets(path: &Path) -> Result<Vec<(u32, u64)>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read sample offsets {}: {}", path.display(), err))?;
+    parse_sample_offsets(&contents)
+        .map_err(|err| format!("invalid sample offsets {}: {}", path.display(), err))
+}
+
+pub(crate) fn parse_sample_offsets(contents: &str) -> Result<Vec<(u32, u64)>> {
+    let mut offsets = Vec::new();
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let parsed = match fields.as_slice() {
+            [track, sample] => track.parse::<u32>().ok().zip(sample.parse::<u64>().ok()),
+            _ => None,
+        };
+        let Some((track, sample)) = parsed.filter(|(track, _)| *track > 0) else {
+            return Err(format!(
+                "line {}: expected a track number and a sample offset, found {:?}",
+                index + 1,
+                line
+            ));
+        };
+        offsets.push((track, sample));
+    }
+    Ok(offsets)
+}
+
+pub(crate) fn apply_sample_offsets(disc: &mut CueDisc, offsets: &[(u32, u64)]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    for (number, offset) in offsets {
+        match disc.tracks.iter_mut().find(|track| track.number == *number) {
+            Some(track) => track.rem.sample_offset = Some(*offset),
+            None => warnings.push(format!(
+                "sample offset for track {} is ignored: the cue sheet has no such track",
+                number
+            )),
+        }
+    }
+    warnings
 }
 
 pub(crate) fn report_cue_warnings(warnings: &[String]) {
@@ -142,9 +498,10 @@ unsafe fn parse_cd(cd: *mut cue::CdPointer, encoding: &'static Encoding) -> Resu
         return Err("cue parser returned null CD".to_string());
     }
 
+    let mut mode_issues = Vec::new();
     let disc_mode = unsafe { cue::cd_get_mode(cd) };
     if !matches!(disc_mode, cue::DiscMode::CD_DA) {
-        return Err("cue sheet is not audio (CD_DA)".to_string());
+        mode_issues.push("cue sheet disc mode is not CD_DA".to_string());
     }
 
     let cdtext = unsafe { cue::cd_get_cdtext(cd) };
@@ -174,7 +531,8 @@ unsafe fn parse_cd(cd: *mut cue::CdPointer, encoding: &'static Encoding) -> Resu
             unsafe { cue::track_get_mode(track_ptr) },
             cue::TrackMode::Audio
         ) {
-            return Err(format!("track {} is not audio", index));
+            mode_issues.push(format!("track {} is not audio and is skipped", index));
+            continue;
         }
 
         let track_cdtext = unsafe { cue::track_get_cdtext(track_ptr) };
@@ -199,6 +557,8 @@ unsafe fn parse_cd(cd: *mut cue::CdPointer, encoding: &'static Encoding) -> Resu
             isrc: opt_cstr_with_encoding(unsafe { cue::track_get_isrc(track_ptr) }, encoding),
             start_frames: start,
             length_frames,
+            sub_indexes: Vec::new(),
+            pregap_start: None,
             filename,
             rem: track_rem,
         };
@@ -215,6 +575,8 @@ unsafe fn parse_cd(cd: *mut cue::CdPointer, encoding: &'static Encoding) -> Resu
         disc_id,
         rem,
         tracks,
+        discs: Vec::new(),
+        mode_issues,
     })
 }
 
@@ -253,6 +615,7 @@ fn cue_rem_from_ptr(rem: *mut cue::RemPointer, encoding: &'static Encoding) -> C
         replaygain_album_peak: rem_get_string(rem, REM_REPLAYGAIN_ALBUM_PEAK, encoding),
         replaygain_track_gain: rem_get_string(rem, REM_REPLAYGAIN_TRACK_GAIN, encoding),
         replaygain_track_peak: rem_get_string(rem, REM_REPLAYGAIN_TRACK_PEAK, encoding),
+        sample_offset: None,
     }
 }
 
@@ -268,13 +631,21 @@ fn rem_get_string(
     opt_cstr_with_encoding(ptr, encoding)
 }
 
+// libcue reports syntax errors on stderr. The descriptor is redirected to a
+// pipe for the duration of a parse; the lock keeps concurrent parses from
+// swapping it under each other, and the pipe is drained on a thread so a long
+// list of errors cannot fill it and block the parser.
+static STDERR_LOCK: Mutex<()> = Mutex::new(());
+
 struct StderrCapture {
-    read_fd: c_int,
     old_fd: c_int,
+    reader: JoinHandle<Vec<u8>>,
+    _lock: MutexGuard<'static, ()>,
 }
 
 impl StderrCapture {
     fn start() -> Result<Self> {
+        let lock = STDERR_LOCK.lock().unwrap_or_else(|err| err.into_inner());
         let mut fds = [0; 2];
         let rc = unsafe { libc::pipe(fds.as_mut_ptr()) };
         if rc != 0 {
@@ -302,37 +673,42 @@ impl StderrCapture {
             return Err("failed to redirect stderr".to_string());
         }
 
+        let read_fd = fds[0];
+        let reader = std::thread::spawn(move || {
+            let mut buffer = Vec::new();
+            let mut chunk = [0u8; 4096];
+            loop {
+                let read = unsafe {
+                    libc::read(read_fd, chunk.as_mut_ptr() as *mut libc_void, chunk.len())
+                };
+                if read <= 0 {
+                    break;
+                }
+                buffer.extend_from_slice(&chunk[..read as usize]);
+            }
+            unsafe {
+                libc::close(read_fd);
+            }
+            buffer
+        });
+
         Ok(Self {
-            read_fd: fds[0],
             old_fd,
+            reader,
+            _lock: lock,
         })
     }
 
     fn finish(self) -> Result<String> {
+        // Restoring stderr closes the pipe's last write end, which ends the reader.
         unsafe {
             libc::dup2(self.old_fd, libc::STDERR_FILENO);
             libc::close(self.old_fd);
         }
-
-        let mut buffer = Vec::new();
-        let mut chunk = [0u8; 4096];
-        loop {
-            let read = unsafe {
-                libc::read(
-                    self.read_fd,
-                    chunk.as_mut_ptr() as *mut libc_void,
-                    chunk.len(),
-                )
-            };
-            if read <= 0 {
-                break;
-            }
-            buffer.extend_from_slice(&chunk[..read as usize]);
-        }
-        unsafe {
-            libc::close(self.read_fd);
-        }
-
+        let buffer = self
+            .reader
+            .join()
+            .map_err(|_| "failed to read captured stderr".to_string())?;
         Ok(String::from_utf8_lossy(&buffer).into_owned())
     }
 }
@@ -1,10 +1,41 @@
+use std::fs::File;
+use std::io::{BufReader, Read};
 use std::path::{Path, PathBuf};
 
 use crate::Result;
-use crate::flac::FlacDecoder;
+use crate::aiff::AiffDecoder;
+use crate::alac::AlacDecoder;
+#[cfg(feature = "ape")]
+use crate::ape::ApeDecoder;
+use crate::apl::{APL_EXTENSION, AplDecoder};
+use crate::flac::{FlacDecoder, FlacReaderDecoder};
+use crate::id3::starts_with_id3v2;
+use crate::remote::{HttpReader, is_url, url_extension};
+use crate::tta::TtaDecoder;
 use crate::types::InputMetadata;
+use crate::wav::WavDecoder;
 use crate::wavpack::WavPackDecoder;
 
+pub(crate) const DEFAULT_BLOCK_SIZE: usize = 4096;
+pub(crate) const STDIN_NAME: &str = "-";
+pub(crate) const STDIN_DISPLAY: &str = "<stdin>";
+const MIN_BLOCK_SIZE: usize = 256;
+const MAX_BLOCK_SIZE: usize = 1 << 20;
+
+// Extensions of the audio images that can be split, in scan order.
+pub(crate) const AUDIO_EXTENSIONS: &[&str] = &[
+    "flac",
+    "wv",
+    "tta",
+    "m4a",
+    "wav",
+    "aiff",
+    "aif",
+    "aifc",
+    #[cfg(feature = "ape")]
+    "ape",
+];
+
 pub(crate) struct DecoderMetadata {
     pub(crate) input_meta: InputMetadata,
     pub(crate) picture_names: Vec<String>,
@@ -28,10 +59,183 @@ impl AudioBlock {
 
 pub(crate) trait Decoder {
     fn read_metadata(&mut self) -> Result<DecoderMetadata>;
-    fn into_blocks(self: Box<Self>) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>>;
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum ByteOrder {
+    #[default]
+    Little,
+    Big,
+}
+
+// Interleaved integer PCM as WAV and AIFF files store it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PcmFormat {
+    pub(crate) channels: u32,
+    // Valid bits; the samples are stored left-justified in `bytes_per_sample`.
+    pub(crate) bits_per_sample: u32,
+    pub(crate) bytes_per_sample: usize,
+    pub(crate) byte_order: ByteOrder,
+}
+
+impl PcmFormat {
+    pub(crate) fn block_align(&self) -> usize {
+        self.channels as usize * self.bytes_per_sample
+    }
+}
+
+pub(crate) struct PcmBlockIter<R> {
+    reader: R,
+    format: PcmFormat,
+    remaining: u64,
+    sample_index: u64,
+    buffer: Vec<u8>,
+    done: bool,
+}
+
+impl<R: Read> PcmBlockIter<R> {
+    pub(crate) fn new(reader: R, format: PcmFormat, total_samples: u64, block_size: usize) -> Self {
+        Self {
+            reader,
+            format,
+            remaining: total_samples,
+            sample_index: 0,
+            buffer: vec![0u8; block_size.max(1) * format.block_align()],
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Iterator for PcmBlockIter<R> {
+    type Item = Result<AudioBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining == 0 {
+            return None;
+        }
+        let block_align = self.format.block_align();
+        let samples = (self.buffer.len() / block_align).min(self.remaining as usize);
+        let bytes = &mut self.buffer[..samples * block_align];
+        if let Err(err) = self.reader.read_exact(bytes) {
+            self.done = true;
+            return Some(Err(format!("failed to read PCM data: {}", err)));
+        }
+
+        let mut interleaved = match self.format.byte_order {
+            ByteOrder::Little => decode_pcm_le(bytes, self.format.bytes_per_sample),
+            ByteOrder::Big => decode_pcm_be(bytes, self.format.bytes_per_sample),
+        };
+        let shift = self.format.bytes_per_sample as u32 * 8 - self.format.bits_per_sample;
+        if shift > 0 {
+            for sample in &mut interleaved {
+                *sample >>= shift;
+            }
+        }
+        let sample_index = self.sample_index;
+        self.sample_index += samples as u64;
+        self.remaining -= samples as u64;
+        Some(Ok(AudioBlock {
+            sample_index,
+            channels: self.format.channels,
+            interleaved,
+        }))
+    }
+}
+
+// Little-endian PCM as libMAC and WAV files store it; 8-bit samples are
+// unsigned, wider ones signed.
+pub(crate) fn decode_pcm_le(bytes: &[u8], bytes_per_sample: usize) -> Vec<i32> {
+    bytes
+        .chunks_exact(bytes_per_sample)
+        .map(|sample| match *sample {
+            [byte] => i32::from(byte) - 128,
+            [low, high] => i32::from(i16::from_le_bytes([low, high])),
+            [low, mid, high] => i32::from_le_bytes([0, low, mid, high]) >> 8,
+            [b0, b1, b2, b3] => i32::from_le_bytes([b0, b1, b2, b3]),
+            _ => 0,
+        })
+        .collect()
+}
+
+// Big-endian PCM as AIFF files store it; all sample sizes are signed.
+fn decode_pcm_be(bytes: &[u8], bytes_per_sample: usize) -> Vec<i32> {
+    bytes
+        .chunks_exact(bytes_per_sample)
+        .map(|sample| match *sample {
+            [byte] => i32::from(byte as i8),
+            [high, low] => i32::from(i16::from_be_bytes([high, low])),
+            [high, mid, low] => i32::from_be_bytes([high, mid, low, 0]) >> 8,
+            [b0, b1, b2, b3] => i32::from_be_bytes([b0, b1, b2, b3]),
+            _ => 0,
+        })
+        .collect()
+}
+
+pub(crate) fn parse_block_size(value: &str) -> Result<usize> {
+    let trimmed = value.trim();
+    let (number, multiplier) = match trimmed
+        .strip_suffix('k')
+        .or_else(|| trimmed.strip_suffix('K'))
+    {
+        Some(number) => (number, 1024),
+        None => (trimmed, 1),
+    };
+    let size = number
+        .trim()
+        .parse::<usize>()
+        .ok()
+        .and_then(|size| size.checked_mul(multiplier))
+        .ok_or_else(|| format!("invalid block size {:?} (examples: 4096, 64k)", value))?;
+    if !(MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(&size) {
+        return Err(format!(
+            "block size must be between {} and {} samples",
+            MIN_BLOCK_SIZE, MAX_BLOCK_SIZE
+        ));
+    }
+    Ok(size)
+}
+
+pub(crate) fn count_samples(path: &Path) -> Result<u64> {
+    let decoder = create_decoder(path)?;
+    let mut total = 0u64;
+    for block in decoder.into_blocks(DEFAULT_BLOCK_SIZE)? {
+        total += block?.sample_count() as u64;
+    }
+    Ok(total)
+}
+
+pub(crate) fn supported_audio_exts_label() -> String {
+    AUDIO_EXTENSIONS
+        .iter()
+        .map(|ext| format!(".{}", ext))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub(crate) fn is_stdin_path(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == STDIN_NAME)
 }
 
 pub(crate) fn create_decoder(path: &Path) -> Result<Box<dyn Decoder>> {
+    if is_stdin_path(path) {
+        return Err("standard input can only be decoded once".to_string());
+    }
+    if is_url(path) {
+        let url = path.to_string_lossy();
+        if url_extension(&url) != "flac" {
+            return Err(format!(
+                "only FLAC images can be streamed from a URL: {}",
+                url
+            ));
+        }
+        let reader = HttpReader::open(&url)?;
+        return Ok(Box::new(FlacReaderDecoder::new(Box::new(reader), &url)?));
+    }
+
     let ext = path
         .extension()
         .and_then(|ext| ext.to_str())
@@ -40,11 +244,35 @@ pub(crate) fn create_decoder(path: &Path) -> Result<Box<dyn Decoder>> {
 
     let path = PathBuf::from(path);
     match ext.as_str() {
+        // libFLAC loses sync on some files with an ID3v2 tag in front, so those are
+        // read through a stream that starts after the tag.
+        "flac" if starts_with_id3v2(&path) => {
+            let file = File::open(&path)
+                .map_err(|err| format!("failed to open {}: {}", path.display(), err))?;
+            let name = path.display().to_string();
+            Ok(Box::new(FlacReaderDecoder::new(
+                Box::new(BufReader::new(file)),
+                &name,
+            )?))
+        }
         "flac" => Ok(Box::new(FlacDecoder::new(path))),
         "wv" => Ok(Box::new(WavPackDecoder::new(path))),
-        _ => Err(format!(
-            "unsupported input format {} (expected .flac or .wv)",
+        "tta" => Ok(Box::new(TtaDecoder::new(path))),
+        "m4a" => Ok(Box::new(AlacDecoder::new(path))),
+        "wav" => Ok(Box::new(WavDecoder::new(path))),
+        "aiff" | "aif" | "aifc" => Ok(Box::new(AiffDecoder::new(path))),
+        #[cfg(feature = "ape")]
+        "ape" => Ok(Box::new(ApeDecoder::new(path))),
+        APL_EXTENSION => Ok(Box::new(AplDecoder::open(path)?)),
+        #[cfg(not(feature = "ape"))]
+        "ape" => Err(format!(
+            "Monkey's Audio images are not supported (built without the `ape` feature); convert the image to FLAC or WavPack first: {}",
             path.display()
         )),
+        _ => Err(format!(
+            "unsupported input format {} (expected {})",
+            path.display(),
+            supported_audio_exts_label()
+        )),
     }
 }
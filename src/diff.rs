@@ -0,0 +1,281 @@
+use owo_colors::OwoColorize;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::checksum::format_md5;
+use crate::cli::{DiffArgs, display_path, find_track_files};
+use crate::decoder::create_decoder;
+use crate::jobs::run_parallel;
+use crate::metadata::{diff_tags, find_tag};
+use crate::types::InputMetadata;
+use crate::ui::format_time;
+use crate::verify::hash_file;
+
+struct DiffFile {
+    path: PathBuf,
+    name: String,
+    meta: InputMetadata,
+}
+
+#[derive(Serialize, Debug)]
+struct DiffReport {
+    old: String,
+    new: String,
+    identical: bool,
+    tracks: Vec<TrackDiff>,
+}
+
+#[derive(Serialize, Debug)]
+struct TrackDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_samples: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_samples: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    old_md5: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    new_md5: Option<String>,
+    same_audio: bool,
+    tags_removed: Vec<(String, String)>,
+    tags_added: Vec<(String, String)>,
+}
+
+impl TrackDiff {
+    fn identical(&self) -> bool {
+        self.old.is_some()
+            && self.new.is_some()
+            && self.same_audio
+            && self.tags_removed.is_empty()
+            && self.tags_added.is_empty()
+    }
+}
+
+pub(crate) fn run_diff(args: DiffArgs) -> Result<()> {
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let old_dir = cwd.join(&args.old);
+    let new_dir = cwd.join(&args.new);
+    let old_files = read_dir_tracks(&old_dir)?;
+    let new_files = read_dir_tracks(&new_dir)?;
+
+    let all_files: Vec<&DiffFile> = old_files.iter().chain(new_files.iter()).collect();
+    let md5s = run_parallel(all_files, args.jobs, |file: &DiffFile| {
+        match file.meta.md5 {
+            Some(md5) => Ok(md5),
+            None => hash_file(&file.path, file.meta.bits_per_sample),
+        }
+    })
+    .into_iter()
+    .collect::<Result<Vec<[u8; 16]>>>()?;
+    let (old_md5s, new_md5s) = md5s.split_at(old_files.len());
+
+    let keys = |files: &[DiffFile]| -> Vec<Option<(u32, u32)>> {
+        files.iter().map(|file| track_key(&file.meta)).collect()
+    };
+    let mut tracks = Vec::new();
+    for (old, new) in pair_indexes(&keys(&old_files), &keys(&new_files)) {
+        let old_file = old.map(|index| &old_files[index]);
+        let new_file = new.map(|index| &new_files[index]);
+        let old_md5 = old.map(|index| old_md5s[index]);
+        let new_md5 = new.map(|index| new_md5s[index]);
+        let (tags_removed, tags_added) = match (old_file, new_file) {
+            (Some(old_file), Some(new_file)) => {
+                diff_tags(&old_file.meta.comments, &new_file.meta.comments)
+            }
+            _ => (Vec::new(), Vec::new()),
+        };
+        tracks.push(TrackDiff {
+            old: old_file.map(|file| file.name.clone()),
+            new: new_file.map(|file| file.name.clone()),
+            old_samples: old_file.map(|file| file.meta.total_samples),
+            new_samples: new_file.map(|file| file.meta.total_samples),
+            old_md5: old_md5.as_ref().map(format_md5),
+            new_md5: new_md5.as_ref().map(format_md5),
+            same_audio: old_md5.is_some() && old_md5 == new_md5,
+            tags_removed,
+            tags_added,
+        });
+    }
+
+    let report = DiffReport {
+        old: display_path(Some(cwd.as_path()), &old_dir)
+            .display()
+            .to_string(),
+        new: display_path(Some(cwd.as_path()), &new_dir)
+            .display()
+            .to_string(),
+        identical: tracks.iter().all(TrackDiff::identical),
+        tracks,
+    };
+    if args.json {
+        let json = serde_json::to_string_pretty(&report)
+            .map_err(|err| format!("failed to serialize diff: {}", err))?;
+        println!("{}", json);
+    } else {
+        let sample_rate = old_files[0].meta.sample_rate;
+        print_report(&report, sample_rate);
+    }
+    Ok(())
+}
+
+fn print_report(report: &DiffReport, sample_rate: u32) {
+    println!("{}", "Diff".bold());
+    println!("  {} {}", "Old:".cyan(), report.old);
+    println!("  {} {}", "New:".cyan(), report.new);
+
+    for track in &report.tracks {
+        let (old, new) = match (&track.old, &track.new) {
+            (Some(old), Some(new)) => (old, new),
+            (Some(old), None) => {
+                println!("{} {}", "only in old".red().bold(), old);
+                continue;
+            }
+            (None, Some(new)) => {
+                println!("{} {}", "only in new".red().bold(), new);
+                continue;
+            }
+            (None, None) => continue,
+        };
+        let name = if old == new {
+            old.clone()
+        } else {
+            format!("{} -> {}", old, new)
+        };
+        if track.identical() {
+            println!("{} {}", "same".green().bold(), name);
+            continue;
+        }
+        println!("{} {}", "diff".yellow().bold(), name.bold());
+        if let (Some(old_samples), Some(new_samples)) = (track.old_samples, track.new_samples)
+            && old_samples != new_samples
+        {
+            println!(
+                "  length {} -> {} ({:+} samples)",
+                format_time(old_samples, sample_rate),
+                format_time(new_samples, sample_rate),
+                new_samples as i64 - old_samples as i64
+            );
+        }
+        if !track.same_audio
+            && let (Some(old_md5), Some(new_md5)) = (&track.old_md5, &track.new_md5)
+        {
+            println!("  audio MD5 {} -> {}", old_md5, new_md5.yellow());
+        }
+        for (key, value) in &track.tags_removed {
+            println!("  {}", format!("- {}={}", key, value).red());
+        }
+        for (key, value) in &track.tags_added {
+            println!("  {}", format!("+ {}={}", key, value).green());
+        }
+    }
+
+    let differing = report
+        .tracks
+        .iter()
+        .filter(|track| !track.identical())
+        .count();
+    if differing == 0 {
+        println!("{}", "Splits are identical".green().bold());
+    } else {
+        println!(
+            "{}",
+            format!("{} of {} tracks differ", differing, report.tracks.len())
+                .yellow()
+                .bold()
+        );
+    }
+}
+
+fn read_dir_tracks(dir: &Path) -> Result<Vec<DiffFile>> {
+    if !dir.is_dir() {
+        return Err(format!("directory not found: {}", dir.display()));
+    }
+    let mut files = Vec::new();
+    for path in find_track_files(dir)? {
+        let mut decoder = create_decoder(&path)?;
+        let meta = decoder.read_metadata()?.input_meta;
+        let name = display_path(Some(dir), &path).display().to_string();
+        files.push(DiffFile { path, name, meta });
+    }
+    if files.is_empty() {
+        return Err(format!("no .flac files found in {}", dir.display()));
+    }
+    Ok(files)
+}
+
+fn track_key(meta: &InputMetadata) -> Option<(u32, u32)> {
+    let number = |key: &str| {
+        find_tag(&meta.comments, key)
+            .and_then(|value| value.split('/').next())
+            .and_then(|value| value.trim().parse::<u32>().ok())
+    };
+    Some((number("DISCNUMBER").unwrap_or(1), number("TRACKNUMBER")?))
+}
+
+fn pair_indexes(
+    old: &[Option<(u32, u32)>],
+    new: &[Option<(u32, u32)>],
+) -> Vec<(Option<usize>, Option<usize>)> {
+    let unique = |keys: &[Option<(u32, u32)>]| {
+        let mut seen = HashSet::new();
+        keys.iter()
+            .all(|key| key.is_some_and(|key| seen.insert(key)))
+    };
+    // Files are matched by disc and track number so that a re-split with a
+    // different naming template still lines up; without usable numbers the
+    // sorted file order is used.
+    if !unique(old) || !unique(new) {
+        let count = old.len().max(new.len());
+        return (0..count)
+            .map(|index| {
+                (
+                    (index < old.len()).then_some(index),
+                    (index < new.len()).then_some(index),
+                )
+            })
+            .collect();
+    }
+    let mut pairs: BTreeMap<(u32, u32), (Option<usize>, Option<usize>)> = BTreeMap::new();
+    for (index, key) in old.iter().flatten().enumerate() {
+        pairs.entry(*key).or_default().0 = Some(index);
+    }
+    for (index, key) in new.iter().flatten().enumerate() {
+        pairs.entry(*key).or_default().1 = Some(index);
+    }
+    pairs.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pair_indexes;
+
+    #[test]
+    fn pair_indexes_matches_by_track_number_or_position() {
+        assert_eq!(
+            pair_indexes(
+                &[Some((1, 1)), Some((1, 2)), Some((1, 3))],
+                &[Some((1, 2)), Some((1, 1)), Some((1, 4))]
+            ),
+            vec![
+                (Some(0), Some(1)),
+                (Some(1), Some(0)),
+                (Some(2), None),
+                (None, Some(2)),
+            ]
+        );
+        assert_eq!(
+            pair_indexes(&[Some((1, 1)), None], &[Some((1, 1))]),
+            vec![(Some(0), Some(0)), (Some(1), None)]
+        );
+        assert_eq!(
+            pair_indexes(&[Some((1, 1)), Some((1, 1))], &[Some((1, 1)), Some((1, 2))]),
+            vec![(Some(0), Some(0)), (Some(1), Some(1))]
+        );
+    }
+}
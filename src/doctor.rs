@@ -0,0 +1,189 @@
+use owo_colors::OwoColorize;
+use std::f64::consts::TAU;
+use std::fs;
+use std::io::IsTerminal;
+use std::path::Path;
+
+use crate::Result;
+use crate::cue::parse_cue_from_str;
+use crate::decoder::{DEFAULT_BLOCK_SIZE, create_decoder};
+use crate::flac::{EncoderOptions, EncoderOutput, flac_library_version, start_encoder};
+use crate::i18n::current_lang;
+use crate::jobs::default_jobs;
+use crate::types::InputMetadata;
+use crate::wavpack::wavpack_library_version;
+
+const TEST_SAMPLE_RATE: u32 = 44100;
+const TEST_CUE: &str = r#"
+FILE "doctor.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "One"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Two"
+    INDEX 01 00:01:00
+"#;
+
+pub(crate) fn run_doctor() -> Result<()> {
+    println!("{}", "Doctor".bold());
+    println!(
+        "  {} {}",
+        "flac-cue-split:".cyan(),
+        env!("CARGO_PKG_VERSION")
+    );
+    println!("  {} {}", "libFLAC:".cyan(), flac_library_version());
+    println!("  {} {}", "WavPack:".cyan(), wavpack_library_version());
+    println!(
+        "  {} {}",
+        "Monkey's Audio:".cyan(),
+        if cfg!(feature = "ape") {
+            "linked (the library does not report its version)".dimmed()
+        } else {
+            "not built (enable the `ape` feature)".dimmed()
+        }
+    );
+    println!(
+        "  {} {}",
+        "libcue:".cyan(),
+        "linked (the library does not report its version)".dimmed()
+    );
+
+    println!("{}", "Checks".bold());
+    let results = [
+        report("FLAC round-trip:", check_flac_round_trip()),
+        report("Cue parser:", check_cue_parser()),
+    ];
+
+    println!("{}", "Terminal".bold());
+    println!(
+        "  {} {}",
+        "stdin:".cyan(),
+        terminal_label(std::io::stdin().is_terminal())
+    );
+    println!(
+        "  {} {}",
+        "stdout:".cyan(),
+        terminal_label(std::io::stdout().is_terminal())
+    );
+    println!(
+        "  {} {}",
+        "stderr:".cyan(),
+        terminal_label(std::io::stderr().is_terminal())
+    );
+    for key in ["TERM", "NO_COLOR", "COLUMNS"] {
+        println!("  {} {}", format!("{}:", key).cyan(), env_label(key));
+    }
+
+    println!("{}", "Settings".bold());
+    println!(
+        "  {} {}",
+        "Language:".cyan(),
+        format!("{:?}", current_lang()).to_lowercase()
+    );
+    for key in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+        println!("  {} {}", format!("{}:", key).cyan(), env_label(key));
+    }
+    println!("  {} {}", "Jobs:".cyan(), default_jobs());
+    println!("  {} {} samples", "Block size:".cyan(), DEFAULT_BLOCK_SIZE);
+    println!(
+        "  {} {}",
+        "Temp directory:".cyan(),
+        std::env::temp_dir().display()
+    );
+
+    if results.iter().all(|ok| *ok) {
+        Ok(())
+    } else {
+        Err("some checks failed".to_string())
+    }
+}
+
+fn report(label: &str, result: Result<String>) -> bool {
+    match &result {
+        Ok(detail) => println!("  {} {} {}", label.cyan(), "ok".green(), detail.dimmed()),
+        Err(err) => println!("  {} {} {}", label.cyan(), "failed".red().bold(), err),
+    }
+    result.is_ok()
+}
+
+fn terminal_label(is_terminal: bool) -> String {
+    if is_terminal {
+        "terminal".green().to_string()
+    } else {
+        "not a terminal".dimmed().to_string()
+    }
+}
+
+fn env_label(key: &str) -> String {
+    match std::env::var(key) {
+        Ok(value) => value,
+        Err(_) => "(not set)".dimmed().to_string(),
+    }
+}
+
+fn check_flac_round_trip() -> Result<String> {
+    let samples = TEST_SAMPLE_RATE as usize / 2;
+    let interleaved: Vec<i32> = (0..samples)
+        .flat_map(|index| {
+            let phase = index as f64 * 440.0 * TAU / f64::from(TEST_SAMPLE_RATE);
+            let value = (phase.sin() * 12000.0) as i32;
+            [value, -value]
+        })
+        .collect();
+    let mut meta = InputMetadata::new();
+    meta.sample_rate = TEST_SAMPLE_RATE;
+    meta.channels = 2;
+    meta.bits_per_sample = 16;
+    meta.total_samples = samples as u64;
+
+    let path =
+        std::env::temp_dir().join(format!("flac-cue-split-doctor-{}.flac", std::process::id()));
+    let result = round_trip(&meta, &interleaved, &path);
+    let _ = fs::remove_file(&path);
+    result
+}
+
+fn round_trip(meta: &InputMetadata, interleaved: &[i32], path: &Path) -> Result<String> {
+    let mut encoder = start_encoder(
+        meta,
+        meta.channels,
+        &EncoderOptions {
+            verify: true,
+            ..EncoderOptions::default()
+        },
+        meta.total_samples,
+        Vec::new(),
+        EncoderOutput::File(path),
+    )?;
+    encoder.write_interleaved(interleaved, meta.total_samples as u32)?;
+    encoder.finish()?;
+    let size = fs::metadata(path)
+        .map_err(|err| format!("failed to read {}: {}", path.display(), err))?
+        .len();
+
+    let decoder = create_decoder(path)?;
+    let mut decoded = Vec::with_capacity(interleaved.len());
+    for block in decoder.into_blocks(DEFAULT_BLOCK_SIZE)? {
+        decoded.extend(block?.interleaved);
+    }
+    if decoded != interleaved {
+        return Err(format!(
+            "decoded audio differs from the encoded test signal ({} of {} samples read back)",
+            decoded.len() / 2,
+            interleaved.len() / 2
+        ));
+    }
+    Ok(format!(
+        "{} samples encoded and decoded, {} bytes",
+        meta.total_samples, size
+    ))
+}
+
+fn check_cue_parser() -> Result<String> {
+    let disc = parse_cue_from_str(TEST_CUE)?;
+    let starts: Vec<i64> = disc.tracks.iter().map(|track| track.start_frames).collect();
+    if starts != [0, 75] {
+        return Err(format!("test cue parsed with track starts {:?}", starts));
+    }
+    Ok(format!("{} tracks read from a test cue", disc.tracks.len()))
+}
@@ -0,0 +1,153 @@
+use clap::ValueEnum;
+
+use crate::Result;
+use crate::channels::speaker_layout;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Downmix {
+    Stereo,
+    Mono,
+}
+
+impl Downmix {
+    pub(crate) fn channels(self) -> u32 {
+        match self {
+            Downmix::Stereo => 2,
+            Downmix::Mono => 1,
+        }
+    }
+
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            Downmix::Stereo => "stereo",
+            Downmix::Mono => "mono",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Downmixer {
+    target: Downmix,
+    input_channels: usize,
+    matrix: Vec<Vec<f64>>,
+    min: i64,
+    max: i64,
+}
+
+impl Downmixer {
+    pub(crate) fn new(
+        target: Downmix,
+        input_channels: u32,
+        channel_mask: Option<u32>,
+        bits_per_sample: u32,
+    ) -> Result<Self> {
+        if input_channels <= target.channels() {
+            return Err(format!(
+                "cannot downmix {} channel(s) to {}",
+                input_channels,
+                target.label()
+            ));
+        }
+        let layout = speaker_layout(input_channels, channel_mask)
+            .map_err(|err| format!("cannot downmix: {}", err))?;
+        let mut matrix = match target {
+            Downmix::Stereo => vec![
+                layout
+                    .iter()
+                    .map(|speaker| speaker.left)
+                    .collect::<Vec<f64>>(),
+                layout.iter().map(|speaker| speaker.right).collect(),
+            ],
+            Downmix::Mono => vec![
+                layout
+                    .iter()
+                    .map(|speaker| (speaker.left + speaker.right) / 2.0)
+                    .collect(),
+            ],
+        };
+        for row in &mut matrix {
+            let sum: f64 = row.iter().sum();
+            if sum > 1.0 {
+                for gain in row.iter_mut() {
+                    *gain /= sum;
+                }
+            }
+        }
+
+        let max = (1i64 << (bits_per_sample.clamp(1, 32) - 1)) - 1;
+        Ok(Self {
+            target,
+            input_channels: input_channels as usize,
+            matrix,
+            min: -max - 1,
+            max,
+        })
+    }
+
+    pub(crate) fn target(&self) -> Downmix {
+        self.target
+    }
+
+    pub(crate) fn output_channels(&self) -> usize {
+        self.matrix.len()
+    }
+
+    pub(crate) fn process(&self, interleaved: &[i32]) -> Vec<i32> {
+        let frames = interleaved.len() / self.input_channels;
+        let mut out = Vec::with_capacity(frames * self.matrix.len());
+        for frame in interleaved.chunks_exact(self.input_channels) {
+            for row in &self.matrix {
+                let mixed: f64 = row
+                    .iter()
+                    .zip(frame.iter())
+                    .map(|(gain, sample)| gain * *sample as f64)
+                    .sum();
+                out.push((mixed.round() as i64).clamp(self.min, self.max) as i32);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Downmix, Downmixer};
+
+    #[test]
+    fn stereo_to_mono_averages_channels() {
+        let downmixer = Downmixer::new(Downmix::Mono, 2, None, 16).unwrap();
+        assert_eq!(downmixer.output_channels(), 1);
+        assert_eq!(
+            downmixer.process(&[100, 300, -32768, -32768]),
+            vec![200, -32768]
+        );
+    }
+
+    #[test]
+    fn surround_to_stereo_drops_lfe_and_stays_in_range() {
+        let downmixer = Downmixer::new(Downmix::Stereo, 6, None, 16).unwrap();
+        let out = downmixer.process(&[1000, 0, 0, 32767, 0, 0]);
+        assert_eq!(out.len(), 2);
+        assert!(out[0] > 0 && out[0] < 1000);
+        assert_eq!(out[1], 0);
+
+        let loud = downmixer.process(&[32767; 6]);
+        assert!(loud.iter().all(|sample| *sample <= 32767));
+    }
+
+    #[test]
+    fn channel_mask_selects_the_speaker_layout() {
+        let downmixer = Downmixer::new(Downmix::Stereo, 3, Some(0x103), 16).unwrap();
+        let out = downmixer.process(&[0, 0, 1000]);
+        assert_eq!(out[0], out[1]);
+        assert!(out[0] > 0);
+    }
+
+    #[test]
+    fn rejects_upmix_and_unknown_layouts() {
+        assert!(Downmixer::new(Downmix::Stereo, 2, None, 16).is_err());
+        assert!(Downmixer::new(Downmix::Mono, 1, None, 16).is_err());
+        assert!(Downmixer::new(Downmix::Stereo, 9, None, 16).is_err());
+        assert!(Downmixer::new(Downmix::Stereo, 6, Some(0x3), 16).is_err());
+    }
+}
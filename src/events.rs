@@ -0,0 +1,142 @@
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Mutex;
+
+use crate::Result;
+use crate::cli::ProgressArgs;
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum Event<'a> {
+    Start {
+        albums: usize,
+        total_samples: u64,
+    },
+    AlbumStart {
+        source: &'a str,
+        tracks: usize,
+        total_samples: u64,
+    },
+    TrackStart {
+        source: &'a str,
+        track: u32,
+        output: &'a str,
+    },
+    Progress {
+        source: &'a str,
+        samples: u64,
+        total_samples: u64,
+    },
+    AlbumDone {
+        source: &'a str,
+        result: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+    },
+    Finish {
+        result: &'a str,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+    },
+}
+
+pub(crate) struct EventSink {
+    out: Mutex<Option<Box<dyn Write + Send>>>,
+}
+
+impl EventSink {
+    pub(crate) fn open(args: &ProgressArgs) -> Result<Option<Self>> {
+        let out: Box<dyn Write + Send> = if let Some(fd) = args.progress_fd {
+            open_fd(fd)?
+        } else if let Some(path) = args.progress_socket.as_ref() {
+            open_socket(path)?
+        } else {
+            return Ok(None);
+        };
+        Ok(Some(Self::new(out)))
+    }
+
+    pub(crate) fn new(out: Box<dyn Write + Send>) -> Self {
+        Self {
+            out: Mutex::new(Some(out)),
+        }
+    }
+
+    pub(crate) fn emit(&self, event: &Event) {
+        let Ok(mut line) = serde_json::to_vec(event) else {
+            return;
+        };
+        line.push(b'\n');
+        let mut out = self.out.lock().unwrap();
+        let written = out
+            .as_mut()
+            .map(|out| out.write_all(&line).and_then(|()| out.flush()));
+        if let Some(Err(_)) = written {
+            // The frontend went away; keep splitting without progress messages.
+            *out = None;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn open_fd(fd: i32) -> Result<Box<dyn Write + Send>> {
+    use std::os::fd::FromRawFd;
+
+    if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } == -1 {
+        return Err(format!(
+            "--progress-fd {} is not an open file descriptor",
+            fd
+        ));
+    }
+    Ok(Box::new(unsafe { std::fs::File::from_raw_fd(fd) }))
+}
+
+#[cfg(unix)]
+fn open_socket(path: &std::path::Path) -> Result<Box<dyn Write + Send>> {
+    let stream = std::os::unix::net::UnixStream::connect(path).map_err(|err| {
+        format!(
+            "failed to connect to progress socket {}: {}",
+            path.display(),
+            err
+        )
+    })?;
+    Ok(Box::new(stream))
+}
+
+#[cfg(not(unix))]
+fn open_fd(_fd: i32) -> Result<Box<dyn Write + Send>> {
+    Err("--progress-fd is only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+fn open_socket(_path: &std::path::Path) -> Result<Box<dyn Write + Send>> {
+    Err("--progress-socket is only supported on Unix".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Event;
+
+    #[test]
+    fn events_serialize_as_tagged_json_lines() {
+        let event = Event::TrackStart {
+            source: "Album.flac",
+            track: 3,
+            output: "03 - Three.flac",
+        };
+        assert_eq!(
+            serde_json::to_string(&event).unwrap(),
+            r#"{"event":"track_start","source":"Album.flac","track":3,"output":"03 - Three.flac"}"#
+        );
+
+        let done = Event::AlbumDone {
+            source: "Album.flac",
+            result: "done",
+            error: None,
+        };
+        assert_eq!(
+            serde_json::to_string(&done).unwrap(),
+            r#"{"event":"album_done","source":"Album.flac","result":"done"}"#
+        );
+    }
+}
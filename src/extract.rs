@@ -0,0 +1,55 @@
+use owo_colors::OwoColorize;
+use std::fs;
+
+use crate::Result;
+use crate::cli::{ExtractCueArgs, display_path};
+use crate::decoder::create_decoder;
+
+pub(crate) fn run_extract_cue(args: ExtractCueArgs) -> Result<()> {
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let file_abs = if args.file.is_absolute() {
+        args.file.clone()
+    } else {
+        cwd.join(&args.file)
+    };
+    if !file_abs.is_file() {
+        return Err(format!("file not found: {}", file_abs.display()));
+    }
+
+    let out_path = match args.out.as_ref() {
+        Some(path) if path.is_absolute() => path.clone(),
+        Some(path) => cwd.join(path),
+        None => file_abs.with_extension("cue"),
+    };
+    if out_path.exists() && !args.overwrite {
+        return Err(format!(
+            "output file already exists: {}",
+            out_path.display()
+        ));
+    }
+
+    let mut decoder = create_decoder(&file_abs)?;
+    let meta = decoder.read_metadata()?.input_meta;
+    let mut cue_text = meta.embedded_cue.ok_or_else(|| {
+        format!(
+            "no embedded cue sheet (CUESHEET block or tag) found in {}",
+            file_abs.display()
+        )
+    })?;
+    if !cue_text.ends_with('\n') {
+        cue_text.push('\n');
+    }
+
+    fs::write(&out_path, cue_text)
+        .map_err(|err| format!("failed to write cue file {}: {}", out_path.display(), err))?;
+    println!(
+        "{} {}",
+        "Created".green().bold(),
+        display_path(Some(cwd.as_path()), &out_path)
+            .display()
+            .to_string()
+            .bold()
+    );
+    Ok(())
+}
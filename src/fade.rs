@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use crate::Result;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Fade {
+    pub(crate) fade_in: Duration,
+    pub(crate) fade_out: Duration,
+}
+
+impl Fade {
+    pub(crate) fn is_none(&self) -> bool {
+        self.fade_in.is_zero() && self.fade_out.is_zero()
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.fade_in.is_zero() {
+            parts.push(format!("in {}", format_fade_length(self.fade_in)));
+        }
+        if !self.fade_out.is_zero() {
+            parts.push(format!("out {}", format_fade_length(self.fade_out)));
+        }
+        parts.join(", ")
+    }
+}
+
+pub(crate) fn parse_fade_length(value: &str) -> Result<Duration> {
+    let trimmed = value.trim();
+    let invalid = || format!("invalid fade length {:?} (examples: 10ms, 1.5s)", value);
+    let (number, scale) = if let Some(number) = trimmed.strip_suffix("ms") {
+        (number, 0.001)
+    } else if let Some(number) = trimmed.strip_suffix('s') {
+        (number, 1.0)
+    } else {
+        return Err(invalid());
+    };
+    let number: f64 = number.trim().parse().map_err(|_| invalid())?;
+    if !number.is_finite() || number < 0.0 {
+        return Err(invalid());
+    }
+    Duration::try_from_secs_f64(number * scale).map_err(|_| invalid())
+}
+
+fn format_fade_length(length: Duration) -> String {
+    if length.subsec_millis() == 0 && length.as_secs() > 0 {
+        format!("{} s", length.as_secs())
+    } else {
+        format!("{} ms", length.as_millis())
+    }
+}
+
+pub(crate) struct FadeStage {
+    fade_in: u64,
+    fade_out: u64,
+    length: u64,
+    position: u64,
+}
+
+impl FadeStage {
+    pub(crate) fn new(fade: Fade, sample_rate: u32, length: u64) -> Self {
+        let samples = |duration: Duration| {
+            ((duration.as_secs_f64() * f64::from(sample_rate)).round() as u64).min(length)
+        };
+        Self {
+            fade_in: samples(fade.fade_in),
+            fade_out: samples(fade.fade_out),
+            length,
+            position: 0,
+        }
+    }
+
+    pub(crate) fn apply(&mut self, samples: &mut [i32], channels: usize) {
+        // Linear ramps that reach zero on the first and last sample of the track.
+        for frame in samples.chunks_mut(channels.max(1)) {
+            let mut factor = 1.0;
+            if self.position < self.fade_in {
+                factor *= self.position as f64 / self.fade_in as f64;
+            }
+            let left = self.length.saturating_sub(self.position + 1);
+            if left < self.fade_out {
+                factor *= left as f64 / self.fade_out as f64;
+            }
+            if factor < 1.0 {
+                for sample in frame.iter_mut() {
+                    *sample = (*sample as f64 * factor).round() as i32;
+                }
+            }
+            self.position += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fade, FadeStage, parse_fade_length};
+    use std::time::Duration;
+
+    #[test]
+    fn fades_ramp_track_edges_to_silence() {
+        assert_eq!(
+            parse_fade_length("10ms").unwrap(),
+            Duration::from_millis(10)
+        );
+        assert_eq!(
+            parse_fade_length("1.5s").unwrap(),
+            Duration::from_millis(1500)
+        );
+        assert!(parse_fade_length("10").is_err());
+        assert!(parse_fade_length("-5ms").is_err());
+
+        let fade = Fade {
+            fade_in: Duration::from_millis(4),
+            fade_out: Duration::from_millis(2),
+        };
+        assert_eq!(fade.describe(), "in 4 ms, out 2 ms");
+
+        // 1 kHz makes every millisecond one sample; the track is split across
+        // two blocks of stereo frames.
+        let mut stage = FadeStage::new(fade, 1000, 8);
+        let mut first = vec![100; 10];
+        let mut second = vec![-100; 6];
+        stage.apply(&mut first, 2);
+        stage.apply(&mut second, 2);
+        assert_eq!(first, vec![0, 0, 25, 25, 50, 50, 75, 75, 100, 100]);
+        assert_eq!(second, vec![-100, -100, -50, -50, 0, 0]);
+    }
+}
@@ -1,16 +1,122 @@
+use clap::ValueEnum;
 use indicatif::ProgressBar;
 use libflac_sys as flac;
 use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
-use std::ffi::{CString, c_void};
+use std::ffi::{CStr, CString, c_void};
+use std::io::{self, Read, Write};
 use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
 
 use crate::Result;
+use crate::channels::{CHANNEL_MASK_TAG, parse_channel_mask};
 use crate::cli::display_path;
-use crate::decoder::{AudioBlock, Decoder, DecoderMetadata};
-use crate::metadata::{build_track_metadata, parse_vorbis_comment};
-use crate::types::{CueDisc, InputMetadata, TrackSpan};
+use crate::cue::render_cue_sheet;
+use crate::decoder::{AudioBlock, DEFAULT_BLOCK_SIZE, Decoder, DecoderMetadata};
+use crate::i18n::{Msg, tr};
+use crate::id3::{merge_id3_tags, strip_id3v2};
+use crate::metadata::{append_comment, build_track_metadata, find_tag, parse_vorbis_comment};
+use crate::picture::convert_legacy_picture_tags;
+use crate::types::{CueDisc, CueRem, CueTrack, InputMetadata, LayoutBlock, TrackSpan};
+
+const MAX_FLAC_CHANNELS: u32 = 8;
+const SEEK_POINT_SECONDS: u32 = 10;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum OutputFormat {
+    #[default]
+    Flac,
+    Oggflac,
+}
+
+impl OutputFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Flac => "flac",
+            OutputFormat::Oggflac => "oga",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct EncoderOptions {
+    pub(crate) compression_level: u8,
+    pub(crate) format: OutputFormat,
+    pub(crate) verify: bool,
+    pub(crate) seektable: bool,
+    pub(crate) padding: u32,
+    pub(crate) keep_block_layout: bool,
+    // Encoder threads per track; only libFLAC 1.5 and later built with
+    // threading support use more than one.
+    pub(crate) threads: u32,
+}
+
+impl Default for EncoderOptions {
+    fn default() -> Self {
+        Self {
+            compression_level: 5,
+            format: OutputFormat::Flac,
+            verify: false,
+            seektable: false,
+            padding: 0,
+            keep_block_layout: false,
+            threads: 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Preset {
+    Archive,
+    Default,
+    Fast,
+    Portable,
+}
+
+impl Preset {
+    pub(crate) fn options(self) -> EncoderOptions {
+        match self {
+            Preset::Archive => EncoderOptions {
+                compression_level: 8,
+                format: OutputFormat::Flac,
+                verify: true,
+                seektable: true,
+                padding: 8192,
+                keep_block_layout: false,
+                threads: 1,
+            },
+            Preset::Default => EncoderOptions {
+                compression_level: 5,
+                format: OutputFormat::Flac,
+                verify: false,
+                seektable: true,
+                padding: 8192,
+                keep_block_layout: false,
+                threads: 1,
+            },
+            Preset::Fast => EncoderOptions {
+                compression_level: 0,
+                format: OutputFormat::Flac,
+                verify: false,
+                seektable: false,
+                padding: 0,
+                keep_block_layout: false,
+                threads: 1,
+            },
+            Preset::Portable => EncoderOptions {
+                compression_level: 3,
+                format: OutputFormat::Flac,
+                verify: false,
+                seektable: true,
+                padding: 1024,
+                keep_block_layout: false,
+                threads: 1,
+            },
+        }
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct FlacMetadata {
@@ -50,6 +156,12 @@ impl FlacMetadata {
         unsafe { self.ptr.as_mut() }
     }
 
+    pub(crate) fn into_raw(self) -> *mut flac::FLAC__StreamMetadata {
+        let ptr = self.ptr.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+
     fn from_raw(ptr: *mut flac::FLAC__StreamMetadata, err: &str) -> Result<Self> {
         match NonNull::new(ptr) {
             Some(ptr) => Ok(Self { ptr }),
@@ -58,6 +170,10 @@ impl FlacMetadata {
     }
 }
 
+// Each FlacMetadata owns its libFLAC object exclusively, and shared access only reads it.
+unsafe impl Send for FlacMetadata {}
+unsafe impl Sync for FlacMetadata {}
+
 impl Drop for FlacMetadata {
     fn drop(&mut self) {
         unsafe {
@@ -98,15 +214,15 @@ impl FlacDecoder {
             return Err(err);
         }
 
-        let input_meta = std::mem::replace(&mut state.meta, InputMetadata::new());
-        Ok(DecoderMetadata {
-            input_meta,
-            picture_names: Vec::new(),
-        })
+        let file_name = self
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned());
+        Ok(finish_metadata(&mut state, file_name))
     }
 
-    fn block_iter(&self) -> Result<FlacBlockIter> {
-        FlacBlockIter::new(&self.path)
+    fn block_iter(&self, block_size: usize) -> Result<FlacBlockIter> {
+        FlacBlockIter::new(&self.path, block_size)
     }
 }
 
@@ -115,8 +231,11 @@ impl Decoder for FlacDecoder {
         self.read_metadata_internal()
     }
 
-    fn into_blocks(self: Box<Self>) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
-        Ok(Box::new(self.block_iter()?))
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        Ok(Box::new(self.block_iter(block_size)?))
     }
 }
 
@@ -186,6 +305,39 @@ impl FlacStreamDecoder {
         Ok(())
     }
 
+    fn init_stream(
+        &mut self,
+        read_cb: flac::FLAC__StreamDecoderReadCallback,
+        write_cb: flac::FLAC__StreamDecoderWriteCallback,
+        metadata_cb: flac::FLAC__StreamDecoderMetadataCallback,
+        error_cb: flac::FLAC__StreamDecoderErrorCallback,
+        client_data: *mut c_void,
+    ) -> Result<()> {
+        let init_status = unsafe {
+            flac::FLAC__stream_decoder_set_metadata_respond_all(self.decoder);
+            flac::FLAC__stream_decoder_init_stream(
+                self.decoder,
+                read_cb,
+                None,
+                None,
+                None,
+                None,
+                write_cb,
+                metadata_cb,
+                error_cb,
+                client_data,
+            )
+        };
+        if init_status != flac::FLAC__STREAM_DECODER_INIT_STATUS_OK {
+            return Err(format!(
+                "failed to init FLAC stream decoder (status {}: {})",
+                init_status,
+                decoder_init_status_label(init_status),
+            ));
+        }
+        Ok(())
+    }
+
     fn process_until_end_of_metadata(&mut self) -> i32 {
         unsafe { flac::FLAC__stream_decoder_process_until_end_of_metadata(self.decoder) }
     }
@@ -211,8 +363,141 @@ impl Drop for FlacStreamDecoder {
     }
 }
 
+pub(crate) struct FlacReaderDecoder {
+    blocks: FlacBlockIter,
+    name: String,
+    id3_tags: Vec<(String, String)>,
+}
+
+// The decoder and its callback state are owned by this value and only used by one thread at a time.
+unsafe impl Send for FlacReaderDecoder {}
+
+impl FlacReaderDecoder {
+    pub(crate) fn new(input: Box<dyn Read + Send>, name: &str) -> Result<Self> {
+        let (input, id3_tags) =
+            strip_id3v2(input).map_err(|err| format!("failed to read {}: {}", name, err))?;
+        let mut decoder = FlacStreamDecoder::new()?;
+        let mut state = Box::new(FlacBlockState::new(DEFAULT_BLOCK_SIZE));
+        state.input = Some(StreamInput {
+            reader: input,
+            name: name.to_string(),
+        });
+        state.metadata = Some(FlacMetadataState::new());
+
+        decoder.init_stream(
+            Some(flac_reader_read_callback),
+            Some(flac_write_callback),
+            Some(flac_reader_metadata_callback),
+            Some(flac_stream_error_callback),
+            state.as_mut() as *mut _ as *mut c_void,
+        )?;
+
+        Ok(Self {
+            blocks: FlacBlockIter {
+                decoder,
+                state,
+                done: false,
+                failed: None,
+            },
+            name: name.to_string(),
+            id3_tags,
+        })
+    }
+}
+
+impl Decoder for FlacReaderDecoder {
+    fn read_metadata(&mut self) -> Result<DecoderMetadata> {
+        let ok = self.blocks.decoder.process_until_end_of_metadata();
+        let error = self.blocks.state.error.take();
+        if ok == 0 {
+            return Err(
+                error.unwrap_or_else(|| format!("failed to read FLAC metadata from {}", self.name))
+            );
+        }
+        if let Some(err) = error {
+            return Err(err);
+        }
+        let mut state = self
+            .blocks
+            .state
+            .metadata
+            .take()
+            .ok_or_else(|| format!("FLAC metadata from {} was already read", self.name))?;
+        let mut metadata = finish_metadata(&mut state, None);
+        merge_id3_tags(&mut metadata.input_meta.comments, &self.id3_tags);
+        Ok(metadata)
+    }
+
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        let mut blocks = self.blocks;
+        blocks.state.block_size = block_size;
+        Ok(Box::new(blocks))
+    }
+}
+
+unsafe extern "C" fn flac_reader_read_callback(
+    _decoder: *const flac::FLAC__StreamDecoder,
+    buffer: *mut flac::FLAC__byte,
+    bytes: *mut usize,
+    client_data: *mut c_void,
+) -> flac::FLAC__StreamDecoderReadStatus {
+    if buffer.is_null() || bytes.is_null() || client_data.is_null() {
+        return flac::FLAC__STREAM_DECODER_READ_STATUS_ABORT;
+    }
+
+    let state = unsafe { &mut *(client_data as *mut FlacBlockState) };
+    let wanted = unsafe { *bytes };
+    let Some(input) = state.input.as_mut() else {
+        return flac::FLAC__STREAM_DECODER_READ_STATUS_ABORT;
+    };
+    if wanted == 0 {
+        return flac::FLAC__STREAM_DECODER_READ_STATUS_ABORT;
+    }
+
+    let target = unsafe { std::slice::from_raw_parts_mut(buffer, wanted) };
+    loop {
+        match input.reader.read(target) {
+            Ok(0) => {
+                unsafe { *bytes = 0 };
+                return flac::FLAC__STREAM_DECODER_READ_STATUS_END_OF_STREAM;
+            }
+            Ok(read) => {
+                unsafe { *bytes = read };
+                return flac::FLAC__STREAM_DECODER_READ_STATUS_CONTINUE;
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => {
+                state.error = Some(format!("failed to read {}: {}", input.name, err));
+                unsafe { *bytes = 0 };
+                return flac::FLAC__STREAM_DECODER_READ_STATUS_ABORT;
+            }
+        }
+    }
+}
+
+fn finish_metadata(state: &mut FlacMetadataState, file_name: Option<String>) -> DecoderMetadata {
+    let mut input_meta = std::mem::replace(&mut state.meta, InputMetadata::new());
+    if let Some(mut disc) = state.cuesheet.take() {
+        for track in &mut disc.tracks {
+            track.filename = file_name.clone();
+        }
+        input_meta.embedded_cue = Some(render_cue_sheet(&disc));
+    } else if let Some(text) = find_tag(&input_meta.comments, "CUESHEET") {
+        input_meta.embedded_cue = Some(text.to_string());
+    }
+    convert_legacy_picture_tags(&mut input_meta);
+    DecoderMetadata {
+        input_meta,
+        picture_names: Vec::new(),
+    }
+}
+
 struct FlacMetadataState {
     meta: InputMetadata,
+    cuesheet: Option<CueDisc>,
     error: Option<String>,
 }
 
@@ -220,6 +505,7 @@ impl FlacMetadataState {
     fn new() -> Self {
         Self {
             meta: InputMetadata::new(),
+            cuesheet: None,
             error: None,
         }
     }
@@ -235,8 +521,74 @@ unsafe extern "C" fn flac_metadata_callback(
     }
 
     let state = unsafe { &mut *(client_data as *mut FlacMetadataState) };
+    record_metadata(state, unsafe { &*metadata });
+}
+
+unsafe extern "C" fn flac_reader_metadata_callback(
+    _decoder: *const flac::FLAC__StreamDecoder,
+    metadata: *const flac::FLAC__StreamMetadata,
+    client_data: *mut c_void,
+) {
+    if client_data.is_null() || metadata.is_null() {
+        return;
+    }
+
+    let state = unsafe { &mut *(client_data as *mut FlacBlockState) };
     let metadata_ref = unsafe { &*metadata };
+    record_block_size(state, metadata_ref);
+    if let Some(metadata_state) = state.metadata.as_mut() {
+        record_metadata(metadata_state, metadata_ref);
+    }
+}
+
+unsafe extern "C" fn flac_block_metadata_callback(
+    _decoder: *const flac::FLAC__StreamDecoder,
+    metadata: *const flac::FLAC__StreamMetadata,
+    client_data: *mut c_void,
+) {
+    if client_data.is_null() || metadata.is_null() {
+        return;
+    }
+
+    let state = unsafe { &mut *(client_data as *mut FlacBlockState) };
+    record_block_size(state, unsafe { &*metadata });
+}
+
+fn record_block_size(state: &mut FlacBlockState, metadata_ref: &flac::FLAC__StreamMetadata) {
+    if metadata_ref.type_ == flac::FLAC__METADATA_TYPE_STREAMINFO {
+        let info = unsafe { metadata_ref.data.stream_info };
+        state.fixed_block_size = (info.min_blocksize == info.max_blocksize
+            && info.max_blocksize > 0)
+            .then_some(info.max_blocksize);
+    }
+}
 
+// Variable-blocksize frames carry their first sample number; fixed-blocksize
+// frames carry a frame number, which only maps to a position through the
+// STREAMINFO block size. Without one, the running sample count is used.
+pub(crate) fn frame_sample_index(
+    sample_numbered: bool,
+    number: u64,
+    fixed_block_size: Option<u32>,
+    next_sample_number: u64,
+) -> u64 {
+    if sample_numbered {
+        return number;
+    }
+    match fixed_block_size {
+        Some(size) => number.saturating_mul(u64::from(size)),
+        None => next_sample_number,
+    }
+}
+
+fn record_metadata(state: &mut FlacMetadataState, metadata_ref: &flac::FLAC__StreamMetadata) {
+    if metadata_ref.type_ != flac::FLAC__METADATA_TYPE_STREAMINFO {
+        let layout = match metadata_ref.type_ {
+            flac::FLAC__METADATA_TYPE_PADDING => LayoutBlock::Padding(metadata_ref.length),
+            kind => layout_kind(kind),
+        };
+        state.meta.block_layout.push(layout);
+    }
     match metadata_ref.type_ {
         flac::FLAC__METADATA_TYPE_STREAMINFO => {
             let info = unsafe { metadata_ref.data.stream_info };
@@ -244,21 +596,114 @@ unsafe extern "C" fn flac_metadata_callback(
             state.meta.channels = info.channels;
             state.meta.bits_per_sample = info.bits_per_sample;
             state.meta.total_samples = info.total_samples;
+            if info.md5sum.iter().any(|byte| *byte != 0) {
+                state.meta.md5 = Some(info.md5sum);
+            }
         }
         flac::FLAC__METADATA_TYPE_VORBIS_COMMENT => {
             let (vendor, comments) = parse_vorbis_comment(metadata_ref);
+            state.meta.channel_mask =
+                find_tag(&comments, CHANNEL_MASK_TAG).and_then(parse_channel_mask);
             state.meta.vendor = vendor;
             state.meta.comments = comments;
         }
         flac::FLAC__METADATA_TYPE_PICTURE => {
-            if let Some(clone) = FlacMetadata::clone_from_raw(metadata) {
+            if let Some(clone) = FlacMetadata::clone_from_raw(metadata_ref) {
                 state.meta.pictures.push(clone);
             }
         }
+        flac::FLAC__METADATA_TYPE_CUESHEET => {
+            state.cuesheet = cuesheet_block_to_disc(metadata_ref, state.meta.sample_rate);
+        }
         _ => {}
     }
 }
 
+fn cuesheet_block_to_disc(
+    metadata: &flac::FLAC__StreamMetadata,
+    sample_rate: u32,
+) -> Option<CueDisc> {
+    if sample_rate == 0 || !sample_rate.is_multiple_of(75) {
+        return None;
+    }
+    let samples_per_frame = (sample_rate / 75) as u64;
+    let sheet = unsafe { metadata.data.cue_sheet };
+    if sheet.num_tracks == 0 || sheet.tracks.is_null() {
+        return None;
+    }
+
+    let tracks = unsafe { std::slice::from_raw_parts(sheet.tracks, sheet.num_tracks as usize) };
+    let mut cue_tracks = Vec::new();
+    for track in tracks {
+        if track.number == 0 || track.number > 99 {
+            continue;
+        }
+        let indices = if track.indices.is_null() || track.num_indices == 0 {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(track.indices, track.num_indices as usize) }
+        };
+        let index_offset = indices
+            .iter()
+            .find(|index| index.number == 1)
+            .or_else(|| indices.first())
+            .map(|index| index.offset)
+            .unwrap_or(0);
+        let start = track.offset + index_offset;
+        cue_tracks.push(CueTrack {
+            number: track.number as u32,
+            title: None,
+            performer: None,
+            songwriter: None,
+            composer: None,
+            isrc: c_chars_to_string(&track.isrc),
+            start_frames: (start / samples_per_frame) as i64,
+            length_frames: None,
+            sub_indexes: indices
+                .iter()
+                .filter(|index| index.number > 1)
+                .map(|index| {
+                    let frames = (track.offset + index.offset) / samples_per_frame;
+                    (u32::from(index.number), frames as i64)
+                })
+                .collect(),
+            pregap_start: indices
+                .iter()
+                .find(|index| index.number == 0)
+                .map(|index| ((track.offset + index.offset) / samples_per_frame) as i64),
+            filename: None,
+            rem: CueRem::default(),
+        });
+    }
+    if cue_tracks.is_empty() {
+        return None;
+    }
+
+    Some(CueDisc {
+        title: None,
+        performer: None,
+        songwriter: None,
+        composer: None,
+        genre: None,
+        message: None,
+        disc_id: None,
+        rem: CueRem::default(),
+        tracks: cue_tracks,
+        discs: Vec::new(),
+        mode_issues: Vec::new(),
+    })
+}
+
+fn c_chars_to_string(chars: &[std::os::raw::c_char]) -> Option<String> {
+    let bytes: Vec<u8> = chars
+        .iter()
+        .take_while(|ch| **ch != 0)
+        .map(|ch| *ch as u8)
+        .collect();
+    let value = String::from_utf8_lossy(&bytes).trim().to_string();
+    if value.is_empty() { None } else { Some(value) }
+}
+
 unsafe extern "C" fn flac_metadata_error_callback(
     _decoder: *const flac::FLAC__StreamDecoder,
     status: flac::FLAC__StreamDecoderErrorStatus,
@@ -279,14 +724,34 @@ struct FlacBlockState {
     blocks: VecDeque<AudioBlock>,
     error: Option<String>,
     next_sample_number: u64,
+    fixed_block_size: Option<u32>,
+    block_size: usize,
+    input: Option<StreamInput>,
+    metadata: Option<FlacMetadataState>,
+}
+
+struct StreamInput {
+    reader: Box<dyn Read + Send>,
+    name: String,
 }
 
 impl FlacBlockState {
-    fn new() -> Self {
+    fn new(block_size: usize) -> Self {
         Self {
             blocks: VecDeque::new(),
             error: None,
             next_sample_number: 0,
+            fixed_block_size: None,
+            block_size,
+            input: None,
+            metadata: None,
+        }
+    }
+
+    fn has_ready_block(&self, done: bool) -> bool {
+        match self.blocks.front() {
+            Some(block) => done || self.blocks.len() > 1 || block.sample_count() >= self.block_size,
+            None => false,
         }
     }
 }
@@ -295,17 +760,18 @@ struct FlacBlockIter {
     decoder: FlacStreamDecoder,
     state: Box<FlacBlockState>,
     done: bool,
+    failed: Option<String>,
 }
 
 impl FlacBlockIter {
-    fn new(path: &Path) -> Result<Self> {
+    fn new(path: &Path, block_size: usize) -> Result<Self> {
         let mut decoder = FlacStreamDecoder::new()?;
-        let mut state = Box::new(FlacBlockState::new());
+        let mut state = Box::new(FlacBlockState::new(block_size));
 
         decoder.init_file(
             path,
             Some(flac_write_callback),
-            None,
+            Some(flac_block_metadata_callback),
             Some(flac_stream_error_callback),
             state.as_mut() as *mut _ as *mut c_void,
         )?;
@@ -314,6 +780,7 @@ impl FlacBlockIter {
             decoder,
             state,
             done: false,
+            failed: None,
         })
     }
 }
@@ -322,37 +789,37 @@ impl Iterator for FlacBlockIter {
     type Item = Result<AudioBlock>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if let Some(block) = self.state.blocks.pop_front() {
-            return Some(Ok(block));
-        }
-        if self.done {
-            return None;
-        }
-
         loop {
+            if self.state.has_ready_block(self.done) {
+                return self.state.blocks.pop_front().map(Ok);
+            }
+            if let Some(err) = self.failed.take() {
+                return Some(Err(err));
+            }
+            if self.done {
+                return None;
+            }
+
             let ok = self.decoder.process_single();
             if ok == 0 {
                 self.done = true;
-                let err = self
-                    .state
-                    .error
-                    .take()
-                    .unwrap_or_else(|| "FLAC decoding failed".to_string());
-                return Some(Err(err));
+                self.failed = Some(
+                    self.state
+                        .error
+                        .take()
+                        .unwrap_or_else(|| "FLAC decoding failed".to_string()),
+                );
+                continue;
             }
 
             if let Some(err) = self.state.error.take() {
                 self.done = true;
-                return Some(Err(err));
-            }
-
-            if let Some(block) = self.state.blocks.pop_front() {
-                return Some(Ok(block));
+                self.failed = Some(err);
+                continue;
             }
 
             if self.decoder.state() == flac::FLAC__STREAM_DECODER_END_OF_STREAM {
                 self.done = true;
-                return None;
             }
         }
     }
@@ -405,35 +872,52 @@ unsafe extern "C" fn flac_write_callback(
         return flac::FLAC__STREAM_DECODER_WRITE_STATUS_CONTINUE;
     }
 
-    let sample_index =
-        if frame_ref.header.number_type == flac::FLAC__FRAME_NUMBER_TYPE_SAMPLE_NUMBER {
-            unsafe { frame_ref.header.number.sample_number }
-        } else {
-            state.next_sample_number
-        };
+    let sample_numbered =
+        frame_ref.header.number_type == flac::FLAC__FRAME_NUMBER_TYPE_SAMPLE_NUMBER;
+    let number = if sample_numbered {
+        unsafe { frame_ref.header.number.sample_number }
+    } else {
+        u64::from(unsafe { frame_ref.header.number.frame_number })
+    };
+    let sample_index = frame_sample_index(
+        sample_numbered,
+        number,
+        state.fixed_block_size,
+        state.next_sample_number,
+    );
     state.next_sample_number = sample_index + block_samples as u64;
 
-    let mut interleaved = Vec::with_capacity(block_samples * channels);
+    let extends_last = state.blocks.back().is_some_and(|last| {
+        last.channels == channels as u32
+            && last.sample_index + last.sample_count() as u64 == sample_index
+            && last.sample_count() < state.block_size
+    });
+    if !extends_last {
+        state.blocks.push_back(AudioBlock {
+            sample_index,
+            channels: channels as u32,
+            interleaved: Vec::with_capacity(state.block_size.max(block_samples) * channels),
+        });
+    }
+    let Some(block) = state.blocks.back_mut() else {
+        return flac::FLAC__STREAM_DECODER_WRITE_STATUS_ABORT;
+    };
     for i in 0..block_samples {
         for ch in 0..channels {
             unsafe {
                 let chan_ptr = *buffer.add(ch);
-                interleaved.push(*chan_ptr.add(i));
+                block.interleaved.push(*chan_ptr.add(i));
             }
         }
     }
 
-    state.blocks.push_back(AudioBlock {
-        sample_index,
-        channels: channels as u32,
-        interleaved,
-    });
-
     flac::FLAC__STREAM_DECODER_WRITE_STATUS_CONTINUE
 }
 
 pub(crate) struct TrackEncoder {
     encoder: *mut flac::FLAC__StreamEncoder,
+    // libFLAC fills in the seek table while finishing, so the blocks must outlive the encoder.
+    _metadata_blocks: Vec<FlacMetadata>,
 }
 
 impl TrackEncoder {
@@ -482,26 +966,97 @@ impl Drop for TrackEncoder {
     }
 }
 
+pub(crate) struct TrackEncoderSettings<'a> {
+    pub(crate) channels: u32,
+    pub(crate) encoder: EncoderOptions,
+    pub(crate) extra_tags: &'a [(String, String)],
+    pub(crate) to_stdout: bool,
+}
+
+#[derive(Clone, Copy)]
+pub(crate) enum EncoderOutput<'a> {
+    File(&'a Path),
+    Stdout,
+}
+
 pub(crate) fn start_track_encoder(
     meta: &InputMetadata,
     cue: &CueDisc,
     tracks: &[TrackSpan],
     track: &TrackSpan,
-    compression_level: u8,
+    settings: &TrackEncoderSettings,
     display_base_abs: Option<&Path>,
     progress: Option<&ProgressBar>,
 ) -> Result<TrackEncoder> {
+    let mut metadata_blocks = build_track_metadata(meta, cue, tracks, track)?;
+    for (key, value) in settings.extra_tags {
+        append_comment(&mut metadata_blocks[0], key, value)?;
+    }
+    let output = if settings.to_stdout {
+        EncoderOutput::Stdout
+    } else {
+        EncoderOutput::File(&track.output_path)
+    };
+    let options = EncoderOptions {
+        compression_level: track
+            .compression_level
+            .unwrap_or(settings.encoder.compression_level),
+        ..settings.encoder
+    };
+    let encoder = start_encoder(
+        meta,
+        settings.channels,
+        &options,
+        track.end - track.start,
+        metadata_blocks,
+        output,
+    )?;
+
+    announce_track_start(display_base_abs, progress, track, output);
+
+    Ok(encoder)
+}
+
+pub(crate) fn start_encoder(
+    meta: &InputMetadata,
+    channels: u32,
+    options: &EncoderOptions,
+    total_samples: u64,
+    mut metadata_blocks: Vec<FlacMetadata>,
+    output: EncoderOutput,
+) -> Result<TrackEncoder> {
+    if channels == 0 || channels > MAX_FLAC_CHANNELS {
+        return Err(format!(
+            "FLAC supports 1 to {} channels, source has {}",
+            MAX_FLAC_CHANNELS, channels
+        ));
+    }
+
+    let ogg = options.format == OutputFormat::Oggflac;
+    // Ogg FLAC has no seek table; Ogg pages carry their own granule positions.
+    if options.seektable && !ogg && total_samples > 0 {
+        metadata_blocks.push(seektable_block(total_samples, meta.sample_rate)?);
+    }
+    if options.keep_block_layout && !meta.block_layout.is_empty() {
+        metadata_blocks = arrange_metadata_blocks(metadata_blocks, &meta.block_layout)?;
+    } else if options.padding > 0 {
+        metadata_blocks.push(padding_block(options.padding)?);
+    }
+
     let encoder = unsafe { flac::FLAC__stream_encoder_new() };
     if encoder.is_null() {
         return Err("failed to create FLAC encoder".to_string());
     }
 
     let ok = unsafe {
-        flac::FLAC__stream_encoder_set_channels(encoder, meta.channels) != 0
+        flac::FLAC__stream_encoder_set_channels(encoder, channels) != 0
             && flac::FLAC__stream_encoder_set_bits_per_sample(encoder, meta.bits_per_sample) != 0
             && flac::FLAC__stream_encoder_set_sample_rate(encoder, meta.sample_rate) != 0
-            && flac::FLAC__stream_encoder_set_compression_level(encoder, compression_level as u32)
-                != 0
+            && flac::FLAC__stream_encoder_set_compression_level(
+                encoder,
+                u32::from(options.compression_level),
+            ) != 0
+            && flac::FLAC__stream_encoder_set_verify(encoder, i32::from(options.verify)) != 0
     };
     if !ok {
         unsafe {
@@ -510,12 +1065,14 @@ pub(crate) fn start_track_encoder(
         return Err("failed to configure FLAC encoder".to_string());
     }
 
-    let track_samples = track.end - track.start;
     unsafe {
-        flac::FLAC__stream_encoder_set_total_samples_estimate(encoder, track_samples);
+        flac::FLAC__stream_encoder_set_total_samples_estimate(encoder, total_samples);
+        // A libFLAC built without threads refuses; it then encodes on one.
+        if options.threads > 1 {
+            flac::FLAC__stream_encoder_set_num_threads(encoder, options.threads);
+        }
     }
 
-    let mut metadata_blocks = build_track_metadata(meta, cue, tracks, track)?;
     if !metadata_blocks.is_empty() {
         let mut metadata_ptrs = FlacMetadata::collect_raw_ptrs(&mut metadata_blocks);
         let ok = unsafe {
@@ -533,43 +1090,382 @@ pub(crate) fn start_track_encoder(
         }
     }
 
-    let path_c = path_to_cstring(&track.output_path)?;
-    let init_status = unsafe {
-        flac::FLAC__stream_encoder_init_file(encoder, path_c.as_ptr(), None, std::ptr::null_mut())
+    let init_status = match output {
+        EncoderOutput::File(path) => {
+            let path_c = match path_to_cstring(path) {
+                Ok(path_c) => path_c,
+                Err(err) => {
+                    unsafe {
+                        flac::FLAC__stream_encoder_delete(encoder);
+                    }
+                    return Err(err);
+                }
+            };
+            let init = if ogg {
+                flac::FLAC__stream_encoder_init_ogg_file
+            } else {
+                flac::FLAC__stream_encoder_init_file
+            };
+            unsafe { init(encoder, path_c.as_ptr(), None, std::ptr::null_mut()) }
+        }
+        EncoderOutput::Stdout if ogg => unsafe {
+            flac::FLAC__stream_encoder_init_ogg_stream(
+                encoder,
+                None,
+                Some(flac_stdout_write_callback),
+                None,
+                None,
+                None,
+                std::ptr::null_mut(),
+            )
+        },
+        EncoderOutput::Stdout => unsafe {
+            flac::FLAC__stream_encoder_init_stream(
+                encoder,
+                Some(flac_stdout_write_callback),
+                None,
+                None,
+                None,
+                std::ptr::null_mut(),
+            )
+        },
     };
 
     if init_status != flac::FLAC__STREAM_ENCODER_INIT_STATUS_OK {
         unsafe {
             flac::FLAC__stream_encoder_delete(encoder);
         }
-        return Err(format!(
-            "failed to init encoder for {}",
-            track.output_path.display()
-        ));
+        return Err(match output {
+            EncoderOutput::File(path) => format!("failed to init encoder for {}", path.display()),
+            EncoderOutput::Stdout => "failed to init encoder for stdout".to_string(),
+        });
+    }
+
+    Ok(TrackEncoder {
+        encoder,
+        _metadata_blocks: metadata_blocks,
+    })
+}
+
+fn padding_block(length: u32) -> Result<FlacMetadata> {
+    let mut padding = FlacMetadata::new(flac::FLAC__METADATA_TYPE_PADDING)?;
+    padding.as_mut().length = length;
+    Ok(padding)
+}
+
+fn layout_kind(kind: flac::FLAC__MetadataType) -> LayoutBlock {
+    match kind {
+        flac::FLAC__METADATA_TYPE_VORBIS_COMMENT => LayoutBlock::VorbisComment,
+        flac::FLAC__METADATA_TYPE_PICTURE => LayoutBlock::Picture,
+        flac::FLAC__METADATA_TYPE_SEEKTABLE => LayoutBlock::SeekTable,
+        _ => LayoutBlock::Other,
+    }
+}
+
+fn arrange_metadata_blocks(
+    blocks: Vec<FlacMetadata>,
+    layout: &[LayoutBlock],
+) -> Result<Vec<FlacMetadata>> {
+    let kinds: Vec<LayoutBlock> = blocks
+        .iter()
+        .map(|block| layout_kind(unsafe { (*block.as_ptr()).type_ }))
+        .collect();
+    let mut blocks: Vec<Option<FlacMetadata>> = blocks.into_iter().map(Some).collect();
+    let mut arranged = Vec::with_capacity(blocks.len());
+    for slot in layout_order(layout, &kinds) {
+        match slot {
+            LayoutSlot::Block(index) => arranged.extend(blocks[index].take()),
+            LayoutSlot::Padding(length) => arranged.push(padding_block(length)?),
+        }
+    }
+    Ok(arranged)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LayoutSlot {
+    Block(usize),
+    Padding(u32),
+}
+
+// Blocks take the places of the source blocks of their kind, in order, and
+// padding keeps the source sizes. Blocks the source had no place for (an
+// added cover, a new seek table) go before the trailing padding.
+pub(crate) fn layout_order(layout: &[LayoutBlock], kinds: &[LayoutBlock]) -> Vec<LayoutSlot> {
+    let mut used = vec![false; kinds.len()];
+    let mut order = Vec::with_capacity(layout.len() + kinds.len());
+    for block in layout {
+        if let LayoutBlock::Padding(length) = *block {
+            order.push(LayoutSlot::Padding(length));
+        } else if let Some(index) =
+            (0..kinds.len()).find(|&index| !used[index] && kinds[index] == *block)
+        {
+            used[index] = true;
+            order.push(LayoutSlot::Block(index));
+        }
+    }
+    let trailing = order
+        .iter()
+        .rev()
+        .take_while(|slot| matches!(slot, LayoutSlot::Padding(_)))
+        .count();
+    let insert_at = order.len() - trailing;
+    let leftovers: Vec<LayoutSlot> = (0..kinds.len())
+        .filter(|index| !used[*index])
+        .map(LayoutSlot::Block)
+        .collect();
+    order.splice(insert_at..insert_at, leftovers);
+    order
+}
+
+fn seektable_block(total_samples: u64, sample_rate: u32) -> Result<FlacMetadata> {
+    let mut block = FlacMetadata::new(flac::FLAC__METADATA_TYPE_SEEKTABLE)?;
+    let ok = unsafe {
+        flac::FLAC__metadata_object_seektable_template_append_spaced_points_by_samples(
+            block.as_mut_ptr(),
+            sample_rate.saturating_mul(SEEK_POINT_SECONDS).max(1),
+            total_samples,
+        ) != 0
+            && flac::FLAC__metadata_object_seektable_template_sort(block.as_mut_ptr(), 1) != 0
+    };
+    if !ok {
+        return Err("failed to build FLAC seek table".to_string());
+    }
+    Ok(block)
+}
+
+pub(crate) fn flac_library_version() -> String {
+    let version = unsafe { flac::FLAC__VERSION_STRING };
+    if version.is_null() {
+        return "unknown".to_string();
+    }
+    unsafe { CStr::from_ptr(version) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+unsafe extern "C" fn flac_stdout_write_callback(
+    _encoder: *const flac::FLAC__StreamEncoder,
+    buffer: *const flac::FLAC__byte,
+    bytes: usize,
+    _samples: u32,
+    _current_frame: u32,
+    _client_data: *mut c_void,
+) -> flac::FLAC__StreamEncoderWriteStatus {
+    if buffer.is_null() {
+        return flac::FLAC__STREAM_ENCODER_WRITE_STATUS_FATAL_ERROR;
+    }
+    let data = unsafe { std::slice::from_raw_parts(buffer, bytes) };
+    let mut stdout = io::stdout().lock();
+    match stdout.write_all(data).and_then(|()| stdout.flush()) {
+        Ok(()) => flac::FLAC__STREAM_ENCODER_WRITE_STATUS_OK,
+        Err(_) => flac::FLAC__STREAM_ENCODER_WRITE_STATUS_FATAL_ERROR,
+    }
+}
+
+struct MetadataChain {
+    chain: *mut flac::FLAC__Metadata_Chain,
+    iterator: *mut flac::FLAC__Metadata_Iterator,
+}
+
+impl MetadataChain {
+    fn read(path: &Path) -> Result<Self> {
+        let path_c = path_to_cstring(path)?;
+        let chain = unsafe { flac::FLAC__metadata_chain_new() };
+        if chain.is_null() {
+            return Err("failed to allocate FLAC metadata chain".to_string());
+        }
+        let iterator = unsafe { flac::FLAC__metadata_iterator_new() };
+        let this = Self { chain, iterator };
+        if iterator.is_null() {
+            return Err("failed to allocate FLAC metadata iterator".to_string());
+        }
+
+        let ok = unsafe { flac::FLAC__metadata_chain_read(this.chain, path_c.as_ptr()) != 0 };
+        if !ok {
+            return Err(format!(
+                "failed to read FLAC metadata from {} (chain status {})",
+                path.display(),
+                this.status()
+            ));
+        }
+        Ok(this)
+    }
+
+    fn status(&self) -> flac::FLAC__Metadata_ChainStatus {
+        unsafe { flac::FLAC__metadata_chain_status(self.chain) }
+    }
+
+    fn rewind(&mut self) {
+        unsafe {
+            flac::FLAC__metadata_iterator_init(self.iterator, self.chain);
+        }
     }
 
-    announce_track_start(display_base_abs, progress, track);
+    fn remove_blocks(&mut self, kinds: &[flac::FLAC__MetadataType]) {
+        self.rewind();
+        loop {
+            let kind = unsafe { flac::FLAC__metadata_iterator_get_block_type(self.iterator) };
+            if kinds.contains(&kind) {
+                unsafe {
+                    flac::FLAC__metadata_iterator_delete_block(self.iterator, 0);
+                }
+            }
+            if unsafe { flac::FLAC__metadata_iterator_next(self.iterator) } == 0 {
+                break;
+            }
+        }
+    }
 
-    Ok(TrackEncoder { encoder })
+    fn find_block(
+        &mut self,
+        kind: flac::FLAC__MetadataType,
+    ) -> Option<*mut flac::FLAC__StreamMetadata> {
+        self.rewind();
+        loop {
+            if unsafe { flac::FLAC__metadata_iterator_get_block_type(self.iterator) } == kind {
+                let block = unsafe { flac::FLAC__metadata_iterator_get_block(self.iterator) };
+                return (!block.is_null()).then_some(block);
+            }
+            if unsafe { flac::FLAC__metadata_iterator_next(self.iterator) } == 0 {
+                return None;
+            }
+        }
+    }
+
+    fn insert_after_streaminfo(&mut self, blocks: Vec<FlacMetadata>) -> Result<()> {
+        self.rewind();
+        for block in blocks {
+            let raw = block.into_raw();
+            let ok =
+                unsafe { flac::FLAC__metadata_iterator_insert_block_after(self.iterator, raw) };
+            if ok == 0 {
+                unsafe {
+                    flac::FLAC__metadata_object_delete(raw);
+                }
+                return Err("failed to insert FLAC metadata block".to_string());
+            }
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, path: &Path) -> Result<()> {
+        unsafe {
+            flac::FLAC__metadata_chain_sort_padding(self.chain);
+        }
+        let ok = unsafe { flac::FLAC__metadata_chain_write(self.chain, 1, 1) != 0 };
+        if !ok {
+            return Err(format!(
+                "failed to write FLAC metadata to {} (chain status {})",
+                path.display(),
+                self.status()
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MetadataChain {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.iterator.is_null() {
+                flac::FLAC__metadata_iterator_delete(self.iterator);
+            }
+            flac::FLAC__metadata_chain_delete(self.chain);
+        }
+    }
 }
 
-fn path_to_cstring(path: &Path) -> Result<CString> {
-    let path_str = path.to_string_lossy();
+pub(crate) fn rewrite_tag_blocks(path: &Path, blocks: Vec<FlacMetadata>) -> Result<()> {
+    let mut chain = MetadataChain::read(path)?;
+    chain.remove_blocks(&[
+        flac::FLAC__METADATA_TYPE_VORBIS_COMMENT,
+        flac::FLAC__METADATA_TYPE_PICTURE,
+    ]);
+    chain.insert_after_streaminfo(blocks)?;
+    chain.write(path)
+}
+
+pub(crate) fn replace_comment_tag(path: &Path, key: &str, value: &str) -> Result<()> {
+    let mut chain = MetadataChain::read(path)?;
+    let block = chain
+        .find_block(flac::FLAC__METADATA_TYPE_VORBIS_COMMENT)
+        .ok_or_else(|| format!("{} has no Vorbis comment block", path.display()))?;
+
+    let comment = format!("{}={}", key, value);
+    let bytes = comment.as_bytes();
+    let entry = flac::FLAC__StreamMetadata_VorbisComment_Entry {
+        length: bytes.len() as u32,
+        entry: bytes.as_ptr() as *mut flac::FLAC__byte,
+    };
+    let ok =
+        unsafe { flac::FLAC__metadata_object_vorbiscomment_replace_comment(block, entry, 1, 1) };
+    if ok == 0 {
+        return Err(format!("failed to set {} in {}", key, path.display()));
+    }
+    chain.write(path)
+}
+
+#[cfg(unix)]
+pub(crate) fn path_to_cstring(path: &Path) -> Result<CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| format!("path contains NUL byte: {}", path.display()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn path_to_cstring(path: &Path) -> Result<CString> {
+    let path_str = path.to_str().ok_or_else(|| {
+        format!(
+            "path is not valid UTF-8 and cannot be passed to the codec library: {}",
+            path.display()
+        )
+    })?;
+    #[cfg(windows)]
+    let path_str = &extended_length_path(path_str);
     CString::new(path_str.as_bytes())
         .map_err(|_| format!("path contains NUL byte: {}", path.display()))
 }
 
+#[cfg(windows)]
+pub(crate) fn extended_length_path(path: &str) -> String {
+    const MAX_PATH: usize = 260;
+
+    if path.len() < MAX_PATH || path.starts_with(r"\\?\") {
+        return path.to_string();
+    }
+    let path = path.replace('/', "\\");
+    if let Some(share) = path.strip_prefix(r"\\") {
+        return format!(r"\\?\UNC\{}", share);
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' && bytes[2] == b'\\' {
+        return format!(r"\\?\{}", path);
+    }
+    path
+}
+
 fn announce_track_start(
     display_base_abs: Option<&Path>,
     progress: Option<&ProgressBar>,
     track: &TrackSpan,
+    output: EncoderOutput,
 ) {
-    let output_display = display_path(display_base_abs, &track.output_path);
-    let line = format!(
-        "{} {}",
-        "Creating".green().bold(),
-        output_display.display().to_string().bold()
-    );
+    let line = match output {
+        EncoderOutput::File(path) => format!(
+            "{} {}",
+            tr(Msg::Creating).green().bold(),
+            display_path(display_base_abs, path)
+                .display()
+                .to_string()
+                .bold()
+        ),
+        EncoderOutput::Stdout => format!(
+            "{} track {:02} to stdout",
+            "Streaming".green().bold(),
+            track.number
+        ),
+    };
     if let Some(progress) = progress {
         progress.println(line);
     } else {
@@ -0,0 +1,132 @@
+use crate::Result;
+
+const MAX_GAIN_DB: f64 = 48.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct GainSetting {
+    pub(crate) track: Option<u32>,
+    pub(crate) db: f64,
+}
+
+pub(crate) fn parse_gain(value: &str) -> Result<GainSetting> {
+    let (track, gain) = match value.split_once(':') {
+        Some((track, gain)) => {
+            let number: u32 = track
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid track number {:?} in gain {:?}", track, value))?;
+            (Some(number), gain)
+        }
+        None => (None, value),
+    };
+    let trimmed = gain.trim();
+    let number = trimmed
+        .strip_suffix("dB")
+        .or_else(|| trimmed.strip_suffix("db"))
+        .unwrap_or(trimmed)
+        .trim();
+    let db: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid gain {:?} (examples: +3dB, 5:-1.5dB)", value))?;
+    if !db.is_finite() || db.abs() > MAX_GAIN_DB {
+        return Err(format!(
+            "gain must be between -{} and +{} dB",
+            MAX_GAIN_DB, MAX_GAIN_DB
+        ));
+    }
+    Ok(GainSetting { track, db })
+}
+
+pub(crate) fn gain_for_track(settings: &[GainSetting], number: u32) -> Option<f64> {
+    let per_track = settings
+        .iter()
+        .rev()
+        .find(|setting| setting.track == Some(number));
+    let album_wide = settings
+        .iter()
+        .rev()
+        .find(|setting| setting.track.is_none());
+    per_track
+        .or(album_wide)
+        .map(|setting| setting.db)
+        .filter(|db| *db != 0.0)
+}
+
+pub(crate) fn format_gain(db: f64) -> String {
+    format!("{:+.1} dB", db)
+}
+
+pub(crate) struct GainStage {
+    factor: f64,
+    min: i64,
+    max: i64,
+    clipped: u64,
+}
+
+impl GainStage {
+    pub(crate) fn new(db: f64, bits_per_sample: u32) -> Self {
+        let max = (1i64 << (bits_per_sample.clamp(1, 32) - 1)) - 1;
+        Self {
+            factor: 10f64.powf(db / 20.0),
+            min: -max - 1,
+            max,
+            clipped: 0,
+        }
+    }
+
+    pub(crate) fn apply(&mut self, samples: &mut [i32]) {
+        for sample in samples.iter_mut() {
+            let scaled = (*sample as f64 * self.factor).round() as i64;
+            if scaled > self.max || scaled < self.min {
+                self.clipped += 1;
+            }
+            *sample = scaled.clamp(self.min, self.max) as i32;
+        }
+    }
+
+    pub(crate) fn clipped(&self) -> u64 {
+        self.clipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GainSetting, GainStage, gain_for_track, parse_gain};
+
+    #[test]
+    fn parse_gain_accepts_album_and_track_values() {
+        assert_eq!(
+            parse_gain("+3dB").unwrap(),
+            GainSetting {
+                track: None,
+                db: 3.0
+            }
+        );
+        assert_eq!(
+            parse_gain("5:-1.5").unwrap(),
+            GainSetting {
+                track: Some(5),
+                db: -1.5
+            }
+        );
+        assert!(parse_gain("loud").is_err());
+        assert!(parse_gain("x:+1dB").is_err());
+        assert!(parse_gain("+60dB").is_err());
+    }
+
+    #[test]
+    fn per_track_gain_overrides_album_gain() {
+        let settings = vec![parse_gain("+3dB").unwrap(), parse_gain("2:0dB").unwrap()];
+        assert_eq!(gain_for_track(&settings, 1), Some(3.0));
+        assert_eq!(gain_for_track(&settings, 2), None);
+    }
+
+    #[test]
+    fn gain_stage_limits_and_counts_clipped_samples() {
+        let mut stage = GainStage::new(6.0206, 16);
+        let mut samples = vec![1000, -1000, 20000, -20000];
+        stage.apply(&mut samples);
+        assert_eq!(samples, vec![2000, -2000, 32767, -32768]);
+        assert_eq!(stage.clipped(), 2);
+    }
+}
@@ -0,0 +1,295 @@
+use std::fs;
+use std::path::Path;
+
+use crate::Result;
+use crate::tags::split_csv_line;
+use crate::types::CueDisc;
+
+const ID3V1_GENRES: [&str; 192] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "Alternative Rock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+    "Folk",
+    "Folk-Rock",
+    "National Folk",
+    "Swing",
+    "Fast Fusion",
+    "Bebop",
+    "Latin",
+    "Revival",
+    "Celtic",
+    "Bluegrass",
+    "Avantgarde",
+    "Gothic Rock",
+    "Progressive Rock",
+    "Psychedelic Rock",
+    "Symphonic Rock",
+    "Slow Rock",
+    "Big Band",
+    "Chorus",
+    "Easy Listening",
+    "Acoustic",
+    "Humour",
+    "Speech",
+    "Chanson",
+    "Opera",
+    "Chamber Music",
+    "Sonata",
+    "Symphony",
+    "Booty Bass",
+    "Primus",
+    "Porn Groove",
+    "Satire",
+    "Slow Jam",
+    "Club",
+    "Tango",
+    "Samba",
+    "Folklore",
+    "Ballad",
+    "Power Ballad",
+    "Rhythmic Soul",
+    "Freestyle",
+    "Duet",
+    "Punk Rock",
+    "Drum Solo",
+    "A Cappella",
+    "Euro-House",
+    "Dance Hall",
+    "Goa",
+    "Drum & Bass",
+    "Club-House",
+    "Hardcore",
+    "Terror",
+    "Indie",
+    "BritPop",
+    "Afro-Punk",
+    "Polsk Punk",
+    "Beat",
+    "Christian Gangsta Rap",
+    "Heavy Metal",
+    "Black Metal",
+    "Crossover",
+    "Contemporary Christian",
+    "Christian Rock",
+    "Merengue",
+    "Salsa",
+    "Thrash Metal",
+    "Anime",
+    "JPop",
+    "Synthpop",
+    "Abstract",
+    "Art Rock",
+    "Baroque",
+    "Bhangra",
+    "Big Beat",
+    "Breakbeat",
+    "Chillout",
+    "Downtempo",
+    "Dub",
+    "EBM",
+    "Eclectic",
+    "Electro",
+    "Electroclash",
+    "Emo",
+    "Experimental",
+    "Garage",
+    "Global",
+    "IDM",
+    "Illbient",
+    "Industro-Goth",
+    "Jam Band",
+    "Krautrock",
+    "Leftfield",
+    "Lounge",
+    "Math Rock",
+    "New Romantic",
+    "Nu-Breakz",
+    "Post-Punk",
+    "Post-Rock",
+    "Psytrance",
+    "Shoegaze",
+    "Space Rock",
+    "Trop Rock",
+    "World Music",
+    "Neoclassical",
+    "Audiobook",
+    "Audio Theatre",
+    "Neue Deutsche Welle",
+    "Podcast",
+    "Indie Rock",
+    "G-Funk",
+    "Dubstep",
+    "Garage Rock",
+    "Psybient",
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct GenreMap {
+    entries: Vec<(String, String)>,
+}
+
+impl GenreMap {
+    pub(crate) fn map(&self, genre: &str) -> Option<String> {
+        let genre = genre.trim();
+        let name = id3v1_genre(genre).unwrap_or(genre);
+        let key = name.to_lowercase();
+        let mapped = self
+            .entries
+            .iter()
+            .find(|(from, _)| *from == key)
+            .map_or(name, |(_, to)| to.as_str());
+        (!mapped.is_empty()).then(|| mapped.to_string())
+    }
+}
+
+pub(crate) fn load_genre_map(path: &Path) -> Result<GenreMap> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read genre map {}: {}", path.display(), err))?;
+    parse_genre_map(&contents)
+        .map_err(|err| format!("invalid genre map {}: {}", path.display(), err))
+}
+
+pub(crate) fn map_cue_genre(cue: &mut CueDisc, map: &GenreMap) {
+    cue.genre = cue.genre.as_deref().and_then(|genre| map.map(genre));
+}
+
+fn parse_genre_map(contents: &str) -> Result<GenreMap> {
+    let mut entries = Vec::new();
+    for (index, raw) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw.trim_start_matches('\u{feff}').trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let fields =
+            split_csv_line(line).map_err(|err| format!("line {}: {}", line_number, err))?;
+        if fields.len() != 2 {
+            return Err(format!(
+                "line {}: expected 2 columns (from, to), found {}",
+                line_number,
+                fields.len()
+            ));
+        }
+        let from = fields[0].trim();
+        if entries.is_empty() && from.eq_ignore_ascii_case("from") {
+            continue;
+        }
+        if from.is_empty() {
+            return Err(format!("line {}: empty genre to map", line_number));
+        }
+        let from = id3v1_genre(from).unwrap_or(from).to_lowercase();
+        entries.push((from, fields[1].trim().to_string()));
+    }
+    Ok(GenreMap { entries })
+}
+
+fn id3v1_genre(genre: &str) -> Option<&'static str> {
+    let number = genre
+        .strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .unwrap_or(genre);
+    if number.is_empty() || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    ID3V1_GENRES.get(number.parse::<usize>().ok()?).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GenreMap, parse_genre_map};
+
+    #[test]
+    fn genre_map_translates_numbers_and_listed_names() {
+        let map =
+            parse_genre_map("from,to\nRock/Pop,Rock\n\"Hip-Hop\",Rap\n# drop\nOther,\n").unwrap();
+        assert_eq!(map.map("rock/pop").as_deref(), Some("Rock"));
+        assert_eq!(map.map("(7)").as_deref(), Some("Rap"));
+        assert_eq!(map.map("17").as_deref(), Some("Rock"));
+        assert_eq!(map.map("12"), None);
+        assert_eq!(map.map("Jazz").as_deref(), Some("Jazz"));
+        assert_eq!(map.map("255").as_deref(), Some("255"));
+
+        let builtin = GenreMap::default();
+        assert_eq!(builtin.map(" 32 ").as_deref(), Some("Classical"));
+        assert_eq!(builtin.map("+1").as_deref(), Some("+1"));
+        assert!(parse_genre_map("Rock\n").is_err());
+    }
+}
@@ -0,0 +1,405 @@
+use clap::ValueEnum;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum Lang {
+    En,
+    Ru,
+}
+
+static LANG: OnceLock<Lang> = OnceLock::new();
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Msg {
+    Plan,
+    Input,
+    SourceAction,
+    WillBeDeleted,
+    WillBeTrashed,
+    WillBeMovedTo,
+    WillBeRenamedTo,
+    Sidecars,
+    CueSheet,
+    CoverFile,
+    AlsoEncode,
+    FromFolders,
+    CheckBeforeConfirming,
+    SameAction,
+    CueEncoding,
+    Autodetected,
+    Tracks,
+    Length,
+    EstimatedSize,
+    MatchesCue,
+    CueHasNoEnd,
+    TrimSilence,
+    Pregaps,
+    PregapsAppended,
+    PregapsDiscarded,
+    Pregap,
+    Channels,
+    Gain,
+    Fade,
+    CompleteKept,
+    Skipped,
+    Jobs,
+    WarningRecap,
+    Proceed,
+    ProceedBatch,
+    PairPrompt,
+    SelectPairs,
+    ChooseCue,
+    ConfigureSubdirs,
+    Subdir,
+    NothingToSplit,
+    UpToDate,
+    SavedPlan,
+    Creating,
+    Done,
+    Truncated,
+    Aborted,
+    AbortedByUser,
+    FileNotFound,
+    NoFileFound,
+    MultipleFilesFound,
+    UnsupportedAudio,
+    OutputExists,
+    NotEnoughSpace,
+    NoAudio,
+    KeepTruncated,
+    StdinNeedsYes,
+    StdoutSingleAlbum,
+    ArchiveNotQueued,
+    ArchivePlanNotSaved,
+    NoCurrentDir,
+    PictureNotFound,
+    StdoutWriteFailed,
+    PairOf,
+    Pair,
+    JobsDetail,
+    MissingSubdir,
+    SubdirReadFailed,
+    EmptySubdir,
+    DuplicateSubdir,
+    PairSelectionFailed,
+    SeveralCues,
+    CueChoiceFailed,
+    SubdirNotDerived,
+    DerivedDuplicateSubdir,
+    CopiedCover,
+    WroteCover,
+    SourceCueKept,
+    WroteCueSheet,
+    Warning,
+    TruncatedSourceKept,
+    Clipped,
+    SignalAnomalies,
+    LengthDiffers,
+    LengthBeyondTolerance,
+    ShortTrack,
+    TracksOverlap,
+    UncoveredGap,
+}
+
+impl Msg {
+    fn texts(self) -> (&'static str, &'static str) {
+        match self {
+            Msg::Plan => ("Plan", "План"),
+            Msg::Input => ("Input:", "Источник:"),
+            Msg::SourceAction => ("Source action:", "Действие с источником:"),
+            Msg::WillBeDeleted => (
+                "will be deleted after successful split",
+                "будет удалён после успешного разделения",
+            ),
+            Msg::WillBeTrashed => (
+                "will be moved to the trash after successful split",
+                "будет перемещён в корзину после успешного разделения",
+            ),
+            Msg::WillBeMovedTo => (
+                "will be moved to {} after successful split",
+                "будет перемещён в {} после успешного разделения",
+            ),
+            Msg::WillBeRenamedTo => (
+                "will be renamed to {} after successful split",
+                "будет переименован в {} после успешного разделения",
+            ),
+            Msg::Sidecars => ("Sidecars:", "Сопутствующие файлы:"),
+            Msg::CueSheet => ("Cue sheet:", "Файл CUE:"),
+            Msg::CoverFile => ("Cover file:", "Файл обложки:"),
+            Msg::AlsoEncode => ("Also encode:", "Также кодировать:"),
+            Msg::FromFolders => ("From folder names:", "Из имён папок:"),
+            Msg::CheckBeforeConfirming => (
+                "(not in the cue; check before confirming)",
+                "(нет в cue; проверьте перед подтверждением)",
+            ),
+            Msg::SameAction => ("(same action)", "(то же действие)"),
+            Msg::CueEncoding => ("CUE encoding:", "Кодировка CUE:"),
+            Msg::Autodetected => ("(autodetected)", "(определена автоматически)"),
+            Msg::Tracks => ("Tracks:", "Треки:"),
+            Msg::Length => ("Length:", "Длительность:"),
+            Msg::EstimatedSize => ("Estimated size:", "Оценка размера:"),
+            Msg::MatchesCue => ("(matches cue)", "(совпадает с cue)"),
+            Msg::CueHasNoEnd => (
+                "(cue has no end; last track runs to the end of the audio)",
+                "(в cue нет конца; последний трек идёт до конца аудио)",
+            ),
+            Msg::TrimSilence => ("Trim silence:", "Обрезка тишины:"),
+            Msg::Pregaps => ("Pregaps:", "Паузы перед треками:"),
+            Msg::PregapsAppended => (
+                "{} appended to the previous tracks",
+                "{} добавлено к предыдущим трекам",
+            ),
+            Msg::PregapsDiscarded => ("{} discarded", "{} отброшено"),
+            Msg::Pregap => ("(pregap {})", "(пауза {})"),
+            Msg::Channels => ("Channels:", "Каналы:"),
+            Msg::Gain => ("Gain:", "Усиление:"),
+            Msg::Fade => ("Fade:", "Нарастание и затухание:"),
+            Msg::CompleteKept => ("(complete, kept)", "(готов, оставлен)"),
+            Msg::Skipped => ("(skipped)", "(пропущен)"),
+            Msg::Jobs => ("Jobs:", "Задания:"),
+            Msg::WarningRecap => ("Warnings ({} album(s)):", "Предупреждения (альбомов: {}):"),
+            Msg::Proceed => ("Proceed? [y/N]: ", "Продолжить? [y/N]: "),
+            Msg::ProceedBatch => (
+                "Proceed? [y/{}ubdirs/{}er pair/N]: ",
+                "Продолжить? [y/{}ubdirs/{}er pair/N]: ",
+            ),
+            Msg::PairPrompt => (
+                "Split {}? [y/n/a(ll)/s(kip)/q(uit)]: ",
+                "Разделить {}? [y/n/a(ll)/s(kip)/q(uit)]: ",
+            ),
+            Msg::SelectPairs => (
+                "Pairs to plan (space toggles, enter confirms)",
+                "Пары для разделения (пробел переключает, enter подтверждает)",
+            ),
+            Msg::ChooseCue => (
+                "Several cue sheets reference {}; which one to use?",
+                "На {} ссылаются несколько файлов CUE; какой использовать?",
+            ),
+            Msg::ConfigureSubdirs => (
+                "Configure output subdirectories",
+                "Настройка подкаталогов для треков",
+            ),
+            Msg::Subdir => ("Subdir", "Подкаталог"),
+            Msg::NothingToSplit => ("Nothing to split", "Нечего разделять"),
+            Msg::UpToDate => ("Up to date", "Уже разделено"),
+            Msg::SavedPlan => ("Saved plan", "План сохранён"),
+            Msg::Creating => ("Creating", "Создаётся"),
+            Msg::Done => ("done", "готово"),
+            Msg::Truncated => ("truncated", "обрезано"),
+            Msg::Aborted => ("aborted", "прервано"),
+            Msg::AbortedByUser => ("aborted by user", "отменено пользователем"),
+            Msg::FileNotFound => ("file not found: {}", "файл не найден: {}"),
+            Msg::NoFileFound => ("no {} file found in {}", "файл {} не найден в {}"),
+            Msg::MultipleFilesFound => (
+                "multiple {} files found in {}, please specify {}",
+                "несколько файлов {} в {}, укажите {}",
+            ),
+            Msg::UnsupportedAudio => (
+                "unsupported audio input {} (expected {})",
+                "неподдерживаемый аудиофайл {} (ожидается {})",
+            ),
+            Msg::OutputExists => (
+                "output file already exists: {}",
+                "выходной файл уже существует: {}",
+            ),
+            Msg::NotEnoughSpace => (
+                "not enough disk space in {}: about {} needed, {} available",
+                "недостаточно места на диске в {}: нужно около {}, доступно {}",
+            ),
+            Msg::NoAudio => ("input contains no audio: {}", "в источнике нет аудио: {}"),
+            Msg::KeepTruncated => (
+                "{}; use --allow-truncated to keep the complete tracks",
+                "{}; используйте --allow-truncated, чтобы сохранить полные треки",
+            ),
+            Msg::StdinNeedsYes => (
+                "reading the image from stdin requires --yes",
+                "для чтения образа из stdin нужен --yes",
+            ),
+            Msg::StdoutSingleAlbum => (
+                "--stdout accepts a single album, but {} were found",
+                "--stdout принимает один альбом, а найдено {}",
+            ),
+            Msg::ArchiveNotQueued => (
+                "archives cannot be queued; extract {} first",
+                "архивы нельзя ставить в очередь; сначала распакуйте {}",
+            ),
+            Msg::ArchivePlanNotSaved => (
+                "a plan for {} cannot be saved, because the archive is only extracted while splitting",
+                "план для {} нельзя сохранить: архив распаковывается только на время разделения",
+            ),
+            Msg::NoCurrentDir => (
+                "failed to get current directory: {}",
+                "не удалось определить текущий каталог: {}",
+            ),
+            Msg::PictureNotFound => (
+                "picture file not found: {}",
+                "файл изображения не найден: {}",
+            ),
+            Msg::StdoutWriteFailed => (
+                "failed to write to stdout: {}",
+                "не удалось записать в stdout: {}",
+            ),
+            Msg::PairOf => ("Pair {}/{}", "Пара {}/{}"),
+            Msg::Pair => ("Pair:", "Пара:"),
+            Msg::JobsDetail => (
+                "{} ({} album(s) at a time, {} encoder thread(s) each)",
+                "{} (альбомов одновременно: {}, потоков кодировщика на альбом: {})",
+            ),
+            Msg::MissingSubdir => (
+                "missing derived output subdirectory for pair {}",
+                "нет подкаталога для пары {}",
+            ),
+            Msg::SubdirReadFailed => (
+                "failed to read subdir name: {}",
+                "не удалось прочитать имя подкаталога: {}",
+            ),
+            Msg::EmptySubdir => (
+                "subdir name cannot be empty",
+                "имя подкаталога не может быть пустым",
+            ),
+            Msg::DuplicateSubdir => (
+                "duplicate output subdirectory name: {}",
+                "повторяющееся имя подкаталога: {}",
+            ),
+            Msg::PairSelectionFailed => (
+                "failed to read pair selection: {}",
+                "не удалось прочитать выбор пар: {}",
+            ),
+            Msg::SeveralCues => (
+                "several cue sheets reference {}:\n  {}\nuse --prefer-cue-style or --cue to pick one",
+                "на {} ссылаются несколько файлов CUE:\n  {}\nвыберите один с помощью --prefer-cue-style или --cue",
+            ),
+            Msg::CueChoiceFailed => (
+                "failed to read cue choice: {}",
+                "не удалось прочитать выбор CUE: {}",
+            ),
+            Msg::SubdirNotDerived => (
+                "failed to derive output subdirectory name",
+                "не удалось получить имя подкаталога",
+            ),
+            Msg::DerivedDuplicateSubdir => (
+                "derived duplicate output subdirectory name: {}",
+                "получено повторяющееся имя подкаталога: {}",
+            ),
+            Msg::CopiedCover => ("Copied cover", "Скопирована обложка"),
+            Msg::WroteCover => ("Wrote cover", "Записана обложка"),
+            Msg::SourceCueKept => ("Not replacing the source cue", "Исходный CUE не заменяется"),
+            Msg::WroteCueSheet => ("Wrote cue sheet", "Записан файл CUE"),
+            Msg::Warning => ("WARNING:", "ВНИМАНИЕ:"),
+            Msg::TruncatedSourceKept => (
+                "Source file was left in place because it is truncated",
+                "Источник оставлен на месте, так как он обрезан",
+            ),
+            Msg::Clipped => (
+                "track {}: {} samples clipped by {} gain; consider a lower --gain",
+                "трек {}: {} сэмплов обрезано усилением {}; уменьшите --gain",
+            ),
+            Msg::SignalAnomalies => (
+                "track {}: {} in the source audio",
+                "трек {}: {} в исходном аудио",
+            ),
+            Msg::LengthDiffers => (
+                "audio length {} differs from the cue length {} by {}",
+                "длина аудио {} отличается от длины по CUE {} на {}",
+            ),
+            Msg::LengthBeyondTolerance => (
+                "{} ({} frames allowed by --length-tolerance)",
+                "{} (--length-tolerance допускает кадров: {})",
+            ),
+            Msg::ShortTrack => (
+                "track {} is only {} long (CDDA tracks are at least {} seconds)",
+                "трек {} длится всего {} (треки CDDA не короче {} секунд)",
+            ),
+            Msg::TracksOverlap => (
+                "tracks {} and {} overlap by {}",
+                "треки {} и {} перекрываются на {}",
+            ),
+            Msg::UncoveredGap => (
+                "gap of {} between tracks {} and {} is not part of any track",
+                "промежуток {} между треками {} и {} не входит ни в один трек",
+            ),
+        }
+    }
+}
+
+pub(crate) fn set_lang(lang: Option<Lang>) {
+    let _ = LANG.set(lang.unwrap_or_else(detect_lang));
+}
+
+pub(crate) fn current_lang() -> Lang {
+    LANG.get().copied().unwrap_or(Lang::En)
+}
+
+pub(crate) fn tr(msg: Msg) -> &'static str {
+    let (en, ru) = msg.texts();
+    match LANG.get() {
+        Some(Lang::Ru) => ru,
+        _ => en,
+    }
+}
+
+pub(crate) fn tr_with(msg: Msg, args: &[&dyn Display]) -> String {
+    fill(tr(msg), args)
+}
+
+fn fill(template: &str, args: &[&dyn Display]) -> String {
+    let mut out = String::new();
+    let mut args = args.iter();
+    let mut parts = template.split("{}");
+    if let Some(first) = parts.next() {
+        out.push_str(first);
+    }
+    for part in parts {
+        if let Some(arg) = args.next() {
+            out.push_str(&arg.to_string());
+        }
+        out.push_str(part);
+    }
+    out
+}
+
+fn detect_lang() -> Lang {
+    let locale = locale_var().map(|(_, value)| value);
+    lang_from_locale(locale.as_deref())
+}
+
+pub(crate) fn locale_var() -> Option<(&'static str, String)> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"].iter().find_map(|key| {
+        std::env::var(key)
+            .ok()
+            .filter(|value| !value.is_empty())
+            .map(|value| (*key, value))
+    })
+}
+
+fn lang_from_locale(locale: Option<&str>) -> Lang {
+    match locale {
+        Some(value) if value.to_ascii_lowercase().starts_with("ru") => Lang::Ru,
+        _ => Lang::En,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Lang, Msg, fill, lang_from_locale};
+
+    #[test]
+    fn locale_selects_russian_only_for_ru() {
+        assert_eq!(lang_from_locale(Some("ru_RU.UTF-8")), Lang::Ru);
+        assert_eq!(lang_from_locale(Some("RU")), Lang::Ru);
+        assert_eq!(lang_from_locale(Some("en_US.UTF-8")), Lang::En);
+        assert_eq!(lang_from_locale(Some("C")), Lang::En);
+        assert_eq!(lang_from_locale(None), Lang::En);
+    }
+
+    #[test]
+    fn fill_substitutes_placeholders_in_order() {
+        assert_eq!(
+            fill(Msg::WillBeRenamedTo.texts().1, &[&"Album.flac.processed"]),
+            "будет переименован в Album.flac.processed после успешного разделения"
+        );
+        assert_eq!(fill("{} of {}", &[&1, &2]), "1 of 2");
+        assert_eq!(fill("no args", &[]), "no args");
+    }
+}
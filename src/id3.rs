@@ -0,0 +1,194 @@
+use encoding_rs::{UTF_8, UTF_16BE, UTF_16LE};
+use std::fs::File;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use crate::metadata::find_tag;
+
+const HEADER_LEN: usize = 10;
+const FLAG_EXTENDED_HEADER: u8 = 0x40;
+const FLAG_FOOTER: u8 = 0x10;
+
+pub(crate) fn starts_with_id3v2(path: &Path) -> bool {
+    let mut magic = [0u8; 3];
+    File::open(path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .is_ok()
+        && &magic == b"ID3"
+}
+
+pub(crate) fn strip_id3v2(
+    mut input: Box<dyn Read + Send>,
+) -> io::Result<(Box<dyn Read + Send>, Vec<(String, String)>)> {
+    let mut tags = Vec::new();
+    loop {
+        let mut header = [0u8; HEADER_LEN];
+        let read = read_up_to(&mut *input, &mut header)?;
+        let Some((size, footer)) = tag_size(&header[..read]) else {
+            let prefix = Cursor::new(header[..read].to_vec());
+            return Ok((Box::new(prefix.chain(input)), tags));
+        };
+        let mut body = vec![0u8; size];
+        input.read_exact(&mut body)?;
+        if footer {
+            input.read_exact(&mut [0u8; HEADER_LEN])?;
+        }
+        tags.extend(id3v2_text_tags(header[3], header[5], &body));
+    }
+}
+
+pub(crate) fn merge_id3_tags(comments: &mut Vec<(String, String)>, id3_tags: &[(String, String)]) {
+    for (key, value) in id3_tags {
+        if find_tag(comments, key).is_none() {
+            comments.push((key.clone(), value.clone()));
+        }
+    }
+}
+
+fn read_up_to(input: &mut dyn Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+fn tag_size(header: &[u8]) -> Option<(usize, bool)> {
+    if header.len() < HEADER_LEN
+        || &header[..3] != b"ID3"
+        || header[3] == 0xFF
+        || header[4] == 0xFF
+        || header[6..10].iter().any(|byte| byte & 0x80 != 0)
+    {
+        return None;
+    }
+    Some((syncsafe(&header[6..10]), header[5] & FLAG_FOOTER != 0))
+}
+
+fn syncsafe(bytes: &[u8]) -> usize {
+    bytes
+        .iter()
+        .fold(0, |acc, byte| (acc << 7) | usize::from(byte & 0x7F))
+}
+
+fn frame_size(version: u8, bytes: &[u8]) -> usize {
+    if version >= 4 {
+        syncsafe(bytes)
+    } else {
+        u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+    }
+}
+
+fn id3v2_text_tags(version: u8, flags: u8, body: &[u8]) -> Vec<(String, String)> {
+    // ID3v2.2 uses three-letter frame ids; such old tags are only skipped.
+    if version < 3 {
+        return Vec::new();
+    }
+    let mut pos = 0;
+    if flags & FLAG_EXTENDED_HEADER != 0 && body.len() >= 4 {
+        pos = frame_size(version, &body[..4]);
+        if version == 3 {
+            pos += 4;
+        }
+    }
+
+    let mut tags = Vec::new();
+    while pos + HEADER_LEN <= body.len() {
+        let id = &body[pos..pos + 4];
+        if !id
+            .iter()
+            .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit())
+        {
+            break;
+        }
+        let start = pos + HEADER_LEN;
+        let end = start + frame_size(version, &body[pos + 4..pos + 8]);
+        if end > body.len() {
+            break;
+        }
+        if let Some(key) = vorbis_key(id)
+            && let Some(value) = decode_text(&body[start..end])
+        {
+            tags.push((key.to_string(), value));
+        }
+        pos = end;
+    }
+    tags
+}
+
+fn vorbis_key(id: &[u8]) -> Option<&'static str> {
+    match id {
+        b"TIT2" => Some("TITLE"),
+        b"TPE1" => Some("ARTIST"),
+        b"TPE2" => Some("ALBUMARTIST"),
+        b"TALB" => Some("ALBUM"),
+        b"TCOM" => Some("COMPOSER"),
+        b"TCON" => Some("GENRE"),
+        b"TYER" | b"TDRC" => Some("DATE"),
+        b"TPOS" => Some("DISCNUMBER"),
+        _ => None,
+    }
+}
+
+fn decode_text(frame: &[u8]) -> Option<String> {
+    let (&encoding, text) = frame.split_first()?;
+    let decoded = match encoding {
+        0 => text.iter().map(|&byte| char::from(byte)).collect(),
+        1 => UTF_16LE.decode(text).0.into_owned(),
+        2 => UTF_16BE.decode(text).0.into_owned(),
+        3 => UTF_8.decode(text).0.into_owned(),
+        _ => return None,
+    };
+    let value = decoded.split('\0').next().unwrap_or_default().trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_id3v2;
+    use std::io::{Cursor, Read};
+
+    fn frame(id: &[u8], text: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+        out.extend_from_slice(&[0, 0, 3]);
+        out.extend_from_slice(text);
+        out
+    }
+
+    #[test]
+    fn strip_id3v2_skips_the_tag_and_keeps_text_frames() {
+        let mut body = frame(b"TIT2", b"Album");
+        body.extend(frame(b"TPE1", b"Artist"));
+        body.extend(frame(b"APIC", b"ignored"));
+        body.extend([0; 16]);
+        let mut data = b"ID3\x03\x00\x00".to_vec();
+        let size = body.len();
+        data.extend([0, 0, (size >> 7) as u8, (size & 0x7F) as u8]);
+        data.extend(body);
+        data.extend_from_slice(b"fLaC rest");
+
+        let (mut reader, tags) = strip_id3v2(Box::new(Cursor::new(data))).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"fLaC rest");
+        assert_eq!(
+            tags,
+            vec![
+                ("TITLE".to_string(), "Album".to_string()),
+                ("ARTIST".to_string(), "Artist".to_string()),
+            ]
+        );
+
+        let (mut reader, tags) = strip_id3v2(Box::new(Cursor::new(b"fLaC".to_vec()))).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, b"fLaC");
+        assert!(tags.is_empty());
+    }
+}
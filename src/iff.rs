@@ -0,0 +1,98 @@
+use encoding_rs::WINDOWS_1252;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use crate::Result;
+use crate::decoder::ByteOrder;
+use crate::id3::strip_id3v2;
+
+// Tag chunks larger than this are skipped instead of read into memory, and
+// other chunks of that size are rejected as corrupt.
+pub(crate) const MAX_TAG_CHUNK: u64 = 16 << 20;
+
+// A chunk header of a RIFF (WAV) or IFF (AIFF) file.
+pub(crate) struct ChunkHeader {
+    pub(crate) id: [u8; 4],
+    pub(crate) size: u32,
+    // Offset of the chunk body in the file.
+    pub(crate) start: u64,
+}
+
+// Visits the chunks that follow the 12-byte form header in file order. The
+// reader is at the start of the body when `visit` runs, and `visit` returns
+// the body length, which RF64 keeps outside the 32-bit size field. Chunks are
+// padded to an even length.
+pub(crate) fn walk_chunks<R, F>(
+    reader: &mut R,
+    file_len: u64,
+    byte_order: ByteOrder,
+    mut visit: F,
+) -> Result<()>
+where
+    R: Read + Seek,
+    F: FnMut(&mut R, &ChunkHeader) -> Result<u64>,
+{
+    let io_error = |err: std::io::Error| err.to_string();
+    let mut pos = 12u64;
+    reader.seek(SeekFrom::Start(pos)).map_err(io_error)?;
+    while pos + 8 <= file_len {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).map_err(io_error)?;
+        let size = [header[4], header[5], header[6], header[7]];
+        let chunk = ChunkHeader {
+            id: [header[0], header[1], header[2], header[3]],
+            size: match byte_order {
+                ByteOrder::Little => u32::from_le_bytes(size),
+                ByteOrder::Big => u32::from_be_bytes(size),
+            },
+            start: pos + 8,
+        };
+        let len = visit(reader, &chunk)?;
+        pos = chunk.start + len + (len & 1);
+        reader.seek(SeekFrom::Start(pos)).map_err(io_error)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_body<R: Read>(reader: &mut R, size: u64) -> Result<Vec<u8>> {
+    if size > MAX_TAG_CHUNK {
+        return Err(format!("chunk of {} bytes is too large", size));
+    }
+    let mut body = vec![0u8; size as usize];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| err.to_string())?;
+    Ok(body)
+}
+
+// WAV `id3 ` and AIFF `ID3 ` chunks hold a complete ID3v2 tag.
+pub(crate) fn read_id3_chunk<R: Read>(reader: &mut R, size: u64) -> Result<Vec<(String, String)>> {
+    let body = read_body(reader, size)?;
+    let (_, tags) = strip_id3v2(Box::new(Cursor::new(body))).map_err(|err| err.to_string())?;
+    Ok(tags)
+}
+
+// Text chunks have no declared encoding; they hold ASCII, UTF-8 or a Windows
+// code page, and may end in NUL padding.
+pub(crate) fn decode_text(bytes: &[u8]) -> Option<String> {
+    let text = bytes.split(|&byte| byte == 0).next().unwrap_or_default();
+    let value = match std::str::from_utf8(text) {
+        Ok(value) => value.to_string(),
+        Err(_) => WINDOWS_1252.decode(text).0.into_owned(),
+    };
+    let value = value.trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+#[cfg(test)]
+pub(crate) fn chunk(id: &[u8], body: &[u8], byte_order: ByteOrder) -> Vec<u8> {
+    let mut out = id.to_vec();
+    out.extend_from_slice(&match byte_order {
+        ByteOrder::Little => (body.len() as u32).to_le_bytes(),
+        ByteOrder::Big => (body.len() as u32).to_be_bytes(),
+    });
+    out.extend_from_slice(body);
+    if body.len() % 2 == 1 {
+        out.push(0);
+    }
+    out
+}
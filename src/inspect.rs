@@ -0,0 +1,154 @@
+use owo_colors::OwoColorize;
+
+use crate::Result;
+use crate::cli::{InspectArgs, display_path};
+use crate::cue::{parse_cue_file, parse_cue_from_str, report_cue_warnings, resolve_encoding};
+use crate::decoder::create_decoder;
+use crate::picture::describe_picture;
+use crate::split::compute_track_spans;
+use crate::types::{CueDisc, InputMetadata};
+use crate::ui::format_msf;
+
+const MAX_TAG_VALUE_CHARS: usize = 80;
+
+pub(crate) fn run_inspect(args: InspectArgs) -> Result<()> {
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let file_abs = if args.file.is_absolute() {
+        args.file.clone()
+    } else {
+        cwd.join(&args.file)
+    };
+    if !file_abs.is_file() {
+        return Err(format!("file not found: {}", file_abs.display()));
+    }
+
+    let mut decoder = create_decoder(&file_abs)?;
+    let decoded = decoder.read_metadata()?;
+    let meta = &decoded.input_meta;
+
+    println!("{}", "Inspect".bold());
+    println!(
+        "  {} {}",
+        "File:".cyan(),
+        display_path(Some(cwd.as_path()), &file_abs).display()
+    );
+    print_stream_info(meta);
+
+    println!("{}", "Tags".bold());
+    if meta.comments.is_empty() {
+        println!("  {}", "(none)".dimmed());
+    }
+    for (key, value) in &meta.comments {
+        println!("  {}={}", key.cyan(), shorten_value(value).yellow());
+    }
+
+    println!("{}", "Pictures".bold());
+    if meta.pictures.is_empty() {
+        println!("  {}", "(none)".dimmed());
+    }
+    for (index, picture) in meta.pictures.iter().enumerate() {
+        let name = decoded
+            .picture_names
+            .get(index)
+            .map(|name| format!(" [{}]", name))
+            .unwrap_or_default();
+        println!("  {}: {}{}", index + 1, describe_picture(picture), name);
+    }
+
+    let embedded_label = if meta.embedded_cue.is_some() {
+        "present".green().to_string()
+    } else {
+        "none".dimmed().to_string()
+    };
+    println!("{} {}", "Embedded cue:".bold(), embedded_label);
+
+    let toc_source = if let Some(cue_path) = args.cue.as_ref() {
+        let cue_abs = if cue_path.is_absolute() {
+            cue_path.clone()
+        } else {
+            cwd.join(cue_path)
+        };
+        let encoding = match args.cue_encoding.as_deref() {
+            Some(label) => Some(resolve_encoding(label)?),
+            None => None,
+        };
+        let (disc, warnings, _, _) = parse_cue_file(&cue_abs, encoding)?;
+        report_cue_warnings(&warnings);
+        Some((
+            disc,
+            display_path(Some(cwd.as_path()), &cue_abs)
+                .display()
+                .to_string(),
+        ))
+    } else if let Some(text) = meta.embedded_cue.as_deref() {
+        Some((parse_cue_from_str(text)?, "embedded cue".to_string()))
+    } else {
+        None
+    };
+
+    if let Some((disc, source)) = toc_source {
+        print_toc(meta, &disc, &source)?;
+    }
+
+    Ok(())
+}
+
+fn print_stream_info(meta: &InputMetadata) {
+    let length = if meta.sample_rate > 0 && meta.total_samples > 0 {
+        let frames = meta.total_samples * 75 / meta.sample_rate as u64;
+        format!(" ({})", format_msf(frames))
+    } else {
+        String::new()
+    };
+    println!(
+        "  {} {} Hz, {} ch, {} bits, {} samples{}",
+        "Stream:".cyan(),
+        meta.sample_rate,
+        meta.channels,
+        meta.bits_per_sample,
+        meta.total_samples,
+        length
+    );
+    if let Some(vendor) = meta.vendor.as_deref() {
+        println!("  {} {}", "Vendor:".cyan(), vendor);
+    }
+}
+
+fn print_toc(meta: &InputMetadata, disc: &CueDisc, source: &str) -> Result<()> {
+    let spans = compute_track_spans(disc, meta.sample_rate, meta.total_samples)?;
+    let samples_per_frame = (meta.sample_rate / 75) as u64;
+
+    println!("{} {}", "TOC".bold(), format!("({})", source).dimmed());
+    for span in &spans {
+        let start = span.start / samples_per_frame;
+        let length = (span.end - span.start) / samples_per_frame;
+        let title = span.title.as_deref().unwrap_or("");
+        println!(
+            "  {:02} {} {} {} {}",
+            span.number,
+            format_msf(start),
+            format_msf(length).dimmed(),
+            format!("[{}-{})", span.start, span.end).dimmed(),
+            title
+        );
+    }
+    if let Some(last) = spans.last() {
+        println!(
+            "  {} {}",
+            "lead-out".dimmed(),
+            format_msf(last.end / samples_per_frame)
+        );
+    }
+    Ok(())
+}
+
+fn shorten_value(value: &str) -> String {
+    let single_line = value.replace(['\r', '\n'], " ");
+    let count = single_line.chars().count();
+    if count <= MAX_TAG_VALUE_CHARS {
+        return single_line;
+    }
+    let prefix: String = single_line.chars().take(MAX_TAG_VALUE_CHARS).collect();
+    format!("{}... ({} bytes)", prefix, value.len())
+}
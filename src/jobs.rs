@@ -0,0 +1,78 @@
+use std::sync::Mutex;
+use std::thread;
+
+use crate::Result;
+
+pub(crate) fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |count| count.get())
+}
+
+pub(crate) fn parse_jobs(value: &str) -> Result<usize> {
+    let jobs: usize = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid job count {:?}", value))?;
+    if jobs == 0 {
+        return Err("job count must be at least 1".to_string());
+    }
+    Ok(jobs)
+}
+
+pub(crate) fn run_parallel<T, R, F>(items: Vec<T>, workers: usize, work: F) -> Vec<R>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let count = items.len();
+    let workers = workers.clamp(1, count.max(1));
+    if workers == 1 {
+        return items.into_iter().map(work).collect();
+    }
+
+    let queue = Mutex::new(items.into_iter().enumerate());
+    let results = Mutex::new((0..count).map(|_| None).collect::<Vec<Option<R>>>());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let next = queue.lock().unwrap().next();
+                    let Some((index, item)) = next else {
+                        break;
+                    };
+                    let result = work(item);
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.expect("worker finished every item"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_jobs, run_parallel};
+
+    #[test]
+    fn run_parallel_keeps_input_order() {
+        let items: Vec<u64> = (0..50).collect();
+        let squares = run_parallel(items, 4, |value| value * value);
+        assert_eq!(
+            squares,
+            (0..50).map(|value| value * value).collect::<Vec<_>>()
+        );
+        assert!(run_parallel(Vec::<u64>::new(), 4, |value| value).is_empty());
+    }
+
+    #[test]
+    fn parse_jobs_rejects_zero() {
+        assert_eq!(parse_jobs("3").unwrap(), 3);
+        assert!(parse_jobs("0").is_err());
+        assert!(parse_jobs("many").is_err());
+    }
+}
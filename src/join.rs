@@ -0,0 +1,348 @@
+use owo_colors::OwoColorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::cli::{JoinArgs, display_path, find_track_files};
+use crate::cue::{render_cue_sheet, report_cue_warnings};
+use crate::decoder::{DEFAULT_BLOCK_SIZE, create_decoder};
+use crate::flac::{EncoderOptions, EncoderOutput, start_encoder};
+use crate::i18n::{Msg, tr};
+use crate::metadata::{build_vorbis_comment_from_tags, find_tag};
+use crate::priority::lower_priority;
+use crate::types::{CueDisc, CueRem, CueTrack, InputMetadata};
+use crate::ui::{ConfirmAction, confirm_or_exit, finish_progress, format_msf, make_progress_bar};
+
+const PER_TRACK_TAGS: &[&str] = &[
+    "TITLE",
+    "TRACKNUMBER",
+    "TRACKTOTAL",
+    "TOTALTRACKS",
+    "ISRC",
+    "REPLAYGAIN_TRACK_GAIN",
+    "REPLAYGAIN_TRACK_PEAK",
+];
+
+struct JoinTrack {
+    path: PathBuf,
+    meta: InputMetadata,
+    offset: u64,
+}
+
+pub(crate) fn run_join(args: JoinArgs) -> Result<()> {
+    lower_priority(&args.priority)?;
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let dir_abs = if args.dir.is_absolute() {
+        args.dir.clone()
+    } else {
+        cwd.join(&args.dir)
+    };
+    if !dir_abs.is_dir() {
+        return Err(format!("directory not found: {}", dir_abs.display()));
+    }
+
+    let image_path = match args.out.as_ref() {
+        Some(path) if path.is_absolute() => path.clone(),
+        Some(path) => cwd.join(path),
+        None => default_image_path(&dir_abs)?,
+    };
+    let cue_path = image_path.with_extension("cue");
+
+    let tracks = read_join_tracks(&dir_abs, &image_path)?;
+    let album_tags = common_album_tags(&tracks);
+    let (disc, warnings) = build_join_cue(&tracks, &album_tags, &image_path)?;
+    let first = &tracks[0].meta;
+    let total_samples: u64 = tracks.iter().map(|track| track.meta.total_samples).sum();
+
+    println!("{}", "Join plan".bold());
+    println!(
+        "  {} {}",
+        "Input:".cyan(),
+        display_path(Some(cwd.as_path()), &dir_abs).display()
+    );
+    println!(
+        "  {} {}",
+        "Image:".cyan(),
+        display_path(Some(cwd.as_path()), &image_path).display()
+    );
+    println!(
+        "  {} {}",
+        "CUE:".cyan(),
+        display_path(Some(cwd.as_path()), &cue_path).display()
+    );
+    println!(
+        "  {} {} ({} Hz, {} ch, {} bits, compression {})",
+        "Tracks:".cyan(),
+        tracks.len(),
+        first.sample_rate,
+        first.channels,
+        first.bits_per_sample,
+        args.compression_level
+    );
+    for (track, cue_track) in tracks.iter().zip(disc.tracks.iter()) {
+        let name = track
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| track.path.display().to_string());
+        println!(
+            "{} {}",
+            format_msf(cue_track.start_frames as u64).dimmed(),
+            name.bold()
+        );
+    }
+    report_cue_warnings(&warnings);
+
+    for path in [&image_path, &cue_path] {
+        if path.exists() && !args.overwrite {
+            return Err(format!("output file already exists: {}", path.display()));
+        }
+    }
+
+    match confirm_or_exit(args.yes, false)? {
+        ConfirmAction::Proceed => {}
+        _ => return Err(tr(Msg::AbortedByUser).to_string()),
+    }
+
+    let vendor = first.vendor.as_deref().unwrap_or("flac-cue-split");
+    let mut blocks = vec![build_vorbis_comment_from_tags(vendor, &album_tags)?];
+    for picture in &first.pictures {
+        if let Some(clone) = picture.try_clone() {
+            blocks.push(clone);
+        }
+    }
+
+    let mut encoder = start_encoder(
+        first,
+        first.channels,
+        &EncoderOptions {
+            compression_level: args.compression_level,
+            ..EncoderOptions::default()
+        },
+        total_samples,
+        blocks,
+        EncoderOutput::File(&image_path),
+    )?;
+    let mut progress = Some(make_progress_bar(total_samples));
+
+    let result = (|| {
+        for track in &tracks {
+            let decoder = create_decoder(&track.path)?;
+            for block in decoder.into_blocks(DEFAULT_BLOCK_SIZE)? {
+                let block = block?;
+                if block.channels != first.channels {
+                    return Err(format!(
+                        "decoder channel count {} does not match metadata {} in {}",
+                        block.channels,
+                        first.channels,
+                        track.path.display()
+                    ));
+                }
+                let samples = block.sample_count();
+                if samples == 0 {
+                    continue;
+                }
+                encoder.write_interleaved(&block.interleaved, samples as u32)?;
+                if let Some(pb) = progress.as_ref() {
+                    pb.inc(samples as u64);
+                }
+            }
+        }
+        encoder.finish()
+    })();
+
+    match result {
+        Ok(()) => finish_progress(&mut progress, tr(Msg::Done)),
+        Err(err) => {
+            finish_progress(&mut progress, tr(Msg::Aborted));
+            return Err(err);
+        }
+    }
+
+    fs::write(&cue_path, render_cue_sheet(&disc))
+        .map_err(|err| format!("failed to write cue file {}: {}", cue_path.display(), err))?;
+
+    for path in [&image_path, &cue_path] {
+        println!(
+            "{} {}",
+            "Created".green().bold(),
+            display_path(Some(cwd.as_path()), path)
+                .display()
+                .to_string()
+                .bold()
+        );
+    }
+
+    Ok(())
+}
+
+fn default_image_path(dir: &Path) -> Result<PathBuf> {
+    let name = dir
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("failed to derive image name from {}", dir.display()))?;
+    let parent = dir
+        .parent()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    Ok(parent.join(format!("{}.flac", name)))
+}
+
+fn read_join_tracks(dir: &Path, image_path: &Path) -> Result<Vec<JoinTrack>> {
+    let paths: Vec<PathBuf> = find_track_files(dir)?
+        .into_iter()
+        .filter(|path| path != image_path)
+        .collect();
+    if paths.is_empty() {
+        return Err(format!("no .flac files found in {}", dir.display()));
+    }
+
+    let mut tracks: Vec<JoinTrack> = Vec::with_capacity(paths.len());
+    let mut offset = 0u64;
+    for path in paths {
+        let mut decoder = create_decoder(&path)?;
+        let meta = decoder.read_metadata()?.input_meta;
+        if meta.total_samples == 0 {
+            return Err(format!(
+                "track length is unknown (STREAMINFO total samples is 0): {}",
+                path.display()
+            ));
+        }
+        if let Some(first) = tracks.first() {
+            let first = &first.meta;
+            if meta.sample_rate != first.sample_rate
+                || meta.channels != first.channels
+                || meta.bits_per_sample != first.bits_per_sample
+            {
+                return Err(format!(
+                    "audio format of {} ({} Hz, {} ch, {} bits) does not match the first track ({} Hz, {} ch, {} bits)",
+                    path.display(),
+                    meta.sample_rate,
+                    meta.channels,
+                    meta.bits_per_sample,
+                    first.sample_rate,
+                    first.channels,
+                    first.bits_per_sample
+                ));
+            }
+        }
+
+        let length = meta.total_samples;
+        tracks.push(JoinTrack { path, meta, offset });
+        offset += length;
+    }
+
+    Ok(tracks)
+}
+
+fn common_album_tags(tracks: &[JoinTrack]) -> Vec<(String, String)> {
+    let Some(first) = tracks.first() else {
+        return Vec::new();
+    };
+
+    first
+        .meta
+        .comments
+        .iter()
+        .filter(|(key, _)| {
+            !PER_TRACK_TAGS
+                .iter()
+                .any(|candidate| key.eq_ignore_ascii_case(candidate))
+        })
+        .filter(|pair| {
+            tracks[1..]
+                .iter()
+                .all(|track| track.meta.comments.contains(*pair))
+        })
+        .cloned()
+        .collect()
+}
+
+fn build_join_cue(
+    tracks: &[JoinTrack],
+    album_tags: &[(String, String)],
+    image_path: &Path,
+) -> Result<(CueDisc, Vec<String>)> {
+    let sample_rate = tracks
+        .first()
+        .map(|track| track.meta.sample_rate)
+        .unwrap_or(0);
+    if sample_rate == 0 || !sample_rate.is_multiple_of(75) {
+        return Err(format!(
+            "sample rate {} is not divisible by 75 (CUE frames)",
+            sample_rate
+        ));
+    }
+    let samples_per_frame = (sample_rate / 75) as u64;
+    let image_name = image_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| format!("invalid image path: {}", image_path.display()))?;
+
+    let album_value = |key: &str| find_tag(album_tags, key).map(str::to_string);
+    let performer = album_value("ALBUMARTIST").or_else(|| album_value("ARTIST"));
+
+    let mut warnings = Vec::new();
+    let mut cue_tracks = Vec::with_capacity(tracks.len());
+    for (index, track) in tracks.iter().enumerate() {
+        let number = (index + 1) as u32;
+        if !track.offset.is_multiple_of(samples_per_frame) {
+            warnings.push(format!(
+                "track {} starts at sample {}, which is not on a CD frame boundary; cue index is rounded down and the exact start kept as REM SAMPLE_OFFSET",
+                number, track.offset
+            ));
+        }
+
+        let comments = &track.meta.comments;
+        let track_value = |key: &str| {
+            find_tag(comments, key)
+                .filter(|value| find_tag(album_tags, key) != Some(*value))
+                .map(str::to_string)
+        };
+        cue_tracks.push(CueTrack {
+            number,
+            title: find_tag(comments, "TITLE").map(str::to_string),
+            performer: track_value("ARTIST"),
+            songwriter: None,
+            composer: track_value("COMPOSER"),
+            isrc: find_tag(comments, "ISRC").map(str::to_string),
+            start_frames: (track.offset / samples_per_frame) as i64,
+            length_frames: None,
+            sub_indexes: Vec::new(),
+            pregap_start: None,
+            filename: Some(image_name.clone()),
+            rem: CueRem {
+                date: track_value("DATE"),
+                replaygain_track_gain: find_tag(comments, "REPLAYGAIN_TRACK_GAIN")
+                    .map(str::to_string),
+                replaygain_track_peak: find_tag(comments, "REPLAYGAIN_TRACK_PEAK")
+                    .map(str::to_string),
+                sample_offset: (!track.offset.is_multiple_of(samples_per_frame))
+                    .then_some(track.offset),
+                ..CueRem::default()
+            },
+        });
+    }
+
+    let disc = CueDisc {
+        title: album_value("ALBUM"),
+        performer,
+        songwriter: None,
+        composer: album_value("COMPOSER"),
+        genre: album_value("GENRE"),
+        message: album_value("COMMENT"),
+        disc_id: album_value("DISCID"),
+        rem: CueRem {
+            date: album_value("DATE"),
+            replaygain_album_gain: album_value("REPLAYGAIN_ALBUM_GAIN"),
+            replaygain_album_peak: album_value("REPLAYGAIN_ALBUM_PEAK"),
+            ..CueRem::default()
+        },
+        tracks: cue_tracks,
+        discs: Vec::new(),
+        mode_issues: Vec::new(),
+    };
+
+    Ok((disc, warnings))
+}
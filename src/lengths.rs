@@ -0,0 +1,164 @@
+use std::fs;
+use std::path::Path;
+
+use crate::Result;
+use crate::split::{ComputedTrack, frames_to_samples};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum TrackLengths {
+    Samples(Vec<u64>),
+    Sectors(Vec<u64>),
+}
+
+impl TrackLengths {
+    fn samples(&self, sample_rate: u32) -> Result<Vec<u64>> {
+        match self {
+            TrackLengths::Samples(lengths) => Ok(lengths.clone()),
+            TrackLengths::Sectors(sectors) => sectors
+                .iter()
+                .map(|sectors| frames_to_samples(*sectors as i64, sample_rate))
+                .collect(),
+        }
+    }
+}
+
+pub(crate) fn load_track_lengths(path: &Path) -> Result<TrackLengths> {
+    let contents = fs::read(path)
+        .map_err(|err| format!("failed to read track lengths {}: {}", path.display(), err))?;
+    parse_track_lengths(&String::from_utf8_lossy(&contents))
+        .map_err(|err| format!("invalid track lengths {}: {}", path.display(), err))
+}
+
+fn parse_track_lengths(contents: &str) -> Result<TrackLengths> {
+    // A CUETools or EAC log is recognized by its TOC table; everything else in
+    // the log is ignored. Otherwise every line is a length in samples.
+    let sectors: Vec<u64> = contents.lines().filter_map(toc_row_sectors).collect();
+    if !sectors.is_empty() {
+        return Ok(TrackLengths::Sectors(sectors));
+    }
+
+    let mut lengths = Vec::new();
+    for (index, raw) in contents.lines().enumerate() {
+        let line = raw.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.parse::<u64>() {
+            Ok(length) if length > 0 => lengths.push(length),
+            _ => {
+                return Err(format!(
+                    "line {}: expected a track length in samples, found {:?}",
+                    index + 1,
+                    line
+                ));
+            }
+        }
+    }
+    if lengths.is_empty() {
+        return Err("no track lengths found".to_string());
+    }
+    Ok(TrackLengths::Samples(lengths))
+}
+
+fn toc_row_sectors(line: &str) -> Option<u64> {
+    // `  1  |  0:00.00 |  4:31.12 |         0    |    20361   `
+    let fields: Vec<&str> = line.split('|').map(str::trim).collect();
+    if fields.len() < 5 {
+        return None;
+    }
+    fields[0].parse::<u32>().ok()?;
+    let start = fields[3].parse::<u64>().ok()?;
+    let end = fields[4].parse::<u64>().ok()?;
+    (end >= start).then(|| end - start + 1)
+}
+
+pub(crate) fn apply_track_lengths(
+    mut tracks: Vec<ComputedTrack>,
+    lengths: &TrackLengths,
+    sample_rate: u32,
+    total_samples: u64,
+) -> Result<(Vec<ComputedTrack>, Vec<String>)> {
+    let lengths = lengths.samples(sample_rate)?;
+    if lengths.len() != tracks.len() {
+        return Err(format!(
+            "the track lengths list {} tracks, but the cue sheet has {}",
+            lengths.len(),
+            tracks.len()
+        ));
+    }
+
+    let samples_per_frame = u64::from(sample_rate / 75);
+    let mut warnings = Vec::new();
+    let mut position = tracks.first().map_or(0, |track| track.start);
+    for (track, length) in tracks.iter_mut().zip(lengths) {
+        if position.abs_diff(track.start) >= samples_per_frame {
+            warnings.push(format!(
+                "track {} starts {} samples away from its cue position after applying the track lengths",
+                track.number,
+                position as i64 - track.start as i64
+            ));
+        }
+        track.start = position;
+        track.end = position + length;
+        position = track.end;
+    }
+    if total_samples > 0 && position > total_samples {
+        return Err(format!(
+            "the track lengths run {} samples past the end of the audio",
+            position - total_samples
+        ));
+    }
+    Ok((tracks, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TrackLengths, apply_track_lengths, parse_track_lengths};
+    use crate::cli::SplitPoint;
+    use crate::split::{compute_track_spans, split_points_cue};
+
+    #[test]
+    fn track_lengths_read_sample_lists_and_log_tables() {
+        assert_eq!(
+            parse_track_lengths("# exact lengths\n11973612\n\n9406764\n").unwrap(),
+            TrackLengths::Samples(vec![11973612, 9406764])
+        );
+        assert!(parse_track_lengths("120\n4:31.12\n").is_err());
+        assert!(parse_track_lengths("0\n").is_err());
+
+        let log = r#"
+TOC of the extracted CD
+
+     Track |   Start  |  Length  | Start sector | End sector
+    ---------------------------------------------------------
+        1  |  0:00.00 |  4:31.37 |         0    |    20361
+        2  |  4:31.37 |  3:33.28 |     20362    |    36364
+
+Track 1
+     Copy CRC 1B2C3D4E
+"#;
+        assert_eq!(
+            parse_track_lengths(log).unwrap(),
+            TrackLengths::Sectors(vec![20362, 16003])
+        );
+        assert_eq!(
+            TrackLengths::Sectors(vec![2]).samples(44100).unwrap(),
+            vec![1176]
+        );
+
+        let points = [SplitPoint::Sample(0), SplitPoint::Sample(88200)];
+        let (disc, _) = split_points_cue(&points, 44100).unwrap();
+        let spans = compute_track_spans(&disc, 44100, 200_000).unwrap();
+        let lengths = TrackLengths::Samples(vec![88210, 100_000]);
+        let (spans, warnings) = apply_track_lengths(spans, &lengths, 44100, 200_000).unwrap();
+        let bounds: Vec<(u64, u64)> = spans.iter().map(|span| (span.start, span.end)).collect();
+        assert_eq!(bounds, vec![(0, 88210), (88210, 188210)]);
+        assert!(warnings.is_empty());
+
+        let spans = compute_track_spans(&disc, 44100, 200_000).unwrap();
+        let too_long = TrackLengths::Samples(vec![100_000, 100_001]);
+        assert!(apply_track_lengths(spans.clone(), &too_long, 44100, 200_000).is_err());
+        let too_few = TrackLengths::Samples(vec![100_000]);
+        assert!(apply_track_lengths(spans, &too_few, 44100, 200_000).is_err());
+    }
+}
@@ -1,18 +1,69 @@
 pub type Result<T> = std::result::Result<T, String>;
 
+mod aiff;
+mod alac;
+#[cfg(feature = "ape")]
+mod ape;
+mod apetag;
+mod apl;
 mod app;
+mod archive;
+mod case;
+mod cdtext;
+mod channels;
+mod checksum;
 mod cli;
+mod config;
 mod cue;
 mod decoder;
+mod diff;
+mod doctor;
+mod downmix;
+mod events;
+mod extract;
+mod fade;
 mod flac;
+mod gain;
+mod genre;
+mod i18n;
+mod id3;
+mod iff;
+mod inspect;
+mod jobs;
+mod join;
+mod lengths;
+mod lossy;
+mod manifest;
 mod metadata;
+mod permissions;
 mod picture;
+mod plan_file;
+mod priority;
+mod profile;
+mod remote;
+mod rename;
+mod retag;
+mod rules;
+mod serve;
+mod signal;
+mod silence;
+mod spectrum;
 mod split;
+mod tags;
+mod template;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+mod tracklist;
+mod tta;
 mod types;
 mod ui;
+mod verify;
+mod wav;
 mod wavpack;
 
 pub use app::run;
+pub use cue::parse_cue;
+pub use types::CueDisc;
 
 #[cfg(test)]
 mod tests;
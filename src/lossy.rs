@@ -0,0 +1,340 @@
+use std::io::{BufWriter, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread::JoinHandle;
+
+use crate::Result;
+use crate::picture::PictureBlock;
+
+const OPUSENC: &str = "opusenc";
+
+// Albums run in parallel share the process, so picture files also carry a
+// counter.
+static PICTURE_FILES: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LossyFormat {
+    Opus,
+}
+
+impl LossyFormat {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            LossyFormat::Opus => "opus",
+        }
+    }
+
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            LossyFormat::Opus => "opus",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct AlsoEncode {
+    pub(crate) format: LossyFormat,
+    pub(crate) dir: PathBuf,
+}
+
+pub(crate) fn parse_also_encode(value: &str) -> Result<AlsoEncode> {
+    let invalid = || {
+        format!(
+            "invalid target {:?} (expected FORMAT:DIR, e.g. opus:/media/phone)",
+            value
+        )
+    };
+    let (format, dir) = value.split_once(':').ok_or_else(invalid)?;
+    if dir.is_empty() {
+        return Err(invalid());
+    }
+    let format = match format.to_ascii_lowercase().as_str() {
+        "opus" => LossyFormat::Opus,
+        _ => {
+            return Err(format!(
+                "unsupported lossy format {:?} (supported: opus)",
+                format
+            ));
+        }
+    };
+    Ok(AlsoEncode {
+        format,
+        dir: PathBuf::from(dir),
+    })
+}
+
+// Encodes one track through an external encoder that reads raw 24-bit
+// little-endian PCM from a pipe.
+pub(crate) struct LossyEncoder {
+    child: Option<Child>,
+    stdin: Option<BufWriter<ChildStdin>>,
+    // Read on its own thread so a chatty encoder cannot fill the pipe and stall.
+    stderr: Option<JoinHandle<Vec<u8>>>,
+    // opusenc only reads pictures from files.
+    picture_files: Vec<PathBuf>,
+    path: PathBuf,
+    bits_per_sample: u32,
+    bytes: Vec<u8>,
+}
+
+impl LossyEncoder {
+    pub(crate) fn start(
+        format: LossyFormat,
+        path: &Path,
+        sample_rate: u32,
+        channels: usize,
+        bits_per_sample: u32,
+        tags: &[(String, String)],
+        pictures: &[PictureBlock],
+    ) -> Result<Self> {
+        let mut command = match format {
+            LossyFormat::Opus => Command::new(OPUSENC),
+        };
+        command
+            .args([
+                "--quiet",
+                "--raw",
+                "--raw-bits",
+                "24",
+                "--raw-endianness",
+                "0",
+            ])
+            .arg("--raw-rate")
+            .arg(sample_rate.to_string())
+            .arg("--raw-chan")
+            .arg(channels.to_string());
+        for (key, value) in tags {
+            command.arg("--comment").arg(format!("{}={}", key, value));
+        }
+        let picture_files = write_picture_files(pictures)?;
+        for (picture, file) in pictures.iter().zip(&picture_files) {
+            command.arg("--picture").arg(picture_spec(picture, file));
+        }
+        command
+            .arg("-")
+            .arg(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                remove_files(&picture_files);
+                return Err(match err.kind() {
+                    ErrorKind::NotFound => format!(
+                        "{} was not found; install opus-tools to use --also-encode opus",
+                        OPUSENC
+                    ),
+                    _ => format!("failed to start {}: {}", OPUSENC, err),
+                });
+            }
+        };
+        let stdin = child.stdin.take().map(BufWriter::new);
+        let stderr = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut output = Vec::new();
+                let _ = stderr.read_to_end(&mut output);
+                output
+            })
+        });
+        Ok(Self {
+            child: Some(child),
+            stdin,
+            stderr,
+            picture_files,
+            path: path.to_path_buf(),
+            bits_per_sample,
+            bytes: Vec::new(),
+        })
+    }
+
+    pub(crate) fn write(&mut self, interleaved: &[i32]) -> Result<()> {
+        self.bytes.clear();
+        for sample in interleaved {
+            self.bytes
+                .extend_from_slice(&to_24_bit(*sample, self.bits_per_sample).to_le_bytes()[..3]);
+        }
+        let Some(stdin) = self.stdin.as_mut() else {
+            return Ok(());
+        };
+        match stdin.write_all(&self.bytes) {
+            Ok(()) => Ok(()),
+            // The encoder quit; its own message says why.
+            Err(err) if err.kind() == ErrorKind::BrokenPipe => {
+                self.stdin = None;
+                Err(self.wait().err().unwrap_or_else(|| {
+                    format!("{} stopped reading {}", OPUSENC, self.path.display())
+                }))
+            }
+            Err(err) => Err(format!(
+                "failed to feed {} for {}: {}",
+                OPUSENC,
+                self.path.display(),
+                err
+            )),
+        }
+    }
+
+    // Closes the pipe, waits for the encoder and returns the written file.
+    pub(crate) fn finish(mut self) -> Result<PathBuf> {
+        if let Some(mut stdin) = self.stdin.take() {
+            stdin.flush().map_err(|err| {
+                format!(
+                    "failed to feed {} for {}: {}",
+                    OPUSENC,
+                    self.path.display(),
+                    err
+                )
+            })?;
+        }
+        self.wait()?;
+        Ok(self.path.clone())
+    }
+
+    fn wait(&mut self) -> Result<()> {
+        let Some(mut child) = self.child.take() else {
+            return Ok(());
+        };
+        let status = child.wait();
+        let stderr = self.join_stderr();
+        remove_files(&std::mem::take(&mut self.picture_files));
+        let status = status.map_err(|err| format!("failed to wait for {}: {}", OPUSENC, err))?;
+        if status.success() {
+            return Ok(());
+        }
+        let _ = std::fs::remove_file(&self.path);
+        let stderr = String::from_utf8_lossy(&stderr);
+        Err(format!(
+            "{} failed for {} ({}): {}",
+            OPUSENC,
+            self.path.display(),
+            status,
+            stderr.trim()
+        ))
+    }
+
+    fn join_stderr(&mut self) -> Vec<u8> {
+        self.stderr
+            .take()
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for LossyEncoder {
+    // A track that was not finished leaves no half-written copy behind.
+    fn drop(&mut self) {
+        self.stdin = None;
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = std::fs::remove_file(&self.path);
+        }
+        self.join_stderr();
+        remove_files(&self.picture_files);
+    }
+}
+
+fn write_picture_files(pictures: &[PictureBlock]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::with_capacity(pictures.len());
+    for picture in pictures {
+        let file = std::env::temp_dir().join(format!(
+            "flac-cue-split-picture-{}-{}",
+            std::process::id(),
+            PICTURE_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(err) = std::fs::write(&file, &picture.data) {
+            remove_files(&files);
+            return Err(format!(
+                "failed to write picture {}: {}",
+                file.display(),
+                err
+            ));
+        }
+        files.push(file);
+    }
+    Ok(files)
+}
+
+fn remove_files(files: &[PathBuf]) {
+    for file in files {
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+// opusenc's `TYPE|MIME|DESCRIPTION|WxHxD|FILE` form keeps the picture type.
+// Fields left empty are read from the image itself.
+fn picture_spec(picture: &PictureBlock, file: &Path) -> String {
+    let description = String::from_utf8_lossy(&picture.description);
+    let description = if description.contains('|') {
+        ""
+    } else {
+        description.as_ref()
+    };
+    format!(
+        "{}|{}|{}||{}",
+        picture.kind,
+        picture.mime,
+        description,
+        file.display()
+    )
+}
+
+fn to_24_bit(sample: i32, bits_per_sample: u32) -> i32 {
+    if bits_per_sample <= 24 {
+        sample << (24 - bits_per_sample)
+    } else {
+        sample >> (bits_per_sample - 24)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AlsoEncode, LossyFormat, parse_also_encode, picture_spec, to_24_bit};
+    use crate::picture::PictureBlock;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn also_encode_targets_are_parsed() {
+        assert_eq!(
+            parse_also_encode("opus:/media/phone").unwrap(),
+            AlsoEncode {
+                format: LossyFormat::Opus,
+                dir: PathBuf::from("/media/phone"),
+            }
+        );
+        assert_eq!(
+            parse_also_encode("OPUS:C:\\Phone").unwrap().dir,
+            PathBuf::from("C:\\Phone")
+        );
+        assert!(parse_also_encode("opus:").is_err());
+        assert!(parse_also_encode("/media/phone").is_err());
+        assert!(parse_also_encode("mp3:/media/phone").is_err());
+
+        assert_eq!(to_24_bit(-1, 16), -256);
+        assert_eq!(to_24_bit(0x7F_FFFF, 24), 0x7F_FFFF);
+        assert_eq!(to_24_bit(i32::MIN, 32), -0x80_0000);
+    }
+
+    #[test]
+    fn embedded_pictures_keep_their_type() {
+        let mut picture = PictureBlock {
+            kind: 4,
+            mime: "image/png".to_string(),
+            description: b"Back".to_vec(),
+            width: 1,
+            height: 1,
+            depth: 24,
+            colors: 0,
+            data: vec![1, 2, 3],
+        };
+        let file = Path::new("/tmp/picture");
+        assert_eq!(
+            picture_spec(&picture, file),
+            "4|image/png|Back||/tmp/picture"
+        );
+        picture.description = b"A|B".to_vec();
+        assert_eq!(picture_spec(&picture, file), "4|image/png|||/tmp/picture");
+    }
+}
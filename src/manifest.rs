@@ -0,0 +1,237 @@
+use encoding_rs::Encoding;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::cli::{InputPair, InputPath, display_path, parse_compression_level};
+use crate::cue::resolve_encoding;
+use crate::tags::{TagOverride, is_valid_tag_key};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+    jobs: Vec<ManifestEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ManifestEntry {
+    flac: PathBuf,
+    #[serde(default)]
+    cue: Option<PathBuf>,
+    #[serde(default)]
+    encoding: Option<String>,
+    #[serde(default)]
+    subdir: Option<PathBuf>,
+    #[serde(default)]
+    compression: Option<String>,
+    #[serde(default)]
+    tags: BTreeMap<String, String>,
+    #[serde(default)]
+    track_tags: BTreeMap<u32, BTreeMap<String, String>>,
+    #[serde(default)]
+    track_compression: BTreeMap<u32, String>,
+}
+
+pub(crate) struct ManifestJob {
+    pub(crate) pair: InputPair,
+    pub(crate) encoding: Option<&'static Encoding>,
+    pub(crate) subdir: Option<PathBuf>,
+    pub(crate) compression_level: Option<u8>,
+    pub(crate) track_compression: BTreeMap<u32, u8>,
+    pub(crate) tag_overrides: Vec<TagOverride>,
+}
+
+pub(crate) fn load_manifest(
+    path: &Path,
+    display_base_abs: Option<&Path>,
+) -> Result<Vec<ManifestJob>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read manifest {}: {}", path.display(), err))?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let jobs = parse_manifest(&contents, dir, display_base_abs)
+        .map_err(|err| format!("invalid manifest {}: {}", path.display(), err))?;
+    for job in &jobs {
+        for input in [&job.pair.flac, &job.pair.cue] {
+            if !input.abs.is_file() {
+                return Err(format!(
+                    "manifest {} lists a missing file: {}",
+                    path.display(),
+                    input.abs.display()
+                ));
+            }
+        }
+    }
+    Ok(jobs)
+}
+
+fn parse_manifest(
+    contents: &str,
+    dir: &Path,
+    display_base_abs: Option<&Path>,
+) -> Result<Vec<ManifestJob>> {
+    let manifest: ManifestFile = serde_norway::from_str(contents).map_err(|err| err.to_string())?;
+    if manifest.jobs.is_empty() {
+        return Err("no jobs listed".to_string());
+    }
+    let input = |path: &Path| {
+        let abs = dir.join(path);
+        InputPath {
+            display: display_path(display_base_abs, &abs),
+            abs,
+        }
+    };
+
+    let mut jobs = Vec::with_capacity(manifest.jobs.len());
+    for (index, entry) in manifest.jobs.into_iter().enumerate() {
+        let context = |err: String| format!("job {}: {}", index + 1, err);
+        let cue = entry
+            .cue
+            .unwrap_or_else(|| entry.flac.with_extension("cue"));
+        let encoding = entry
+            .encoding
+            .as_deref()
+            .map(resolve_encoding)
+            .transpose()
+            .map_err(context)?;
+        let compression_level = entry
+            .compression
+            .as_deref()
+            .map(parse_compression_level)
+            .transpose()
+            .map_err(context)?;
+        let track_compression = entry
+            .track_compression
+            .iter()
+            .map(|(number, level)| Ok((*number, parse_compression_level(level)?)))
+            .collect::<Result<BTreeMap<u32, u8>>>()
+            .map_err(context)?;
+        if let Some(subdir) = &entry.subdir
+            && subdir.is_absolute()
+        {
+            return Err(context(format!(
+                "subdir {} must be a relative path",
+                subdir.display()
+            )));
+        }
+        let album_tags = entry
+            .tags
+            .into_iter()
+            .map(|(key, value)| (None, key, value));
+        let track_tags = entry.track_tags.into_iter().flat_map(|(number, tags)| {
+            tags.into_iter()
+                .map(move |(key, value)| (Some(number), key, value))
+        });
+        let mut tag_overrides = Vec::new();
+        for (track, key, value) in album_tags.chain(track_tags) {
+            let key = key.trim().to_ascii_uppercase();
+            if !is_valid_tag_key(&key) {
+                return Err(context(format!("invalid tag name {:?}", key)));
+            }
+            tag_overrides.push(TagOverride {
+                track,
+                key,
+                value: value.trim().to_string(),
+            });
+        }
+        jobs.push(ManifestJob {
+            pair: InputPair {
+                flac: input(&entry.flac),
+                cue: input(&cue),
+            },
+            encoding,
+            subdir: entry.subdir,
+            compression_level,
+            track_compression,
+            tag_overrides,
+        });
+    }
+    Ok(jobs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_manifest;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn parse_manifest_resolves_paths_and_overrides() {
+        let manifest = r#"
+jobs:
+  - flac: Disc 1/Album.flac
+    encoding: windows-1251
+    subdir: CD1
+    compression: max
+    tags:
+      album: Album (Remaster)
+      date: 1999
+    track_tags:
+      3:
+        TITLE: Three
+    track_compression:
+      9: max
+  - flac: Disc 2/Album.wv
+    cue: Disc 2/Other.cue
+"#;
+        let jobs =
+            parse_manifest(manifest, Path::new("/music"), Some(Path::new("/music"))).unwrap();
+        assert_eq!(jobs.len(), 2);
+
+        let first = &jobs[0];
+        assert_eq!(
+            first.pair.flac.abs,
+            PathBuf::from("/music/Disc 1/Album.flac")
+        );
+        assert_eq!(first.pair.cue.display, PathBuf::from("Disc 1/Album.cue"));
+        assert_eq!(
+            first.encoding.map(|encoding| encoding.name()),
+            Some("windows-1251")
+        );
+        assert_eq!(first.subdir, Some(PathBuf::from("CD1")));
+        assert_eq!(first.compression_level, Some(8));
+        assert_eq!(first.track_compression.get(&9), Some(&8));
+        let overrides: Vec<_> = first
+            .tag_overrides
+            .iter()
+            .map(|entry| (entry.track, entry.key.as_str(), entry.value.as_str()))
+            .collect();
+        assert_eq!(
+            overrides,
+            vec![
+                (None, "ALBUM", "Album (Remaster)"),
+                (None, "DATE", "1999"),
+                (Some(3), "TITLE", "Three"),
+            ]
+        );
+
+        let second = &jobs[1];
+        assert_eq!(
+            second.pair.cue.abs,
+            PathBuf::from("/music/Disc 2/Other.cue")
+        );
+        assert_eq!(second.encoding, None);
+        assert_eq!(second.compression_level, None);
+        assert!(second.track_compression.is_empty());
+        assert!(second.tag_overrides.is_empty());
+
+        assert!(parse_manifest("jobs: []", Path::new("/music"), None).is_err());
+        assert!(
+            parse_manifest(
+                "jobs:\n  - flac: a.flac\n    compression: 12\n",
+                Path::new("/music"),
+                None
+            )
+            .is_err()
+        );
+        assert!(
+            parse_manifest(
+                "jobs:\n  - flac: a.flac\n    speed: fast\n",
+                Path::new("/music"),
+                None
+            )
+            .is_err()
+        );
+    }
+}
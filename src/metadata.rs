@@ -2,7 +2,9 @@ use libflac_sys as flac;
 use std::collections::{HashMap, HashSet};
 
 use crate::Result;
+use crate::channels::{CHANNEL_MASK_TAG, format_channel_mask};
 use crate::flac::FlacMetadata;
+use crate::tags::apply_tag_overrides;
 use crate::types::{CueDisc, InputMetadata, TrackSpan};
 
 pub(crate) fn build_track_metadata(
@@ -30,23 +32,54 @@ fn build_vorbis_comment(
     cue: &CueDisc,
     tracks: &[TrackSpan],
     track: &TrackSpan,
+) -> Result<FlacMetadata> {
+    let vendor = meta.vendor.as_deref().unwrap_or("flac-cue-split");
+    let tags = build_track_tags(meta, cue, track_total(tracks), track);
+    build_vorbis_comment_from_tags(vendor, &tags)
+}
+
+pub(crate) fn build_vorbis_comment_from_tags(
+    vendor: &str,
+    tags: &[(String, String)],
 ) -> Result<FlacMetadata> {
     let mut object = FlacMetadata::new(flac::FLAC__METADATA_TYPE_VORBIS_COMMENT)
         .map_err(|_| "failed to allocate Vorbis comment metadata".to_string())?;
 
-    let vendor = meta.vendor.as_deref().unwrap_or("flac-cue-split");
     set_vendor_string(&mut object, vendor)?;
 
-    let overrides = build_override_tags(cue, tracks.len(), track);
-    let merged = merge_tags(&meta.comments, &overrides);
-
-    for (key, value) in merged {
-        append_comment(&mut object, &key, &value)?;
+    for (key, value) in tags {
+        append_comment(&mut object, key, value)?;
     }
 
     Ok(object)
 }
 
+pub(crate) const REQUIRED_TAGS: [&str; 5] = ["TITLE", "ARTIST", "ALBUM", "DATE", "TRACKNUMBER"];
+
+pub(crate) fn missing_required_tags(
+    tags: &[(String, String)],
+    track_number: u32,
+) -> Vec<&'static str> {
+    REQUIRED_TAGS
+        .into_iter()
+        .filter(|key| match find_tag(tags, key).map(str::trim) {
+            None | Some("") => true,
+            // The placeholder title written for a cue track without TITLE.
+            Some(value) if *key == "TITLE" => {
+                value == format!("Track {}", track_number)
+                    || value == format!("Track {:02}", track_number)
+            }
+            Some(_) => false,
+        })
+        .collect()
+}
+
+pub(crate) fn find_tag<'a>(tags: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    tags.iter()
+        .find(|(existing, _)| existing.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value.as_str())
+}
+
 fn set_vendor_string(object: &mut FlacMetadata, vendor: &str) -> Result<()> {
     let bytes = vendor.as_bytes();
     let entry = flac::FLAC__StreamMetadata_VorbisComment_Entry {
@@ -64,7 +97,7 @@ fn set_vendor_string(object: &mut FlacMetadata, vendor: &str) -> Result<()> {
     Ok(())
 }
 
-fn append_comment(object: &mut FlacMetadata, key: &str, value: &str) -> Result<()> {
+pub(crate) fn append_comment(object: &mut FlacMetadata, key: &str, value: &str) -> Result<()> {
     let comment = format!("{}={}", key, value);
     let bytes = comment.as_bytes();
     let entry = flac::FLAC__StreamMetadata_VorbisComment_Entry {
@@ -81,6 +114,16 @@ fn append_comment(object: &mut FlacMetadata, key: &str, value: &str) -> Result<(
     Ok(())
 }
 
+// Index pieces of a track (`--split-indexes`) share its number, so the track
+// total counts numbers rather than files.
+pub(crate) fn track_total(tracks: &[TrackSpan]) -> usize {
+    tracks
+        .iter()
+        .map(|track| track.number)
+        .collect::<HashSet<_>>()
+        .len()
+}
+
 pub(crate) fn build_override_tags(
     cue: &CueDisc,
     total_tracks: usize,
@@ -157,6 +200,49 @@ pub(crate) fn build_override_tags(
     tags
 }
 
+pub(crate) fn build_track_tags(
+    meta: &InputMetadata,
+    cue: &CueDisc,
+    total_tracks: usize,
+    track: &TrackSpan,
+) -> Vec<(String, String)> {
+    if let Some(tags) = track.fixed_tags.as_ref() {
+        return tags.clone();
+    }
+    let mut overrides = build_override_tags(cue, total_tracks, track);
+    if meta.channels > 2
+        && let Some(mask) = meta.channel_mask
+    {
+        overrides.push((CHANNEL_MASK_TAG.to_string(), format_channel_mask(mask)));
+    }
+    let merged = merge_tags(&meta.comments, &overrides);
+    track
+        .tag_rules
+        .apply(apply_tag_overrides(merged, &track.tag_overrides))
+}
+
+pub(crate) fn diff_tags(
+    old: &[(String, String)],
+    new: &[(String, String)],
+) -> (Vec<(String, String)>, Vec<(String, String)>) {
+    let contains = |tags: &[(String, String)], (key, value): &(String, String)| {
+        tags.iter().any(|(other_key, other_value)| {
+            other_key.eq_ignore_ascii_case(key) && other_value == value
+        })
+    };
+    let removed = old
+        .iter()
+        .filter(|pair| !contains(new, *pair))
+        .cloned()
+        .collect();
+    let added = new
+        .iter()
+        .filter(|pair| !contains(old, *pair))
+        .cloned()
+        .collect();
+    (removed, added)
+}
+
 pub(crate) fn merge_tags(
     base: &[(String, String)],
     overrides: &[(String, String)],
@@ -177,6 +263,10 @@ pub(crate) fn merge_tags(
     merged
 }
 
+pub(crate) fn strip_replaygain_tags(tags: &mut Vec<(String, String)>) {
+    tags.retain(|(key, _)| !key.to_ascii_uppercase().starts_with("REPLAYGAIN_"));
+}
+
 pub(crate) fn compute_common_metadata(
     meta: &InputMetadata,
     cue: &CueDisc,
@@ -190,8 +280,7 @@ pub(crate) fn compute_common_metadata(
     let track_count = tracks.len();
 
     for track in tracks {
-        let overrides = build_override_tags(cue, track_count, track);
-        let merged = merge_tags(&meta.comments, &overrides);
+        let merged = build_track_tags(meta, cue, track_total(tracks), track);
         let mut seen: HashSet<(String, String)> = HashSet::new();
         for pair in merged {
             seen.insert(pair);
@@ -222,8 +311,7 @@ pub(crate) fn compute_unique_metadata_pairs(
     track: &TrackSpan,
     common: &[(String, String)],
 ) -> Vec<(String, String)> {
-    let overrides = build_override_tags(cue, tracks.len(), track);
-    let merged = merge_tags(&meta.comments, &overrides);
+    let merged = build_track_tags(meta, cue, track_total(tracks), track);
     let mut unique: Vec<(String, String)> = Vec::new();
     let common_set: HashSet<(String, String)> = common.iter().cloned().collect();
     for pair in merged {
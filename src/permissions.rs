@@ -0,0 +1,209 @@
+use std::fs;
+use std::path::Path;
+
+use crate::Result;
+use crate::cli::PermissionArgs;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Owner {
+    uid: Option<u32>,
+    gid: Option<u32>,
+}
+
+pub(crate) fn parse_owner(value: &str) -> Result<Owner> {
+    let (user, group) = match value.split_once(':') {
+        Some((user, group)) => (user, group),
+        None => (value, ""),
+    };
+    let uid = (!user.is_empty())
+        .then(|| resolve_id(user, user_id))
+        .transpose()?;
+    let gid = (!group.is_empty())
+        .then(|| resolve_id(group, group_id))
+        .transpose()?;
+    if uid.is_none() && gid.is_none() {
+        return Err(format!(
+            "invalid owner {:?} (examples: media, media:media, :1000)",
+            value
+        ));
+    }
+    Ok(Owner { uid, gid })
+}
+
+fn resolve_id(name: &str, lookup: fn(&str) -> Result<u32>) -> Result<u32> {
+    match name.parse::<u32>() {
+        Ok(id) => Ok(id),
+        Err(_) => lookup(name),
+    }
+}
+
+pub(crate) fn parse_mode(value: &str) -> Result<u32> {
+    let digits = value.strip_prefix("0o").unwrap_or(value);
+    let invalid = || format!("invalid mode {:?} (octal, e.g. 644 or 0775)", value);
+    if digits.is_empty() || !digits.bytes().all(|byte| (b'0'..=b'7').contains(&byte)) {
+        return Err(invalid());
+    }
+    match u32::from_str_radix(digits, 8) {
+        Ok(mode) if mode <= 0o7777 => Ok(mode),
+        _ => Err(invalid()),
+    }
+}
+
+pub(crate) fn apply_file_permissions(path: &Path, args: &PermissionArgs) -> Result<()> {
+    apply_permissions(path, args.owner, args.file_mode)
+}
+
+pub(crate) fn create_output_dir(dir: &Path, args: &PermissionArgs) -> Result<()> {
+    // Only the directories created here get the owner and mode; existing
+    // parents such as the library root are left alone.
+    let missing: Vec<&Path> = dir
+        .ancestors()
+        .take_while(|path| !path.as_os_str().is_empty() && !path.exists())
+        .collect();
+    fs::create_dir_all(dir).map_err(|err| {
+        format!(
+            "failed to create output directory {}: {}",
+            dir.display(),
+            err
+        )
+    })?;
+    for path in missing.into_iter().rev() {
+        apply_permissions(path, args.owner, args.dir_mode)?;
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn user_id(name: &str) -> Result<u32> {
+    let unknown = || format!("unknown user {:?}", name);
+    let name = std::ffi::CString::new(name).map_err(|_| unknown())?;
+    let entry = unsafe { libc::getpwnam(name.as_ptr()) };
+    if entry.is_null() {
+        return Err(unknown());
+    }
+    Ok(unsafe { (*entry).pw_uid })
+}
+
+#[cfg(unix)]
+fn group_id(name: &str) -> Result<u32> {
+    let unknown = || format!("unknown group {:?}", name);
+    let name = std::ffi::CString::new(name).map_err(|_| unknown())?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        return Err(unknown());
+    }
+    Ok(unsafe { (*entry).gr_gid })
+}
+
+#[cfg(unix)]
+fn apply_permissions(path: &Path, owner: Option<Owner>, mode: Option<u32>) -> Result<()> {
+    use crate::flac::path_to_cstring;
+    use std::os::unix::fs::PermissionsExt;
+
+    // The owner goes first: chown clears the setuid and setgid bits.
+    if let Some(owner) = owner {
+        let c_path = path_to_cstring(path)?;
+        // An id of -1 leaves that part of the ownership unchanged.
+        let uid = owner.uid.map_or(libc::uid_t::MAX, |uid| uid as libc::uid_t);
+        let gid = owner.gid.map_or(libc::gid_t::MAX, |gid| gid as libc::gid_t);
+        if unsafe { libc::chown(c_path.as_ptr(), uid, gid) } != 0 {
+            return Err(format!(
+                "failed to change the owner of {}: {}",
+                path.display(),
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    if let Some(mode) = mode {
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|err| {
+            format!(
+                "failed to set mode {:o} on {}: {}",
+                mode,
+                path.display(),
+                err
+            )
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn user_id(_name: &str) -> Result<u32> {
+    Err("user names in --owner are only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+fn group_id(_name: &str) -> Result<u32> {
+    Err("group names in --owner are only supported on Unix".to_string())
+}
+
+#[cfg(not(unix))]
+fn apply_permissions(_path: &Path, owner: Option<Owner>, mode: Option<u32>) -> Result<()> {
+    if owner.is_some() || mode.is_some() {
+        return Err("--owner, --file-mode and --dir-mode are only supported on Unix".to_string());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Owner, parse_mode, parse_owner};
+
+    #[test]
+    fn owner_and_mode_values_are_parsed() {
+        assert_eq!(
+            parse_owner("1000:100").unwrap(),
+            Owner {
+                uid: Some(1000),
+                gid: Some(100)
+            }
+        );
+        assert_eq!(
+            parse_owner(":100").unwrap(),
+            Owner {
+                uid: None,
+                gid: Some(100)
+            }
+        );
+        assert!(parse_owner(":").is_err());
+        assert!(parse_owner("no-such-user-for-tests").is_err());
+
+        assert_eq!(parse_mode("644").unwrap(), 0o644);
+        assert_eq!(parse_mode("0o2775").unwrap(), 0o2775);
+        assert!(parse_mode("0799").is_err());
+        assert!(parse_mode("17777").is_err());
+        assert!(parse_mode("").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn created_directories_and_files_get_the_requested_mode() {
+        use super::{apply_file_permissions, create_output_dir};
+        use crate::cli::PermissionArgs;
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let root =
+            std::env::temp_dir().join(format!("flac-cue-split-permissions-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o700)).unwrap();
+        let args = PermissionArgs {
+            owner: None,
+            file_mode: Some(0o640),
+            dir_mode: Some(0o750),
+        };
+        let dir = root.join("Artist").join("Album");
+        create_output_dir(&dir, &args).unwrap();
+        let track = dir.join("01.flac");
+        fs::write(&track, b"").unwrap();
+        apply_file_permissions(&track, &args).unwrap();
+
+        let mode =
+            |path: &std::path::Path| fs::metadata(path).unwrap().permissions().mode() & 0o7777;
+        assert_eq!(mode(&root), 0o700);
+        assert_eq!(mode(&root.join("Artist")), 0o750);
+        assert_eq!(mode(&dir), 0o750);
+        assert_eq!(mode(&track), 0o640);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,24 +1,86 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use clap::ValueEnum;
+use image::codecs::jpeg::JpegEncoder;
 use libflac_sys as flac;
+use serde::{Deserialize, Serialize};
 use std::ffi::{CStr, CString};
 use std::fs;
 use std::path::{Path, PathBuf};
 
 use crate::Result;
 use crate::flac::FlacMetadata;
+use crate::metadata::find_tag;
 use crate::types::InputMetadata;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum CoverMode {
+    #[default]
+    Embed,
+    File,
+    Both,
+}
+
+impl CoverMode {
+    pub(crate) fn embeds(self) -> bool {
+        self != CoverMode::File
+    }
+
+    pub(crate) fn copies(self) -> bool {
+        self != CoverMode::Embed
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum ArtStrategy {
+    #[default]
+    Embed,
+    SidecarThumbnail,
+}
+
+// Large enough for player screens, small enough to repeat in every track.
+pub(crate) const THUMBNAIL_SIZE: u32 = 300;
+const THUMBNAIL_QUALITY: u8 = 85;
+
+#[derive(Debug)]
+pub(crate) struct SidecarArt {
+    pub(crate) file_name: String,
+    pub(crate) data: Vec<u8>,
+}
+
+pub(crate) fn find_external_picture(
+    search_dir: &Path,
+    explicit_path: Option<&Path>,
+) -> Result<Option<PathBuf>> {
+    match explicit_path {
+        Some(path) => Ok(Some(path.to_path_buf())),
+        None => find_picture_file(search_dir),
+    }
+}
+
+pub(crate) fn cover_file_name(picture_path: &Path) -> String {
+    let ext = picture_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_else(|| "jpg".to_string());
+    let ext = if ext == "jpeg" {
+        "jpg".to_string()
+    } else {
+        ext
+    };
+    format!("cover.{}", ext)
+}
+
 pub(crate) fn add_external_picture(
     meta: &mut InputMetadata,
     picture_names: &mut Vec<String>,
     search_dir: &Path,
     explicit_path: Option<&Path>,
-) -> Result<()> {
-    let picture_path = match explicit_path {
-        Some(path) => path.to_path_buf(),
-        None => match find_picture_file(search_dir)? {
-            Some(path) => path,
-            None => return Ok(()),
-        },
+) -> Result<Option<PathBuf>> {
+    let Some(picture_path) = find_external_picture(search_dir, explicit_path)? else {
+        return Ok(None);
     };
 
     let picture = load_picture_metadata(&picture_path)?;
@@ -26,7 +88,7 @@ pub(crate) fn add_external_picture(
     if let Some(name) = picture_path.file_name() {
         picture_names.push(name.to_string_lossy().into_owned());
     }
-    Ok(())
+    Ok(Some(picture_path))
 }
 
 pub(crate) fn build_picture_metadata_from_data(
@@ -43,6 +105,266 @@ pub(crate) fn build_picture_metadata_from_data(
     create_picture_metadata(data, mime)
 }
 
+pub(crate) fn thumbnail_cover_art(
+    meta: &mut InputMetadata,
+) -> Result<(Option<SidecarArt>, Vec<String>)> {
+    let front_cover = meta.pictures.iter().position(|picture| {
+        let block = unsafe { &(*picture.as_ptr()).data.picture };
+        block.type_ == flac::FLAC__STREAM_METADATA_PICTURE_TYPE_FRONT_COVER
+    });
+    let Some(index) = front_cover.or((!meta.pictures.is_empty()).then_some(0)) else {
+        return Ok((None, Vec::new()));
+    };
+
+    let (data, kind) = {
+        let block = unsafe { &(*meta.pictures[index].as_ptr()).data.picture };
+        let data = if block.data.is_null() {
+            Vec::new()
+        } else {
+            unsafe { std::slice::from_raw_parts(block.data, block.data_length as usize) }.to_vec()
+        };
+        (data, block.type_)
+    };
+    let Some(mime) = picture_mime_type_from_data(&data) else {
+        return Ok((
+            None,
+            vec!["cover art is not a known image type; it stays embedded in full".to_string()],
+        ));
+    };
+    let extension = match mime.strip_prefix("image/") {
+        Some("jpeg") | None => "jpg",
+        Some(other) => other,
+    };
+
+    let mut warnings = Vec::new();
+    match make_thumbnail(&data) {
+        Ok((thumbnail, width, height)) if thumbnail.len() < data.len() => {
+            let mut picture = create_picture_metadata(&thumbnail, "image/jpeg")?;
+            {
+                let block = unsafe { &mut picture.as_mut().data.picture };
+                block.type_ = kind;
+                block.width = width;
+                block.height = height;
+                block.depth = 24;
+            }
+            meta.pictures[index] = picture;
+        }
+        Ok(_) => {}
+        Err(err) => warnings.push(format!(
+            "cannot make a thumbnail of the cover art ({}); it stays embedded in full",
+            err
+        )),
+    }
+    Ok((
+        Some(SidecarArt {
+            file_name: format!("cover.{}", extension),
+            data,
+        }),
+        warnings,
+    ))
+}
+
+pub(crate) fn make_thumbnail(data: &[u8]) -> Result<(Vec<u8>, u32, u32)> {
+    let image = image::load_from_memory(data).map_err(|err| err.to_string())?;
+    let thumbnail = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8();
+    let mut encoded = Vec::new();
+    JpegEncoder::new_with_quality(&mut encoded, THUMBNAIL_QUALITY)
+        .encode_image(&thumbnail)
+        .map_err(|err| err.to_string())?;
+    Ok((encoded, thumbnail.width(), thumbnail.height()))
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PictureBlock {
+    pub(crate) kind: u32,
+    pub(crate) mime: String,
+    pub(crate) description: Vec<u8>,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) depth: u32,
+    pub(crate) colors: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+pub(crate) fn convert_legacy_picture_tags(meta: &mut InputMetadata) {
+    let cover_mime = find_tag(&meta.comments, "COVERARTMIME").map(str::to_string);
+    let mut converted_cover = false;
+    let mut comments = Vec::with_capacity(meta.comments.len());
+    for (key, value) in std::mem::take(&mut meta.comments) {
+        let picture = match key.to_ascii_uppercase().as_str() {
+            "METADATA_BLOCK_PICTURE" => decode_base64(&value)
+                .and_then(|block| parse_picture_block(&block))
+                .and_then(|block| create_picture_from_block(&block)),
+            "COVERART" => decode_base64(&value).and_then(|data| match cover_mime.as_deref() {
+                Some(mime) => create_picture_metadata(&data, mime),
+                None => build_picture_metadata_from_data(&data, None),
+            }),
+            _ => {
+                comments.push((key, value));
+                continue;
+            }
+        };
+        // Tags that do not decode are kept as they are rather than dropped.
+        match picture {
+            Ok(picture) => {
+                converted_cover |= key.eq_ignore_ascii_case("COVERART");
+                meta.pictures.push(picture);
+            }
+            Err(_) => comments.push((key, value)),
+        }
+    }
+    if converted_cover {
+        comments.retain(|(key, _)| !key.eq_ignore_ascii_case("COVERARTMIME"));
+    }
+    meta.comments = comments;
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>> {
+    let compact: String = value.chars().filter(|ch| !ch.is_whitespace()).collect();
+    BASE64
+        .decode(compact)
+        .map_err(|err| format!("invalid base64 picture: {}", err))
+}
+
+pub(crate) fn parse_picture_block(block: &[u8]) -> Result<PictureBlock> {
+    let mut rest = block;
+    let kind = read_u32(&mut rest)?;
+    let mime_len = read_u32(&mut rest)? as usize;
+    let mime = String::from_utf8_lossy(take_bytes(&mut rest, mime_len)?).into_owned();
+    let description_len = read_u32(&mut rest)? as usize;
+    let description = take_bytes(&mut rest, description_len)?.to_vec();
+    let width = read_u32(&mut rest)?;
+    let height = read_u32(&mut rest)?;
+    let depth = read_u32(&mut rest)?;
+    let colors = read_u32(&mut rest)?;
+    let data_len = read_u32(&mut rest)? as usize;
+    let data = take_bytes(&mut rest, data_len)?.to_vec();
+    Ok(PictureBlock {
+        kind,
+        mime,
+        description,
+        width,
+        height,
+        depth,
+        colors,
+        data,
+    })
+}
+
+fn read_u32(rest: &mut &[u8]) -> Result<u32> {
+    let bytes = take_bytes(rest, 4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn take_bytes<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if rest.len() < len {
+        return Err("picture block is truncated".to_string());
+    }
+    let (head, tail) = rest.split_at(len);
+    *rest = tail;
+    Ok(head)
+}
+
+fn create_picture_from_block(block: &PictureBlock) -> Result<FlacMetadata> {
+    let mime = if block.mime.is_empty() {
+        picture_mime_type_from_data(&block.data)
+            .ok_or_else(|| "unsupported embedded picture type".to_string())?
+    } else {
+        block.mime.as_str()
+    };
+    let mut object = create_picture_metadata(&block.data, mime)?;
+
+    let desc_c = CString::new(block.description.clone())
+        .map_err(|_| "picture description contains NUL".to_string())?;
+    let ok = unsafe {
+        flac::FLAC__metadata_object_picture_set_description(
+            object.as_mut_ptr(),
+            desc_c.as_ptr() as *mut flac::FLAC__byte,
+            1,
+        ) != 0
+    };
+    if !ok {
+        return Err("failed to set picture description".to_string());
+    }
+
+    let picture = unsafe { &mut object.as_mut().data.picture };
+    picture.type_ = block.kind;
+    picture.width = block.width;
+    picture.height = block.height;
+    picture.depth = block.depth;
+    picture.colors = block.colors;
+    Ok(object)
+}
+
+pub(crate) fn picture_block(picture: &FlacMetadata) -> PictureBlock {
+    let block = unsafe { &(*picture.as_ptr()).data.picture };
+    let data = if block.data.is_null() {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(block.data, block.data_length as usize) }.to_vec()
+    };
+    let description = if block.description.is_null() {
+        Vec::new()
+    } else {
+        unsafe { CStr::from_ptr(block.description as *const std::ffi::c_char) }
+            .to_bytes()
+            .to_vec()
+    };
+    PictureBlock {
+        kind: block.type_,
+        mime: if block.mime_type.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(block.mime_type) }
+                .to_string_lossy()
+                .into_owned()
+        },
+        description,
+        width: block.width,
+        height: block.height,
+        depth: block.depth,
+        colors: block.colors,
+        data,
+    }
+}
+
+pub(crate) fn describe_picture(picture: &FlacMetadata) -> String {
+    let block = unsafe { &(*picture.as_ptr()).data.picture };
+    let mime = if block.mime_type.is_null() {
+        "unknown".to_string()
+    } else {
+        unsafe {
+            CStr::from_ptr(block.mime_type)
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+    let dimensions = if block.width > 0 && block.height > 0 {
+        format!(", {}x{}", block.width, block.height)
+    } else {
+        String::new()
+    };
+    format!(
+        "{}, {}{}, {} bytes",
+        picture_type_label(block.type_),
+        mime,
+        dimensions,
+        block.data_length
+    )
+}
+
+fn picture_type_label(kind: flac::FLAC__StreamMetadata_Picture_Type) -> String {
+    match kind {
+        flac::FLAC__STREAM_METADATA_PICTURE_TYPE_OTHER => "other".to_string(),
+        flac::FLAC__STREAM_METADATA_PICTURE_TYPE_FRONT_COVER => "front cover".to_string(),
+        flac::FLAC__STREAM_METADATA_PICTURE_TYPE_BACK_COVER => "back cover".to_string(),
+        flac::FLAC__STREAM_METADATA_PICTURE_TYPE_LEAFLET_PAGE => "leaflet page".to_string(),
+        flac::FLAC__STREAM_METADATA_PICTURE_TYPE_MEDIA => "media".to_string(),
+        flac::FLAC__STREAM_METADATA_PICTURE_TYPE_ARTIST => "artist".to_string(),
+        other => format!("type {}", other),
+    }
+}
+
 fn find_picture_file(dir: &Path) -> Result<Option<PathBuf>> {
     let mut matches = Vec::new();
     let read_dir = fs::read_dir(dir)
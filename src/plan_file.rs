@@ -0,0 +1,298 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::cue::resolve_encoding;
+use crate::flac::{EncoderOptions, OutputFormat};
+use crate::metadata::{build_track_tags, find_tag, track_total};
+use crate::picture::CoverMode;
+use crate::remote::is_url;
+use crate::rules::TagRules;
+use crate::split::{Plan, SavedSplitOptions, SourceAction, prepare_saved_split};
+use crate::template::DEFAULT_RENAME_TEMPLATE;
+use crate::types::{CueRem, TrackSpan};
+
+const PLAN_FILE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PlanFile {
+    version: u32,
+    jobs: Vec<PlanJob>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PlanJob {
+    source: PathBuf,
+    cue: PathBuf,
+    cue_encoding: String,
+    total_samples: u64,
+    compression_level: u8,
+    #[serde(default)]
+    output_format: OutputFormat,
+    #[serde(default)]
+    verify: bool,
+    #[serde(default)]
+    seektable: bool,
+    #[serde(default)]
+    padding: u32,
+    #[serde(default)]
+    keep_block_layout: bool,
+    #[serde(default)]
+    picture: Option<PathBuf>,
+    #[serde(default)]
+    cover: CoverMode,
+    #[serde(default)]
+    source_action: PlanSourceAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rename_template: Option<String>,
+    #[serde(default)]
+    include_sidecars: bool,
+    tracks: Vec<PlanTrack>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum PlanSourceAction {
+    #[default]
+    Keep,
+    Delete,
+    Rename,
+    Trash,
+    Archive {
+        dir: PathBuf,
+        base: PathBuf,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct PlanTrack {
+    number: u32,
+    start: u64,
+    end: u64,
+    output: PathBuf,
+    tags: Vec<(String, String)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    compression_level: Option<u8>,
+}
+
+pub(crate) fn write_plan_file(path: &Path, plans: &[Plan]) -> Result<()> {
+    let file = PlanFile {
+        version: PLAN_FILE_VERSION,
+        jobs: plans.iter().map(plan_to_job).collect(),
+    };
+    let mut json = serde_json::to_string_pretty(&file)
+        .map_err(|err| format!("failed to serialize plan: {}", err))?;
+    json.push('\n');
+    fs::write(path, json)
+        .map_err(|err| format!("failed to write plan file {}: {}", path.display(), err))
+}
+
+pub(crate) fn load_plan_file(
+    path: &Path,
+    display_base_abs: Option<&Path>,
+    overwrite: bool,
+    allow_truncated: bool,
+) -> Result<Vec<Plan>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read plan file {}: {}", path.display(), err))?;
+    let file = parse_plan_file(&contents)
+        .map_err(|err| format!("invalid plan file {}: {}", path.display(), err))?;
+
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    file.jobs
+        .into_iter()
+        .map(|job| job_to_plan(job, base_dir, display_base_abs, overwrite, allow_truncated))
+        .collect()
+}
+
+fn parse_plan_file(contents: &str) -> Result<PlanFile> {
+    let file: PlanFile = serde_json::from_str(contents).map_err(|err| err.to_string())?;
+    if file.version != PLAN_FILE_VERSION {
+        return Err(format!(
+            "unsupported plan version {} (expected {})",
+            file.version, PLAN_FILE_VERSION
+        ));
+    }
+    if file.jobs.is_empty() {
+        return Err("plan has no jobs".to_string());
+    }
+    Ok(file)
+}
+
+fn plan_to_job(plan: &Plan) -> PlanJob {
+    let (encoding, _) = plan.cue_encoding();
+    let rename_template = match plan.source_action() {
+        SourceAction::Rename { template } => Some(template.clone()),
+        _ => None,
+    };
+    let source_action = match plan.source_action() {
+        SourceAction::Keep => PlanSourceAction::Keep,
+        SourceAction::Delete => PlanSourceAction::Delete,
+        SourceAction::Rename { .. } => PlanSourceAction::Rename,
+        SourceAction::Trash => PlanSourceAction::Trash,
+        SourceAction::Archive { dir, base } => PlanSourceAction::Archive {
+            dir: dir.clone(),
+            base: base.clone(),
+        },
+    };
+    let tracks = plan
+        .tracks()
+        .iter()
+        .filter(|track| plan.track_selected(track.number))
+        .map(|track| PlanTrack {
+            number: track.number,
+            start: track.start,
+            end: track.end,
+            output: track.output_path.clone(),
+            tags: build_track_tags(
+                plan.input_meta(),
+                plan.cue(),
+                track_total(plan.tracks()),
+                track,
+            ),
+            compression_level: track.compression_level,
+        })
+        .collect();
+
+    PlanJob {
+        source: plan.flac_path().to_path_buf(),
+        cue: plan.cue_path().to_path_buf(),
+        cue_encoding: encoding.name().to_string(),
+        total_samples: plan.input_meta().total_samples,
+        compression_level: plan.encoder_options().compression_level,
+        output_format: plan.encoder_options().format,
+        verify: plan.encoder_options().verify,
+        seektable: plan.encoder_options().seektable,
+        padding: plan.encoder_options().padding,
+        keep_block_layout: plan.encoder_options().keep_block_layout,
+        picture: plan.picture_path().map(Path::to_path_buf),
+        cover: plan.cover_mode(),
+        source_action,
+        rename_template,
+        include_sidecars: plan.includes_sidecars(),
+        tracks,
+    }
+}
+
+fn job_to_plan(
+    job: PlanJob,
+    base_dir: &Path,
+    display_base_abs: Option<&Path>,
+    overwrite: bool,
+    allow_truncated: bool,
+) -> Result<Plan> {
+    let resolve = |path: PathBuf| {
+        if path.is_absolute() || is_url(&path) {
+            path
+        } else {
+            base_dir.join(path)
+        }
+    };
+    let track_levels = job
+        .tracks
+        .iter()
+        .filter_map(|track| track.compression_level);
+    if let Some(level) = std::iter::once(job.compression_level)
+        .chain(track_levels)
+        .find(|level| *level > 8)
+    {
+        return Err(format!(
+            "invalid compression level {} in plan (expected 0-8)",
+            level
+        ));
+    }
+
+    let tracks = job
+        .tracks
+        .into_iter()
+        .map(|track| TrackSpan {
+            number: track.number,
+            start: track.start,
+            end: track.end,
+            title: find_tag(&track.tags, "TITLE").map(str::to_string),
+            performer: None,
+            songwriter: None,
+            composer: None,
+            isrc: None,
+            rem: CueRem::default(),
+            tag_overrides: Vec::new(),
+            tag_rules: TagRules::default(),
+            fixed_tags: Some(track.tags),
+            compression_level: track.compression_level,
+            output_path: resolve(track.output),
+        })
+        .collect();
+
+    prepare_saved_split(SavedSplitOptions {
+        flac_abs: resolve(job.source),
+        cue_abs: resolve(job.cue),
+        display_base_abs: display_base_abs.map(Path::to_path_buf),
+        cue_encoding: resolve_encoding(&job.cue_encoding)?,
+        total_samples: job.total_samples,
+        encoder: EncoderOptions {
+            compression_level: job.compression_level,
+            format: job.output_format,
+            verify: job.verify,
+            seektable: job.seektable,
+            padding: job.padding,
+            keep_block_layout: job.keep_block_layout,
+            threads: 1,
+        },
+        picture_path: job.picture.map(resolve),
+        cover_mode: job.cover,
+        overwrite,
+        source_action: match job.source_action {
+            PlanSourceAction::Keep => SourceAction::Keep,
+            PlanSourceAction::Delete => SourceAction::Delete,
+            PlanSourceAction::Rename => SourceAction::Rename {
+                template: job
+                    .rename_template
+                    .unwrap_or_else(|| DEFAULT_RENAME_TEMPLATE.to_string()),
+            },
+            PlanSourceAction::Trash => SourceAction::Trash,
+            PlanSourceAction::Archive { dir, base } => SourceAction::Archive {
+                dir: resolve(dir),
+                base: resolve(base),
+            },
+        },
+        include_sidecars: job.include_sidecars,
+        allow_truncated,
+        tracks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PlanSourceAction, parse_plan_file};
+
+    #[test]
+    fn parse_plan_file_applies_defaults_and_checks_version() {
+        let json = r#"{
+            "version": 1,
+            "jobs": [{
+                "source": "Album.flac",
+                "cue": "Album.cue",
+                "cue_encoding": "UTF-8",
+                "total_samples": 1176,
+                "compression_level": 5,
+                "tracks": [
+                    {"number": 1, "start": 0, "end": 588, "output": "01 - One.flac",
+                     "tags": [["TITLE", "One"], ["TRACKNUMBER", "1"]]}
+                ]
+            }]
+        }"#;
+        let file = parse_plan_file(json).unwrap();
+        let job = &file.jobs[0];
+        assert_eq!(job.source_action, PlanSourceAction::Keep);
+        assert!(job.picture.is_none());
+        assert_eq!(
+            job.tracks[0].tags[0],
+            ("TITLE".to_string(), "One".to_string())
+        );
+
+        let newer = json.replace("\"version\": 1", "\"version\": 2");
+        assert!(parse_plan_file(&newer).is_err());
+        assert!(parse_plan_file(r#"{"version": 1, "jobs": []}"#).is_err());
+    }
+}
@@ -0,0 +1,119 @@
+use clap::ValueEnum;
+use std::io;
+
+use crate::Result;
+use crate::cli::PriorityArgs;
+
+const BACKGROUND_NICE: i32 = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum IoClass {
+    Idle,
+    Low,
+}
+
+pub(crate) fn lower_priority(args: &PriorityArgs) -> Result<()> {
+    let (nice, io_class) = resolve_priority(args);
+    if let Some(nice) = nice {
+        let rc = unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) };
+        if rc != 0 {
+            return Err(format!(
+                "failed to set nice level {}: {}",
+                nice,
+                io::Error::last_os_error()
+            ));
+        }
+    }
+    if let Some(class) = io_class {
+        set_io_class(class)?;
+    }
+    Ok(())
+}
+
+fn resolve_priority(args: &PriorityArgs) -> (Option<i32>, Option<IoClass>) {
+    let nice = args
+        .nice
+        .or_else(|| args.background.then_some(BACKGROUND_NICE));
+    let io_class = args
+        .ionice
+        .or_else(|| args.background.then_some(IoClass::Idle));
+    (nice, io_class)
+}
+
+#[cfg(target_os = "linux")]
+fn io_priority(class: IoClass) -> libc::c_int {
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    const IOPRIO_CLASS_BE: libc::c_int = 2;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    match class {
+        IoClass::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        IoClass::Low => (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | 7,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_io_class(class: IoClass) -> Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    let rc = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            io_priority(class),
+        )
+    };
+    if rc != 0 {
+        return Err(format!(
+            "failed to set I/O priority: {}",
+            io::Error::last_os_error()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_io_class(_class: IoClass) -> Result<()> {
+    Err("--ionice is only supported on Linux".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IoClass, resolve_priority};
+    use crate::cli::PriorityArgs;
+
+    #[test]
+    fn background_preset_yields_to_explicit_values() {
+        let background = PriorityArgs {
+            nice: None,
+            ionice: None,
+            background: true,
+        };
+        assert_eq!(
+            resolve_priority(&background),
+            (Some(10), Some(IoClass::Idle))
+        );
+
+        let explicit = PriorityArgs {
+            nice: Some(19),
+            ionice: Some(IoClass::Low),
+            background: true,
+        };
+        assert_eq!(resolve_priority(&explicit), (Some(19), Some(IoClass::Low)));
+
+        let none = PriorityArgs {
+            nice: None,
+            ionice: None,
+            background: false,
+        };
+        assert_eq!(resolve_priority(&none), (None, None));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn io_priority_encodes_class_and_level() {
+        use super::io_priority;
+
+        assert_eq!(io_priority(IoClass::Idle), 3 << 13);
+        assert_eq!(io_priority(IoClass::Low), (2 << 13) | 7);
+    }
+}
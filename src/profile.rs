@@ -0,0 +1,223 @@
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::{ArgMatches, Command, CommandFactory};
+
+use crate::Result;
+use crate::cli::Cli;
+
+pub(crate) const CONFIG_ENV: &str = "FLAC_CUE_SPLIT_CONFIG";
+
+pub(crate) struct AppliedProfile {
+    pub(crate) name: String,
+    pub(crate) argv: Vec<OsString>,
+    // Long names of the options the profile filled in.
+    pub(crate) options: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum ProfileValue {
+    Switch(bool),
+    Values(Vec<String>),
+}
+
+pub(crate) fn config_path() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(CONFIG_ENV) {
+        return Some(PathBuf::from(path));
+    }
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("flac-cue-split").join("config.toml"))
+}
+
+// Returns the command line with the selected profile's options added, or None
+// when no profile is selected. Options given on the command line win.
+pub(crate) fn apply_profile(
+    argv: &[OsString],
+    matches: &ArgMatches,
+) -> Result<Option<AppliedProfile>> {
+    let mut root = Cli::command();
+    root.build();
+    let (command, matches) = match matches.subcommand() {
+        Some((name, sub_matches)) => match root.find_subcommand(name) {
+            Some(command) => (command.clone(), sub_matches),
+            None => return Ok(None),
+        },
+        None => (root, matches),
+    };
+    let Some(name) = matches
+        .try_get_one::<String>("profile")
+        .ok()
+        .flatten()
+        .cloned()
+    else {
+        return Ok(None);
+    };
+
+    let path = config_path()
+        .ok_or_else(|| format!("--profile needs a config file; set {} or HOME", CONFIG_ENV))?;
+    let text = fs::read_to_string(&path)
+        .map_err(|err| format!("failed to read config {}: {}", path.display(), err))?;
+    let entries =
+        parse_profile(&text, &name).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let extra = profile_args(&command, matches, &name, &entries)?;
+    let mut options: Vec<String> = extra
+        .iter()
+        .filter_map(|arg| arg.to_str()?.strip_prefix("--"))
+        .map(|arg| arg.split('=').next().unwrap_or(arg).to_string())
+        .collect();
+    options.dedup();
+
+    // The options go in front of `--`, after which everything is a positional.
+    let mut argv = argv.to_vec();
+    let at = argv
+        .iter()
+        .position(|arg| arg == "--")
+        .unwrap_or(argv.len());
+    argv.splice(at..at, extra);
+    Ok(Some(AppliedProfile {
+        name,
+        argv,
+        options,
+    }))
+}
+
+fn parse_profile(text: &str, name: &str) -> Result<Vec<(String, ProfileValue)>> {
+    let config: toml::Table = text
+        .parse()
+        .map_err(|err: toml::de::Error| format!("invalid config: {}", err.message()))?;
+    let profiles = config.get("profile").and_then(|value| value.as_table());
+    let Some(profile) = profiles.and_then(|profiles| profiles.get(name)) else {
+        let known = profiles
+            .map(|profiles| profiles.keys().cloned().collect::<Vec<_>>().join(", "))
+            .filter(|known| !known.is_empty())
+            .unwrap_or_else(|| "none".to_string());
+        return Err(format!(
+            "no profile {:?} in the config (profiles: {})",
+            name, known
+        ));
+    };
+    let profile = profile
+        .as_table()
+        .ok_or_else(|| format!("profile.{} must be a table", name))?;
+
+    let mut entries = Vec::with_capacity(profile.len());
+    for (key, value) in profile {
+        let invalid = || {
+            format!(
+                "profile.{}.{} must be a string, number, boolean or a list of them",
+                name, key
+            )
+        };
+        let value = match value {
+            toml::Value::Boolean(enabled) => ProfileValue::Switch(*enabled),
+            toml::Value::Array(items) => ProfileValue::Values(
+                items
+                    .iter()
+                    .map(|item| scalar(item).ok_or_else(invalid))
+                    .collect::<Result<_>>()?,
+            ),
+            other => ProfileValue::Values(vec![scalar(other).ok_or_else(invalid)?]),
+        };
+        entries.push((key.clone(), value));
+    }
+    Ok(entries)
+}
+
+fn scalar(value: &toml::Value) -> Option<String> {
+    match value {
+        toml::Value::String(text) => Some(text.clone()),
+        toml::Value::Integer(number) => Some(number.to_string()),
+        toml::Value::Float(number) => Some(number.to_string()),
+        toml::Value::Boolean(enabled) => Some(enabled.to_string()),
+        _ => None,
+    }
+}
+
+fn profile_args(
+    command: &Command,
+    matches: &ArgMatches,
+    name: &str,
+    entries: &[(String, ProfileValue)],
+) -> Result<Vec<OsString>> {
+    let mut args = Vec::new();
+    for (key, value) in entries {
+        let arg = command
+            .get_arguments()
+            .find(|arg| arg.get_long() == Some(key.as_str()) && key != "profile")
+            .ok_or_else(|| format!("unknown option --{} in profile {:?}", key, name))?;
+        if matches.value_source(arg.get_id().as_str()) == Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let takes_values = arg.get_action().takes_values();
+        match value {
+            ProfileValue::Switch(true) if !takes_values => args.push(format!("--{}", key)),
+            ProfileValue::Switch(false) if !takes_values => {}
+            ProfileValue::Switch(enabled) => args.push(format!("--{}={}", key, enabled)),
+            ProfileValue::Values(_) if !takes_values => {
+                return Err(format!(
+                    "--{} in profile {:?} is a switch; set it to true or false",
+                    key, name
+                ));
+            }
+            // `=` keeps values such as `-3dB` from being read as options.
+            ProfileValue::Values(values) => {
+                args.extend(values.iter().map(|value| format!("--{}={}", key, value)));
+            }
+        }
+    }
+    Ok(args.into_iter().map(OsString::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProfileValue, parse_profile, profile_args};
+    use crate::cli::Cli;
+    use clap::CommandFactory;
+
+    const CONFIG: &str = r#"
+[profile.car]
+output-format = "oggflac"
+compression-level = 3
+no-picture = true
+gain = ["-2dB", "3:+1dB"]
+
+[profile.archive]
+preset = "archive"
+"#;
+
+    #[test]
+    fn profiles_become_options_unless_given_on_the_command_line() {
+        let entries = parse_profile(CONFIG, "car").unwrap();
+        assert_eq!(
+            entries[2],
+            ("no-picture".to_string(), ProfileValue::Switch(true))
+        );
+        let error = parse_profile(CONFIG, "phone").unwrap_err();
+        assert!(error.contains("archive, car"), "{}", error);
+
+        let mut command = Cli::command();
+        command.build();
+        let matches = command
+            .clone()
+            .try_get_matches_from(["flac-cue-split", "-c", "8", "Album"])
+            .unwrap();
+        let args = profile_args(&command, &matches, "car", &entries).unwrap();
+        assert_eq!(
+            args,
+            vec![
+                "--gain=-2dB",
+                "--gain=3:+1dB",
+                "--no-picture",
+                "--output-format=oggflac",
+            ]
+        );
+
+        let unknown = parse_profile("[profile.x]\nno-such-option = 1\n", "x").unwrap();
+        assert!(profile_args(&command, &matches, "x", &unknown).is_err());
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{self, Read};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::Result;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+const MAX_RESUME_ATTEMPTS: u32 = 5;
+
+pub(crate) fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|value| {
+        let lower = value.to_ascii_lowercase();
+        lower.starts_with("http://") || lower.starts_with("https://")
+    })
+}
+
+pub(crate) fn url_file_name(url: &str) -> &str {
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let path = without_scheme
+        .split(['?', '#'])
+        .next()
+        .unwrap_or(without_scheme);
+    match path.split_once('/') {
+        Some((_, path)) => path.rsplit('/').next().unwrap_or(path),
+        None => "",
+    }
+}
+
+pub(crate) fn url_extension(url: &str) -> String {
+    Path::new(url_file_name(url))
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+pub(crate) fn fetch_url(url: &str) -> Result<Vec<u8>> {
+    let mut reader = HttpReader::open(url)?;
+    let mut contents = Vec::new();
+    reader
+        .read_to_end(&mut contents)
+        .map_err(|err| format!("failed to download {}: {}", url, err))?;
+    Ok(contents)
+}
+
+pub(crate) struct HttpReader {
+    agent: ureq::Agent,
+    url: String,
+    reader: Box<dyn Read + Send + Sync>,
+    offset: u64,
+    length: Option<u64>,
+    ranges: bool,
+    resumes: u32,
+}
+
+impl HttpReader {
+    pub(crate) fn open(url: &str) -> Result<Self> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(CONNECT_TIMEOUT)
+            .timeout_read(READ_TIMEOUT)
+            .build();
+        let response = agent
+            .get(url)
+            .call()
+            .map_err(|err| format!("failed to fetch {}: {}", url, err))?;
+        let length = response
+            .header("Content-Length")
+            .and_then(|value| value.trim().parse().ok());
+        let ranges = response
+            .header("Accept-Ranges")
+            .is_some_and(|value| value.trim().eq_ignore_ascii_case("bytes"));
+        Ok(Self {
+            agent,
+            url: url.to_string(),
+            reader: response.into_reader(),
+            offset: 0,
+            length,
+            ranges,
+            resumes: 0,
+        })
+    }
+
+    fn can_resume(&self) -> bool {
+        self.ranges && self.resumes < MAX_RESUME_ATTEMPTS
+    }
+
+    fn resume(&mut self) -> io::Result<()> {
+        self.resumes += 1;
+        let response = self
+            .agent
+            .get(&self.url)
+            .set("Range", &format!("bytes={}-", self.offset))
+            .call()
+            .map_err(io::Error::other)?;
+        if response.status() != 206 {
+            return Err(io::Error::other(format!(
+                "server answered the range request with status {}",
+                response.status()
+            )));
+        }
+        self.reader = response.into_reader();
+        Ok(())
+    }
+}
+
+impl Read for HttpReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.reader.read(buf) {
+                Ok(0)
+                    if !buf.is_empty()
+                        && self.length.is_some_and(|length| self.offset < length)
+                        && self.can_resume() =>
+                {
+                    self.resume()?;
+                }
+                Ok(read) => {
+                    self.offset += read as u64;
+                    return Ok(read);
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) if self.can_resume() => self.resume()?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_url, url_extension, url_file_name};
+    use std::path::Path;
+
+    #[test]
+    fn url_helpers_ignore_query_and_fragment() {
+        assert!(is_url(Path::new("https://nas.local/music/Album.flac")));
+        assert!(is_url(Path::new("HTTP://nas.local/Album.cue")));
+        assert!(!is_url(Path::new("/music/Album.flac")));
+        assert!(!is_url(Path::new("ftp://nas.local/Album.flac")));
+
+        assert_eq!(
+            url_file_name("https://nas.local/music/Album%20One.flac?token=abc#x"),
+            "Album%20One.flac"
+        );
+        assert_eq!(url_file_name("https://nas.local"), "");
+        assert_eq!(url_extension("https://nas.local/a/Album.FLAC?dl=1"), "flac");
+        assert_eq!(url_extension("https://nas.local/a/"), "");
+    }
+}
@@ -0,0 +1,141 @@
+use owo_colors::OwoColorize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::cli::{RenameArgs, display_path, find_track_files};
+use crate::decoder::create_decoder;
+use crate::i18n::{Msg, tr};
+use crate::template::render_path_template;
+use crate::ui::{ConfirmAction, confirm_or_exit};
+
+struct RenameEntry {
+    from: PathBuf,
+    to: PathBuf,
+}
+
+pub(crate) fn run_rename(args: RenameArgs) -> Result<()> {
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let dir_abs = if args.dir.is_absolute() {
+        args.dir.clone()
+    } else {
+        cwd.join(&args.dir)
+    };
+    if !dir_abs.is_dir() {
+        return Err(format!("directory not found: {}", dir_abs.display()));
+    }
+
+    let sources = find_track_files(&dir_abs)?;
+    if sources.is_empty() {
+        return Err(format!("no .flac files found in {}", dir_abs.display()));
+    }
+
+    let mut entries = Vec::with_capacity(sources.len());
+    let mut targets = HashSet::new();
+    for from in &sources {
+        let mut decoder = create_decoder(from)?;
+        let comments = decoder.read_metadata()?.input_meta.comments;
+        let relative = render_path_template(&args.template, &comments)
+            .map_err(|err| format!("{}: {}", from.display(), err))?;
+        let mut file_name = relative.into_os_string();
+        file_name.push(".flac");
+        let to = dir_abs.join(file_name);
+        if !targets.insert(to.clone()) {
+            return Err(format!(
+                "template produces duplicate name {}",
+                display_path(Some(cwd.as_path()), &to).display()
+            ));
+        }
+        entries.push(RenameEntry {
+            from: from.clone(),
+            to,
+        });
+    }
+
+    entries.retain(|entry| entry.from != entry.to);
+    if entries.is_empty() {
+        println!("{}", "All files already match the template".green());
+        return Ok(());
+    }
+
+    let moving: HashSet<&Path> = entries.iter().map(|entry| entry.from.as_path()).collect();
+    for entry in &entries {
+        if entry.to.exists() && !moving.contains(entry.to.as_path()) {
+            return Err(format!(
+                "rename target already exists: {}",
+                entry.to.display()
+            ));
+        }
+    }
+
+    println!("{}", "Rename plan".bold());
+    for entry in &entries {
+        println!(
+            "{} -> {}",
+            display_path(Some(dir_abs.as_path()), &entry.from)
+                .display()
+                .to_string()
+                .dimmed(),
+            display_path(Some(dir_abs.as_path()), &entry.to)
+                .display()
+                .to_string()
+                .bold()
+        );
+    }
+
+    match confirm_or_exit(args.yes, false)? {
+        ConfirmAction::Proceed => {}
+        _ => return Err(tr(Msg::AbortedByUser).to_string()),
+    }
+
+    let needs_staging = entries
+        .iter()
+        .any(|entry| moving.contains(entry.to.as_path()));
+    let mut staged = Vec::with_capacity(entries.len());
+    for (index, entry) in entries.iter().enumerate() {
+        let from = if needs_staging {
+            let temp = dir_abs.join(format!(".flac-cue-split-rename-{}.tmp", index));
+            rename_file(&entry.from, &temp)?;
+            temp
+        } else {
+            entry.from.clone()
+        };
+        staged.push(from);
+    }
+
+    for (entry, from) in entries.iter().zip(staged.iter()) {
+        if let Some(parent) = entry.to.parent() {
+            fs::create_dir_all(parent).map_err(|err| {
+                format!(
+                    "failed to create output directory {}: {}",
+                    parent.display(),
+                    err
+                )
+            })?;
+        }
+        rename_file(from, &entry.to)?;
+        println!(
+            "{} {}",
+            "Renamed".yellow().bold(),
+            display_path(Some(dir_abs.as_path()), &entry.to)
+                .display()
+                .to_string()
+                .yellow()
+        );
+    }
+
+    Ok(())
+}
+
+fn rename_file(from: &Path, to: &Path) -> Result<()> {
+    fs::rename(from, to).map_err(|err| {
+        format!(
+            "failed to rename {} -> {}: {}",
+            from.display(),
+            to.display(),
+            err
+        )
+    })
+}
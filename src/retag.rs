@@ -0,0 +1,167 @@
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::case::normalize_cue_case;
+use crate::cli::{RetagArgs, display_path, find_track_files, resolve_input_path};
+use crate::cue::{parse_cue_file, report_cue_warnings, resolve_encoding};
+use crate::decoder::create_decoder;
+use crate::flac::rewrite_tag_blocks;
+use crate::genre::{GenreMap, load_genre_map, map_cue_genre};
+use crate::i18n::{Msg, tr};
+use crate::metadata::{build_track_metadata, build_track_tags, track_total};
+use crate::picture::add_external_picture;
+use crate::rules::{TagRules, load_tag_rules};
+use crate::tags::{load_tag_overrides, overrides_for_track};
+use crate::types::{InputMetadata, TrackSpan};
+use crate::ui::{ConfirmAction, confirm_or_exit, format_tag_pairs};
+
+struct RetagFile {
+    path: PathBuf,
+    meta: InputMetadata,
+}
+
+pub(crate) fn run_retag(args: RetagArgs) -> Result<()> {
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let dir_abs = if args.dir.is_absolute() {
+        args.dir.clone()
+    } else {
+        cwd.join(&args.dir)
+    };
+    if !dir_abs.is_dir() {
+        return Err(format!("directory not found: {}", dir_abs.display()));
+    }
+
+    let encoding = match args.cue_encoding.as_deref() {
+        Some(label) => Some(resolve_encoding(label)?),
+        None => None,
+    };
+    let cue_input = resolve_input_path(&dir_abs, Some(cwd.as_path()), args.cue.as_ref(), "cue")?;
+    let (mut cue, warnings, _, _) = parse_cue_file(&cue_input.abs, encoding)?;
+    report_cue_warnings(&warnings);
+    normalize_cue_case(&mut cue, args.normalize_case);
+    let genre_map = match args.genre_map.as_ref() {
+        Some(path) if path.is_absolute() => load_genre_map(path)?,
+        Some(path) => load_genre_map(&cwd.join(path))?,
+        None => GenreMap::default(),
+    };
+    map_cue_genre(&mut cue, &genre_map);
+    let tag_rules = match args.tag_rules.as_ref() {
+        Some(path) if path.is_absolute() => load_tag_rules(path)?,
+        Some(path) => load_tag_rules(&cwd.join(path))?,
+        None => TagRules::default(),
+    };
+
+    let tag_overrides = match args.tags_from.as_ref() {
+        Some(path) if path.is_absolute() => load_tag_overrides(path)?,
+        Some(path) => load_tag_overrides(&cwd.join(path))?,
+        None => Vec::new(),
+    };
+
+    let mut artwork = InputMetadata::new();
+    let mut artwork_names = Vec::new();
+    if !args.no_picture {
+        let picture_path = args.picture.as_ref().map(|path| {
+            if path.is_absolute() {
+                path.clone()
+            } else {
+                cwd.join(path)
+            }
+        });
+        add_external_picture(
+            &mut artwork,
+            &mut artwork_names,
+            &dir_abs,
+            picture_path.as_deref(),
+        )?;
+    }
+
+    let paths = find_track_files(&dir_abs)?;
+    if paths.len() != cue.tracks.len() {
+        return Err(format!(
+            "found {} .flac files in {} but the cue sheet has {} tracks",
+            paths.len(),
+            dir_abs.display(),
+            cue.tracks.len()
+        ));
+    }
+
+    let mut files = Vec::with_capacity(paths.len());
+    for path in paths {
+        let mut decoder = create_decoder(&path)?;
+        let mut meta = decoder.read_metadata()?.input_meta;
+        if !artwork.pictures.is_empty() {
+            meta.pictures.clear();
+            for picture in &artwork.pictures {
+                if let Some(clone) = picture.try_clone() {
+                    meta.pictures.push(clone);
+                }
+            }
+        }
+        files.push(RetagFile { path, meta });
+    }
+
+    let spans: Vec<TrackSpan> = cue
+        .tracks
+        .iter()
+        .zip(files.iter())
+        .map(|(track, file)| TrackSpan {
+            number: track.number,
+            start: 0,
+            end: file.meta.total_samples,
+            title: track.title.clone(),
+            performer: track.performer.clone(),
+            songwriter: track.songwriter.clone(),
+            composer: track.composer.clone(),
+            isrc: track.isrc.clone(),
+            rem: track.rem.clone(),
+            tag_overrides: overrides_for_track(&tag_overrides, track.number),
+            tag_rules: tag_rules.clone(),
+            fixed_tags: None,
+            compression_level: None,
+            output_path: file.path.clone(),
+        })
+        .collect();
+
+    println!("{}", "Retag plan".bold());
+    println!("  {} {}", "CUE:".cyan(), cue_input.display.display());
+    if !artwork_names.is_empty() {
+        println!("  {} {}", "Artwork:".cyan(), artwork_names.join(", "));
+    }
+    for (file, span) in files.iter().zip(spans.iter()) {
+        let new_tags = build_track_tags(&file.meta, &cue, track_total(&spans), span);
+        let changed: Vec<(String, String)> = new_tags
+            .iter()
+            .filter(|pair| !file.meta.comments.contains(*pair))
+            .cloned()
+            .collect();
+        let name = display_name(&cwd, &file.path);
+        if changed.is_empty() {
+            println!("{} {}", name.bold(), "(tags unchanged)".dimmed());
+        } else {
+            println!("{} {}", name.bold(), format_tag_pairs(&changed));
+        }
+    }
+
+    match confirm_or_exit(args.yes, false)? {
+        ConfirmAction::Proceed => {}
+        _ => return Err(tr(Msg::AbortedByUser).to_string()),
+    }
+
+    for (file, span) in files.iter().zip(spans.iter()) {
+        let blocks = build_track_metadata(&file.meta, &cue, &spans, span)?;
+        rewrite_tag_blocks(&file.path, blocks)?;
+        println!(
+            "{} {}",
+            "Updated".green().bold(),
+            display_name(&cwd, &file.path).bold()
+        );
+    }
+
+    Ok(())
+}
+
+fn display_name(cwd: &Path, path: &Path) -> String {
+    display_path(Some(cwd), path).display().to_string()
+}
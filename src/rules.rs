@@ -0,0 +1,160 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::Result;
+use crate::tags::{apply_tag_overrides, is_valid_tag_key};
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    rules: Vec<RuleEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    key: String,
+    #[serde(rename = "match")]
+    pattern: String,
+    #[serde(default)]
+    replace: Option<String>,
+    #[serde(default)]
+    set: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+struct TagRule {
+    key: String,
+    regex: Regex,
+    replace: Option<String>,
+    set: Vec<(String, String)>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TagRules(Arc<Vec<TagRule>>);
+
+impl TagRules {
+    pub(crate) fn apply(&self, mut tags: Vec<(String, String)>) -> Vec<(String, String)> {
+        for rule in self.0.iter() {
+            let mut extra = Vec::new();
+            tags.retain_mut(|(key, value)| {
+                if !key.eq_ignore_ascii_case(&rule.key) {
+                    return true;
+                }
+                let Some(captures) = rule.regex.captures(value) else {
+                    return true;
+                };
+                for (set_key, template) in &rule.set {
+                    let mut expanded = String::new();
+                    captures.expand(template, &mut expanded);
+                    extra.push((set_key.clone(), expanded.trim().to_string()));
+                }
+                if let Some(replace) = &rule.replace {
+                    *value = rule.regex.replace(value, replace).trim().to_string();
+                }
+                !value.is_empty()
+            });
+            tags = apply_tag_overrides(tags, &extra);
+        }
+        tags
+    }
+}
+
+pub(crate) fn load_tag_rules(path: &Path) -> Result<TagRules> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read tag rules {}: {}", path.display(), err))?;
+    parse_tag_rules(&contents)
+        .map_err(|err| format!("invalid tag rules {}: {}", path.display(), err))
+}
+
+fn parse_tag_rules(contents: &str) -> Result<TagRules> {
+    let file: RulesFile = serde_norway::from_str(contents).map_err(|err| err.to_string())?;
+    let mut rules = Vec::with_capacity(file.rules.len());
+    for (index, entry) in file.rules.into_iter().enumerate() {
+        let context = |err: String| format!("rule {}: {}", index + 1, err);
+        let key = tag_key(&entry.key).map_err(context)?;
+        let regex = Regex::new(&entry.pattern).map_err(|err| context(err.to_string()))?;
+        if entry.replace.is_none() && entry.set.is_empty() {
+            return Err(context("needs `replace` or `set`".to_string()));
+        }
+        let set = entry
+            .set
+            .into_iter()
+            .map(|(key, template)| Ok((tag_key(&key)?, template)))
+            .collect::<Result<Vec<_>>>()
+            .map_err(context)?;
+        rules.push(TagRule {
+            key,
+            regex,
+            replace: entry.replace,
+            set,
+        });
+    }
+    Ok(TagRules(Arc::new(rules)))
+}
+
+fn tag_key(key: &str) -> Result<String> {
+    let key = key.trim().to_ascii_uppercase();
+    if is_valid_tag_key(&key) {
+        Ok(key)
+    } else {
+        Err(format!("invalid tag name {:?}", key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_tag_rules;
+
+    fn tags(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn tag_rules_rewrite_and_split_values() {
+        let rules = parse_tag_rules(
+            r#"
+rules:
+  - key: title
+    match: '\s*\(Remastered \d{4}\)'
+    replace: ''
+  - key: ARTIST
+    match: '^(.*?)\s+feat\.\s+(.*)$'
+    replace: '$1'
+    set:
+      FEATURING: '$2'
+"#,
+        )
+        .unwrap();
+
+        let result = rules.apply(tags(&[
+            ("TITLE", "Song (Remastered 2011)"),
+            ("ARTIST", "Artist feat. Guest"),
+            ("ALBUM", "Album (Remastered 2011)"),
+        ]));
+        assert_eq!(
+            result,
+            tags(&[
+                ("TITLE", "Song"),
+                ("ARTIST", "Artist"),
+                ("ALBUM", "Album (Remastered 2011)"),
+                ("FEATURING", "Guest"),
+            ])
+        );
+
+        let untouched = tags(&[("ARTIST", "Solo")]);
+        assert_eq!(rules.apply(untouched.clone()), untouched);
+
+        assert!(
+            parse_tag_rules("rules:\n  - key: TITLE\n    match: '('\n    replace: ''\n").is_err()
+        );
+        assert!(parse_tag_rules("rules:\n  - key: TITLE\n    match: 'x'\n").is_err());
+    }
+}
@@ -0,0 +1,872 @@
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use owo_colors::OwoColorize;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::ffi::OsString;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::Result;
+use crate::app::plan_split_jobs;
+use crate::cli::{Args, Cli, Command, ServeArgs};
+use crate::decoder::is_stdin_path;
+use crate::events::EventSink;
+use crate::profile::apply_profile;
+use crate::remote::is_url;
+use crate::split::{Plan, execute_plans};
+
+const MAX_BODY_BYTES: usize = 64 * 1024;
+const MAX_HEADER_BYTES: usize = 16 * 1024;
+const MAX_CONNECTIONS: usize = 32;
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Split options a job may use: how the tracks are encoded, cut and tagged.
+// The ones that name files are only accepted with paths inside the job's dir.
+const JOB_OPTIONS: &[&str] = &[
+    "dir",
+    "flac",
+    "cue",
+    "picture",
+    "tags_from",
+    "genre_map",
+    "tag_rules",
+    "sample_offsets",
+    "track_lengths",
+    "cue_string",
+    "split_at",
+    "cue_encoding",
+    "yes",
+    "profile",
+    "preset",
+    "compression_level",
+    "output_format",
+    "verify_encoding",
+    "seektable",
+    "padding",
+    "keep_block_layout",
+    "jobs",
+    "nice",
+    "ionice",
+    "background",
+    "block_size",
+    "no_picture",
+    "cover",
+    "art_strategy",
+    "write_cue",
+    "split_indexes",
+    "check_lossy",
+    "check_tags",
+    "strict",
+    "normalize_case",
+    "no_disc_number",
+    "tags_from_path",
+    "keep_replaygain",
+    "prefer_cue_style",
+    "tracks",
+    "skip_tracks",
+    "skip_numbering",
+    "allow_truncated",
+    "clamp_to_audio",
+    "length_tolerance",
+    "strict_cdda",
+    "offset",
+    "trim_silence",
+    "silence_threshold",
+    "downmix",
+    "gain",
+    "fade_in",
+    "fade_out",
+    "audio_md5",
+    "lang",
+    "time_format",
+];
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct JobRequest {
+    dir: PathBuf,
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    plan_only: bool,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum JobState {
+    Queued,
+    Planning,
+    Planned,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct Job {
+    id: u64,
+    dir: String,
+    state: JobState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    albums: Vec<AlbumStatus>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct AlbumStatus {
+    source: String,
+    cue: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    samples: u64,
+    total_samples: u64,
+    warnings: Vec<String>,
+    tracks: Vec<TrackStatus>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+struct TrackStatus {
+    number: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    samples: u64,
+    output: String,
+    started: bool,
+}
+
+impl Job {
+    fn new(id: u64, dir: String) -> Self {
+        Self {
+            id,
+            dir,
+            state: JobState::Queued,
+            error: None,
+            albums: Vec::new(),
+        }
+    }
+
+    fn album_mut(&mut self, event: &Value) -> Option<&mut AlbumStatus> {
+        let source = event["source"].as_str()?;
+        self.albums.iter_mut().find(|album| album.source == source)
+    }
+
+    fn apply_event(&mut self, event: &Value) {
+        let samples = |key: &str| event[key].as_u64().unwrap_or(0);
+        match event["event"].as_str() {
+            Some("album_start") => {
+                if let Some(album) = self.album_mut(event) {
+                    album.total_samples = samples("total_samples");
+                }
+            }
+            Some("progress") => {
+                if let Some(album) = self.album_mut(event) {
+                    album.samples = samples("samples");
+                }
+            }
+            Some("track_start") => {
+                let number = event["track"].as_u64();
+                if let Some(album) = self.album_mut(event)
+                    && let Some(track) = album
+                        .tracks
+                        .iter_mut()
+                        .find(|track| Some(u64::from(track.number)) == number)
+                {
+                    track.started = true;
+                }
+            }
+            Some("album_done") => {
+                let result = event["result"].as_str().map(str::to_string);
+                let error = event["error"].as_str().map(str::to_string);
+                if let Some(album) = self.album_mut(event) {
+                    if result.as_deref() != Some("failed") {
+                        album.samples = album.total_samples;
+                    }
+                    album.result = result;
+                    album.error = error;
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn album_status(plan: &Plan) -> AlbumStatus {
+    let tracks = plan
+        .tracks()
+        .iter()
+        .filter(|track| plan.track_selected(track.number) && !plan.track_complete(track.number))
+        .map(|track| TrackStatus {
+            number: track.number,
+            title: track.title.clone(),
+            samples: track.end - track.start,
+            output: track.output_path.to_string_lossy().into_owned(),
+            started: false,
+        })
+        .collect();
+    AlbumStatus {
+        source: plan.flac_display().to_string_lossy().into_owned(),
+        cue: plan.cue_display().to_string_lossy().into_owned(),
+        result: None,
+        error: None,
+        samples: 0,
+        total_samples: plan.input_meta().total_samples,
+        warnings: plan.warnings().to_vec(),
+        tracks,
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+struct QueuedJob {
+    job: Arc<Mutex<Job>>,
+    args: Args,
+    plan_only: bool,
+}
+
+struct Server {
+    jobs: Mutex<Vec<Arc<Mutex<Job>>>>,
+    queue: Mutex<Sender<QueuedJob>>,
+}
+
+// Receives the JSON lines of the split's event stream and keeps the job's
+// progress up to date, so the API reports the same events a frontend on
+// --progress-fd would see.
+struct JobEvents {
+    job: Arc<Mutex<Job>>,
+    pending: Vec<u8>,
+}
+
+impl Write for JobEvents {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while let Some(end) = self.pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=end).collect();
+            if let Ok(event) = serde_json::from_slice::<Value>(&line) {
+                self.job.lock().unwrap().apply_event(&event);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn run_serve(args: ServeArgs) -> Result<()> {
+    let listener = TcpListener::bind(args.listen)
+        .map_err(|err| format!("failed to listen on {}: {}", args.listen, err))?;
+    let address = listener
+        .local_addr()
+        .map_err(|err| format!("failed to read the listening address: {}", err))?;
+    if !address.ip().is_loopback() {
+        eprintln!(
+            "{} the API has no authentication and is reachable from other hosts on {}",
+            "WARNING:".yellow().bold(),
+            address
+        );
+    }
+    println!("{} http://{}", "Listening on".green().bold(), address);
+
+    let (queue, jobs) = channel();
+    let server = Arc::new(Server {
+        jobs: Mutex::new(Vec::new()),
+        queue: Mutex::new(queue),
+    });
+    thread::spawn(move || run_queue(jobs));
+
+    // Each connection gets a thread; past the limit new ones are turned away
+    // instead of piling up threads.
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let Ok(mut stream) = stream else {
+            continue;
+        };
+        if active.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            active.fetch_sub(1, Ordering::SeqCst);
+            let body = json!({ "error": "too many connections" });
+            let _ = write_response(&mut stream, 503, &body);
+            continue;
+        }
+        let server = Arc::clone(&server);
+        let active = Arc::clone(&active);
+        thread::spawn(move || {
+            handle_connection(stream, &server, address);
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+fn run_queue(jobs: Receiver<QueuedJob>) {
+    // Jobs run one after another; --jobs in a request's args still splits
+    // that job's albums in parallel.
+    for queued in jobs {
+        queued.job.lock().unwrap().state = JobState::Planning;
+        let result = run_job(&queued);
+        let mut job = queued.job.lock().unwrap();
+        match result {
+            Ok(()) if queued.plan_only => job.state = JobState::Planned,
+            Ok(()) => job.state = JobState::Done,
+            Err(err) => {
+                eprintln!("{} job {}: {}", "ERROR:".red().bold(), job.id, err);
+                job.state = JobState::Failed;
+                job.error = Some(err);
+            }
+        }
+    }
+}
+
+fn run_job(queued: &QueuedJob) -> Result<()> {
+    let plans = plan_split_jobs(&queued.args)?;
+    {
+        let mut job = queued.job.lock().unwrap();
+        job.albums = plans.iter().map(album_status).collect();
+        if queued.plan_only {
+            return Ok(());
+        }
+        job.state = JobState::Running;
+    }
+    let events = EventSink::new(Box::new(JobEvents {
+        job: Arc::clone(&queued.job),
+        pending: Vec::new(),
+    }));
+    execute_plans(plans, queued.args.jobs, Some(&events))
+}
+
+fn handle_connection(stream: TcpStream, server: &Server, address: SocketAddr) {
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let (status, body) = match read_request(&mut BufReader::new(stream)) {
+        Ok(request) => match check_request(&request, address) {
+            Ok(()) => route(server, &request.method, &request.path, &request.body),
+            Err((status, err)) => (status, json!({ "error": err })),
+        },
+        Err(err) => (400, json!({ "error": err })),
+    };
+    let _ = write_response(&mut writer, status, &body);
+}
+
+// Web pages can reach a local server too: browsers add an Origin header to
+// their requests, DNS rebinding shows up as a foreign Host, and a JSON
+// content type cannot be sent by a plain HTML form.
+fn check_request(request: &Request, address: SocketAddr) -> std::result::Result<(), (u16, String)> {
+    if request.header("origin").is_some() {
+        return Err((403, "requests from web pages are not accepted".to_string()));
+    }
+    let host = request.header("host").unwrap_or_default();
+    if !host_matches(host, address) {
+        return Err((403, format!("Host {:?} is not the listen address", host)));
+    }
+    let content_type = request
+        .header("content-type")
+        .and_then(|value| value.split(';').next())
+        .map(str::trim);
+    if request.method == "POST"
+        && !content_type.is_some_and(|value| value.eq_ignore_ascii_case("application/json"))
+    {
+        return Err((415, "the request body must be application/json".to_string()));
+    }
+    Ok(())
+}
+
+// An unspecified listen address (0.0.0.0) accepts any IP address or
+// `localhost`, but never another name.
+fn host_matches(host: &str, address: SocketAddr) -> bool {
+    let Some((name, port)) = host.rsplit_once(':') else {
+        return false;
+    };
+    if port.parse::<u16>().ok() != Some(address.port()) {
+        return false;
+    }
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    let ip = address.ip();
+    match name.parse::<IpAddr>() {
+        Ok(name) => name == ip || ip.is_unspecified(),
+        Err(_) => {
+            name.eq_ignore_ascii_case("localhost") && (ip.is_loopback() || ip.is_unspecified())
+        }
+    }
+}
+
+fn read_request(reader: &mut impl BufRead) -> Result<Request> {
+    let mut budget = MAX_HEADER_BYTES;
+    let line = read_header_line(reader, &mut budget)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err("malformed request line".to_string());
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+
+    let mut headers = Vec::new();
+    let mut length = 0;
+    loop {
+        let header = read_header_line(reader, &mut budget)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            continue;
+        };
+        let (name, value) = (name.trim(), value.trim());
+        if name.eq_ignore_ascii_case("content-length") {
+            length = value
+                .parse::<usize>()
+                .map_err(|_| "invalid Content-Length".to_string())?;
+        }
+        headers.push((name.to_string(), value.to_string()));
+    }
+    if length > MAX_BODY_BYTES {
+        return Err(format!(
+            "request body is larger than {} bytes",
+            MAX_BODY_BYTES
+        ));
+    }
+    let mut body = vec![0; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|err| format!("failed to read the request body: {}", err))?;
+    Ok(Request {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+// The request line and headers share one budget, so a client cannot make the
+// server buffer an endless line.
+fn read_header_line(reader: &mut impl BufRead, budget: &mut usize) -> Result<String> {
+    let mut line = String::new();
+    let read = reader
+        .by_ref()
+        .take(*budget as u64)
+        .read_line(&mut line)
+        .map_err(|err| format!("failed to read the request: {}", err))?;
+    *budget -= read;
+    if *budget == 0 && !line.ends_with('\n') {
+        return Err(format!(
+            "request headers are larger than {} bytes",
+            MAX_HEADER_BYTES
+        ));
+    }
+    Ok(line)
+}
+
+fn route(server: &Server, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+    let segments: Vec<&str> = path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect();
+    let result = match (method, segments.as_slice()) {
+        ("POST", ["jobs"]) => submit_job(server, body),
+        ("GET", ["jobs"]) => {
+            let jobs: Vec<Job> = server
+                .jobs
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|job| job.lock().unwrap().clone())
+                .collect();
+            Ok((200, json!({ "jobs": jobs })))
+        }
+        ("GET", ["jobs", id]) => find_job(server, id).map(|job| (200, json!(job))),
+        ("GET", ["jobs", id, "results"]) => find_job(server, id).map(|job| (200, results(&job))),
+        (_, ["jobs"]) | (_, ["jobs", _]) | (_, ["jobs", _, "results"]) => {
+            Err((405, format!("method {} is not allowed here", method)))
+        }
+        _ => Err((404, format!("no such endpoint: {}", path))),
+    };
+    match result {
+        Ok(response) => response,
+        Err((status, err)) => (status, json!({ "error": err })),
+    }
+}
+
+fn submit_job(server: &Server, body: &[u8]) -> std::result::Result<(u16, Value), (u16, String)> {
+    let request: JobRequest = serde_json::from_slice(body)
+        .map_err(|err| (400, format!("invalid job request: {}", err)))?;
+    let args = job_args(&request).map_err(|err| (400, err))?;
+
+    let mut jobs = server.jobs.lock().unwrap();
+    let id = jobs.len() as u64 + 1;
+    let job = Arc::new(Mutex::new(Job::new(id, request.dir.display().to_string())));
+    jobs.push(Arc::clone(&job));
+    let queued = QueuedJob {
+        job: Arc::clone(&job),
+        args,
+        plan_only: request.plan_only,
+    };
+    server
+        .queue
+        .lock()
+        .unwrap()
+        .send(queued)
+        .map_err(|_| (500, "the job queue has stopped".to_string()))?;
+    let job = job.lock().unwrap().clone();
+    Ok((202, json!(job)))
+}
+
+fn job_args(request: &JobRequest) -> Result<Args> {
+    if !request.dir.is_absolute() {
+        return Err(format!(
+            "dir must be an absolute path: {}",
+            request.dir.display()
+        ));
+    }
+    let mut argv = vec!["flac-cue-split".to_string(), "split".to_string()];
+    argv.extend(request.args.iter().cloned());
+    argv.push("--".to_string());
+    argv.push(request.dir.to_string_lossy().into_owned());
+    let parse = |argv: &[OsString]| {
+        Cli::command().try_get_matches_from(argv).map_err(|err| {
+            let message = err.to_string();
+            message.lines().next().unwrap_or_default().to_string()
+        })
+    };
+    let argv: Vec<OsString> = argv.into_iter().map(OsString::from).collect();
+    let mut matches = parse(&argv)?;
+    if let Some(profile) = apply_profile(&argv, &matches)? {
+        matches = parse(&profile.argv)?;
+    }
+    check_job_options(&matches)?;
+    let cli = Cli::from_arg_matches(&matches).map_err(|err| err.to_string())?;
+    let Some(Command::Split(mut args)) = cli.command else {
+        return Err("args must be split options".to_string());
+    };
+    let paths = [
+        (&args.flac, "--flac"),
+        (&args.cue, "--cue"),
+        (&args.picture, "--picture"),
+        (&args.tags_from, "--tags-from"),
+        (&args.genre_map, "--genre-map"),
+        (&args.tag_rules, "--tag-rules"),
+        (&args.sample_offsets, "--sample-offsets"),
+        (&args.track_lengths, "--track-lengths"),
+    ];
+    for (path, option) in paths {
+        if let Some(path) = path {
+            check_job_path(path, option)?;
+        }
+    }
+    args.yes = true;
+    Ok(args)
+}
+
+// A job only reads the album in its dir and writes tracks next to it, so the
+// options that read other files, fetch URLs, overwrite, move or delete files,
+// change ownership, write elsewhere or need a terminal stay with the CLI. This
+// also applies to the options a --profile fills in.
+fn check_job_options(matches: &ArgMatches) -> Result<()> {
+    let Some(split) = matches.subcommand_matches("split") else {
+        return Err("args must be split options".to_string());
+    };
+    let command = Cli::command();
+    let Some(split_command) = command.find_subcommand("split") else {
+        return Err("args must be split options".to_string());
+    };
+    for arg in split_command.get_arguments() {
+        let id = arg.get_id().as_str();
+        if split.value_source(id) != Some(ValueSource::CommandLine) || JOB_OPTIONS.contains(&id) {
+            continue;
+        }
+        let option = arg
+            .get_long()
+            .map(|long| format!("--{}", long))
+            .unwrap_or_else(|| id.to_string());
+        return Err(format!("{} cannot be used in a job", option));
+    }
+    Ok(())
+}
+
+fn check_job_path(path: &Path, option: &str) -> Result<()> {
+    let inside = !is_url(path)
+        && !is_stdin_path(path)
+        && path
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return Err(format!(
+            "{} must name a file inside dir: {}",
+            option,
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+fn find_job(server: &Server, id: &str) -> std::result::Result<Job, (u16, String)> {
+    let not_found = || (404, format!("no such job: {}", id));
+    let index = id.parse::<usize>().map_err(|_| not_found())?;
+    let jobs = server.jobs.lock().unwrap();
+    let job = index
+        .checked_sub(1)
+        .and_then(|index| jobs.get(index))
+        .ok_or_else(not_found)?;
+    Ok(job.lock().unwrap().clone())
+}
+
+fn results(job: &Job) -> Value {
+    let files: Vec<Value> = job
+        .albums
+        .iter()
+        .flat_map(|album| &album.tracks)
+        .filter_map(|track| {
+            let bytes = std::fs::metadata(&track.output).ok()?.len();
+            Some(json!({ "track": track.number, "path": track.output, "bytes": bytes }))
+        })
+        .collect();
+    json!({ "id": job.id, "state": job.state, "files": files })
+}
+
+fn write_response(out: &mut impl Write, status: u16, body: &Value) -> std::io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        415 => "Unsupported Media Type",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    };
+    let mut text = serde_json::to_string_pretty(body).unwrap_or_default();
+    text.push('\n');
+    write!(
+        out,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        text.len(),
+        text
+    )?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AlbumStatus, Job, JobState, MAX_HEADER_BYTES, Server, TrackStatus, check_request,
+        read_request, route,
+    };
+    use serde_json::json;
+    use std::io::Cursor;
+    use std::net::SocketAddr;
+    use std::sync::Mutex;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn api_queues_jobs_and_reports_their_state() {
+        let request = b"POST /jobs HTTP/1.1\r\nHost: 127.0.0.1:8717\r\n\
+                        Content-Type: application/json\r\ncontent-length: 7\r\n\r\n{\"a\":1}";
+        let request = read_request(&mut Cursor::new(&request[..])).unwrap();
+        assert_eq!(
+            (request.method.as_str(), request.path.as_str()),
+            ("POST", "/jobs")
+        );
+        assert_eq!(request.body, b"{\"a\":1}");
+        let body = request.body;
+
+        let (queue, queued) = channel();
+        let server = Server {
+            jobs: Mutex::new(Vec::new()),
+            queue: Mutex::new(queue),
+        };
+        assert_eq!(route(&server, "POST", "/jobs", &body).0, 400);
+        let relative = br#"{"dir": "Album"}"#;
+        assert_eq!(route(&server, "POST", "/jobs", relative).0, 400);
+        let select = br#"{"dir": "/music/Album", "args": ["--select"]}"#;
+        assert_eq!(route(&server, "POST", "/jobs", select).0, 400);
+        let delete = br#"{"dir": "/music/Album", "args": ["--delete-original"]}"#;
+        assert_eq!(route(&server, "POST", "/jobs", delete).0, 400);
+        let owner = br#"{"dir": "/music/Album", "args": ["--owner", "0:0"]}"#;
+        assert_eq!(route(&server, "POST", "/jobs", owner).0, 400);
+        for args in [
+            r#"["--overwrite"]"#,
+            r#"["-o"]"#,
+            r#"["--flac", "/etc/passwd"]"#,
+            r#"["--cue", "http://169.254.169.254/latest.cue"]"#,
+            r#"["--tags-from=../../secrets.csv"]"#,
+            r#"["--picture", "/home/user/.ssh/id_rsa"]"#,
+            r#"["--flac", "-"]"#,
+            r#"["--print-config"]"#,
+        ] {
+            let request = format!(r#"{{"dir": "/music/Album", "args": {}}}"#, args);
+            assert_eq!(
+                route(&server, "POST", "/jobs", request.as_bytes()).0,
+                400,
+                "{}",
+                args
+            );
+        }
+
+        let submit = br#"{"dir": "/music/Album", "args": ["-c", "8", "--tags-from", "fixes/tags.csv"], "plan_only": true}"#;
+        let (status, job) = route(&server, "POST", "/jobs", submit);
+        assert_eq!(status, 202);
+        assert_eq!(job["id"], 1);
+        assert_eq!(job["state"], "queued");
+        let queued = queued.try_recv().unwrap();
+        assert!(queued.plan_only && queued.args.yes);
+        assert_eq!(queued.args.compression_level, Some(8));
+        assert!(queued.args.tags_from.is_some());
+
+        assert_eq!(
+            route(&server, "GET", "/jobs/1", b"").1["dir"],
+            "/music/Album"
+        );
+        assert_eq!(route(&server, "GET", "/jobs", b"").1["jobs"][0]["id"], 1);
+        assert_eq!(route(&server, "GET", "/jobs/2", b"").0, 404);
+        assert_eq!(route(&server, "DELETE", "/jobs/1", b"").0, 405);
+        assert_eq!(route(&server, "GET", "/albums", b"").0, 404);
+    }
+
+    #[test]
+    fn oversized_request_headers_are_refused() {
+        let mut request = b"GET /jobs HTTP/1.1\r\nX-Padding: ".to_vec();
+        request.extend(vec![b'a'; MAX_HEADER_BYTES]);
+        request.extend(b"\r\n\r\n");
+        let err = read_request(&mut Cursor::new(&request[..])).err().unwrap();
+        assert!(err.contains("request headers are larger than"));
+
+        let endless = vec![b'G'; MAX_HEADER_BYTES * 4];
+        assert!(read_request(&mut Cursor::new(&endless[..])).is_err());
+    }
+
+    #[test]
+    fn requests_from_web_pages_are_refused() {
+        let status = |address: &str, request: &str| {
+            let request = read_request(&mut Cursor::new(request.as_bytes())).unwrap();
+            let address: SocketAddr = address.parse().unwrap();
+            check_request(&request, address)
+                .err()
+                .map(|(status, _)| status)
+        };
+        let post = |headers: &str| format!("POST /jobs HTTP/1.1\r\n{}\r\n", headers);
+        let json = "Content-Type: application/json; charset=utf-8\r\n";
+
+        let local = "127.0.0.1:8717";
+        assert_eq!(
+            status(local, &post(&format!("Host: 127.0.0.1:8717\r\n{}", json))),
+            None
+        );
+        assert_eq!(
+            status(local, &post(&format!("Host: localhost:8717\r\n{}", json))),
+            None
+        );
+        assert_eq!(
+            status(local, "GET /jobs HTTP/1.1\r\nHost: localhost:8717\r\n\r\n"),
+            None
+        );
+        assert_eq!(
+            status(
+                local,
+                &post("Host: localhost:8717\r\nContent-Type: text/plain\r\n")
+            ),
+            Some(415)
+        );
+        assert_eq!(status(local, &post(json)), Some(403));
+        assert_eq!(
+            status(
+                local,
+                &post(&format!("Host: evil.example:8717\r\n{}", json))
+            ),
+            Some(403)
+        );
+        assert_eq!(
+            status(local, &post(&format!("Host: localhost:80\r\n{}", json))),
+            Some(403)
+        );
+        assert_eq!(
+            status(
+                local,
+                &post(&format!(
+                    "Host: localhost:8717\r\nOrigin: https://evil.example\r\n{}",
+                    json
+                ))
+            ),
+            Some(403)
+        );
+
+        let any = "0.0.0.0:8717";
+        assert_eq!(
+            status(any, &post(&format!("Host: 192.168.1.5:8717\r\n{}", json))),
+            None
+        );
+        assert_eq!(
+            status(
+                any,
+                &post(&format!("Host: nas.evil.example:8717\r\n{}", json))
+            ),
+            Some(403)
+        );
+        assert_eq!(
+            status(
+                "[::1]:8717",
+                &post(&format!("Host: [::1]:8717\r\n{}", json))
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn split_events_update_album_progress() {
+        let mut job = Job::new(1, "/music/Album".to_string());
+        job.state = JobState::Running;
+        job.albums.push(AlbumStatus {
+            source: "Album.flac".to_string(),
+            cue: "Album.cue".to_string(),
+            result: None,
+            error: None,
+            samples: 0,
+            total_samples: 0,
+            warnings: Vec::new(),
+            tracks: vec![TrackStatus {
+                number: 1,
+                title: Some("One".to_string()),
+                samples: 100,
+                output: "/music/Album/01 - One.flac".to_string(),
+                started: false,
+            }],
+        });
+        job.apply_event(&json!({"event": "album_start", "source": "Album.flac", "tracks": 1, "total_samples": 100}));
+        job.apply_event(
+            &json!({"event": "track_start", "source": "Album.flac", "track": 1, "output": "x"}),
+        );
+        job.apply_event(&json!({"event": "progress", "source": "Album.flac", "samples": 40, "total_samples": 100}));
+        assert_eq!(job.albums[0].samples, 40);
+        assert!(job.albums[0].tracks[0].started);
+        job.apply_event(&json!({"event": "album_done", "source": "Album.flac", "result": "done"}));
+        assert_eq!(job.albums[0].samples, 100);
+        assert_eq!(job.albums[0].result.as_deref(), Some("done"));
+    }
+}
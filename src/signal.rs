@@ -0,0 +1,106 @@
+const DC_OFFSET_LIMIT: f64 = 0.001;
+
+// Counts samples in runs of at least two equal full-scale values (flat-topped
+// waveforms) and sums each channel to measure its DC offset.
+pub(crate) struct SignalStats {
+    channels: usize,
+    min: i32,
+    max: i32,
+    frames: u64,
+    clipped: u64,
+    runs: Vec<u32>,
+    sums: Vec<i128>,
+}
+
+impl SignalStats {
+    pub(crate) fn new(bits_per_sample: u32, channels: usize) -> Self {
+        let max = (1i64 << (bits_per_sample.clamp(1, 32) - 1)) - 1;
+        Self {
+            channels: channels.max(1),
+            min: (-max - 1) as i32,
+            max: max as i32,
+            frames: 0,
+            clipped: 0,
+            runs: vec![0; channels.max(1)],
+            sums: vec![0; channels.max(1)],
+        }
+    }
+
+    pub(crate) fn update(&mut self, interleaved: &[i32]) {
+        for frame in interleaved.chunks_exact(self.channels) {
+            for (channel, &sample) in frame.iter().enumerate() {
+                self.sums[channel] += i128::from(sample);
+                let run = &mut self.runs[channel];
+                if sample == self.max || sample == self.min {
+                    *run += 1;
+                    self.clipped += match *run {
+                        1 => 0,
+                        2 => 2,
+                        _ => 1,
+                    };
+                } else {
+                    *run = 0;
+                }
+            }
+            self.frames += 1;
+        }
+    }
+
+    pub(crate) fn clipped(&self) -> u64 {
+        self.clipped
+    }
+
+    pub(crate) fn dc_offsets(&self) -> Vec<f64> {
+        if self.frames == 0 {
+            return vec![0.0; self.channels];
+        }
+        self.sums
+            .iter()
+            .map(|sum| *sum as f64 / self.frames as f64 / f64::from(self.max))
+            .collect()
+    }
+
+    pub(crate) fn anomalies(&self) -> Vec<String> {
+        let mut anomalies = Vec::new();
+        if self.clipped > 0 {
+            anomalies.push(format!("{} clipped samples", self.clipped));
+        }
+        for (channel, offset) in self.dc_offsets().into_iter().enumerate() {
+            if offset.abs() >= DC_OFFSET_LIMIT {
+                anomalies.push(format!(
+                    "DC offset {:+.2}% on channel {}",
+                    offset * 100.0,
+                    channel + 1
+                ));
+            }
+        }
+        anomalies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SignalStats;
+
+    #[test]
+    fn signal_stats_count_flat_tops_and_dc_offset() {
+        let mut stats = SignalStats::new(16, 2);
+        stats.update(&[32767, 100, 32767, 100, 32767, 100, 0, 100]);
+        stats.update(&[-32768, 100, 5, 100]);
+        assert_eq!(stats.clipped(), 3);
+        let offsets = stats.dc_offsets();
+        assert!((offsets[1] - 100.0 / 32767.0).abs() < 1e-9);
+        assert_eq!(
+            stats.anomalies(),
+            vec![
+                "3 clipped samples".to_string(),
+                "DC offset +33.34% on channel 1".to_string(),
+                "DC offset +0.31% on channel 2".to_string(),
+            ]
+        );
+
+        let mut quiet = SignalStats::new(16, 1);
+        quiet.update(&[32767, 0, -32768, 3, -3]);
+        assert!(quiet.anomalies().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use clap::ValueEnum;
+
+use crate::Result;
+
+pub(crate) const DEFAULT_SILENCE_THRESHOLD_DB: f64 = -60.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub(crate) enum TrimMode {
+    Leading,
+    Trailing,
+    Both,
+}
+
+impl TrimMode {
+    pub(crate) fn label(self) -> &'static str {
+        match self {
+            TrimMode::Leading => "leading",
+            TrimMode::Trailing => "trailing",
+            TrimMode::Both => "both",
+        }
+    }
+}
+
+pub(crate) struct SilenceTrimmer {
+    trim_leading: bool,
+    trim_trailing: bool,
+    threshold: i64,
+    started: bool,
+    pending: Vec<i32>,
+}
+
+impl SilenceTrimmer {
+    pub(crate) fn new(mode: Option<TrimMode>, threshold_db: f64, bits_per_sample: u32) -> Self {
+        let full_scale = (1i64 << bits_per_sample.clamp(1, 32).saturating_sub(1)) as f64;
+        Self {
+            trim_leading: matches!(mode, Some(TrimMode::Leading | TrimMode::Both)),
+            trim_trailing: matches!(mode, Some(TrimMode::Trailing | TrimMode::Both)),
+            threshold: (full_scale * 10f64.powf(threshold_db / 20.0)) as i64,
+            started: false,
+            pending: Vec::new(),
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.started = false;
+        self.pending.clear();
+    }
+
+    pub(crate) fn process(
+        &mut self,
+        interleaved: &[i32],
+        channels: usize,
+        mut write: impl FnMut(&[i32]) -> Result<()>,
+    ) -> Result<()> {
+        let mut data = interleaved;
+        if self.trim_leading && !self.started {
+            match self.first_loud_frame(data, channels) {
+                Some(frame) => {
+                    self.started = true;
+                    data = &data[frame * channels..];
+                }
+                None => return Ok(()),
+            }
+        }
+
+        if !self.trim_trailing {
+            return write_nonempty(data, &mut write);
+        }
+
+        match self.last_loud_frame(data, channels) {
+            Some(frame) => {
+                let split = (frame + 1) * channels;
+                write_nonempty(&self.pending, &mut write)?;
+                self.pending.clear();
+                write_nonempty(&data[..split], &mut write)?;
+                self.pending.extend_from_slice(&data[split..]);
+            }
+            None => self.pending.extend_from_slice(data),
+        }
+        Ok(())
+    }
+
+    fn is_loud(&self, frame: &[i32]) -> bool {
+        frame
+            .iter()
+            .any(|sample| (*sample as i64).abs() > self.threshold)
+    }
+
+    fn first_loud_frame(&self, data: &[i32], channels: usize) -> Option<usize> {
+        data.chunks_exact(channels)
+            .position(|frame| self.is_loud(frame))
+    }
+
+    fn last_loud_frame(&self, data: &[i32], channels: usize) -> Option<usize> {
+        data.chunks_exact(channels)
+            .rposition(|frame| self.is_loud(frame))
+    }
+}
+
+fn write_nonempty(data: &[i32], write: &mut impl FnMut(&[i32]) -> Result<()>) -> Result<()> {
+    if data.is_empty() { Ok(()) } else { write(data) }
+}
+
+pub(crate) fn parse_silence_threshold(value: &str) -> Result<f64> {
+    let trimmed = value.trim();
+    let number = trimmed
+        .strip_suffix("dB")
+        .or_else(|| trimmed.strip_suffix("db"))
+        .unwrap_or(trimmed)
+        .trim();
+    let threshold: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid silence threshold {:?} (example: -60dB)", value))?;
+    if !threshold.is_finite() || threshold >= 0.0 {
+        return Err("silence threshold must be below 0 dB".to_string());
+    }
+    Ok(threshold)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SilenceTrimmer, TrimMode, parse_silence_threshold};
+
+    fn run(trimmer: &mut SilenceTrimmer, blocks: &[&[i32]]) -> Vec<i32> {
+        let mut out = Vec::new();
+        for block in blocks {
+            trimmer
+                .process(block, 1, |data| {
+                    out.extend_from_slice(data);
+                    Ok(())
+                })
+                .unwrap();
+        }
+        out
+    }
+
+    #[test]
+    fn trims_leading_and_trailing_silence_across_blocks() {
+        let mut trimmer = SilenceTrimmer::new(Some(TrimMode::Both), -60.0, 16);
+        let out = run(
+            &mut trimmer,
+            &[&[0, 1], &[0, 500, 0], &[0, 700, 2], &[0, 0]],
+        );
+        assert_eq!(out, vec![500, 0, 0, 700]);
+
+        trimmer.reset();
+        let out = run(&mut trimmer, &[&[0, 900, 0]]);
+        assert_eq!(out, vec![900]);
+    }
+
+    #[test]
+    fn leading_mode_keeps_trailing_silence() {
+        let mut trimmer = SilenceTrimmer::new(Some(TrimMode::Leading), -60.0, 16);
+        let out = run(&mut trimmer, &[&[0, 0], &[800, 0, 0]]);
+        assert_eq!(out, vec![800, 0, 0]);
+    }
+
+    #[test]
+    fn parse_silence_threshold_accepts_db_suffix() {
+        assert_eq!(parse_silence_threshold("-50dB").unwrap(), -50.0);
+        assert_eq!(parse_silence_threshold("-72.5").unwrap(), -72.5);
+        assert!(parse_silence_threshold("3dB").is_err());
+        assert!(parse_silence_threshold("loud").is_err());
+    }
+}
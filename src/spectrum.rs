@@ -0,0 +1,200 @@
+use std::f64::consts::PI;
+use std::path::Path;
+
+use crate::Result;
+use crate::decoder::{DEFAULT_BLOCK_SIZE, create_decoder};
+use crate::types::TrackSpan;
+
+const FFT_SIZE: usize = 4096;
+const BAND_HZ: f64 = 500.0;
+const SEARCH_FROM_HZ: f64 = 14000.0;
+const LOSSY_CUTOFF_MAX_HZ: f64 = 20500.0;
+const CUTOFF_DROP_DB: f64 = 30.0;
+const MIN_WINDOWS: u32 = 8;
+
+pub(crate) struct SpectrumAnalyzer {
+    sample_rate: u32,
+    window: Vec<f64>,
+    buffer: Vec<f64>,
+    power: Vec<f64>,
+    windows: u32,
+}
+
+impl SpectrumAnalyzer {
+    pub(crate) fn new(sample_rate: u32) -> Self {
+        let window = (0..FFT_SIZE)
+            .map(|index| 0.5 - 0.5 * (2.0 * PI * index as f64 / FFT_SIZE as f64).cos())
+            .collect();
+        Self {
+            sample_rate,
+            window,
+            buffer: Vec::with_capacity(FFT_SIZE),
+            power: vec![0.0; FFT_SIZE / 2],
+            windows: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, sample: f64) {
+        self.buffer.push(sample);
+        if self.buffer.len() < FFT_SIZE {
+            return;
+        }
+        let mut re: Vec<f64> = self
+            .buffer
+            .iter()
+            .zip(&self.window)
+            .map(|(sample, weight)| sample * weight)
+            .collect();
+        let mut im = vec![0.0; FFT_SIZE];
+        fft(&mut re, &mut im);
+        for (bin, power) in self.power.iter_mut().enumerate() {
+            *power += re[bin] * re[bin] + im[bin] * im[bin];
+        }
+        self.windows += 1;
+        self.buffer.clear();
+    }
+
+    // A lossy encoder's lowpass shows up as a brick wall: the level falls by
+    // CUTOFF_DROP_DB within one kilohertz and never comes back up.
+    pub(crate) fn lossy_cutoff_hz(&self) -> Option<f64> {
+        if self.windows < MIN_WINDOWS {
+            return None;
+        }
+        let bin_hz = f64::from(self.sample_rate) / FFT_SIZE as f64;
+        let bins_per_band = (BAND_HZ / bin_hz).round().max(1.0) as usize;
+        let band_hz = bins_per_band as f64 * bin_hz;
+        let levels: Vec<f64> = self
+            .power
+            .chunks_exact(bins_per_band)
+            .map(|band| {
+                let mean = band.iter().sum::<f64>() / band.len() as f64 / f64::from(self.windows);
+                10.0 * (mean + 1e-20).log10()
+            })
+            .collect();
+        let first = (SEARCH_FROM_HZ / band_hz) as usize;
+        for band in first.max(1)..levels.len().saturating_sub(1) {
+            let cutoff = band as f64 * band_hz;
+            if cutoff > LOSSY_CUTOFF_MAX_HZ {
+                break;
+            }
+            let before = levels[band - 1];
+            let after = levels[band + 1..].iter().copied().fold(f64::MIN, f64::max);
+            if before - after >= CUTOFF_DROP_DB {
+                return Some(cutoff);
+            }
+        }
+        None
+    }
+}
+
+pub(crate) fn find_lossy_tracks(
+    path: &Path,
+    tracks: &[TrackSpan],
+    sample_rate: u32,
+    bits_per_sample: u32,
+) -> Result<Vec<(u32, f64)>> {
+    let scale = f64::from(1u32 << (bits_per_sample.clamp(1, 32) - 1).min(31));
+    if tracks.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut analyzers: Vec<SpectrumAnalyzer> = tracks
+        .iter()
+        .map(|_| SpectrumAnalyzer::new(sample_rate))
+        .collect();
+    let mut index = 0;
+    let decoder = create_decoder(path)?;
+    'blocks: for block in decoder.into_blocks(DEFAULT_BLOCK_SIZE)? {
+        let block = block?;
+        let channels = block.channels.max(1) as usize;
+        for (offset, frame) in block.interleaved.chunks_exact(channels).enumerate() {
+            let position = block.sample_index + offset as u64;
+            while position >= tracks[index].end {
+                index += 1;
+                if index == tracks.len() {
+                    break 'blocks;
+                }
+            }
+            if position < tracks[index].start {
+                continue;
+            }
+            let sum: f64 = frame.iter().map(|sample| f64::from(*sample)).sum();
+            analyzers[index].push(sum / channels as f64 / scale);
+        }
+    }
+    Ok(tracks
+        .iter()
+        .zip(&analyzers)
+        .filter_map(|(track, analyzer)| {
+            analyzer
+                .lossy_cutoff_hz()
+                .map(|cutoff| (track.number, cutoff))
+        })
+        .collect())
+}
+
+fn fft(re: &mut [f64], im: &mut [f64]) {
+    let n = re.len();
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f64;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f64).sin_cos();
+                let a = start + k;
+                let b = a + len / 2;
+                let tr = re[b] * cos - im[b] * sin;
+                let ti = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - tr;
+                im[b] = im[a] - ti;
+                re[a] += tr;
+                im[a] += ti;
+            }
+        }
+        len <<= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpectrumAnalyzer;
+    use std::f64::consts::TAU;
+
+    fn analyze(top_hz: u32) -> Option<f64> {
+        let sample_rate = 44100;
+        let mut analyzer = SpectrumAnalyzer::new(sample_rate);
+        let tones: Vec<f64> = (1..)
+            .map(|step| f64::from(step * 200))
+            .take_while(|hz| *hz <= f64::from(top_hz))
+            .collect();
+        for index in 0..sample_rate {
+            let t = f64::from(index) / f64::from(sample_rate);
+            let sample: f64 = tones
+                .iter()
+                .enumerate()
+                .map(|(tone, hz)| (TAU * hz * t + tone as f64).sin() * 0.005)
+                .sum();
+            analyzer.push(sample);
+        }
+        analyzer.lossy_cutoff_hz()
+    }
+
+    #[test]
+    fn brick_wall_lowpass_is_reported_as_lossy() {
+        let cutoff = analyze(15800).expect("cutoff below 16 kHz");
+        assert!((15000.0..16500.0).contains(&cutoff), "{}", cutoff);
+        assert_eq!(analyze(21800), None);
+    }
+}
@@ -1,50 +1,186 @@
-use encoding_rs::Encoding;
+use clap::ValueEnum;
+use encoding_rs::{Encoding, UTF_8};
 use indicatif::ProgressBar;
 use owo_colors::OwoColorize;
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::Result;
-use crate::cli::{InputPath, display_path};
-use crate::cue::parse_cue_file;
-use crate::decoder::{AudioBlock, create_decoder};
-use crate::flac::{TrackEncoder, start_track_encoder};
-use crate::picture::add_external_picture;
-use crate::types::{CueDisc, CueRem, InputMetadata, TrackSpan};
-use crate::ui::{finish_progress, make_progress_bar};
+use crate::case::{CaseStyle, normalize_cue_case};
+use crate::channels::{CHANNEL_MASK_TAG, describe_channel_layout};
+use crate::checksum::{AUDIO_MD5_TAG, PcmHasher, SOURCE_MD5_TAG, format_md5};
+use crate::cli::{
+    InputPath, PermissionArgs, SplitPoint, SplitPoints, TrackSelection, display_path,
+};
+use crate::cue::{
+    apply_sample_offsets, cue_file_name, disc_track_count, parse_cue_contents, parse_cue_file,
+    render_cue_sheet, track_disc,
+};
+use crate::decoder::{
+    AudioBlock, DEFAULT_BLOCK_SIZE, Decoder, DecoderMetadata, STDIN_DISPLAY, STDIN_NAME,
+    count_samples, create_decoder, is_stdin_path,
+};
+use crate::downmix::{Downmix, Downmixer};
+use crate::events::{Event, EventSink};
+use crate::fade::{Fade, FadeStage};
+use crate::flac::{
+    EncoderOptions, FlacReaderDecoder, TrackEncoder, TrackEncoderSettings, replace_comment_tag,
+    start_track_encoder,
+};
+use crate::gain::{GainSetting, GainStage, format_gain, gain_for_track};
+use crate::genre::{GenreMap, map_cue_genre};
+use crate::i18n::{Msg, tr, tr_with};
+use crate::jobs::run_parallel;
+use crate::lengths::{TrackLengths, apply_track_lengths};
+use crate::lossy::{AlsoEncode, LossyEncoder, LossyFormat};
+use crate::metadata::{
+    REQUIRED_TAGS, build_track_tags, find_tag, missing_required_tags, strip_replaygain_tags,
+    track_total,
+};
+use crate::permissions::{apply_file_permissions, create_output_dir};
+use crate::picture::{
+    ArtStrategy, CoverMode, SidecarArt, add_external_picture, cover_file_name,
+    find_external_picture, picture_block, thumbnail_cover_art,
+};
+use crate::rules::TagRules;
+use crate::signal::SignalStats;
+use crate::silence::{DEFAULT_SILENCE_THRESHOLD_DB, SilenceTrimmer, TrimMode};
+use crate::spectrum::find_lossy_tracks;
+use crate::tags::{TagOverride, overrides_for_track, stack_tags};
+use crate::template::render_rename_template;
+use crate::types::{CueDisc, CueRem, CueTrack, InputMetadata, TrackSpan};
+use crate::ui::{
+    AlbumReport, BatchProgress, finish_progress, format_length_difference, format_msf, format_size,
+    format_time, make_progress_bar,
+};
+
+const PROGRESS_EVENT_INTERVAL: Duration = Duration::from_millis(200);
+const OUTPUT_CUE_NAME: &str = "Album.cue";
 
 pub(crate) struct SplitOptions {
     pub(crate) flac_input: InputPath,
     pub(crate) cue_input: InputPath,
+    pub(crate) cue_text: Option<String>,
+    pub(crate) split_points: Option<SplitPoints>,
     pub(crate) display_base_abs: Option<PathBuf>,
     pub(crate) cue_encoding: Option<&'static Encoding>,
     pub(crate) overwrite: bool,
-    pub(crate) compression_level: u8,
+    pub(crate) encoder: EncoderOptions,
     pub(crate) search_dir: PathBuf,
     pub(crate) picture_enabled: bool,
     pub(crate) picture_path: Option<PathBuf>,
-    pub(crate) delete_original: bool,
-    pub(crate) rename_original: bool,
+    pub(crate) cover_mode: CoverMode,
+    pub(crate) art_strategy: ArtStrategy,
+    pub(crate) write_cue: bool,
+    pub(crate) source_action: SourceAction,
+    pub(crate) include_sidecars: bool,
+    pub(crate) allow_truncated: bool,
+    pub(crate) clamp_to_audio: bool,
+    pub(crate) length_tolerance: Option<u64>,
+    pub(crate) strict_cdda: bool,
+    pub(crate) read_offset: i64,
+    pub(crate) trim_silence: Option<TrimMode>,
+    pub(crate) silence_threshold_db: f64,
+    pub(crate) downmix: Option<Downmix>,
+    pub(crate) gain: Vec<GainSetting>,
+    pub(crate) fade: Fade,
+    pub(crate) audio_md5: bool,
+    pub(crate) keep_replaygain: bool,
+    pub(crate) update: bool,
+    pub(crate) resume: bool,
+    pub(crate) split_indexes: bool,
+    pub(crate) check_lossy: bool,
+    pub(crate) check_tags: bool,
+    pub(crate) strict: bool,
+    pub(crate) normalize_case: CaseStyle,
+    pub(crate) genre_map: GenreMap,
+    pub(crate) tag_rules: TagRules,
+    pub(crate) sample_offsets: Vec<(u32, u64)>,
+    pub(crate) track_lengths: Option<TrackLengths>,
+    pub(crate) block_size: usize,
+    pub(crate) selected_tracks: Option<TrackSelection>,
+    pub(crate) skipped_tracks: Option<TrackSelection>,
+    pub(crate) track_numbering: TrackNumbering,
+    pub(crate) to_stdout: bool,
+    pub(crate) status_to_stderr: bool,
+    pub(crate) permissions: PermissionArgs,
+    pub(crate) output_root: Option<PathBuf>,
     pub(crate) output_subdir: Option<PathBuf>,
     pub(crate) enforce_cue_filename_match: bool,
+    pub(crate) tag_overrides: Vec<TagOverride>,
+    pub(crate) track_compression: BTreeMap<u32, u8>,
+    pub(crate) path_tags: Option<PathTags>,
+    pub(crate) also_encode: Option<AlsoEncode>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PathTags {
+    pub(crate) artist: Option<String>,
+    pub(crate) album: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) enum SourceAction {
+    #[default]
+    Keep,
+    Delete,
+    Rename {
+        template: String,
+    },
+    Trash,
+    Archive {
+        dir: PathBuf,
+        base: PathBuf,
+    },
 }
 
 pub(crate) struct Plan {
     cue: CueDisc,
     input_meta: InputMetadata,
     tracks: Vec<TrackSpan>,
-    compression_level: u8,
+    encoder: EncoderOptions,
     display_base_abs: Option<PathBuf>,
     picture_names: Vec<String>,
+    picture_path: Option<PathBuf>,
+    cover_mode: CoverMode,
+    sidecar_art: Option<SidecarArt>,
+    write_cue: bool,
     total_samples: u64,
+    source_bytes: Option<u64>,
+    cue_length: Option<u64>,
+    inferred_tags: Vec<(String, String)>,
     warnings: Vec<String>,
     flac_display: PathBuf,
     cue_display: PathBuf,
     flac_abs: PathBuf,
+    cue_abs: PathBuf,
     overwrite: bool,
-    delete_original: bool,
-    rename_original: bool,
+    source_action: SourceAction,
+    source_files: Vec<PathBuf>,
+    include_sidecars: bool,
+    allow_truncated: bool,
+    trim_silence: Option<TrimMode>,
+    silence_threshold_db: f64,
+    channel_layout: String,
+    downmixer: Option<Downmixer>,
+    gain: Vec<GainSetting>,
+    fade: Fade,
+    audio_md5: bool,
+    update: bool,
+    up_to_date: bool,
+    complete_tracks: HashSet<u32>,
+    selected_tracks: Option<TrackSelection>,
+    skipped_tracks: Option<TrackSelection>,
+    to_stdout: bool,
+    status_to_stderr: bool,
+    permissions: PermissionArgs,
+    block_size: usize,
+    also_encode: Option<AlsoEncode>,
+    stdin_decoder: Option<FlacReaderDecoder>,
     encoding_used: &'static Encoding,
     encoding_autodetected: bool,
 }
@@ -62,8 +198,8 @@ impl Plan {
         &self.tracks
     }
 
-    pub(crate) fn compression_level(&self) -> u8 {
-        self.compression_level
+    pub(crate) fn encoder_options(&self) -> &EncoderOptions {
+        &self.encoder
     }
 
     pub(crate) fn display_base_abs(&self) -> Option<&Path> {
@@ -82,136 +218,1318 @@ impl Plan {
         &self.cue_display
     }
 
+    pub(crate) fn flac_path(&self) -> &Path {
+        &self.flac_abs
+    }
+
+    pub(crate) fn cue_path(&self) -> &Path {
+        &self.cue_abs
+    }
+
+    pub(crate) fn picture_path(&self) -> Option<&Path> {
+        self.picture_path.as_deref()
+    }
+
+    pub(crate) fn cover_mode(&self) -> CoverMode {
+        self.cover_mode
+    }
+
+    fn output_dir(&self) -> Option<&Path> {
+        if self.to_stdout {
+            return None;
+        }
+        let dir = self.tracks.first()?.output_path.parent()?;
+        if self.cue.discs.is_empty() {
+            Some(dir)
+        } else {
+            // The album directory above the per-disc subdirectories.
+            dir.parent()
+        }
+    }
+
+    pub(crate) fn cover_target(&self) -> Option<PathBuf> {
+        if let Some(art) = self.sidecar_art.as_ref() {
+            return Some(self.output_dir()?.join(&art.file_name));
+        }
+        if !self.cover_mode.copies() {
+            return None;
+        }
+        let picture = self.picture_path.as_deref()?;
+        Some(self.output_dir()?.join(cover_file_name(picture)))
+    }
+
+    pub(crate) fn cue_target(&self) -> Option<PathBuf> {
+        if !self.write_cue {
+            return None;
+        }
+        Some(self.output_dir()?.join(OUTPUT_CUE_NAME))
+    }
+
+    // The lossy copies of an album go into a directory of the same name below
+    // the --also-encode directory.
+    pub(crate) fn also_encode_target(&self) -> Option<(LossyFormat, PathBuf)> {
+        let also = self.also_encode.as_ref()?;
+        let dir = match self.output_dir()?.file_name() {
+            Some(name) => also.dir.join(name),
+            None => also.dir.clone(),
+        };
+        Some((also.format, dir))
+    }
+
+    fn lossy_path(&self, track: &TrackSpan) -> Option<PathBuf> {
+        let (format, dir) = self.also_encode_target()?;
+        let relative = track.output_path.strip_prefix(self.output_dir()?).ok()?;
+        Some(dir.join(relative).with_extension(format.extension()))
+    }
+
     pub(crate) fn cue_encoding(&self) -> (&'static Encoding, bool) {
         (self.encoding_used, self.encoding_autodetected)
     }
 
-    pub(crate) fn source_actions(&self) -> (bool, bool) {
-        (self.delete_original, self.rename_original)
+    pub(crate) fn cue_length(&self) -> Option<u64> {
+        self.cue_length
+    }
+
+    pub(crate) fn inferred_tags(&self) -> &[(String, String)] {
+        &self.inferred_tags
+    }
+
+    pub(crate) fn trim_silence(&self) -> Option<(TrimMode, f64)> {
+        self.trim_silence
+            .map(|mode| (mode, self.silence_threshold_db))
+    }
+
+    pub(crate) fn channel_layout(&self) -> &str {
+        &self.channel_layout
+    }
+
+    pub(crate) fn downmix(&self) -> Option<Downmix> {
+        self.downmixer.as_ref().map(Downmixer::target)
+    }
+
+    pub(crate) fn track_gain(&self, number: u32) -> Option<f64> {
+        gain_for_track(&self.gain, number)
+    }
+
+    pub(crate) fn fade(&self) -> Fade {
+        self.fade
+    }
+
+    pub(crate) fn up_to_date(&self) -> bool {
+        self.up_to_date
+    }
+
+    pub(crate) fn track_complete(&self, number: u32) -> bool {
+        self.complete_tracks.contains(&number)
+    }
+
+    pub(crate) fn track_selected(&self, number: u32) -> bool {
+        self.selected_tracks
+            .as_ref()
+            .is_none_or(|selection| selection.contains(number))
+            && !self
+                .skipped_tracks
+                .as_ref()
+                .is_some_and(|skipped| skipped.contains(number))
+    }
+
+    fn writes_track(&self, number: u32) -> bool {
+        self.track_selected(number) && !self.track_complete(number)
+    }
+
+    pub(crate) fn written_track_paths(&self) -> Vec<PathBuf> {
+        if self.to_stdout {
+            return Vec::new();
+        }
+        self.tracks
+            .iter()
+            .filter(|track| self.writes_track(track.number))
+            .map(|track| track.output_path.clone())
+            .collect()
+    }
+
+    pub(crate) fn estimated_track_size(&self, track: &TrackSpan) -> Option<u64> {
+        let total = self.input_meta.total_samples;
+        if total == 0 {
+            return None;
+        }
+        let input_channels = self.input_meta.channels.max(1);
+        let output_channels = self.downmixer.as_ref().map_or(input_channels, |downmixer| {
+            downmixer.output_channels() as u32
+        });
+        let samples = track.end.saturating_sub(track.start);
+        let bytes =
+            u128::from(self.source_bytes?) * u128::from(samples) * u128::from(output_channels)
+                / (u128::from(total) * u128::from(input_channels));
+        u64::try_from(bytes).ok()
+    }
+
+    pub(crate) fn estimated_output_size(&self) -> Option<u64> {
+        self.tracks
+            .iter()
+            .filter(|track| self.writes_track(track.number))
+            .map(|track| self.estimated_track_size(track))
+            .sum()
+    }
+
+    pub(crate) fn overwrite(&self) -> bool {
+        self.overwrite
+    }
+
+    pub(crate) fn source_action(&self) -> &SourceAction {
+        &self.source_action
+    }
+
+    pub(crate) fn source_files(&self) -> &[PathBuf] {
+        &self.source_files
+    }
+
+    pub(crate) fn includes_sidecars(&self) -> bool {
+        self.include_sidecars
     }
 
     pub(crate) fn warnings(&self) -> &[String] {
         &self.warnings
     }
 
-    pub(crate) fn execute(self) -> Result<()> {
-        ensure_output_paths_available(&self.tracks, self.overwrite)?;
+    pub(crate) fn reads_stdin(&self) -> bool {
+        self.stdin_decoder.is_some()
+    }
 
-        let mut progress = Some(make_progress_bar(self.total_samples));
+    pub(crate) fn to_stdout(&self) -> bool {
+        self.to_stdout
+    }
 
-        let result = (|| {
-            let decoder = create_decoder(&self.flac_abs)?;
-            let blocks = decoder.into_blocks()?;
+    fn copy_cover(&self, report: &mut AlbumReport) -> Result<()> {
+        if let Some(art) = self.sidecar_art.as_ref() {
+            return self.write_sidecar_art(art, report);
+        }
+        let (Some(source), Some(target)) = (self.picture_path.as_deref(), self.cover_target())
+        else {
+            return Ok(());
+        };
+        if target == source || (target.exists() && !self.overwrite) {
+            return Ok(());
+        }
+        fs::copy(source, &target).map_err(|err| {
+            format!(
+                "failed to copy cover {} to {}: {}",
+                source.display(),
+                target.display(),
+                err
+            )
+        })?;
+        apply_file_permissions(&target, &self.permissions)?;
+        report.note(format!(
+            "{} {}",
+            tr(Msg::CopiedCover).green(),
+            display_path(self.display_base_abs.as_deref(), &target).display()
+        ));
+        Ok(())
+    }
+
+    fn write_sidecar_art(&self, art: &SidecarArt, report: &mut AlbumReport) -> Result<()> {
+        let Some(target) = self.cover_target() else {
+            return Ok(());
+        };
+        if target.exists() && !self.overwrite {
+            return Ok(());
+        }
+        fs::write(&target, &art.data)
+            .map_err(|err| format!("failed to write cover {}: {}", target.display(), err))?;
+        apply_file_permissions(&target, &self.permissions)?;
+        report.note(format!(
+            "{} {}",
+            tr(Msg::WroteCover).green(),
+            display_path(self.display_base_abs.as_deref(), &target).display()
+        ));
+        Ok(())
+    }
 
-            let mut state = SplitState::new();
+    fn write_output_cue(&self, report: &mut AlbumReport) -> Result<()> {
+        let Some(target) = self.cue_target() else {
+            return Ok(());
+        };
+        if target == self.cue_abs {
+            report.note(format!(
+                "{} {}",
+                tr(Msg::SourceCueKept).yellow(),
+                display_path(self.display_base_abs.as_deref(), &target).display()
+            ));
+            return Ok(());
+        }
+        if target.exists() && !self.overwrite {
+            return Ok(());
+        }
+        let tracks: Vec<&TrackSpan> = self
+            .tracks
+            .iter()
+            .filter(|track| self.track_selected(track.number))
+            .collect();
+        let disc = output_cue_sheet(&self.cue, &tracks);
+        fs::write(&target, render_cue_sheet(&disc))
+            .map_err(|err| format!("failed to write cue file {}: {}", target.display(), err))?;
+        apply_file_permissions(&target, &self.permissions)?;
+        report.note(format!(
+            "{} {}",
+            tr(Msg::WroteCueSheet).green(),
+            display_path(self.display_base_abs.as_deref(), &target).display()
+        ));
+        Ok(())
+    }
+
+    // Directories are only made once the split is confirmed, so a preview
+    // leaves the disk untouched.
+    fn create_output_dirs(&self) -> Result<()> {
+        if let Some(dir) = self.output_dir() {
+            create_output_dir(dir, &self.permissions)?;
+        }
+        for track in self
+            .tracks
+            .iter()
+            .filter(|track| self.writes_track(track.number))
+        {
+            if let Some(parent) = track.output_path.parent() {
+                create_output_dir(parent, &self.permissions)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_free_space(&self) -> Result<()> {
+        let Some(needed) = self.estimated_output_size() else {
+            return Ok(());
+        };
+        let Some(dir) = self
+            .tracks
+            .iter()
+            .find(|track| self.writes_track(track.number))
+            .and_then(|track| track.output_path.parent())
+        else {
+            return Ok(());
+        };
+        match available_space(dir) {
+            Some(available) if available < needed => Err(tr_with(
+                Msg::NotEnoughSpace,
+                &[
+                    &dir.display(),
+                    &format_size(needed),
+                    &format_size(available),
+                ],
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn execute(
+        mut self,
+        batch: Option<&BatchProgress>,
+        events: Option<&EventSink>,
+    ) -> Result<()> {
+        if !self.to_stdout {
+            ensure_output_paths_available(
+                self.tracks
+                    .iter()
+                    .filter(|track| self.writes_track(track.number)),
+                self.overwrite,
+            )?;
+            let lossy_paths: Vec<PathBuf> = self
+                .tracks
+                .iter()
+                .filter(|track| self.writes_track(track.number))
+                .filter_map(|track| self.lossy_path(track))
+                .collect();
+            ensure_paths_available(lossy_paths.iter().map(PathBuf::as_path), self.overwrite)?;
+            self.create_output_dirs()?;
+            self.check_free_space()?;
+        }
+
+        let mut progress = Some(match batch {
+            Some(batch) => batch.add_album_bar(self.total_samples),
+            None => make_progress_bar(self.total_samples),
+        });
+        let mut clipped = Vec::new();
+        let mut anomalies = Vec::new();
+        let source = self.flac_display.to_string_lossy().into_owned();
+        let mut report = AlbumReport::new(batch, &source, self.status_to_stderr);
+        if let Some(events) = events {
+            events.emit(&Event::AlbumStart {
+                source: &source,
+                tracks: self
+                    .tracks
+                    .iter()
+                    .filter(|track| self.writes_track(track.number))
+                    .count(),
+                total_samples: self.total_samples,
+            });
+        }
+
+        let stdin_decoder = self.stdin_decoder.take();
+        let result = (|| {
+            let decoder: Box<dyn Decoder> = match stdin_decoder {
+                Some(decoder) => Box::new(decoder),
+                None => create_decoder(&self.flac_abs)?,
+            };
+            let blocks = decoder.into_blocks(self.block_size)?;
+
+            let mut state = SplitState::new(
+                SilenceTrimmer::new(
+                    self.trim_silence,
+                    self.silence_threshold_db,
+                    self.input_meta.bits_per_sample,
+                ),
+                self.downmixer.clone(),
+                self.permissions.clone(),
+            );
+            let mut decode_error = None;
+            let mut decoded = 0u64;
+            let mut last_event = Instant::now();
             for block in blocks {
-                process_audio_block(&self, &mut state, progress.as_ref(), block?)?;
+                let block = match block {
+                    Ok(block) => block,
+                    Err(err) if self.allow_truncated => {
+                        decode_error = Some(err);
+                        break;
+                    }
+                    Err(err) => return Err(err),
+                };
+                let block_samples = block.sample_count() as u64;
+                process_audio_block(&self, &mut state, progress.as_ref(), events, block)?;
+                if let Some(batch) = batch {
+                    batch.advance(block_samples);
+                }
+                decoded += block_samples;
+                if let Some(events) = events
+                    && last_event.elapsed() >= PROGRESS_EVENT_INTERVAL
+                {
+                    last_event = Instant::now();
+                    events.emit(&Event::Progress {
+                        source: &source,
+                        samples: decoded,
+                        total_samples: self.total_samples,
+                    });
+                }
             }
 
-            if let Some(mut encoder) = state.encoder.take() {
-                encoder.finish()?;
+            let truncation = describe_truncation(&self, &state, decode_error.as_deref());
+            if let Some(message) = truncation.as_ref()
+                && !self.allow_truncated
+            {
+                return Err(tr_with(Msg::KeepTruncated, &[message]));
             }
 
-            Ok(())
+            state.finish_encoder()?;
+            clipped = std::mem::take(&mut state.clipped);
+            anomalies = std::mem::take(&mut state.anomalies);
+
+            Ok(truncation)
         })();
 
-        match result {
-            Ok(()) => {
-                finish_progress(&mut progress, "done");
+        let truncated = matches!(result, Ok(Some(_)));
+        let outcome = match result {
+            Ok(None) => {
+                finish_progress(&mut progress, tr(Msg::Done));
+                report_clipping(&clipped, &mut report);
+                report_signal_anomalies(&anomalies, &mut report);
+                self.copy_cover(&mut report)?;
+                self.write_output_cue(&mut report)?;
                 handle_original_flac(
                     self.display_base_abs.as_deref(),
-                    &self.flac_abs,
-                    self.delete_original,
-                    self.rename_original,
+                    &self.source_files,
+                    &self.source_action,
+                    &mut report,
                 )
             }
+            Ok(Some(message)) => {
+                finish_progress(&mut progress, tr(Msg::Truncated));
+                report_clipping(&clipped, &mut report);
+                report_signal_anomalies(&anomalies, &mut report);
+                report.note(format!(
+                    "{} {}",
+                    tr(Msg::Warning).red().bold(),
+                    message.red().bold()
+                ));
+                if self.source_action != SourceAction::Keep {
+                    report.note(tr(Msg::TruncatedSourceKept).yellow().to_string());
+                }
+                Ok(())
+            }
             Err(err) => {
-                finish_progress(&mut progress, "aborted");
+                finish_progress(&mut progress, tr(Msg::Aborted));
                 Err(err)
             }
+        };
+        if let Some(events) = events {
+            let result = match (&outcome, truncated) {
+                (Err(_), _) => "failed",
+                (Ok(()), true) => "truncated",
+                (Ok(()), false) => "done",
+            };
+            events.emit(&Event::AlbumDone {
+                source: &source,
+                result,
+                error: outcome.as_ref().err().map(String::as_str),
+            });
+        }
+        outcome
+    }
+}
+
+pub(crate) fn split_points_cue(
+    points: &[SplitPoint],
+    sample_rate: u32,
+) -> Result<(CueDisc, Vec<String>)> {
+    if sample_rate == 0 || !sample_rate.is_multiple_of(75) {
+        return Err(format!(
+            "sample rate {} is not divisible by 75 (CUE frames)",
+            sample_rate
+        ));
+    }
+    let samples_per_frame = (sample_rate / 75) as u64;
+    let mut warnings = Vec::new();
+    let mut tracks: Vec<CueTrack> = Vec::with_capacity(points.len());
+    for (index, point) in points.iter().enumerate() {
+        // Sample points are kept exact; the frame is only what the cue shows.
+        let (frames, exact, sample_offset) = match *point {
+            SplitPoint::Millis(millis) => {
+                ((millis * 75 + 500) / 1000, (millis * 75) % 1000 == 0, None)
+            }
+            SplitPoint::Sample(sample) => (
+                (sample + samples_per_frame / 2) / samples_per_frame,
+                true,
+                (sample % samples_per_frame != 0).then_some(sample),
+            ),
+        };
+        let frames = frames as i64;
+        if let Some(previous) = tracks.last()
+            && frames <= previous.start_frames
+        {
+            return Err(format!(
+                "split point {} does not come after the previous one",
+                point
+            ));
+        }
+        if !exact {
+            warnings.push(format!(
+                "split point {} is not on a CD frame; splitting at {}",
+                point,
+                format_msf(frames as u64)
+            ));
+        }
+        tracks.push(CueTrack {
+            number: index as u32 + 1,
+            title: None,
+            performer: None,
+            songwriter: None,
+            composer: None,
+            isrc: None,
+            start_frames: frames,
+            length_frames: None,
+            sub_indexes: Vec::new(),
+            pregap_start: None,
+            filename: None,
+            rem: CueRem {
+                sample_offset,
+                ..CueRem::default()
+            },
+        });
+    }
+    Ok((
+        CueDisc {
+            tracks,
+            ..CueDisc::default()
+        },
+        warnings,
+    ))
+}
+
+pub(crate) fn output_cue_sheet(cue: &CueDisc, tracks: &[&TrackSpan]) -> CueDisc {
+    let tracks = tracks
+        .iter()
+        .map(|track| CueTrack {
+            number: track.number,
+            title: track.title.clone(),
+            performer: track.performer.clone(),
+            songwriter: track.songwriter.clone(),
+            composer: track.composer.clone(),
+            isrc: track.isrc.clone(),
+            start_frames: 0,
+            length_frames: None,
+            sub_indexes: Vec::new(),
+            pregap_start: None,
+            filename: output_cue_file_name(cue, &track.output_path),
+            rem: CueRem {
+                sample_offset: None,
+                ..track.rem.clone()
+            },
+        })
+        .collect();
+    CueDisc {
+        tracks,
+        mode_issues: Vec::new(),
+        ..cue.clone()
+    }
+}
+
+fn output_cue_file_name(cue: &CueDisc, path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_string_lossy().into_owned();
+    if cue.discs.is_empty() {
+        return Some(name);
+    }
+    // The cue sheet sits above the per-disc subdirectories.
+    let disc_dir = path.parent()?.file_name()?.to_string_lossy().into_owned();
+    Some(format!("{}/{}", disc_dir, name))
+}
+
+pub(crate) fn parallel_albums(plans: usize, jobs: usize) -> usize {
+    jobs.clamp(1, plans.max(1))
+}
+
+// Jobs left over when there are fewer albums than jobs go to the encoders.
+pub(crate) fn encoder_threads(plans: usize, jobs: usize) -> u32 {
+    let threads = jobs.max(1) / parallel_albums(plans, jobs);
+    u32::try_from(threads).unwrap_or(u32::MAX)
+}
+
+pub(crate) fn execute_plans(
+    plans: Vec<Plan>,
+    jobs: usize,
+    events: Option<&EventSink>,
+) -> Result<()> {
+    if let Some(events) = events {
+        events.emit(&Event::Start {
+            albums: plans.len(),
+            total_samples: plans.iter().map(|plan| plan.total_samples).sum(),
+        });
+    }
+    let result = run_plans(plans, jobs, events);
+    if let Some(events) = events {
+        events.emit(&Event::Finish {
+            result: if result.is_ok() { "done" } else { "failed" },
+            error: result.as_ref().err().map(String::as_str),
+        });
+    }
+    result
+}
+
+fn run_plans(mut plans: Vec<Plan>, jobs: usize, events: Option<&EventSink>) -> Result<()> {
+    let threads = encoder_threads(plans.len(), jobs);
+    for plan in &mut plans {
+        plan.encoder.threads = threads;
+    }
+    if plans.len() < 2 {
+        for plan in plans {
+            plan.execute(None, events)?;
+        }
+        return Ok(());
+    }
+
+    let total_samples = plans.iter().map(|plan| plan.total_samples).sum();
+    let batch = BatchProgress::new(plans.len(), total_samples);
+    let failed = AtomicBool::new(false);
+    let workers = parallel_albums(plans.len(), jobs);
+    let results = run_parallel(plans, workers, |plan| {
+        if failed.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let result = plan.execute(Some(&batch), events);
+        match result {
+            Ok(()) => batch.finish_album(),
+            Err(_) => failed.store(true, Ordering::Relaxed),
+        }
+        result
+    });
+    match results.into_iter().find_map(Result::err) {
+        Some(err) => {
+            batch.abandon();
+            Err(err)
+        }
+        None => {
+            batch.finish();
+            Ok(())
         }
     }
 }
 
+fn report_clipping(clipped: &[(u32, u64, f64)], report: &mut AlbumReport) {
+    for (number, samples, db) in clipped {
+        report.note(format!(
+            "{} {}",
+            tr(Msg::Warning).yellow().bold(),
+            tr_with(
+                Msg::Clipped,
+                &[&format!("{:02}", number), samples, &format_gain(*db)]
+            )
+        ));
+    }
+}
+
+fn report_signal_anomalies(anomalies: &[(u32, Vec<String>)], report: &mut AlbumReport) {
+    for (number, found) in anomalies {
+        report.note(format!(
+            "{} {}",
+            tr(Msg::Warning).yellow().bold(),
+            tr_with(
+                Msg::SignalAnomalies,
+                &[&format!("{:02}", number), &found.join(", ")]
+            )
+        ));
+    }
+}
+
+fn describe_truncation(
+    plan: &Plan,
+    state: &SplitState,
+    decode_error: Option<&str>,
+) -> Option<String> {
+    let expected_end = plan.tracks.last().map(|track| track.end).unwrap_or(0);
+    if state.position >= expected_end {
+        return None;
+    }
+
+    let mut message = format!(
+        "source ends at {} (sample {}), {} samples short of the cue",
+        format_time(state.position, plan.input_meta.sample_rate),
+        state.position,
+        expected_end - state.position
+    );
+    if let Some(err) = decode_error {
+        message.push_str(&format!(" after a decoding error ({})", err));
+    }
+
+    let mut missing = Vec::new();
+    for track in &plan.tracks {
+        if state.position >= track.end {
+            continue;
+        }
+        if state.position > track.start {
+            message.push_str(&format!(
+                "; track {} is incomplete ({} of {} samples)",
+                track.number,
+                state.position - track.start,
+                track.end - track.start
+            ));
+        } else {
+            missing.push(track.number.to_string());
+        }
+    }
+    if !missing.is_empty() {
+        message.push_str(&format!("; missing tracks: {}", missing.join(", ")));
+    }
+    Some(message)
+}
+
 pub(crate) fn prepare_split(options: SplitOptions) -> Result<Plan> {
-    let (cue, warnings, encoding_used, encoding_autodetected) =
-        parse_cue_file(&options.cue_input.abs, options.cue_encoding)?;
+    let (mut cue, mut warnings, encoding_used, encoding_autodetected) =
+        match (options.split_points.is_some(), options.cue_text.as_deref()) {
+            // The synthetic cue needs the sample rate; it is built after reading the source.
+            (true, _) => (CueDisc::default(), Vec::new(), UTF_8, false),
+            (false, Some(text)) => parse_cue_contents(text.as_bytes(), options.cue_encoding)?,
+            (false, None) => parse_cue_file(&options.cue_input.abs, options.cue_encoding)?,
+        };
     validate_cue_files(
         &cue,
         &options.flac_input.abs,
         options.enforce_cue_filename_match,
     )?;
 
-    let mut output_dir = options
-        .flac_input
-        .abs
-        .parent()
-        .map(PathBuf::from)
-        .unwrap_or_else(|| PathBuf::from("."));
+    let mut output_dir = match options.output_root.as_ref() {
+        Some(root) => root.clone(),
+        None => options
+            .flac_input
+            .abs
+            .parent()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
     if let Some(subdir) = options.output_subdir.as_ref() {
         output_dir = output_dir.join(subdir);
     }
-    fs::create_dir_all(&output_dir).map_err(|err| {
-        format!(
-            "failed to create output directory {}: {}",
-            output_dir.display(),
-            err
-        )
-    })?;
 
-    let mut decoder = create_decoder(&options.flac_input.abs)?;
-    let mut decoded = decoder.read_metadata()?;
+    let (mut decoded, stdin_decoder) = read_source(&options.flac_input.abs)?;
+    if !options.keep_replaygain {
+        // Gain values measured over the whole image do not describe the tracks.
+        strip_replaygain_tags(&mut decoded.input_meta.comments);
+    }
+    if decoded.input_meta.total_samples == 0 && stdin_decoder.is_some() {
+        return Err("the FLAC stream on stdin does not declare its length".to_string());
+    }
+    if decoded.input_meta.total_samples == 0 {
+        let counted = count_samples(&options.flac_input.abs)?;
+        if counted == 0 {
+            return Err(tr_with(Msg::NoAudio, &[&options.flac_input.abs.display()]));
+        }
+        warnings.push(format!(
+            "{} does not declare its length; counted {} samples with an extra decoding pass",
+            options.flac_input.display.display(),
+            counted
+        ));
+        decoded.input_meta.total_samples = counted;
+    }
+
+    if let Some(points) = options.split_points.as_ref() {
+        let (split_cue, split_warnings) =
+            split_points_cue(points.points(), decoded.input_meta.sample_rate)?;
+        cue = split_cue;
+        warnings.extend(split_warnings);
+    }
+    warnings.extend(apply_sample_offsets(&mut cue, &options.sample_offsets));
+    warnings.extend(check_sample_offsets(&cue, decoded.input_meta.sample_rate));
+    normalize_cue_case(&mut cue, options.normalize_case);
+    map_cue_genre(&mut cue, &options.genre_map);
+    let mut inferred_tags = Vec::new();
+    if let Some(path_tags) = options.path_tags.as_ref() {
+        if cue.performer.is_none()
+            && let Some(artist) = path_tags.artist.as_ref()
+        {
+            cue.performer = Some(artist.clone());
+            inferred_tags.push(("ALBUMARTIST".to_string(), artist.clone()));
+        }
+        if cue.title.is_none()
+            && let Some(album) = path_tags.album.as_ref()
+        {
+            cue.title = Some(album.clone());
+            inferred_tags.push(("ALBUM".to_string(), album.clone()));
+        }
+    }
+    validate_tag_overrides(&cue, &options.tag_overrides)?;
 
-    if options.picture_enabled {
+    let picture_path = if !options.picture_enabled {
+        None
+    } else if options.cover_mode.embeds() {
         add_external_picture(
             &mut decoded.input_meta,
             &mut decoded.picture_names,
             &options.search_dir,
             options.picture_path.as_deref(),
-        )?;
+        )?
+    } else {
+        find_external_picture(&options.search_dir, options.picture_path.as_deref())?
+    };
+    let sidecar_art = match options.art_strategy {
+        ArtStrategy::Embed => None,
+        ArtStrategy::SidecarThumbnail => {
+            let (art, art_warnings) = thumbnail_cover_art(&mut decoded.input_meta)?;
+            warnings.extend(art_warnings);
+            art
+        }
+    };
+
+    if options.strict_cdda {
+        check_strict_cdda(&decoded.input_meta, &cue)?;
     }
+    let channel_layout =
+        describe_channel_layout(decoded.input_meta.channels, decoded.input_meta.channel_mask);
+    let downmixer = prepare_downmix(&mut decoded.input_meta, options.downmix)?;
 
     let sample_rate = decoded.input_meta.sample_rate;
     let total_samples = decoded.input_meta.total_samples;
-    let tracks = build_output_tracks(&cue, &output_dir, sample_rate, total_samples, false)?;
+    let cue_length = cue_length_samples(&cue, sample_rate)?;
+    let mut clamp_to_audio = options.clamp_to_audio;
+    if let (Some(cue_length), Some(tolerance)) = (cue_length, options.length_tolerance) {
+        let samples_per_frame = (sample_rate / 75) as u64;
+        let difference = total_samples.abs_diff(cue_length);
+        if difference > tolerance.saturating_mul(samples_per_frame) {
+            let differs = length_difference(total_samples, cue_length, sample_rate);
+            return Err(tr_with(Msg::LengthBeyondTolerance, &[&differs, &tolerance]));
+        }
+        clamp_to_audio = true;
+    }
+    let computed = if clamp_to_audio {
+        let (computed, clamp_warnings) =
+            compute_clamped_track_spans(&cue, sample_rate, total_samples)?;
+        warnings.extend(clamp_warnings);
+        computed
+    } else {
+        compute_track_spans(&cue, sample_rate, total_samples).map_err(|err| {
+            if err.contains("exceeds input total samples") {
+                format!(
+                    "{} (use --clamp-to-audio to cut it at the end of the audio)",
+                    err
+                )
+            } else {
+                err
+            }
+        })?
+    };
+    let computed = match options.track_lengths.as_ref() {
+        Some(lengths) => {
+            let (computed, length_warnings) =
+                apply_track_lengths(computed, lengths, sample_rate, total_samples)?;
+            warnings.extend(length_warnings);
+            computed
+        }
+        None => computed,
+    };
+    let computed = if options.split_indexes {
+        split_sub_indexes(computed, &cue, sample_rate)?
+    } else {
+        computed
+    };
+    let computed = apply_read_offset(computed, options.read_offset, total_samples);
+    warnings.extend(check_span_sanity(&computed, sample_rate));
+    for setting in &options.gain {
+        if let Some(number) = setting.track
+            && !computed.iter().any(|track| track.number == number)
+        {
+            warnings.push(format!(
+                "gain {} for track {} is ignored: the cue sheet has no such track",
+                format_gain(setting.db),
+                number
+            ));
+        }
+    }
+    for number in options.track_compression.keys() {
+        if !computed.iter().any(|track| track.number == *number) {
+            warnings.push(format!(
+                "compression level for track {} is ignored: the cue sheet has no such track",
+                number
+            ));
+        }
+    }
+    let mut tracks = build_output_tracks(
+        computed,
+        &cue,
+        &output_dir,
+        &options.tag_overrides,
+        sample_rate,
+        options.encoder.format.extension(),
+        false,
+    )?;
+    if options.track_numbering == TrackNumbering::Renumber
+        && let Some(skipped) = options.skipped_tracks.as_ref()
+    {
+        renumber_tracks(&mut tracks, &cue, skipped);
+    }
+    for track in &mut tracks {
+        track.compression_level = options.track_compression.get(&track.number).copied();
+        track.tag_rules = options.tag_rules.clone();
+    }
+    let selected = |track: &&TrackSpan| {
+        options
+            .selected_tracks
+            .as_ref()
+            .is_none_or(|selection| selection.contains(track.number))
+            && !options
+                .skipped_tracks
+                .as_ref()
+                .is_some_and(|skipped| skipped.contains(track.number))
+    };
+    warnings.extend(sanitization_warnings(&tracks));
+    let selected_count = tracks.iter().filter(selected).count();
+    if selected_count == 0 {
+        return Err("--tracks and --skip-tracks leave none of the cue sheet's tracks".to_string());
+    }
+    if options.to_stdout && selected_count != 1 {
+        return Err(format!(
+            "--stdout writes a single track, but --tracks selects {}",
+            selected_count
+        ));
+    }
+    if options.check_tags {
+        let missing: Vec<(u32, Vec<&str>)> = tracks
+            .iter()
+            .filter(selected)
+            .map(|track| {
+                let tags = build_track_tags(&decoded.input_meta, &cue, track_total(&tracks), track);
+                (track.number, missing_required_tags(&tags, track.number))
+            })
+            .collect();
+        warnings.extend(tag_completeness_problems(&missing));
+    }
+    if options.check_lossy {
+        if is_stdin_path(&options.flac_input.abs) {
+            warnings.push("--check-lossy is skipped for an image read from stdin".to_string());
+        } else {
+            let lossy = find_lossy_tracks(
+                &options.flac_input.abs,
+                &tracks,
+                sample_rate,
+                decoded.input_meta.bits_per_sample,
+            )?;
+            for (number, cutoff) in lossy {
+                warnings.push(format!(
+                    "track {:02} looks transcoded from a lossy source: its spectrum stops at {:.1} kHz",
+                    number,
+                    cutoff / 1000.0
+                ));
+            }
+        }
+    }
+    if options.strict {
+        // Nothing has been written yet, so every warning can still stop the album.
+        let mut problems = warnings.clone();
+        if let Some(cue_length) = cue_length
+            && cue_length != total_samples
+            && options.length_tolerance.is_none()
+        {
+            problems.push(length_difference(total_samples, cue_length, sample_rate));
+        }
+        if !problems.is_empty() {
+            return Err(format!(
+                "--strict refuses to split {}:\n  {}",
+                options.flac_input.display.display(),
+                problems.join("\n  ")
+            ));
+        }
+    }
+    let output_channels = downmixer
+        .as_ref()
+        .map_or(decoded.input_meta.channels, |downmixer| {
+            downmixer.output_channels() as u32
+        });
+    let up_to_date = options.update
+        && tracks.iter().filter(selected).all(|track| {
+            output_complete(
+                track,
+                &decoded.input_meta,
+                output_channels,
+                options.trim_silence.is_some(),
+            )
+        });
+    let complete_tracks: HashSet<u32> = if options.resume {
+        tracks
+            .iter()
+            .filter(selected)
+            .filter(|track| {
+                output_complete(
+                    track,
+                    &decoded.input_meta,
+                    output_channels,
+                    options.trim_silence.is_some(),
+                ) && output_decodes_fully(track)
+            })
+            .map(|track| track.number)
+            .collect()
+    } else {
+        HashSet::new()
+    };
+    let up_to_date = up_to_date || (options.resume && complete_tracks.len() == selected_count);
+    let source_files = source_files(
+        &options.flac_input.abs,
+        &options.cue_input.abs,
+        &options.source_action,
+        options.include_sidecars,
+    );
 
     Ok(Plan {
         cue,
         input_meta: decoded.input_meta,
         tracks,
-        compression_level: options.compression_level,
+        encoder: options.encoder,
         display_base_abs: options.display_base_abs,
         picture_names: decoded.picture_names,
+        picture_path,
+        cover_mode: options.cover_mode,
+        sidecar_art,
+        write_cue: options.write_cue,
         total_samples,
+        source_bytes: source_bytes(&options.flac_input.abs),
+        cue_length,
+        inferred_tags,
         warnings,
         flac_display: options.flac_input.display,
         cue_display: options.cue_input.display,
         flac_abs: options.flac_input.abs,
+        cue_abs: options.cue_input.abs,
         overwrite: options.overwrite,
-        delete_original: options.delete_original,
-        rename_original: options.rename_original,
+        source_action: options.source_action,
+        source_files,
+        include_sidecars: options.include_sidecars,
+        allow_truncated: options.allow_truncated,
+        trim_silence: options.trim_silence,
+        silence_threshold_db: options.silence_threshold_db,
+        channel_layout,
+        downmixer,
+        gain: options.gain,
+        fade: options.fade,
+        audio_md5: options.audio_md5,
+        update: options.update,
+        up_to_date,
+        complete_tracks,
+        selected_tracks: options.selected_tracks,
+        skipped_tracks: options.skipped_tracks,
+        to_stdout: options.to_stdout,
+        status_to_stderr: options.status_to_stderr,
+        permissions: options.permissions,
+        block_size: options.block_size,
+        also_encode: options.also_encode,
+        stdin_decoder,
         encoding_used,
         encoding_autodetected,
     })
 }
 
+pub(crate) struct SavedSplitOptions {
+    pub(crate) flac_abs: PathBuf,
+    pub(crate) cue_abs: PathBuf,
+    pub(crate) display_base_abs: Option<PathBuf>,
+    pub(crate) cue_encoding: &'static Encoding,
+    pub(crate) total_samples: u64,
+    pub(crate) encoder: EncoderOptions,
+    pub(crate) picture_path: Option<PathBuf>,
+    pub(crate) cover_mode: CoverMode,
+    pub(crate) overwrite: bool,
+    pub(crate) source_action: SourceAction,
+    pub(crate) include_sidecars: bool,
+    pub(crate) allow_truncated: bool,
+    pub(crate) tracks: Vec<TrackSpan>,
+}
+
+fn read_source(path: &Path) -> Result<(DecoderMetadata, Option<FlacReaderDecoder>)> {
+    if is_stdin_path(path) {
+        let mut decoder = FlacReaderDecoder::new(Box::new(std::io::stdin()), STDIN_DISPLAY)?;
+        let decoded = decoder.read_metadata()?;
+        return Ok((decoded, Some(decoder)));
+    }
+    let mut decoder = create_decoder(path)?;
+    Ok((decoder.read_metadata()?, None))
+}
+
+pub(crate) fn prepare_saved_split(options: SavedSplitOptions) -> Result<Plan> {
+    let (mut decoded, stdin_decoder) = read_source(&options.flac_abs)?;
+    let total_samples = decoded.input_meta.total_samples;
+    if total_samples != options.total_samples {
+        return Err(format!(
+            "{} has {} samples but the plan was made for {} samples",
+            options.flac_abs.display(),
+            total_samples,
+            options.total_samples
+        ));
+    }
+    validate_saved_tracks(&options.tracks, total_samples)?;
+
+    let picture_path = match options.picture_path.as_deref() {
+        Some(path) if options.cover_mode.embeds() => add_external_picture(
+            &mut decoded.input_meta,
+            &mut decoded.picture_names,
+            path.parent().unwrap_or(Path::new(".")),
+            Some(path),
+        )?,
+        Some(path) => Some(path.to_path_buf()),
+        None => None,
+    };
+
+    let channel_layout =
+        describe_channel_layout(decoded.input_meta.channels, decoded.input_meta.channel_mask);
+    let source_files = source_files(
+        &options.flac_abs,
+        &options.cue_abs,
+        &options.source_action,
+        options.include_sidecars,
+    );
+    let display = |path: &Path| display_path(options.display_base_abs.as_deref(), path);
+    Ok(Plan {
+        cue: CueDisc::default(),
+        input_meta: decoded.input_meta,
+        encoder: options.encoder,
+        picture_names: decoded.picture_names,
+        picture_path,
+        cover_mode: options.cover_mode,
+        sidecar_art: None,
+        write_cue: false,
+        total_samples,
+        source_bytes: source_bytes(&options.flac_abs),
+        cue_length: None,
+        inferred_tags: Vec::new(),
+        warnings: Vec::new(),
+        flac_display: if stdin_decoder.is_some() {
+            PathBuf::from(STDIN_DISPLAY)
+        } else {
+            display(&options.flac_abs)
+        },
+        cue_display: display(&options.cue_abs),
+        tracks: options.tracks,
+        display_base_abs: options.display_base_abs,
+        flac_abs: options.flac_abs,
+        cue_abs: options.cue_abs,
+        overwrite: options.overwrite,
+        source_action: options.source_action,
+        source_files,
+        include_sidecars: options.include_sidecars,
+        allow_truncated: options.allow_truncated,
+        trim_silence: None,
+        silence_threshold_db: DEFAULT_SILENCE_THRESHOLD_DB,
+        channel_layout,
+        downmixer: None,
+        gain: Vec::new(),
+        fade: Fade::default(),
+        audio_md5: false,
+        update: false,
+        up_to_date: false,
+        complete_tracks: HashSet::new(),
+        selected_tracks: None,
+        skipped_tracks: None,
+        to_stdout: false,
+        status_to_stderr: false,
+        permissions: PermissionArgs::default(),
+        block_size: DEFAULT_BLOCK_SIZE,
+        also_encode: None,
+        stdin_decoder,
+        encoding_used: options.cue_encoding,
+        encoding_autodetected: false,
+    })
+}
+
+fn output_complete(track: &TrackSpan, meta: &InputMetadata, channels: u32, trimmed: bool) -> bool {
+    let Ok(mut decoder) = create_decoder(&track.output_path) else {
+        return false;
+    };
+    let Ok(decoded) = decoder.read_metadata() else {
+        return false;
+    };
+    let output = decoded.input_meta;
+    let expected = track.end - track.start;
+    let length_ok = if trimmed {
+        output.total_samples > 0 && output.total_samples <= expected
+    } else {
+        output.total_samples == expected
+    };
+    let source_ok = match (meta.md5, find_tag(&output.comments, SOURCE_MD5_TAG)) {
+        (Some(md5), Some(tag)) => tag.eq_ignore_ascii_case(&format_md5(&md5)),
+        _ => true,
+    };
+    length_ok
+        && source_ok
+        && output.sample_rate == meta.sample_rate
+        && output.channels == channels
+        && output.bits_per_sample == meta.bits_per_sample
+}
+
+fn output_decodes_fully(track: &TrackSpan) -> bool {
+    let Ok(mut decoder) = create_decoder(&track.output_path) else {
+        return false;
+    };
+    let Ok(decoded) = decoder.read_metadata() else {
+        return false;
+    };
+    count_samples(&track.output_path)
+        .is_ok_and(|samples| samples == decoded.input_meta.total_samples)
+}
+
+fn prepare_downmix(
+    meta: &mut InputMetadata,
+    downmix: Option<Downmix>,
+) -> Result<Option<Downmixer>> {
+    let Some(target) = downmix else {
+        return Ok(None);
+    };
+    let downmixer = Downmixer::new(
+        target,
+        meta.channels,
+        meta.channel_mask,
+        meta.bits_per_sample,
+    )?;
+    meta.channel_mask = None;
+    meta.comments
+        .retain(|(key, _)| !key.eq_ignore_ascii_case(CHANNEL_MASK_TAG));
+    Ok(Some(downmixer))
+}
+
+fn validate_saved_tracks(tracks: &[TrackSpan], total_samples: u64) -> Result<()> {
+    if tracks.is_empty() {
+        return Err("plan has no tracks".to_string());
+    }
+    let mut outputs = HashSet::new();
+    let mut previous_end = 0u64;
+    for track in tracks {
+        if track.start >= track.end {
+            return Err(format!(
+                "track {} has an empty or inverted span ({}-{})",
+                track.number, track.start, track.end
+            ));
+        }
+        if track.start < previous_end {
+            return Err(format!(
+                "track {} starts at {} before the previous track ends at {}",
+                track.number, track.start, previous_end
+            ));
+        }
+        if track.end > total_samples {
+            return Err(format!(
+                "track {} ends at {} past the end of the input ({} samples)",
+                track.number, track.end, total_samples
+            ));
+        }
+        if !outputs.insert(track.output_path.clone()) {
+            return Err(format!(
+                "duplicate output path in plan: {}",
+                track.output_path.display()
+            ));
+        }
+        previous_end = track.end;
+    }
+    Ok(())
+}
+
 struct SplitState {
     track_index: usize,
+    position: u64,
     encoder: Option<TrackEncoder>,
+    lossy: Option<LossyEncoder>,
+    trimmer: SilenceTrimmer,
+    downmixer: Option<Downmixer>,
+    gain: Option<(u32, f64, GainStage)>,
+    fade: Option<FadeStage>,
+    clipped: Vec<(u32, u64, f64)>,
+    hasher: Option<(PathBuf, PcmHasher)>,
+    signal: Option<(u32, SignalStats)>,
+    anomalies: Vec<(u32, Vec<String>)>,
+    output: Option<PathBuf>,
+    permissions: PermissionArgs,
 }
 
 impl SplitState {
-    fn new() -> Self {
+    fn new(
+        trimmer: SilenceTrimmer,
+        downmixer: Option<Downmixer>,
+        permissions: PermissionArgs,
+    ) -> Self {
         Self {
             track_index: 0,
+            position: 0,
             encoder: None,
+            lossy: None,
+            trimmer,
+            downmixer,
+            gain: None,
+            fade: None,
+            clipped: Vec::new(),
+            hasher: None,
+            signal: None,
+            anomalies: Vec::new(),
+            output: None,
+            permissions,
         }
     }
 
     fn finish_encoder(&mut self) -> Result<()> {
+        self.trimmer.reset();
+        self.fade = None;
+        if let Some((number, db, stage)) = self.gain.take()
+            && stage.clipped() > 0
+        {
+            self.clipped.push((number, stage.clipped(), db));
+        }
         if let Some(mut encoder) = self.encoder.take() {
             encoder.finish()?;
         }
+        if let Some(lossy) = self.lossy.take() {
+            let path = lossy.finish()?;
+            apply_file_permissions(&path, &self.permissions)?;
+        }
+        if let Some((path, hasher)) = self.hasher.take() {
+            replace_comment_tag(&path, AUDIO_MD5_TAG, &format_md5(&hasher.finish()))?;
+        }
+        if let Some((number, stats)) = self.signal.take() {
+            let anomalies = stats.anomalies();
+            if !anomalies.is_empty() {
+                self.anomalies.push((number, anomalies));
+            }
+        }
+        if let Some(path) = self.output.take() {
+            apply_file_permissions(&path, &self.permissions)?;
+        }
         Ok(())
     }
 }
@@ -220,6 +1538,7 @@ fn process_audio_block(
     prepared: &Plan,
     state: &mut SplitState,
     progress: Option<&ProgressBar>,
+    events: Option<&EventSink>,
     block: AudioBlock,
 ) -> Result<()> {
     let channels = block.channels as usize;
@@ -244,6 +1563,14 @@ fn process_audio_block(
     if let Some(pb) = progress {
         pb.inc(block_samples as u64);
     }
+    state.position = state
+        .position
+        .max(block.sample_index + block_samples as u64);
+
+    let output_channels = state
+        .downmixer
+        .as_ref()
+        .map_or(channels, Downmixer::output_channels);
 
     let mut sample = block.sample_index;
     let mut local_offset = 0usize;
@@ -257,7 +1584,7 @@ fn process_audio_block(
         let track = prepared.tracks[state.track_index].clone();
 
         if sample < track.start {
-            let skip = std::cmp::min(remaining, (track.start - sample) as usize);
+            let skip = samples_until(sample, track.start, remaining);
             sample += skip as u64;
             local_offset += skip;
             remaining -= skip;
@@ -272,28 +1599,146 @@ fn process_audio_block(
             continue;
         }
 
-        let take = std::cmp::min(remaining, (track.end - sample) as usize);
+        let take = samples_until(sample, track.end, remaining);
         if take == 0 {
             break;
         }
 
+        if !prepared.writes_track(track.number) {
+            sample += take as u64;
+            local_offset += take;
+            remaining -= take;
+            if sample >= track.end {
+                state.track_index += 1;
+            }
+            continue;
+        }
+
         if state.encoder.is_none() {
+            let mut extra_tags = Vec::new();
+            if prepared.update
+                && let Some(md5) = prepared.input_meta.md5
+            {
+                extra_tags.push((SOURCE_MD5_TAG.to_string(), format_md5(&md5)));
+            }
+            if prepared.audio_md5 {
+                extra_tags.push((AUDIO_MD5_TAG.to_string(), format_md5(&[0; 16])));
+            }
             let encoder = start_track_encoder(
                 &prepared.input_meta,
                 &prepared.cue,
                 &prepared.tracks,
                 &track,
-                prepared.compression_level,
+                &TrackEncoderSettings {
+                    channels: output_channels as u32,
+                    encoder: prepared.encoder,
+                    extra_tags: &extra_tags,
+                    to_stdout: prepared.to_stdout,
+                },
                 prepared.display_base_abs.as_deref(),
                 progress,
             )?;
+            if let Some(events) = events {
+                let output = if prepared.to_stdout {
+                    Cow::Borrowed(STDIN_NAME)
+                } else {
+                    track.output_path.to_string_lossy()
+                };
+                events.emit(&Event::TrackStart {
+                    source: &prepared.flac_display.to_string_lossy(),
+                    track: track.number,
+                    output: &output,
+                });
+            }
             state.encoder = Some(encoder);
+            if let Some(also) = prepared.also_encode.as_ref()
+                && let Some(path) = prepared.lossy_path(&track)
+            {
+                if let Some(parent) = path.parent() {
+                    create_output_dir(parent, &prepared.permissions)?;
+                }
+                let tags: Vec<(String, String)> = build_track_tags(
+                    &prepared.input_meta,
+                    &prepared.cue,
+                    track_total(&prepared.tracks),
+                    &track,
+                )
+                .into_iter()
+                .filter(|(key, _)| !key.eq_ignore_ascii_case(CHANNEL_MASK_TAG))
+                .collect();
+                // The FLAC tracks' pictures, which include an embedded external cover.
+                let pictures: Vec<_> = prepared
+                    .input_meta
+                    .pictures
+                    .iter()
+                    .map(picture_block)
+                    .collect();
+                state.lossy = Some(LossyEncoder::start(
+                    also.format,
+                    &path,
+                    prepared.input_meta.sample_rate,
+                    output_channels,
+                    prepared.input_meta.bits_per_sample,
+                    &tags,
+                    &pictures,
+                )?);
+            }
+            if !prepared.to_stdout {
+                state.output = Some(track.output_path.clone());
+            }
+            if prepared.audio_md5 {
+                state.hasher = Some((
+                    track.output_path.clone(),
+                    PcmHasher::new(prepared.input_meta.bits_per_sample),
+                ));
+            }
+            state.signal = Some((
+                track.number,
+                SignalStats::new(prepared.input_meta.bits_per_sample, channels),
+            ));
+            state.gain = prepared.track_gain(track.number).map(|db| {
+                (
+                    track.number,
+                    db,
+                    GainStage::new(db, prepared.input_meta.bits_per_sample),
+                )
+            });
+            state.fade = (!prepared.fade.is_none()).then(|| {
+                FadeStage::new(
+                    prepared.fade,
+                    prepared.input_meta.sample_rate,
+                    track.end - track.start,
+                )
+            });
         }
 
         let begin = local_offset * channels;
         let end = (local_offset + take) * channels;
+        if let Some((_, stats)) = state.signal.as_mut() {
+            stats.update(&block.interleaved[begin..end]);
+        }
         if let Some(encoder) = state.encoder.as_mut() {
-            encoder.write_interleaved(&block.interleaved[begin..end], take as u32)?;
+            let mut data = Cow::Borrowed(&block.interleaved[begin..end]);
+            if let Some(downmixer) = state.downmixer.as_ref() {
+                data = Cow::Owned(downmixer.process(&data));
+            }
+            if let Some((_, _, stage)) = state.gain.as_mut() {
+                stage.apply(data.to_mut());
+            }
+            if let Some(stage) = state.fade.as_mut() {
+                stage.apply(data.to_mut(), output_channels);
+            }
+            let mut hasher = state.hasher.as_mut().map(|(_, hasher)| hasher);
+            let mut lossy = state.lossy.as_mut();
+            state.trimmer.process(&data, output_channels, |data| {
+                if let Some(hasher) = hasher.as_mut() {
+                    hasher.update(data);
+                }
+                if let Some(lossy) = lossy.as_mut() {
+                    lossy.write(data)?;
+                }
+                encoder.write_interleaved(data, (data.len() / output_channels) as u32)
+            })?;
         }
 
         sample += take as u64;
@@ -310,16 +1755,36 @@ fn process_audio_block(
 }
 
 fn build_output_tracks(
+    tracks: Vec<ComputedTrack>,
     cue: &CueDisc,
     output_dir: &Path,
+    tag_overrides: &[TagOverride],
     sample_rate: u32,
-    total_samples: u64,
+    extension: &str,
     check_exists: bool,
 ) -> Result<Vec<TrackSpan>> {
-    let tracks = compute_track_spans(cue, sample_rate, total_samples)?;
-    let output_paths = compute_output_paths(&tracks, output_dir, check_exists)?;
+    let output_paths = compute_output_paths(&tracks, cue, output_dir, extension, check_exists)?;
+    let disc_total = cue.discs.iter().copied().max();
     let mut spans = Vec::with_capacity(tracks.len());
     for (track, output_path) in tracks.into_iter().zip(output_paths.into_iter()) {
+        let mut track_overrides = provenance_tags(&track, sample_rate);
+        if let (Some((disc, number)), Some(disc_total)) =
+            (track_disc(cue, track.number), disc_total)
+        {
+            let on_disc = disc_track_count(cue, disc).to_string();
+            track_overrides.extend([
+                ("DISCNUMBER".to_string(), disc.to_string()),
+                ("DISCTOTAL".to_string(), disc_total.to_string()),
+                ("TOTALDISCS".to_string(), disc_total.to_string()),
+                ("TRACKNUMBER".to_string(), number.to_string()),
+                ("TRACKTOTAL".to_string(), on_disc.clone()),
+                ("TOTALTRACKS".to_string(), on_disc),
+            ]);
+        }
+        stack_tags(
+            &mut track_overrides,
+            overrides_for_track(tag_overrides, track.number),
+        );
         spans.push(TrackSpan {
             number: track.number,
             start: track.start,
@@ -330,27 +1795,131 @@ fn build_output_tracks(
             composer: track.composer,
             isrc: track.isrc,
             rem: track.rem,
+            tag_overrides: track_overrides,
+            tag_rules: TagRules::default(),
+            fixed_tags: None,
+            compression_level: None,
             output_path,
         });
     }
     Ok(spans)
 }
 
+// How tracks after a --skip-tracks gap are numbered.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum TrackNumbering {
+    #[default]
+    Original,
+    Renumber,
+}
+
+// Numbers the written tracks without gaps, per disc on multi-disc sheets:
+// the leading digits of file names and the track number tags change.
+fn renumber_tracks(tracks: &mut [TrackSpan], cue: &CueDisc, skipped: &TrackSelection) {
+    let disc_of = |number: u32| track_disc(cue, number).map(|(disc, _)| disc);
+    let mut totals: BTreeMap<Option<u32>, u32> = BTreeMap::new();
+    let mut numbers = Vec::with_capacity(tracks.len());
+    let mut previous = None;
+    for track in tracks.iter() {
+        if skipped.contains(track.number) {
+            numbers.push(None);
+            continue;
+        }
+        let disc = disc_of(track.number);
+        // Index tracks of one cue track share its number.
+        if previous != Some(track.number) {
+            *totals.entry(disc).or_default() += 1;
+            previous = Some(track.number);
+        }
+        numbers.push(Some((disc, totals[&disc])));
+    }
+
+    for (track, number) in tracks.iter_mut().zip(numbers) {
+        let Some((disc, number)) = number else {
+            continue;
+        };
+        if let Some(name) = track.output_path.file_name().and_then(|name| name.to_str()) {
+            let width = name.bytes().take_while(u8::is_ascii_digit).count();
+            if width > 0 {
+                let renamed = format!("{:0width$}{}", number, &name[width..], width = width);
+                track.output_path.set_file_name(renamed);
+            }
+        }
+        let total = totals[&disc].to_string();
+        let mut at = 0;
+        for (key, value) in [
+            ("TRACKNUMBER", number.to_string()),
+            ("TRACKTOTAL", total.clone()),
+            ("TOTALTRACKS", total),
+        ] {
+            // Multi-disc sheets already carry per-disc numbers ahead of the
+            // user's overrides; elsewhere the new numbers go first so that
+            // explicit --tag values still win.
+            match track
+                .tag_overrides
+                .iter_mut()
+                .find(|(existing, _)| existing == key)
+                .filter(|_| disc.is_some())
+            {
+                Some(entry) => entry.1 = value,
+                None => {
+                    track.tag_overrides.insert(at, (key.to_string(), value));
+                    at += 1;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Pregap {
+    pub(crate) track: u32,
+    pub(crate) samples: u64,
+    pub(crate) kept: bool,
+}
+
+pub(crate) fn pregaps(cue: &CueDisc, tracks: &[TrackSpan], sample_rate: u32) -> Vec<Pregap> {
+    let samples_per_frame = u64::from(sample_rate / 75);
+    cue.tracks
+        .iter()
+        .filter_map(|track| {
+            let pregap = u64::try_from(track.pregap_start?).ok()? * samples_per_frame;
+            let start = u64::try_from(track.start_frames).ok()? * samples_per_frame;
+            (start > pregap).then(|| Pregap {
+                track: track.number,
+                samples: start - pregap,
+                kept: tracks
+                    .iter()
+                    .any(|span| span.start < start && span.end > pregap),
+            })
+        })
+        .collect()
+}
+
+pub(crate) fn provenance_tags(track: &ComputedTrack, sample_rate: u32) -> Vec<(String, String)> {
+    let samples_per_frame = u64::from(sample_rate / 75).max(1);
+    vec![
+        (
+            "CUE_TRACK_START".to_string(),
+            format_msf(track.start / samples_per_frame),
+        ),
+        (
+            "SOURCE_SAMPLE_RANGE".to_string(),
+            format!("{}-{}", track.start, track.end),
+        ),
+    ]
+}
+
 fn validate_cue_files(cue: &CueDisc, flac_path: &Path, enforce_filename_match: bool) -> Result<()> {
     let flac_name = flac_path
         .file_name()
         .map(|name| name.to_string_lossy().to_string())
         .unwrap_or_else(|| flac_path.to_string_lossy().to_string());
 
-    let flac_stem = flac_path
-        .file_stem()
-        .map(|stem| stem.to_string_lossy().to_string())
-        .unwrap_or_else(|| flac_name.clone());
-
     let mut files = HashSet::new();
     for track in &cue.tracks {
         if let Some(name) = &track.filename {
-            files.insert(name.clone());
+            files.insert(cue_file_name(name).to_lowercase());
         }
     }
 
@@ -362,20 +1931,18 @@ fn validate_cue_files(cue: &CueDisc, flac_path: &Path, enforce_filename_match: b
         return Ok(());
     }
 
-    if let Some(name) = files.iter().next() {
-        let cue_name = Path::new(name)
-            .file_name()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| name.clone());
-        let cue_stem = Path::new(name)
-            .file_stem()
-            .map(|n| n.to_string_lossy().to_string())
-            .unwrap_or_else(|| cue_name.clone());
-
-        if cue_name != flac_name && cue_stem != flac_stem {
+    if let Some(cue_name) = files.iter().next() {
+        let flac_lower = flac_name.to_lowercase();
+        if *cue_name != flac_lower && file_stem_of(cue_name) != file_stem_of(&flac_lower) {
+            let original = cue
+                .tracks
+                .iter()
+                .find_map(|track| track.filename.as_deref())
+                .map(cue_file_name)
+                .unwrap_or_default();
             return Err(format!(
                 "cue sheet references {}, but --flac is {}",
-                cue_name, flac_name
+                original, flac_name
             ));
         }
     }
@@ -383,9 +1950,32 @@ fn validate_cue_files(cue: &CueDisc, flac_path: &Path, enforce_filename_match: b
     Ok(())
 }
 
+fn file_stem_of(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem,
+        _ => name,
+    }
+}
+
+fn validate_tag_overrides(cue: &CueDisc, overrides: &[TagOverride]) -> Result<()> {
+    for entry in overrides {
+        if let Some(number) = entry.track
+            && !cue.tracks.iter().any(|track| track.number == number)
+        {
+            return Err(format!(
+                "tag file references track {}, but cue sheet has {} tracks",
+                number,
+                cue.tracks.len()
+            ));
+        }
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct ComputedTrack {
     pub(crate) number: u32,
+    pub(crate) index: Option<u32>,
     pub(crate) start: u64,
     pub(crate) end: u64,
     pub(crate) title: Option<String>,
@@ -400,6 +1990,210 @@ pub(crate) fn compute_track_spans(
     cue: &CueDisc,
     sample_rate: u32,
     total_samples: u64,
+) -> Result<Vec<ComputedTrack>> {
+    let tracks = compute_unchecked_track_spans(cue, sample_rate, total_samples)?;
+    if total_samples > 0
+        && let Some(track) = tracks.iter().find(|track| track.end > total_samples)
+    {
+        return Err(format!(
+            "track {} exceeds input total samples",
+            track.number
+        ));
+    }
+    Ok(tracks)
+}
+
+pub(crate) fn compute_clamped_track_spans(
+    cue: &CueDisc,
+    sample_rate: u32,
+    total_samples: u64,
+) -> Result<(Vec<ComputedTrack>, Vec<String>)> {
+    let tracks = compute_unchecked_track_spans(cue, sample_rate, total_samples)?;
+    let mut warnings = Vec::new();
+    let mut kept = Vec::with_capacity(tracks.len());
+    for mut track in tracks {
+        if track.start >= total_samples {
+            warnings.push(format!(
+                "track {} starts after the end of the audio and is skipped",
+                track.number
+            ));
+            continue;
+        }
+        if track.end > total_samples {
+            let missing = track.end - total_samples;
+            warnings.push(format!(
+                "track {} is clamped to the end of the audio; {} ({} samples) missing",
+                track.number,
+                format_time(missing, sample_rate),
+                missing
+            ));
+            track.end = total_samples;
+        }
+        kept.push(track);
+    }
+    if kept.is_empty() {
+        return Err("no cue track starts within the audio".to_string());
+    }
+    Ok((kept, warnings))
+}
+
+pub(crate) fn apply_read_offset(
+    tracks: Vec<ComputedTrack>,
+    offset: i64,
+    total_samples: u64,
+) -> Vec<ComputedTrack> {
+    if offset == 0 {
+        return tracks;
+    }
+    let shift = |position: u64| {
+        let shifted = position as i64 + offset;
+        shifted.clamp(0, total_samples as i64) as u64
+    };
+    tracks
+        .into_iter()
+        .map(|mut track| {
+            track.start = shift(track.start);
+            track.end = shift(track.end);
+            track
+        })
+        .filter(|track| track.end > track.start)
+        .collect()
+}
+
+pub(crate) fn check_strict_cdda(meta: &InputMetadata, cue: &CueDisc) -> Result<()> {
+    const CDDA_SAMPLE_RATE: u32 = 44100;
+    const CDDA_SAMPLES_PER_SECTOR: u64 = 588;
+
+    let mut problems = Vec::new();
+    if meta.sample_rate != CDDA_SAMPLE_RATE || meta.bits_per_sample != 16 || meta.channels != 2 {
+        problems.push(format!(
+            "source is {} Hz, {} bits, {} ch (CDDA is 44100 Hz, 16 bits, 2 ch)",
+            meta.sample_rate, meta.bits_per_sample, meta.channels
+        ));
+    }
+    if !meta.total_samples.is_multiple_of(CDDA_SAMPLES_PER_SECTOR) {
+        problems.push(format!(
+            "source length of {} samples is not a whole number of CD sectors ({} samples left over)",
+            meta.total_samples,
+            meta.total_samples % CDDA_SAMPLES_PER_SECTOR
+        ));
+    }
+    problems.extend(cue.mode_issues.iter().cloned());
+    for track in &cue.tracks {
+        if track.length_frames.is_some_and(|length| length < 0) || track.start_frames < 0 {
+            problems.push(format!(
+                "track {} has a negative sector offset or length",
+                track.number
+            ));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "not a CDDA image (--strict-cdda): {}",
+            problems.join("; ")
+        ))
+    }
+}
+
+pub(crate) fn tag_completeness_problems(missing: &[(u32, Vec<&str>)]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for key in REQUIRED_TAGS {
+        let numbers: Vec<String> = missing
+            .iter()
+            .filter(|(_, keys)| keys.contains(&key))
+            .map(|(number, _)| format!("{:02}", number))
+            .collect();
+        if numbers.is_empty() {
+            continue;
+        }
+        if numbers.len() == missing.len() {
+            problems.push(format!("no track would get {}", key));
+        } else {
+            problems.push(format!(
+                "{} {} would lack {}",
+                if numbers.len() == 1 {
+                    "track"
+                } else {
+                    "tracks"
+                },
+                numbers.join(", "),
+                key
+            ));
+        }
+    }
+    problems
+}
+
+pub(crate) fn check_span_sanity(tracks: &[ComputedTrack], sample_rate: u32) -> Vec<String> {
+    const CDDA_MIN_TRACK_SECONDS: u64 = 4;
+
+    let time = |samples: u64| format_time(samples, sample_rate);
+    let mut warnings = Vec::new();
+    for track in tracks {
+        let length = track.end - track.start;
+        if length < CDDA_MIN_TRACK_SECONDS * sample_rate as u64 {
+            warnings.push(tr_with(
+                Msg::ShortTrack,
+                &[&track.number, &time(length), &CDDA_MIN_TRACK_SECONDS],
+            ));
+        }
+    }
+    for pair in tracks.windows(2) {
+        let (current, next) = (&pair[0], &pair[1]);
+        if current.end > next.start {
+            warnings.push(tr_with(
+                Msg::TracksOverlap,
+                &[
+                    &current.number,
+                    &next.number,
+                    &time(current.end - next.start),
+                ],
+            ));
+        } else if current.end < next.start {
+            warnings.push(tr_with(
+                Msg::UncoveredGap,
+                &[
+                    &time(next.start - current.end),
+                    &current.number,
+                    &next.number,
+                ],
+            ));
+        }
+    }
+    warnings
+}
+
+fn length_difference(total_samples: u64, cue_length: u64, sample_rate: u32) -> String {
+    tr_with(
+        Msg::LengthDiffers,
+        &[
+            &format_time(total_samples, sample_rate),
+            &format_time(cue_length, sample_rate),
+            &format_length_difference(total_samples as i64 - cue_length as i64, sample_rate),
+        ],
+    )
+}
+
+fn cue_length_samples(cue: &CueDisc, sample_rate: u32) -> Result<Option<u64>> {
+    let Some(last) = cue.tracks.last() else {
+        return Ok(None);
+    };
+    match last.length_frames {
+        Some(length) if length >= 0 => Ok(Some(frames_to_samples(
+            last.start_frames + length,
+            sample_rate,
+        )?)),
+        _ => Ok(None),
+    }
+}
+
+fn compute_unchecked_track_spans(
+    cue: &CueDisc,
+    sample_rate: u32,
+    total_samples: u64,
 ) -> Result<Vec<ComputedTrack>> {
     if sample_rate == 0 {
         return Err("input sample rate is zero".to_string());
@@ -413,7 +2207,7 @@ pub(crate) fn compute_track_spans(
 
     let mut tracks = Vec::with_capacity(cue.tracks.len());
     for (idx, track) in cue.tracks.iter().enumerate() {
-        let start = frames_to_samples(track.start_frames, sample_rate)?;
+        let start = track_start_sample(track, sample_rate)?;
         let length_frames = match track.length_frames {
             Some(length) if length >= 0 => Some(length),
             _ => {
@@ -427,7 +2221,13 @@ pub(crate) fn compute_track_spans(
         };
 
         let end = if let Some(length) = length_frames {
-            start + frames_to_samples(length, sample_rate)?
+            let end_frames = track.start_frames + length;
+            match cue.tracks.get(idx + 1) {
+                Some(next) if next.start_frames == end_frames => {
+                    track_start_sample(next, sample_rate)?
+                }
+                _ => frames_to_samples(end_frames, sample_rate)?,
+            }
         } else {
             if total_samples == 0 {
                 return Err("input total samples unavailable for final track".to_string());
@@ -438,15 +2238,10 @@ pub(crate) fn compute_track_spans(
         if end <= start {
             return Err(format!("track {} has invalid length", track.number));
         }
-        if total_samples > 0 && end > total_samples {
-            return Err(format!(
-                "track {} exceeds input total samples",
-                track.number
-            ));
-        }
 
         tracks.push(ComputedTrack {
             number: track.number,
+            index: None,
             start,
             end,
             title: track.title.clone(),
@@ -461,6 +2256,87 @@ pub(crate) fn compute_track_spans(
     Ok(tracks)
 }
 
+fn track_start_sample(track: &CueTrack, sample_rate: u32) -> Result<u64> {
+    match track.rem.sample_offset {
+        Some(offset) => Ok(offset),
+        None => frames_to_samples(track.start_frames, sample_rate),
+    }
+}
+
+pub(crate) fn check_sample_offsets(cue: &CueDisc, sample_rate: u32) -> Vec<String> {
+    let samples_per_frame = (sample_rate / 75) as u64;
+    let mut warnings = Vec::new();
+    for track in &cue.tracks {
+        let (Some(offset), Ok(index)) = (
+            track.rem.sample_offset,
+            frames_to_samples(track.start_frames, sample_rate),
+        ) else {
+            continue;
+        };
+        if offset.abs_diff(index) >= samples_per_frame {
+            warnings.push(format!(
+                "track {} starts at sample {}, more than a CD frame from its INDEX 01 at {}",
+                track.number,
+                offset,
+                format_msf(track.start_frames.max(0) as u64)
+            ));
+        }
+    }
+    warnings
+}
+
+pub(crate) fn split_sub_indexes(
+    tracks: Vec<ComputedTrack>,
+    cue: &CueDisc,
+    sample_rate: u32,
+) -> Result<Vec<ComputedTrack>> {
+    let mut pieces = Vec::with_capacity(tracks.len());
+    for track in tracks {
+        let mut bounds = Vec::new();
+        if let Some(cue_track) = cue
+            .tracks
+            .iter()
+            .find(|cue_track| cue_track.number == track.number)
+        {
+            for (number, frames) in &cue_track.sub_indexes {
+                let position = frames_to_samples(*frames, sample_rate)?;
+                if position > track.start && position < track.end {
+                    bounds.push((*number, position));
+                }
+            }
+        }
+        if bounds.is_empty() {
+            pieces.push(track);
+            continue;
+        }
+        let mut start = track.start;
+        let mut index = 1;
+        for (number, position) in bounds.into_iter().chain(Some((0, track.end))) {
+            if position <= start {
+                return Err(format!(
+                    "index {:02} of track {} does not come after the previous index",
+                    number, track.number
+                ));
+            }
+            pieces.push(ComputedTrack {
+                index: Some(index),
+                start,
+                end: position,
+                ..track.clone()
+            });
+            start = position;
+            index = number;
+        }
+    }
+    Ok(pieces)
+}
+
+// Track positions are 64-bit; the distance to one only has to fit in a block,
+// so it is clamped before narrowing instead of being truncated on 32-bit targets.
+pub(crate) fn samples_until(position: u64, target: u64, limit: usize) -> usize {
+    usize::try_from(target.saturating_sub(position)).map_or(limit, |gap| gap.min(limit))
+}
+
 pub(crate) fn frames_to_samples(frames: i64, sample_rate: u32) -> Result<u64> {
     if frames < 0 {
         return Err("negative frame count in cue sheet".to_string());
@@ -477,10 +2353,22 @@ pub(crate) fn frames_to_samples(frames: i64, sample_rate: u32) -> Result<u64> {
 
 fn compute_output_paths(
     tracks: &[ComputedTrack],
+    cue: &CueDisc,
     output_dir: &Path,
+    extension: &str,
     check_exists: bool,
 ) -> Result<Vec<PathBuf>> {
-    let width = tracks.len().to_string().len();
+    let numbers = match cue.discs.iter().max() {
+        Some(&last) => (1..=last)
+            .map(|disc| disc_track_count(cue, disc))
+            .max()
+            .unwrap_or(0),
+        None => tracks
+            .iter()
+            .filter(|track| track.index.is_none_or(|index| index == 1))
+            .count(),
+    };
+    let width = numbers.to_string().len();
     let mut seen = HashSet::new();
     let mut paths = Vec::with_capacity(tracks.len());
     for track in tracks {
@@ -490,17 +2378,27 @@ fn compute_output_paths(
             .map(sanitize_filename)
             .unwrap_or_else(String::new);
 
+        // Tracks of a merged multi-disc sheet are numbered per disc and go
+        // into one subdirectory per disc.
+        let (track_dir, number) = match track_disc(cue, track.number) {
+            Some((disc, number)) => (output_dir.join(disc_dir_name(disc)), number),
+            None => (output_dir.to_path_buf(), track.number),
+        };
+        let number = match track.index {
+            Some(index) => format!("{:0width$}.{:02}", number, index, width = width),
+            None => format!("{:0width$}", number, width = width),
+        };
         let base = if name.is_empty() {
-            format!("{:0width$}", track.number, width = width)
+            number
         } else {
-            format!("{:0width$} - {}", track.number, name, width = width)
+            format!("{} - {}", number, name)
         };
 
-        let filename = format!("{}.flac", base);
-        let path = output_dir.join(filename);
+        let filename = format!("{}.{}", base, extension);
+        let path = track_dir.join(filename);
 
         if check_exists && path.exists() {
-            return Err(format!("output file already exists: {}", path.display()));
+            return Err(tr_with(Msg::OutputExists, &[&path.display()]));
         }
         if !seen.insert(path.clone()) {
             return Err(format!(
@@ -515,6 +2413,28 @@ fn compute_output_paths(
     Ok(paths)
 }
 
+pub(crate) fn disc_dir_name(disc: u32) -> String {
+    format!("Disc {}", disc)
+}
+
+pub(crate) fn sanitization_warnings(tracks: &[TrackSpan]) -> Vec<String> {
+    // Path separators are routinely swapped for `_`; anything else that
+    // disappears from a title is worth a look.
+    tracks
+        .iter()
+        .filter_map(|track| {
+            let title = track.title.as_deref()?;
+            let sanitized = sanitize_filename(title);
+            (sanitized != title.replace(['/', '\\', '\0'], "_").trim()).then(|| {
+                format!(
+                    "track {:02} title {:?} lost characters in its file name {:?}",
+                    track.number, title, sanitized
+                )
+            })
+        })
+        .collect()
+}
+
 pub(crate) fn sanitize_filename(value: &str) -> String {
     let mut out = String::new();
     for ch in value.chars() {
@@ -530,85 +2450,231 @@ pub(crate) fn sanitize_filename(value: &str) -> String {
     out.trim().to_string()
 }
 
-fn ensure_output_paths_available(tracks: &[TrackSpan], overwrite: bool) -> Result<()> {
-    for track in tracks {
-        if track.output_path.exists() {
+fn source_bytes(path: &Path) -> Option<u64> {
+    if is_stdin_path(path) {
+        return None;
+    }
+    fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+#[cfg(unix)]
+fn available_space(dir: &Path) -> Option<u64> {
+    use crate::flac::path_to_cstring;
+
+    let path = path_to_cstring(dir).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64))
+}
+
+#[cfg(not(unix))]
+fn available_space(_dir: &Path) -> Option<u64> {
+    None
+}
+
+fn ensure_output_paths_available<'a>(
+    tracks: impl Iterator<Item = &'a TrackSpan>,
+    overwrite: bool,
+) -> Result<()> {
+    ensure_paths_available(tracks.map(|track| track.output_path.as_path()), overwrite)
+}
+
+fn ensure_paths_available<'a>(
+    paths: impl Iterator<Item = &'a Path>,
+    overwrite: bool,
+) -> Result<()> {
+    for path in paths {
+        if path.exists() {
             if overwrite {
-                fs::remove_file(&track.output_path).map_err(|err| {
-                    format!(
-                        "failed to remove existing file {}: {}",
-                        track.output_path.display(),
-                        err
-                    )
+                fs::remove_file(path).map_err(|err| {
+                    format!("failed to remove existing file {}: {}", path.display(), err)
                 })?;
             } else {
-                return Err(format!(
-                    "output file already exists: {}",
-                    track.output_path.display()
-                ));
+                return Err(tr_with(Msg::OutputExists, &[&path.display()]));
             }
         }
     }
     Ok(())
 }
 
-pub(crate) fn processed_flac_path(flac_path: &Path) -> Option<PathBuf> {
-    let file_name = flac_path.file_name()?.to_str()?;
-    Some(flac_path.with_file_name(format!("{}.processed", file_name)))
+pub(crate) fn archived_path(path: &Path, dir: &Path, base: &Path) -> PathBuf {
+    match path.strip_prefix(base) {
+        Ok(relative) => dir.join(relative),
+        Err(_) => dir.join(path.file_name().unwrap_or(path.as_os_str())),
+    }
 }
 
-fn handle_original_flac(
-    display_base_abs: Option<&Path>,
+fn source_files(
     flac_path: &Path,
-    delete_original: bool,
-    rename_original: bool,
-) -> Result<()> {
-    if delete_original {
-        fs::remove_file(flac_path).map_err(|err| {
-            format!(
-                "split succeeded, but failed to delete original file {}: {}",
-                flac_path.display(),
-                err
-            )
-        })?;
-        let display = display_path(display_base_abs, flac_path);
-        println!(
-            "{} {}",
-            "Deleted".red().bold(),
-            display.display().to_string().red()
-        );
-        return Ok(());
+    cue_path: &Path,
+    action: &SourceAction,
+    include_sidecars: bool,
+) -> Vec<PathBuf> {
+    let mut files = vec![flac_path.to_path_buf()];
+    if *action == SourceAction::Keep {
+        return files;
     }
+    if cue_path != flac_path
+        && cue_path.is_file()
+        && (include_sidecars || *action != SourceAction::Delete)
+    {
+        files.push(cue_path.to_path_buf());
+    }
+    if include_sidecars {
+        for log in find_log_sidecars(&[flac_path, cue_path]) {
+            if !files.contains(&log) {
+                files.push(log);
+            }
+        }
+    }
+    files
+}
 
-    if rename_original {
-        let renamed = processed_flac_path(flac_path)
-            .ok_or_else(|| format!("failed to rename original file: {}", flac_path.display()))?;
-        fs::rename(flac_path, &renamed).map_err(|err| {
-            format!(
-                "split succeeded, but failed to rename original file {} -> {}: {}",
-                flac_path.display(),
-                renamed.display(),
-                err
-            )
-        })?;
-        let from_display = display_path(display_base_abs, flac_path);
-        let to_display = display_path(display_base_abs, &renamed);
-        println!(
-            "{} {} -> {}",
-            "Renamed".yellow().bold(),
-            from_display.display().to_string().yellow(),
-            to_display.display().to_string().yellow()
-        );
+fn find_log_sidecars(sources: &[&Path]) -> Vec<PathBuf> {
+    let mut logs = Vec::new();
+    for source in sources {
+        let (Some(dir), Some(stem)) = (source.parent(), source.file_stem()) else {
+            continue;
+        };
+        let Ok(entries) = fs::read_dir(dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let is_log = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("log"));
+            if is_log && path.file_stem() == Some(stem) && path.is_file() && !logs.contains(&path) {
+                logs.push(path);
+            }
+        }
+    }
+    logs.sort();
+    logs
+}
+
+fn handle_original_flac(
+    display_base_abs: Option<&Path>,
+    paths: &[PathBuf],
+    action: &SourceAction,
+    report: &mut AlbumReport,
+) -> Result<()> {
+    match action {
+        SourceAction::Keep => {}
+        SourceAction::Trash => {
+            for path in paths {
+                trash::delete(path).map_err(|err| {
+                    format!(
+                        "split succeeded, but failed to move {} to the trash: {}",
+                        path.display(),
+                        err
+                    )
+                })?;
+                let display = display_path(display_base_abs, path);
+                report.status(format!(
+                    "{} {}",
+                    "Trashed".yellow().bold(),
+                    display.display().to_string().yellow()
+                ));
+            }
+        }
+        SourceAction::Archive { dir, base } => {
+            for path in paths {
+                let target = archived_path(path, dir, base);
+                move_file(path, &target).map_err(|err| {
+                    format!(
+                        "split succeeded, but failed to archive {} -> {}: {}",
+                        path.display(),
+                        target.display(),
+                        err
+                    )
+                })?;
+                let from_display = display_path(display_base_abs, path);
+                let to_display = display_path(display_base_abs, &target);
+                report.status(format!(
+                    "{} {} -> {}",
+                    "Archived".yellow().bold(),
+                    from_display.display().to_string().yellow(),
+                    to_display.display().to_string().yellow()
+                ));
+            }
+        }
+        SourceAction::Delete => {
+            for path in paths {
+                fs::remove_file(path).map_err(|err| {
+                    format!(
+                        "split succeeded, but failed to delete original file {}: {}",
+                        path.display(),
+                        err
+                    )
+                })?;
+                let display = display_path(display_base_abs, path);
+                report.status(format!(
+                    "{} {}",
+                    "Deleted".red().bold(),
+                    display.display().to_string().red()
+                ));
+            }
+        }
+        SourceAction::Rename { template } => {
+            for path in paths {
+                let renamed = render_rename_template(template, path)?;
+                if renamed.exists() {
+                    return Err(format!(
+                        "split succeeded, but cannot rename original file {}: {} already exists",
+                        path.display(),
+                        renamed.display()
+                    ));
+                }
+                fs::rename(path, &renamed).map_err(|err| {
+                    format!(
+                        "split succeeded, but failed to rename original file {} -> {}: {}",
+                        path.display(),
+                        renamed.display(),
+                        err
+                    )
+                })?;
+                let from_display = display_path(display_base_abs, path);
+                let to_display = display_path(display_base_abs, &renamed);
+                report.status(format!(
+                    "{} {} -> {}",
+                    "Renamed".yellow().bold(),
+                    from_display.display().to_string().yellow(),
+                    to_display.display().to_string().yellow()
+                ));
+            }
+        }
     }
 
     Ok(())
 }
 
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if to.exists() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::AlreadyExists,
+            "target already exists",
+        ));
+    }
+    if let Some(parent) = to.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
 #[cfg(test)]
 mod tests {
-    use super::validate_cue_files;
-    use crate::types::{CueDisc, CueRem, CueTrack};
-    use std::path::Path;
+    use super::{encoder_threads, renumber_tracks, validate_cue_files};
+    use crate::cli::parse_track_selection;
+    use crate::rules::TagRules;
+    use crate::types::{CueDisc, CueRem, CueTrack, TrackSpan};
+    use std::path::{Path, PathBuf};
 
     fn cue_with_filenames(names: &[&str]) -> CueDisc {
         let tracks = names
@@ -623,6 +2689,8 @@ mod tests {
                 isrc: None,
                 start_frames: 0,
                 length_frames: None,
+                sub_indexes: Vec::new(),
+                pregap_start: None,
                 filename: Some((*name).to_string()),
                 rem: CueRem::default(),
             })
@@ -638,6 +2706,8 @@ mod tests {
             disc_id: None,
             rem: CueRem::default(),
             tracks,
+            discs: Vec::new(),
+            mode_issues: Vec::new(),
         }
     }
 
@@ -655,6 +2725,17 @@ mod tests {
         assert!(validate_cue_files(&cue, flac_path, true).is_err());
     }
 
+    #[test]
+    fn validate_cue_files_ignores_windows_paths_and_case() {
+        let flac_path = Path::new("Album.flac");
+        let cue = cue_with_filenames(&["D:\\RIPS\\ALBUM.WAV"]);
+        assert!(validate_cue_files(&cue, flac_path, true).is_ok());
+        let cue = cue_with_filenames(&["/home/rip/album.flac", "album.flac"]);
+        assert!(validate_cue_files(&cue, flac_path, true).is_ok());
+        let cue = cue_with_filenames(&["C:Other.wav"]);
+        assert!(validate_cue_files(&cue, flac_path, true).is_err());
+    }
+
     #[test]
     fn validate_cue_files_rejects_multiple_audio_files_always() {
         let cue = cue_with_filenames(&["Disc A.flac", "Disc B.flac"]);
@@ -662,4 +2743,59 @@ mod tests {
         assert!(validate_cue_files(&cue, flac_path, false).is_err());
         assert!(validate_cue_files(&cue, flac_path, true).is_err());
     }
+
+    #[test]
+    fn renumbering_closes_the_gaps_of_skipped_tracks() {
+        let cue = cue_with_filenames(&["Album.flac"; 4]);
+        let mut tracks: Vec<TrackSpan> =
+            ["01 - Intro", "02 - Song", "03.01 - Suite", "03.02 - Suite"]
+                .iter()
+                .zip([1, 2, 3, 3])
+                .map(|(name, number)| TrackSpan {
+                    number,
+                    start: 0,
+                    end: 0,
+                    title: None,
+                    performer: None,
+                    songwriter: None,
+                    composer: None,
+                    isrc: None,
+                    rem: CueRem::default(),
+                    tag_overrides: vec![("TRACKTOTAL".to_string(), "9".to_string())],
+                    tag_rules: TagRules::default(),
+                    fixed_tags: None,
+                    compression_level: None,
+                    output_path: PathBuf::from("Album").join(format!("{}.flac", name)),
+                })
+                .collect();
+        renumber_tracks(&mut tracks, &cue, &parse_track_selection("1").unwrap());
+
+        let names: Vec<_> = tracks
+            .iter()
+            .map(|track| track.output_path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "01 - Intro.flac",
+                "01 - Song.flac",
+                "02.01 - Suite.flac",
+                "02.02 - Suite.flac"
+            ]
+        );
+        let tags = &tracks[3].tag_overrides;
+        assert_eq!(tags[0], ("TRACKNUMBER".to_string(), "2".to_string()));
+        assert_eq!(tags[2], ("TOTALTRACKS".to_string(), "2".to_string()));
+        // An explicit override still comes last.
+        assert_eq!(tags[3], ("TRACKTOTAL".to_string(), "9".to_string()));
+    }
+
+    #[test]
+    fn spare_jobs_go_to_the_encoders() {
+        assert_eq!(encoder_threads(1, 8), 8);
+        assert_eq!(encoder_threads(3, 8), 2);
+        assert_eq!(encoder_threads(8, 8), 1);
+        assert_eq!(encoder_threads(20, 8), 1);
+        assert_eq!(encoder_threads(0, 4), 4);
+    }
 }
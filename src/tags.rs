@@ -0,0 +1,286 @@
+use std::fs;
+use std::path::Path;
+
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TagOverride {
+    pub(crate) track: Option<u32>,
+    pub(crate) key: String,
+    pub(crate) value: String,
+}
+
+pub(crate) fn load_tag_overrides(path: &Path) -> Result<Vec<TagOverride>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|err| format!("failed to read tag file {}: {}", path.display(), err))?;
+    parse_tag_overrides(&contents)
+        .map_err(|err| format!("invalid tag file {}: {}", path.display(), err))
+}
+
+pub(crate) fn parse_tag_overrides(contents: &str) -> Result<Vec<TagOverride>> {
+    let mut overrides = Vec::new();
+    for (index, raw) in contents.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw.trim_start_matches('\u{feff}').trim_end_matches('\r');
+        if line.trim().is_empty() || line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        let fields =
+            split_csv_line(line).map_err(|err| format!("line {}: {}", line_number, err))?;
+        if fields.len() != 3 {
+            return Err(format!(
+                "line {}: expected 3 columns (track, key, value), found {}",
+                line_number,
+                fields.len()
+            ));
+        }
+
+        let track_field = fields[0].trim();
+        if overrides.is_empty() && track_field.eq_ignore_ascii_case("track") {
+            continue;
+        }
+        let track = if track_field.is_empty() || track_field == "*" {
+            None
+        } else {
+            let number: u32 = track_field.parse().map_err(|_| {
+                format!(
+                    "line {}: invalid track number {:?} (use a number or *)",
+                    line_number, track_field
+                )
+            })?;
+            Some(number)
+        };
+
+        let key = fields[1].trim().to_ascii_uppercase();
+        if !is_valid_tag_key(&key) {
+            return Err(format!("line {}: invalid tag name {:?}", line_number, key));
+        }
+
+        overrides.push(TagOverride {
+            track,
+            key,
+            value: fields[2].trim().to_string(),
+        });
+    }
+    Ok(overrides)
+}
+
+// A key set for the track replaces the album-wide rows for that key.
+pub(crate) fn overrides_for_track(overrides: &[TagOverride], number: u32) -> Vec<(String, String)> {
+    let per_track: Vec<&TagOverride> = overrides
+        .iter()
+        .filter(|entry| entry.track == Some(number))
+        .collect();
+    let album_wide = overrides.iter().filter(|entry| {
+        entry.track.is_none()
+            && !per_track
+                .iter()
+                .any(|track| track.key.eq_ignore_ascii_case(&entry.key))
+    });
+    album_wide
+        .chain(per_track.iter().copied())
+        .map(|entry| (entry.key.clone(), entry.value.clone()))
+        .collect()
+}
+
+// Stacks a later source of overrides on an earlier one: a key the later source
+// sets for a track, or for all tracks, drops what the earlier one set there.
+pub(crate) fn stack_tag_overrides(
+    mut base: Vec<TagOverride>,
+    top: &[TagOverride],
+) -> Vec<TagOverride> {
+    base.retain(|entry| {
+        !top.iter().any(|over| {
+            over.key.eq_ignore_ascii_case(&entry.key)
+                && (over.track.is_none() || over.track == entry.track)
+        })
+    });
+    base.extend(top.iter().cloned());
+    base
+}
+
+// Like `stack_tag_overrides`, for the overrides of a single track.
+pub(crate) fn stack_tags(base: &mut Vec<(String, String)>, top: Vec<(String, String)>) {
+    base.retain(|(key, _)| !top.iter().any(|(over, _)| over.eq_ignore_ascii_case(key)));
+    base.extend(top);
+}
+
+// Every override of a key replaces the existing values together, so several
+// rows with the same key set a multi-valued tag such as ARTIST or GENRE. Rows
+// with an empty value only remove.
+pub(crate) fn apply_tag_overrides(
+    mut tags: Vec<(String, String)>,
+    overrides: &[(String, String)],
+) -> Vec<(String, String)> {
+    let mut keys: Vec<&str> = Vec::new();
+    for (key, _) in overrides {
+        if !keys.iter().any(|seen| seen.eq_ignore_ascii_case(key)) {
+            keys.push(key);
+        }
+    }
+    for key in keys {
+        tags.retain(|(existing, _)| !existing.eq_ignore_ascii_case(key));
+        tags.extend(
+            overrides
+                .iter()
+                .filter(|(over, value)| over.eq_ignore_ascii_case(key) && !value.is_empty())
+                .cloned(),
+        );
+    }
+    tags
+}
+
+pub(crate) fn is_valid_tag_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|byte| (0x20..=0x7d).contains(&byte) && byte != b'=')
+}
+
+pub(crate) fn split_csv_line(line: &str) -> Result<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        if in_quotes {
+            if ch == '"' {
+                if chars.peek() == Some(&'"') {
+                    current.push('"');
+                    chars.next();
+                } else {
+                    in_quotes = false;
+                }
+            } else {
+                current.push(ch);
+            }
+            continue;
+        }
+
+        match ch {
+            '"' if current.trim().is_empty() => {
+                current.clear();
+                in_quotes = true;
+            }
+            ',' => fields.push(std::mem::take(&mut current)),
+            _ => current.push(ch),
+        }
+    }
+
+    if in_quotes {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(current);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        TagOverride, apply_tag_overrides, overrides_for_track, parse_tag_overrides,
+        stack_tag_overrides,
+    };
+
+    #[test]
+    fn parse_tag_overrides_skips_header_and_comments() {
+        let overrides = parse_tag_overrides(
+            "track,key,value\n# fixes\n1,title,\"Intro, Part 1\"\n*,genre,Jazz\n",
+        )
+        .unwrap();
+        assert_eq!(
+            overrides,
+            vec![
+                TagOverride {
+                    track: Some(1),
+                    key: "TITLE".to_string(),
+                    value: "Intro, Part 1".to_string(),
+                },
+                TagOverride {
+                    track: None,
+                    key: "GENRE".to_string(),
+                    value: "Jazz".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_tag_overrides_rejects_bad_rows() {
+        assert!(parse_tag_overrides("1,TITLE\n").is_err());
+        assert!(parse_tag_overrides("one,TITLE,x\n").is_err());
+        assert!(parse_tag_overrides("1,TI=TLE,x\n").is_err());
+        assert!(parse_tag_overrides("1,TITLE,\"open\n").is_err());
+    }
+
+    #[test]
+    fn parse_tag_overrides_unescapes_double_quotes() {
+        let overrides = parse_tag_overrides("2,TITLE,\"The \"\"Best\"\" Song\"\n").unwrap();
+        assert_eq!(overrides[0].value, "The \"Best\" Song");
+    }
+
+    #[test]
+    fn overrides_replace_and_remove_tags() {
+        let overrides = parse_tag_overrides("*,GENRE,Jazz\n2,TITLE,Second\n2,COMMENT,\n").unwrap();
+        let track_overrides = overrides_for_track(&overrides, 2);
+        let tags = vec![
+            ("TITLE".to_string(), "Old".to_string()),
+            ("COMMENT".to_string(), "rip".to_string()),
+            ("GENRE".to_string(), "Rock".to_string()),
+        ];
+        assert_eq!(
+            apply_tag_overrides(tags, &track_overrides),
+            vec![
+                ("GENRE".to_string(), "Jazz".to_string()),
+                ("TITLE".to_string(), "Second".to_string()),
+            ]
+        );
+        assert_eq!(overrides_for_track(&overrides, 1).len(), 1);
+    }
+
+    #[test]
+    fn repeated_keys_set_every_value() {
+        let overrides = parse_tag_overrides(
+            "*,ARTIST,Album Artist\n2,ARTIST,One\n2,GENRE,Jazz\n2,ARTIST,Two\n",
+        )
+        .unwrap();
+        let tags = vec![
+            ("ARTIST".to_string(), "Old".to_string()),
+            ("GENRE".to_string(), "Rock".to_string()),
+        ];
+        assert_eq!(
+            apply_tag_overrides(tags.clone(), &overrides_for_track(&overrides, 2)),
+            vec![
+                ("ARTIST".to_string(), "One".to_string()),
+                ("ARTIST".to_string(), "Two".to_string()),
+                ("GENRE".to_string(), "Jazz".to_string()),
+            ]
+        );
+        assert_eq!(
+            apply_tag_overrides(tags, &overrides_for_track(&overrides, 1)),
+            vec![
+                ("GENRE".to_string(), "Rock".to_string()),
+                ("ARTIST".to_string(), "Album Artist".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn later_override_sources_replace_earlier_keys() {
+        let detected =
+            parse_tag_overrides("*,DISCNUMBER,2\n*,GENRE,Rock\n3,ARTIST,Guest\n").unwrap();
+        let from_file =
+            parse_tag_overrides("*,DISCNUMBER,1\n*,ARTIST,Band\n*,ARTIST,Friends\n").unwrap();
+        let stacked = stack_tag_overrides(detected, &from_file);
+        assert_eq!(
+            overrides_for_track(&stacked, 3),
+            vec![
+                ("GENRE".to_string(), "Rock".to_string()),
+                ("DISCNUMBER".to_string(), "1".to_string()),
+                ("ARTIST".to_string(), "Band".to_string()),
+                ("ARTIST".to_string(), "Friends".to_string()),
+            ]
+        );
+    }
+}
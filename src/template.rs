@@ -0,0 +1,211 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::Result;
+use crate::metadata::find_tag;
+use crate::split::sanitize_filename;
+
+pub(crate) const DEFAULT_TRACK_TEMPLATE: &str = "{TRACKNUMBER:02} - {TITLE}";
+pub(crate) const DEFAULT_RENAME_TEMPLATE: &str = "{file}.processed";
+
+pub(crate) fn render_template(
+    template: &str,
+    tags: &[(String, String)],
+    transform: impl Fn(&str) -> String,
+) -> Result<String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut field = String::new();
+                let mut closed = false;
+                for next in chars.by_ref() {
+                    if next == '}' {
+                        closed = true;
+                        break;
+                    }
+                    field.push(next);
+                }
+                if !closed {
+                    return Err(format!("unterminated field in template {:?}", template));
+                }
+                out.push_str(&transform(&render_field(&field, tags)?));
+            }
+            '}' => {
+                return Err(format!("unmatched '}}' in template {:?}", template));
+            }
+            _ => out.push(ch),
+        }
+    }
+
+    Ok(out)
+}
+
+fn render_field(field: &str, tags: &[(String, String)]) -> Result<String> {
+    let (key, format) = match field.split_once(':') {
+        Some((key, format)) => (key.trim(), Some(format.trim())),
+        None => (field.trim(), None),
+    };
+    if key.is_empty() {
+        return Err("empty field name in template".to_string());
+    }
+
+    let value = find_tag(tags, key).unwrap_or("");
+    let Some(format) = format else {
+        return Ok(value.to_string());
+    };
+
+    let width: usize = format
+        .parse()
+        .map_err(|_| format!("invalid width {:?} for template field {}", format, key))?;
+    let pad_zero = format.starts_with('0');
+    let number = value.split('/').next().unwrap_or("").trim();
+    match number.parse::<u64>() {
+        Ok(number) if pad_zero => Ok(format!("{:0width$}", number, width = width)),
+        Ok(number) => Ok(format!("{:width$}", number, width = width)),
+        Err(_) => Ok(value.to_string()),
+    }
+}
+
+pub(crate) fn render_path_template(template: &str, tags: &[(String, String)]) -> Result<PathBuf> {
+    let rendered = render_template(template, tags, sanitize_filename)?;
+    let mut path = PathBuf::new();
+    for part in rendered.split('/') {
+        let part = part.trim();
+        if part.is_empty() {
+            continue;
+        }
+        if matches!(
+            Path::new(part).components().next(),
+            Some(Component::ParentDir | Component::CurDir)
+        ) {
+            return Err(format!(
+                "template produced invalid path component {:?}",
+                part
+            ));
+        }
+        path.push(part);
+    }
+    if path.as_os_str().is_empty() {
+        return Err(format!("template {:?} produced an empty name", template));
+    }
+    Ok(path)
+}
+
+pub(crate) fn render_rename_template(template: &str, path: &Path) -> Result<PathBuf> {
+    let file = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("cannot rename {}: invalid file name", path.display()))?;
+    let (name, ext) = match file.rsplit_once('.') {
+        Some((name, ext)) if !name.is_empty() => (name, ext),
+        _ => (file, ""),
+    };
+    let fields = vec![
+        ("file".to_string(), file.to_string()),
+        ("name".to_string(), name.to_string()),
+        ("ext".to_string(), ext.to_string()),
+        ("date".to_string(), today_iso_date()),
+    ];
+
+    let rendered = render_template(template, &fields, str::to_string)?;
+    let rendered = rendered.trim();
+    if rendered.is_empty() || rendered.contains(['/', '\\']) || rendered == file {
+        return Err(format!(
+            "rename template {:?} must produce a new file name without directories (got {:?})",
+            template, rendered
+        ));
+    }
+    Ok(path.with_file_name(rendered))
+}
+
+fn today_iso_date() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{civil_from_days, render_path_template, render_rename_template, render_template};
+    use std::path::PathBuf;
+
+    fn tags() -> Vec<(String, String)> {
+        vec![
+            ("TRACKNUMBER".to_string(), "3".to_string()),
+            ("TITLE".to_string(), "Intro/Outro".to_string()),
+            ("ARTIST".to_string(), "Artist".to_string()),
+        ]
+    }
+
+    #[test]
+    fn render_template_pads_numbers_and_escapes_braces() {
+        let rendered = render_template("{{{tracknumber:02}}} {title}", &tags(), |value| {
+            value.to_string()
+        })
+        .unwrap();
+        assert_eq!(rendered, "{03} Intro/Outro");
+    }
+
+    #[test]
+    fn render_template_rejects_malformed_fields() {
+        assert!(render_template("{TITLE", &tags(), str::to_string).is_err());
+        assert!(render_template("TITLE}", &tags(), str::to_string).is_err());
+        assert!(render_template("{TRACKNUMBER:x}", &tags(), str::to_string).is_err());
+    }
+
+    #[test]
+    fn render_path_template_sanitizes_values_but_keeps_separators() {
+        let path = render_path_template("{ARTIST}/{TRACKNUMBER:02} - {TITLE}", &tags()).unwrap();
+        assert_eq!(path, PathBuf::from("Artist").join("03 - Intro_Outro"));
+        assert!(render_path_template("../{TITLE}", &tags()).is_err());
+        assert!(render_path_template("{MISSING}", &tags()).is_err());
+    }
+
+    #[test]
+    fn render_rename_template_exposes_name_parts() {
+        let path = PathBuf::from("/music/Album.flac");
+        assert_eq!(
+            render_rename_template("{file}.processed", &path).unwrap(),
+            PathBuf::from("/music/Album.flac.processed")
+        );
+        assert_eq!(
+            render_rename_template("{name}.done.{ext}", &path).unwrap(),
+            PathBuf::from("/music/Album.done.flac")
+        );
+        assert!(render_rename_template("{file}", &path).is_err());
+        assert!(render_rename_template("old/{file}", &path).is_err());
+    }
+
+    #[test]
+    fn civil_from_days_converts_unix_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+    }
+}
@@ -0,0 +1,280 @@
+use std::f64::consts::TAU;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::flac::{EncoderOptions, EncoderOutput, start_encoder};
+use crate::metadata::build_vorbis_comment_from_tags;
+use crate::picture::build_picture_metadata_from_data;
+use crate::types::InputMetadata;
+use crate::wavpack::write_wavpack_image;
+
+/// A 1x1 PNG, small enough to embed in every fixture that needs artwork.
+pub const TINY_PNG: &[u8] = &[
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x02, 0x00, 0x00, 0x00, 0x90, 0x77, 0x53,
+    0xde, 0x00, 0x00, 0x00, 0x0c, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8, 0xcf, 0xc0, 0x00,
+    0x00, 0x03, 0x01, 0x01, 0x00, 0xc9, 0xfe, 0x92, 0xef, 0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e,
+    0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+/// Container of a fixture image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Flac,
+    WavPack,
+}
+
+impl ImageFormat {
+    /// File extension of images in this format, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Flac => "flac",
+            ImageFormat::WavPack => "wv",
+        }
+    }
+}
+
+/// Audio of one fixture track, repeated on every channel.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tone {
+    /// Digital silence.
+    Silence,
+    /// A sine wave; the amplitude is a fraction of full scale.
+    Sine { frequency: f64, amplitude: f64 },
+    /// The same value in every sample, so audio taken from the wrong track or
+    /// position changes the output checksums.
+    Constant(i32),
+}
+
+/// One track of a fixture image and its cue sheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FixtureTrack {
+    /// Cue sheet title.
+    pub title: String,
+    /// Cue sheet performer, when it differs from the album's.
+    pub performer: Option<String>,
+    /// Length in samples per channel.
+    pub samples: u64,
+    /// Audio of the track.
+    pub tone: Tone,
+}
+
+impl FixtureTrack {
+    /// A track without a performer of its own.
+    pub fn new(title: &str, samples: u64, tone: Tone) -> Self {
+        Self {
+            title: title.to_string(),
+            performer: None,
+            samples,
+            tone,
+        }
+    }
+}
+
+/// A generated album image with known audio, tags and track layout, for
+/// tests that split it and compare the output.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fixture {
+    /// Container of the image.
+    pub format: ImageFormat,
+    /// Sample rate in Hz.
+    pub sample_rate: u32,
+    /// Channel count.
+    pub channels: u32,
+    /// Sample size in bits.
+    pub bits_per_sample: u32,
+    /// Album performer, in the cue sheet and the `ARTIST` tag.
+    pub performer: String,
+    /// Album title, in the cue sheet and the `ALBUM` tag.
+    pub title: String,
+    /// Extra tags of the image.
+    pub tags: Vec<(String, String)>,
+    /// Image data of an embedded front cover.
+    pub picture: Option<Vec<u8>>,
+    /// Tracks in image order.
+    pub tracks: Vec<FixtureTrack>,
+}
+
+impl Default for Fixture {
+    fn default() -> Self {
+        Self {
+            format: ImageFormat::Flac,
+            sample_rate: 44_100,
+            channels: 2,
+            bits_per_sample: 16,
+            performer: "Test Artist".to_string(),
+            title: "Test Album".to_string(),
+            tags: Vec::new(),
+            picture: None,
+            tracks: ["One", "Two", "Three"]
+                .iter()
+                .map(|title| FixtureTrack::new(title, 44_100, Tone::Silence))
+                .collect(),
+        }
+    }
+}
+
+impl Fixture {
+    /// Length of the image in samples per channel.
+    pub fn total_samples(&self) -> u64 {
+        self.tracks.iter().map(|track| track.samples).sum()
+    }
+
+    /// First sample of every track.
+    pub fn track_starts(&self) -> Vec<u64> {
+        self.tracks
+            .iter()
+            .scan(0, |start, track| {
+                let current = *start;
+                *start += track.samples;
+                Some(current)
+            })
+            .collect()
+    }
+
+    /// Interleaved samples of the track at `index`, or none past the last track.
+    pub fn track_samples(&self, index: usize) -> Vec<i32> {
+        let Some(track) = self.tracks.get(index) else {
+            return Vec::new();
+        };
+        let full_scale = ((1i64 << (self.bits_per_sample.clamp(2, 32) - 1)) - 1) as f64;
+        let channels = self.channels as usize;
+        let mut samples = Vec::with_capacity(track.samples as usize * channels);
+        for position in 0..track.samples {
+            let value = match track.tone {
+                Tone::Silence => 0,
+                Tone::Sine {
+                    frequency,
+                    amplitude,
+                } => {
+                    let phase = position as f64 * frequency * TAU / f64::from(self.sample_rate);
+                    (phase.sin() * amplitude.clamp(0.0, 1.0) * full_scale).round() as i32
+                }
+                Tone::Constant(value) => value,
+            };
+            samples.extend(std::iter::repeat_n(value, channels));
+        }
+        samples
+    }
+
+    /// Interleaved samples of the whole image.
+    pub fn samples(&self) -> Vec<i32> {
+        (0..self.tracks.len())
+            .flat_map(|index| self.track_samples(index))
+            .collect()
+    }
+
+    /// A cue sheet for the image, stored as `image_name`.
+    pub fn cue_sheet(&self, image_name: &str) -> String {
+        let samples_per_frame = u64::from(self.sample_rate / 75).max(1);
+        let mut cue = format!(
+            "PERFORMER \"{}\"\nTITLE \"{}\"\nFILE \"{}\" WAVE\n",
+            self.performer, self.title, image_name
+        );
+        for (index, (track, start)) in self.tracks.iter().zip(self.track_starts()).enumerate() {
+            cue.push_str(&format!("  TRACK {:02} AUDIO\n", index + 1));
+            cue.push_str(&format!("    TITLE \"{}\"\n", track.title));
+            if let Some(performer) = &track.performer {
+                cue.push_str(&format!("    PERFORMER \"{}\"\n", performer));
+            }
+            // Starts between CD frames are kept exact with a sample offset.
+            if !start.is_multiple_of(samples_per_frame) {
+                cue.push_str(&format!("    REM SAMPLE_OFFSET {}\n", start));
+            }
+            let frames = start / samples_per_frame;
+            cue.push_str(&format!(
+                "    INDEX 01 {:02}:{:02}:{:02}\n",
+                frames / 75 / 60,
+                frames / 75 % 60,
+                frames % 75
+            ));
+        }
+        cue
+    }
+
+    /// Writes the image with its tags and picture to `path`.
+    pub fn write_image(&self, path: &Path) -> Result<()> {
+        let mut meta = InputMetadata::new();
+        meta.sample_rate = self.sample_rate;
+        meta.channels = self.channels;
+        meta.bits_per_sample = self.bits_per_sample;
+        meta.total_samples = self.total_samples();
+        let samples = self.samples();
+        let mut tags = vec![
+            ("ARTIST".to_string(), self.performer.clone()),
+            ("ALBUM".to_string(), self.title.clone()),
+        ];
+        tags.extend(self.tags.iter().cloned());
+
+        match self.format {
+            ImageFormat::Flac => {
+                let mut blocks = vec![build_vorbis_comment_from_tags("flac-cue-split", &tags)?];
+                if let Some(picture) = &self.picture {
+                    blocks.push(build_picture_metadata_from_data(picture, None)?);
+                }
+                let mut encoder = start_encoder(
+                    &meta,
+                    meta.channels,
+                    &EncoderOptions::default(),
+                    meta.total_samples,
+                    blocks,
+                    EncoderOutput::File(path),
+                )?;
+                for chunk in samples.chunks(4096 * self.channels.max(1) as usize) {
+                    encoder
+                        .write_interleaved(chunk, (chunk.len() / self.channels as usize) as u32)?;
+                }
+                encoder.finish()
+            }
+            ImageFormat::WavPack => {
+                write_wavpack_image(path, &meta, &samples, &tags, self.picture.as_deref())
+            }
+        }
+    }
+
+    /// Writes `stem.<ext>` and `stem.cue` into `dir` and returns both paths.
+    pub fn write(&self, dir: &Path, stem: &str) -> Result<(PathBuf, PathBuf)> {
+        let image_name = format!("{}.{}", stem, self.format.extension());
+        let image = dir.join(&image_name);
+        let cue = dir.join(format!("{}.cue", stem));
+        self.write_image(&image)?;
+        fs::write(&cue, self.cue_sheet(&image_name))
+            .map_err(|err| format!("failed to write {}: {}", cue.display(), err))?;
+        Ok((image, cue))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fixture, FixtureTrack, Tone};
+
+    #[test]
+    fn fixtures_lay_out_tracks_and_cue_positions() {
+        let fixture = Fixture {
+            channels: 1,
+            tracks: vec![
+                FixtureTrack::new("One", 588 * 75, Tone::Constant(7)),
+                FixtureTrack::new("Two", 1000, Tone::Silence),
+                FixtureTrack::new(
+                    "Three",
+                    4,
+                    Tone::Sine {
+                        frequency: 11_025.0,
+                        amplitude: 0.5,
+                    },
+                ),
+            ],
+            ..Fixture::default()
+        };
+        assert_eq!(fixture.track_starts(), vec![0, 44_100, 45_100]);
+        assert_eq!(fixture.track_samples(0)[..2], [7, 7]);
+        assert_eq!(fixture.track_samples(2), vec![0, 16384, 0, -16384]);
+        assert_eq!(fixture.samples().len(), 45_104);
+
+        let cue = fixture.cue_sheet("album.flac");
+        assert!(cue.contains("FILE \"album.flac\" WAVE\n"));
+        assert!(cue.contains("    TITLE \"Two\"\n    INDEX 01 00:01:00\n"));
+        assert!(cue.contains("    REM SAMPLE_OFFSET 45100\n    INDEX 01 00:01:01\n"));
+    }
+}
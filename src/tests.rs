@@ -1,5 +1,24 @@
-use crate::cue::parse_cue_from_str;
-use crate::split::{compute_track_spans, frames_to_samples, sanitize_filename};
+use crate::cue::{
+    apply_sample_offsets, disc_track_count, parse_cue, parse_cue_from_str, parse_sample_offsets,
+    render_cue_sheet, track_disc,
+};
+use crate::decoder::parse_block_size;
+#[cfg(unix)]
+use crate::flac::path_to_cstring;
+use crate::flac::{LayoutSlot, frame_sample_index, layout_order};
+use crate::metadata::{
+    build_track_tags, diff_tags, find_tag, missing_required_tags, strip_replaygain_tags,
+    track_total,
+};
+use crate::picture::{
+    PictureBlock, THUMBNAIL_SIZE, cover_file_name, make_thumbnail, parse_picture_block,
+};
+use crate::split::{
+    apply_read_offset, archived_path, check_sample_offsets, check_span_sanity, check_strict_cdda,
+    compute_clamped_track_spans, compute_track_spans, frames_to_samples, output_cue_sheet, pregaps,
+    provenance_tags, samples_until, sanitization_warnings, sanitize_filename, split_points_cue,
+    split_sub_indexes, tag_completeness_problems,
+};
 
 #[test]
 fn frames_to_samples_44100() {
@@ -7,11 +26,46 @@ fn frames_to_samples_44100() {
     assert_eq!(frames_to_samples(0, 44100).unwrap(), 0);
 }
 
+#[test]
+fn track_spans_keep_positions_past_u32_samples() {
+    use crate::cli::SplitPoint;
+
+    // 2^33 samples at 192 kHz is an image of about 12.4 hours.
+    let total_samples = 1u64 << 33;
+    let second_start = 4_608_000_000;
+    assert!(second_start > u64::from(u32::MAX));
+    assert_eq!(
+        frames_to_samples(400 * 60 * 75, 192000).unwrap(),
+        second_start
+    );
+
+    let points = [SplitPoint::Sample(0), SplitPoint::Sample(second_start)];
+    let (disc, warnings) = split_points_cue(&points, 192000).unwrap();
+    assert!(warnings.is_empty());
+    let spans = compute_track_spans(&disc, 192000, total_samples).unwrap();
+    assert_eq!(spans[0].end, second_start);
+    assert_eq!(spans[1].start, second_start);
+    assert_eq!(spans[1].end, total_samples);
+
+    assert_eq!(samples_until(0, second_start, 4096), 4096);
+    assert_eq!(samples_until(second_start - 10, second_start, 4096), 10);
+    assert_eq!(samples_until(second_start, second_start, 4096), 0);
+}
+
 #[test]
 fn frames_to_samples_invalid_rate() {
     assert!(frames_to_samples(1, 44101).is_err());
 }
 
+#[test]
+fn parse_block_size_accepts_k_suffix_and_checks_range() {
+    assert_eq!(parse_block_size("4096").unwrap(), 4096);
+    assert_eq!(parse_block_size("64k").unwrap(), 65536);
+    assert!(parse_block_size("16").is_err());
+    assert!(parse_block_size("4096k").is_err());
+    assert!(parse_block_size("big").is_err());
+}
+
 #[test]
 fn parse_cue_and_compute_spans() {
     let cue = r#"
@@ -45,3 +99,746 @@ fn sanitize_filename_removes_separators() {
     assert_eq!(sanitize_filename("Track/01"), "Track_01");
     assert_eq!(sanitize_filename("Track\\02"), "Track_02");
 }
+
+#[test]
+fn sanitization_warns_when_titles_lose_characters() {
+    use crate::rules::TagRules;
+    use crate::types::{CueRem, TrackSpan};
+    use std::path::PathBuf;
+
+    let span = |number: u32, title: &str| TrackSpan {
+        number,
+        start: 0,
+        end: 0,
+        title: Some(title.to_string()),
+        performer: None,
+        songwriter: None,
+        composer: None,
+        isrc: None,
+        rem: CueRem::default(),
+        tag_overrides: Vec::new(),
+        tag_rules: TagRules::default(),
+        fixed_tags: None,
+        compression_level: None,
+        output_path: PathBuf::new(),
+    };
+    let warnings = sanitization_warnings(&[
+        span(1, "AC/DC Cover "),
+        span(2, "Line\nBreak"),
+        span(3, "Plain"),
+    ]);
+    assert_eq!(
+        warnings,
+        vec!["track 02 title \"Line\\nBreak\" lost characters in its file name \"LineBreak\""]
+    );
+}
+
+#[test]
+fn render_cue_sheet_round_trips() {
+    let cue = r#"
+REM DATE 2020
+PERFORMER "Artist"
+TITLE "Album"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "One"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Two"
+    PERFORMER "Guest"
+    INDEX 01 02:30:10
+"#;
+
+    let disc = parse_cue_from_str(cue).unwrap();
+    let rendered = render_cue_sheet(&disc);
+    let reparsed = parse_cue_from_str(&rendered).unwrap();
+    assert_eq!(reparsed.title.as_deref(), Some("Album"));
+    assert_eq!(reparsed.rem.date.as_deref(), Some("2020"));
+    assert_eq!(reparsed.tracks.len(), 2);
+    assert_eq!(reparsed.tracks[1].performer.as_deref(), Some("Guest"));
+    assert_eq!(reparsed.tracks[1].start_frames, disc.tracks[1].start_frames);
+    assert_eq!(reparsed.tracks[0].filename.as_deref(), Some("test.flac"));
+}
+
+#[test]
+fn output_cue_sheet_lists_one_file_per_track() {
+    use crate::rules::TagRules;
+    use crate::types::{CueRem, TrackSpan};
+    use std::path::PathBuf;
+
+    let disc = parse_cue_from_str(
+        r#"
+PERFORMER "Artist"
+TITLE "Album"
+FILE "image.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 02:30:10
+"#,
+    )
+    .unwrap();
+    let span = |number: u32, title: &str, output: &str| TrackSpan {
+        number,
+        start: 0,
+        end: 0,
+        title: Some(title.to_string()),
+        performer: None,
+        songwriter: None,
+        composer: None,
+        isrc: None,
+        rem: CueRem::default(),
+        tag_overrides: Vec::new(),
+        tag_rules: TagRules::default(),
+        fixed_tags: None,
+        compression_level: None,
+        output_path: PathBuf::from(output),
+    };
+    let one = span(1, "One", "/out/01 - One.flac");
+    let two = span(2, "Two", "/out/02 - Two.flac");
+
+    let rendered = render_cue_sheet(&output_cue_sheet(&disc, &[&one, &two]));
+    let reparsed = parse_cue_from_str(&rendered).unwrap();
+    assert_eq!(reparsed.title.as_deref(), Some("Album"));
+    assert_eq!(reparsed.tracks.len(), 2);
+    assert_eq!(
+        reparsed.tracks[1].filename.as_deref(),
+        Some("02 - Two.flac")
+    );
+    assert_eq!(reparsed.tracks[1].title.as_deref(), Some("Two"));
+    assert_eq!(reparsed.tracks[1].start_frames, 0);
+}
+
+#[test]
+fn diff_tags_reports_changed_values_as_removed_and_added() {
+    let pair = |key: &str, value: &str| (key.to_string(), value.to_string());
+    let old = vec![
+        pair("TITLE", "Old"),
+        pair("artist", "Band"),
+        pair("DATE", "1999"),
+    ];
+    let new = vec![
+        pair("TITLE", "New"),
+        pair("ARTIST", "Band"),
+        pair("GENRE", "Rock"),
+    ];
+    let (removed, added) = diff_tags(&old, &new);
+    assert_eq!(removed, vec![pair("TITLE", "Old"), pair("DATE", "1999")]);
+    assert_eq!(added, vec![pair("TITLE", "New"), pair("GENRE", "Rock")]);
+}
+
+#[cfg(unix)]
+#[test]
+fn path_to_cstring_keeps_non_utf8_bytes() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    let raw = b"/music/\xc0\xeb\xfc\xe1\xee\xec/01.flac";
+    let path = Path::new(OsStr::from_bytes(raw));
+    assert_eq!(path_to_cstring(path).unwrap().as_bytes(), raw);
+    assert!(path_to_cstring(Path::new(OsStr::from_bytes(b"bad\0name"))).is_err());
+}
+
+#[cfg(windows)]
+#[test]
+fn extended_length_path_prefixes_only_long_absolute_paths() {
+    use crate::flac::extended_length_path;
+
+    let short = r"C:\Music\01.flac";
+    assert_eq!(extended_length_path(short), short);
+
+    let deep = format!(r"C:\Music\{}\01.flac", "a".repeat(300));
+    assert_eq!(extended_length_path(&deep), format!(r"\\?\{}", deep));
+
+    let share = format!(r"\\server\music\{}.flac", "b".repeat(300));
+    assert_eq!(
+        extended_length_path(&share),
+        format!(r"\\?\UNC\server\music\{}.flac", "b".repeat(300))
+    );
+}
+
+#[test]
+fn compute_clamped_track_spans_cuts_and_skips_tracks_past_the_audio() {
+    let cue = r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 00:01:00
+  TRACK 03 AUDIO
+    INDEX 01 00:03:00
+"#;
+    let disc = parse_cue_from_str(cue).unwrap();
+    assert!(compute_track_spans(&disc, 44100, 88200).is_err());
+
+    let (spans, warnings) = compute_clamped_track_spans(&disc, 44100, 88200).unwrap();
+    assert_eq!(spans.len(), 2);
+    assert_eq!(spans[1].start, 44100);
+    assert_eq!(spans[1].end, 88200);
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[0].contains("track 2 is clamped"));
+    assert!(warnings[0].contains("00:01:00 (44100 samples)"));
+    assert!(warnings[1].contains("track 3 starts after the end"));
+}
+
+#[test]
+fn check_span_sanity_reports_short_tracks_gaps_and_overlaps() {
+    let mut spans = compute_track_spans(
+        &parse_cue_from_str(
+            r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 00:10:00
+  TRACK 03 AUDIO
+    INDEX 01 00:20:00
+"#,
+        )
+        .unwrap(),
+        44100,
+        44100 * 30,
+    )
+    .unwrap();
+    assert!(check_span_sanity(&spans, 44100).is_empty());
+
+    spans[0].end -= 44100;
+    spans[1].end += 44100;
+    spans[2].end = spans[2].start + 44100;
+    let warnings = check_span_sanity(&spans, 44100);
+    assert_eq!(warnings.len(), 3);
+    assert!(warnings[0].contains("track 3 is only 00:01:00"));
+    assert!(warnings[1].contains("gap of 00:01:00 between tracks 1 and 2"));
+    assert!(warnings[2].contains("tracks 2 and 3 overlap by 00:01:00"));
+}
+
+#[test]
+fn tag_completeness_reports_missing_required_tags() {
+    let tags = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    };
+    let full = tags(&[
+        ("TITLE", "One"),
+        ("ARTIST", "Artist"),
+        ("ALBUM", "Album"),
+        ("DATE", "1999"),
+        ("TRACKNUMBER", "1"),
+    ]);
+    assert!(missing_required_tags(&full, 1).is_empty());
+    assert_eq!(
+        missing_required_tags(&tags(&[("TITLE", "Track 02"), ("TRACKNUMBER", "2")]), 2),
+        vec!["TITLE", "ARTIST", "ALBUM", "DATE"]
+    );
+
+    let missing = vec![
+        (1, vec!["DATE"]),
+        (2, vec!["TITLE", "DATE"]),
+        (3, vec!["TITLE", "DATE"]),
+    ];
+    assert_eq!(
+        tag_completeness_problems(&missing),
+        vec![
+            "tracks 02, 03 would lack TITLE".to_string(),
+            "no track would get DATE".to_string(),
+        ]
+    );
+    assert!(tag_completeness_problems(&[(1, Vec::new())]).is_empty());
+}
+
+#[test]
+fn check_strict_cdda_requires_cd_format_and_whole_sectors() {
+    use crate::types::InputMetadata;
+
+    let disc = parse_cue_from_str(
+        r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+"#,
+    )
+    .unwrap();
+    let mut meta = InputMetadata::new();
+    meta.sample_rate = 44100;
+    meta.bits_per_sample = 16;
+    meta.channels = 2;
+    meta.total_samples = 588 * 100;
+    assert!(check_strict_cdda(&meta, &disc).is_ok());
+
+    meta.total_samples += 1;
+    let err = check_strict_cdda(&meta, &disc).unwrap_err();
+    assert!(err.contains("1 samples left over"));
+
+    meta.total_samples -= 1;
+    meta.bits_per_sample = 24;
+    assert!(check_strict_cdda(&meta, &disc).is_err());
+}
+
+#[test]
+fn parse_cue_skips_data_tracks_and_records_mode_issues() {
+    let cue = r#"
+FILE "disc.bin" BINARY
+  TRACK 01 MODE1/2352
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 00:10:00
+"#;
+    let disc = parse_cue_from_str(cue).unwrap();
+    assert_eq!(disc.tracks.len(), 1);
+    assert_eq!(disc.tracks[0].number, 2);
+    assert!(
+        disc.mode_issues
+            .iter()
+            .any(|issue| issue == "track 1 is not audio and is skipped")
+    );
+}
+
+#[test]
+fn merged_multi_disc_cue_sheets_number_tracks_per_disc() {
+    use crate::rules::TagRules;
+    use crate::types::{CueRem, TrackSpan};
+    use std::path::PathBuf;
+
+    let marked = r#"
+FILE "box.flac" WAVE
+REM DISC 1
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 01:00:00
+REM DISC 2
+  TRACK 03 AUDIO
+    INDEX 01 02:00:00
+"#;
+    let disc = parse_cue_from_str(marked).unwrap();
+    assert_eq!(disc.discs, vec![1, 1, 2]);
+    assert_eq!(track_disc(&disc, 2), Some((1, 2)));
+    assert_eq!(track_disc(&disc, 3), Some((2, 1)));
+    assert_eq!(disc_track_count(&disc, 1), 2);
+
+    let restarted = r#"
+FILE "box.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 01:00:00
+  TRACK 01 AUDIO
+    INDEX 01 02:00:00
+"#;
+    let disc = parse_cue_from_str(restarted).unwrap();
+    assert_eq!(disc.tracks.len(), 3);
+    assert_eq!(disc.discs, vec![1, 1, 2]);
+
+    let one = TrackSpan {
+        number: 3,
+        start: 0,
+        end: 0,
+        title: Some("One".to_string()),
+        performer: None,
+        songwriter: None,
+        composer: None,
+        isrc: None,
+        rem: CueRem::default(),
+        tag_overrides: Vec::new(),
+        tag_rules: TagRules::default(),
+        fixed_tags: None,
+        compression_level: None,
+        output_path: PathBuf::from("/music/Box/Disc 2/01 - One.flac"),
+    };
+    let rendered = render_cue_sheet(&output_cue_sheet(&disc, &[&one]));
+    assert!(rendered.contains("FILE \"Disc 2/01 - One.flac\" WAVE"));
+
+    let single =
+        "REM DISCNUMBER 2\nFILE \"cd2.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+    let disc = parse_cue_from_str(single).unwrap();
+    assert!(disc.discs.is_empty());
+    assert_eq!(track_disc(&disc, 1), None);
+}
+
+#[test]
+fn parse_cue_decodes_bytes_and_returns_errors_instead_of_printing() {
+    let cue = b"TITLE \"\xc0\xeb\xfc\xe1\xee\xec\"\nFILE \"a.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n";
+    let (disc, warnings) = parse_cue(cue, Some("windows-1251")).unwrap();
+    assert_eq!(disc.title.as_deref(), Some("Альбом"));
+    assert!(warnings.is_empty());
+    let (disc, _) = parse_cue(cue, None).unwrap();
+    assert_eq!(disc.title.as_deref(), Some("Альбом"));
+
+    assert!(parse_cue(cue, Some("no-such-encoding")).is_err());
+    assert!(parse_cue(b"TITLE \"Album\"\n", None).is_err());
+    assert!(parse_cue(b"TITLE \"a\0b\"\n", None).is_err());
+}
+
+#[test]
+fn apply_read_offset_shifts_spans_and_clamps_at_file_edges() {
+    let disc = parse_cue_from_str(
+        r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 00:01:00
+"#,
+    )
+    .unwrap();
+    let spans = compute_track_spans(&disc, 44100, 88200).unwrap();
+
+    let later = apply_read_offset(spans.clone(), 30, 88200);
+    assert_eq!((later[0].start, later[0].end), (30, 44130));
+    assert_eq!((later[1].start, later[1].end), (44130, 88200));
+
+    let earlier = apply_read_offset(spans, -30, 88200);
+    assert_eq!((earlier[0].start, earlier[0].end), (0, 44070));
+    assert_eq!((earlier[1].start, earlier[1].end), (44070, 88170));
+}
+
+#[test]
+fn archived_path_keeps_tree_relative_to_base() {
+    use std::path::{Path, PathBuf};
+
+    let base = Path::new("/music/inbox");
+    let archive = Path::new("/archive");
+    assert_eq!(
+        archived_path(
+            Path::new("/music/inbox/Artist/Album/Album.flac"),
+            archive,
+            base
+        ),
+        PathBuf::from("/archive/Artist/Album/Album.flac")
+    );
+    assert_eq!(
+        archived_path(Path::new("/elsewhere/Album.cue"), archive, base),
+        PathBuf::from("/archive/Album.cue")
+    );
+}
+
+#[test]
+fn cover_file_name_normalizes_extension() {
+    use std::path::Path;
+
+    assert_eq!(cover_file_name(Path::new("/a/Front.JPEG")), "cover.jpg");
+    assert_eq!(cover_file_name(Path::new("/a/folder.png")), "cover.png");
+    assert_eq!(cover_file_name(Path::new("/a/artwork")), "cover.jpg");
+}
+
+#[test]
+fn cover_thumbnails_fit_the_thumbnail_size() {
+    let cover = image::DynamicImage::new_rgba8(1200, 900);
+    let mut png = Vec::new();
+    cover
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .unwrap();
+    let (thumbnail, width, height) = make_thumbnail(&png).unwrap();
+    assert_eq!((width, height), (THUMBNAIL_SIZE, THUMBNAIL_SIZE * 3 / 4));
+    assert_eq!(thumbnail[..3], [0xFF, 0xD8, 0xFF]);
+    assert!(make_thumbnail(b"not an image").is_err());
+}
+
+#[test]
+fn strip_replaygain_tags_keeps_other_tags() {
+    let mut tags = vec![
+        ("ARTIST".to_string(), "Artist".to_string()),
+        ("REPLAYGAIN_TRACK_GAIN".to_string(), "-7.10 dB".to_string()),
+        ("replaygain_album_peak".to_string(), "0.98".to_string()),
+        (
+            "REPLAYGAIN_REFERENCE_LOUDNESS".to_string(),
+            "89.0 dB".to_string(),
+        ),
+    ];
+    strip_replaygain_tags(&mut tags);
+    assert_eq!(tags, vec![("ARTIST".to_string(), "Artist".to_string())]);
+}
+
+#[test]
+fn split_points_build_numbered_tracks() {
+    use crate::cli::SplitPoint;
+
+    let points = [
+        SplitPoint::Millis(0),
+        SplitPoint::Millis(271_205),
+        SplitPoint::Sample(24_000_001),
+    ];
+    let (disc, warnings) = split_points_cue(&points, 44100).unwrap();
+    let starts: Vec<i64> = disc.tracks.iter().map(|track| track.start_frames).collect();
+    assert_eq!(starts, vec![0, 20340, 40816]);
+    assert_eq!(disc.tracks[2].number, 3);
+    assert_eq!(disc.tracks[1].rem.sample_offset, None);
+    assert_eq!(disc.tracks[2].rem.sample_offset, Some(24_000_001));
+    assert_eq!(warnings.len(), 1);
+    let spans = compute_track_spans(&disc, 44100, 30_000_000).unwrap();
+    assert_eq!(spans[1].end, 24_000_001);
+    assert_eq!(spans[2].start, 24_000_001);
+
+    let unordered = [SplitPoint::Millis(5000), SplitPoint::Millis(4000)];
+    assert!(split_points_cue(&unordered, 44100).is_err());
+}
+
+#[test]
+fn split_sub_indexes_cuts_tracks_at_later_indexes() {
+    use crate::rules::TagRules;
+    use crate::types::{CueRem, InputMetadata, TrackSpan};
+    use std::path::PathBuf;
+
+    let cue = r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "Suite"
+    INDEX 01 00:00:00
+    INDEX 02 00:01:00
+    INDEX 03 00:02:00
+  TRACK 02 AUDIO
+    TITLE "Encore"
+    INDEX 01 00:03:00
+"#;
+
+    let disc = parse_cue_from_str(cue).unwrap();
+    assert_eq!(disc.tracks[0].sub_indexes, vec![(2, 75), (3, 150)]);
+    assert!(disc.tracks[1].sub_indexes.is_empty());
+    assert!(render_cue_sheet(&disc).contains("    INDEX 03 00:02:00\n"));
+
+    let spans = compute_track_spans(&disc, 44100, 176400).unwrap();
+    let pieces = split_sub_indexes(spans, &disc, 44100).unwrap();
+    let layout: Vec<_> = pieces
+        .iter()
+        .map(|piece| (piece.number, piece.index, piece.start, piece.end))
+        .collect();
+    assert_eq!(
+        layout,
+        vec![
+            (1, Some(1), 0, 44100),
+            (1, Some(2), 44100, 88200),
+            (1, Some(3), 88200, 132300),
+            (2, None, 132300, 176400),
+        ]
+    );
+
+    // The pieces of a track count once towards the track total.
+    let spans: Vec<TrackSpan> = pieces
+        .iter()
+        .map(|piece| TrackSpan {
+            number: piece.number,
+            start: piece.start,
+            end: piece.end,
+            title: piece.title.clone(),
+            performer: None,
+            songwriter: None,
+            composer: None,
+            isrc: None,
+            rem: CueRem::default(),
+            tag_overrides: Vec::new(),
+            tag_rules: TagRules::default(),
+            fixed_tags: None,
+            compression_level: None,
+            output_path: PathBuf::new(),
+        })
+        .collect();
+    assert_eq!(track_total(&spans), 2);
+    for span in &spans {
+        let tags = build_track_tags(&InputMetadata::new(), &disc, track_total(&spans), span);
+        assert_eq!(find_tag(&tags, "TRACKTOTAL"), Some("2"));
+        assert_eq!(find_tag(&tags, "TOTALTRACKS"), Some("2"));
+    }
+    let tags = build_track_tags(&InputMetadata::new(), &disc, 2, &spans[2]);
+    assert_eq!(find_tag(&tags, "TRACKNUMBER"), Some("1"));
+}
+
+#[test]
+fn provenance_tags_record_the_source_position() {
+    let cue = r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    INDEX 01 01:02:03
+"#;
+
+    let disc = parse_cue_from_str(cue).unwrap();
+    let spans = compute_track_spans(&disc, 44100, 3_000_000).unwrap();
+    assert_eq!(
+        provenance_tags(&spans[1], 44100),
+        vec![
+            ("CUE_TRACK_START".to_string(), "01:02:03".to_string()),
+            (
+                "SOURCE_SAMPLE_RANGE".to_string(),
+                "2735964-3000000".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn parse_picture_block_reads_the_flac_picture_layout() {
+    let mut block = Vec::new();
+    for field in [3u32, 10] {
+        block.extend_from_slice(&field.to_be_bytes());
+    }
+    block.extend_from_slice(b"image/jpeg");
+    block.extend_from_slice(&5u32.to_be_bytes());
+    block.extend_from_slice(b"Front");
+    for field in [500u32, 400, 24, 0, 3] {
+        block.extend_from_slice(&field.to_be_bytes());
+    }
+    block.extend_from_slice(&[0xFF, 0xD8, 0xFF]);
+
+    assert_eq!(
+        parse_picture_block(&block).unwrap(),
+        PictureBlock {
+            kind: 3,
+            mime: "image/jpeg".to_string(),
+            description: b"Front".to_vec(),
+            width: 500,
+            height: 400,
+            depth: 24,
+            colors: 0,
+            data: vec![0xFF, 0xD8, 0xFF],
+        }
+    );
+    assert!(parse_picture_block(&block[..block.len() - 1]).is_err());
+}
+
+#[test]
+fn pregaps_report_whether_the_gap_audio_is_kept() {
+    use crate::rules::TagRules;
+    use crate::types::{CueRem, TrackSpan};
+    use std::path::PathBuf;
+
+    let cue = r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 00 00:00:00
+    INDEX 01 00:02:00
+  TRACK 02 AUDIO
+    INDEX 00 00:10:00
+    INDEX 01 00:11:00
+  TRACK 03 AUDIO
+    INDEX 01 00:20:00
+"#;
+    let disc = parse_cue_from_str(cue).unwrap();
+    assert_eq!(disc.tracks[1].pregap_start, Some(750));
+    assert!(render_cue_sheet(&disc).contains("    INDEX 00 00:10:00\n    INDEX 01 00:11:00\n"));
+
+    let span = |number: u32, start: u64, end: u64| TrackSpan {
+        number,
+        start,
+        end,
+        title: None,
+        performer: None,
+        songwriter: None,
+        composer: None,
+        isrc: None,
+        rem: CueRem::default(),
+        tag_overrides: Vec::new(),
+        tag_rules: TagRules::default(),
+        fixed_tags: None,
+        compression_level: None,
+        output_path: PathBuf::new(),
+    };
+    let spans = [
+        span(1, 88200, 485100),
+        span(2, 485100, 882000),
+        span(3, 882000, 1323000),
+    ];
+    let found = pregaps(&disc, &spans, 44100);
+    let summary: Vec<_> = found
+        .iter()
+        .map(|pregap| (pregap.track, pregap.samples, pregap.kept))
+        .collect();
+    assert_eq!(summary, vec![(1, 88200, false), (2, 44100, true)]);
+}
+
+#[test]
+fn frame_positions_follow_variable_and_fixed_blocksize_headers() {
+    let positions = |frames: &[(bool, u64, u64)], fixed: Option<u32>| {
+        let mut next = 0;
+        frames
+            .iter()
+            .map(|&(sample_numbered, number, samples)| {
+                let index = frame_sample_index(sample_numbered, number, fixed, next);
+                next = index + samples;
+                index
+            })
+            .collect::<Vec<_>>()
+    };
+
+    // Variable blocksize: each header holds the first sample of the frame.
+    let variable = [
+        (true, 0, 4096),
+        (true, 4096, 1152),
+        (true, 5248, 576),
+        (true, 5824, 4608),
+    ];
+    assert_eq!(positions(&variable, None), vec![0, 4096, 5248, 5824]);
+    assert_eq!(positions(&variable, Some(4096)), vec![0, 4096, 5248, 5824]);
+
+    // Fixed blocksize: frame numbers scale by the STREAMINFO block size, so a
+    // skipped frame does not shift everything after it.
+    let fixed = [(false, 0, 4096), (false, 1, 4096), (false, 3, 1000)];
+    assert_eq!(positions(&fixed, Some(4096)), vec![0, 4096, 12288]);
+
+    // Frame numbers without a fixed block size fall back to counting samples.
+    assert_eq!(positions(&fixed, None), vec![0, 4096, 8192]);
+    assert_eq!(frame_sample_index(false, u64::MAX, Some(4096), 0), u64::MAX);
+}
+
+#[test]
+fn sample_offsets_override_frame_positions() {
+    let cue = r#"
+FILE "test.flac" WAVE
+  TRACK 01 AUDIO
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    REM SAMPLE_OFFSET 88301
+    INDEX 01 00:02:00
+  TRACK 03 AUDIO
+    INDEX 01 00:03:00
+"#;
+    let mut disc = parse_cue_from_str(cue).unwrap();
+    assert_eq!(disc.tracks[1].rem.sample_offset, Some(88301));
+    assert!(render_cue_sheet(&disc).contains("    REM SAMPLE_OFFSET 88301\n"));
+
+    let spans = compute_track_spans(&disc, 44100, 176400).unwrap();
+    let bounds: Vec<(u64, u64)> = spans.iter().map(|span| (span.start, span.end)).collect();
+    assert_eq!(bounds, vec![(0, 88301), (88301, 132300), (132300, 176400)]);
+    assert!(check_sample_offsets(&disc, 44100).is_empty());
+
+    let offsets = parse_sample_offsets("# track sample\n3 132299\n9 1\n").unwrap();
+    let warnings = apply_sample_offsets(&mut disc, &offsets);
+    assert_eq!(warnings.len(), 1);
+    let spans = compute_track_spans(&disc, 44100, 176400).unwrap();
+    assert_eq!((spans[1].end, spans[2].start), (132299, 132299));
+
+    disc.tracks[2].rem.sample_offset = Some(140000);
+    assert_eq!(check_sample_offsets(&disc, 44100).len(), 1);
+    assert!(parse_sample_offsets("2\n").is_err());
+    assert!(parse_sample_offsets("0 100\n").is_err());
+}
+
+#[test]
+fn layout_order_follows_source_blocks_and_padding() {
+    use crate::types::LayoutBlock::{Padding, Picture, SeekTable, VorbisComment};
+
+    // The track has a comment, two pictures (the second an added cover file)
+    // and a seek table.
+    let kinds = [VorbisComment, Picture, Picture, SeekTable];
+    let layout = [Picture, Padding(100), VorbisComment, Padding(4096)];
+    assert_eq!(
+        layout_order(&layout, &kinds),
+        vec![
+            LayoutSlot::Block(1),
+            LayoutSlot::Padding(100),
+            LayoutSlot::Block(0),
+            LayoutSlot::Block(2),
+            LayoutSlot::Block(3),
+            LayoutSlot::Padding(4096),
+        ]
+    );
+
+    let no_padding = [SeekTable, VorbisComment];
+    assert_eq!(
+        layout_order(&no_padding, &kinds[..2]),
+        vec![LayoutSlot::Block(0), LayoutSlot::Block(1)]
+    );
+}
@@ -0,0 +1,321 @@
+use clap::Parser;
+use owo_colors::OwoColorize;
+use std::ffi::OsString;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::Result;
+use crate::cli::{Args, Cli, Command, MakeCueArgs, display_path};
+use crate::cue::render_cue_sheet;
+use crate::decoder::{STDIN_NAME, create_decoder};
+use crate::types::{CueDisc, CueRem, CueTrack};
+use crate::ui::format_msf;
+
+const FRAMES_PER_SECOND: u64 = 75;
+
+#[derive(Debug, PartialEq, Eq)]
+struct ListedTrack {
+    performer: Option<String>,
+    title: String,
+    // Whole seconds; only the last track may leave it out.
+    seconds: Option<u64>,
+}
+
+// Writes a cue sheet for an image from a pasted tracklist and returns the
+// options of the split to run next, if one was asked for.
+pub(crate) fn run_make_cue(args: MakeCueArgs) -> Result<Option<Args>> {
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let audio_abs = cwd.join(&args.flac);
+    if !audio_abs.is_file() {
+        return Err(format!("file not found: {}", audio_abs.display()));
+    }
+    let out_path = match args.out.as_ref() {
+        Some(path) => cwd.join(path),
+        None => audio_abs.with_extension("cue"),
+    };
+    if out_path.exists() && !args.overwrite {
+        return Err(format!(
+            "output file already exists: {}",
+            out_path.display()
+        ));
+    }
+
+    // Bad split options must not leave a cue sheet behind.
+    let split = if args.split {
+        Some(split_args(&audio_abs, &out_path, args.split_options)?)
+    } else {
+        None
+    };
+
+    let text = read_tracklist(&args.tracklist)?;
+    let listed = parse_tracklist(&text)
+        .map_err(|err| format!("invalid tracklist {}: {}", args.tracklist.display(), err))?;
+    let meta = create_decoder(&audio_abs)?.read_metadata()?.input_meta;
+    if meta.sample_rate == 0 {
+        return Err(format!("{} has no sample rate", audio_abs.display()));
+    }
+    let audio_frames = meta.total_samples * FRAMES_PER_SECOND / u64::from(meta.sample_rate);
+    let file_name = audio_abs
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| format!("invalid unicode filename: {}", audio_abs.display()))?;
+
+    let mut disc = CueDisc {
+        title: args.title.clone(),
+        performer: args.performer.clone(),
+        genre: args.genre.clone(),
+        ..CueDisc::default()
+    };
+    disc.rem.date = args.date.clone();
+    if let Some(warning) = length_warning(&listed, audio_frames) {
+        eprintln!("{}", warning.yellow());
+    }
+    disc.tracks = cue_tracks(listed, file_name, audio_frames)?;
+
+    fs::write(&out_path, render_cue_sheet(&disc))
+        .map_err(|err| format!("failed to write cue file {}: {}", out_path.display(), err))?;
+    println!(
+        "{} {}",
+        "Created".green().bold(),
+        display_path(Some(cwd.as_path()), &out_path)
+            .display()
+            .to_string()
+            .bold()
+    );
+    Ok(split)
+}
+
+fn split_args(audio: &Path, cue: &Path, options: Vec<OsString>) -> Result<Args> {
+    let mut argv: Vec<OsString> = vec![
+        "flac-cue-split".into(),
+        "split".into(),
+        "--flac".into(),
+        audio.as_os_str().to_owned(),
+        "--cue".into(),
+        cue.as_os_str().to_owned(),
+    ];
+    argv.extend(options);
+    match Cli::try_parse_from(argv) {
+        Ok(Cli {
+            command: Some(Command::Split(split)),
+            ..
+        }) => Ok(split),
+        Ok(_) => Err("make-cue --split could not build the split options".to_string()),
+        Err(err) => Err(format!(
+            "invalid split options after --: {}",
+            err.to_string().trim_start_matches("error: ").trim()
+        )),
+    }
+}
+
+fn read_tracklist(path: &Path) -> Result<String> {
+    if path.as_os_str() == STDIN_NAME {
+        let mut text = String::new();
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|err| format!("failed to read the tracklist from stdin: {}", err))?;
+        return Ok(text);
+    }
+    fs::read_to_string(path)
+        .map_err(|err| format!("failed to read tracklist {}: {}", path.display(), err))
+}
+
+// Reads one track per line, e.g. `1. Title 3:45`, `A2 Artist – Title (4:02)`
+// or Discogs' tab-separated `3\tTitle\t5:10`. Lines with neither a position
+// nor a duration, such as `Side B` or credits, are skipped.
+fn parse_tracklist(text: &str) -> Result<Vec<ListedTrack>> {
+    let mut tracks: Vec<ListedTrack> = Vec::new();
+    let mut open_line = None;
+    for (index, line) in text.lines().enumerate() {
+        let (rest, seconds) = split_duration(line.trim());
+        let (numbered, rest) = split_position(rest);
+        if seconds.is_none() && !numbered {
+            continue;
+        }
+        if let Some(open_line) = open_line {
+            return Err(format!(
+                "line {}: the track has no duration; only the last track may leave it out",
+                open_line
+            ));
+        }
+        if seconds.is_none() {
+            open_line = Some(index + 1);
+        }
+        let rest = rest
+            .trim_start_matches(|ch: char| ch.is_whitespace() || "-–—.:|".contains(ch))
+            .trim_end_matches(|ch: char| ch.is_whitespace() || "-–—|".contains(ch));
+        if rest.is_empty() {
+            return Err(format!("line {}: the track has no title", index + 1));
+        }
+        // Compilations list `Artist – Title` with an en dash.
+        let (performer, title) = match rest.split_once(" – ") {
+            Some((performer, title)) => (Some(performer.trim().to_string()), title.trim()),
+            None => (None, rest),
+        };
+        tracks.push(ListedTrack {
+            performer,
+            title: title.to_string(),
+            seconds,
+        });
+    }
+    if tracks.is_empty() {
+        return Err("no tracks found".to_string());
+    }
+    Ok(tracks)
+}
+
+fn split_duration(line: &str) -> (&str, Option<u64>) {
+    let (rest, token) = line.rsplit_once(char::is_whitespace).unwrap_or(("", line));
+    match parse_duration(token.trim_matches(|ch| "()[]".contains(ch))) {
+        Some(seconds) => (rest, Some(seconds)),
+        None => (line, None),
+    }
+}
+
+// `M:SS` or `H:MM:SS`.
+fn parse_duration(value: &str) -> Option<u64> {
+    let parts: Vec<&str> = value.split(':').collect();
+    if !(2..=3).contains(&parts.len())
+        || parts
+            .iter()
+            .any(|part| part.is_empty() || !part.bytes().all(|byte| byte.is_ascii_digit()))
+        || parts[1..].iter().any(|part| part.len() != 2)
+    {
+        return None;
+    }
+    let numbers: Vec<u64> = parts
+        .iter()
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    if numbers[1..].iter().any(|&number| number >= 60) {
+        return None;
+    }
+    Some(numbers.iter().fold(0, |total, number| total * 60 + number))
+}
+
+// Positions look like `3`, `03.`, `A1`, `CD2` or Discogs' `2-05`.
+fn split_position(line: &str) -> (bool, &str) {
+    let Some(token) = line.split_whitespace().next() else {
+        return (false, line);
+    };
+    let position = token.trim_end_matches(['.', ')', ':']);
+    let digits = position.trim_start_matches(|ch: char| ch.is_ascii_alphabetic());
+    let numbered = position.len() - digits.len() <= 2
+        && !digits.is_empty()
+        && digits
+            .split('-')
+            .all(|part| !part.is_empty() && part.bytes().all(|byte| byte.is_ascii_digit()));
+    if numbered {
+        (true, &line.trim_start()[token.len()..])
+    } else {
+        (false, line)
+    }
+}
+
+fn cue_tracks(
+    listed: Vec<ListedTrack>,
+    file_name: &str,
+    audio_frames: u64,
+) -> Result<Vec<CueTrack>> {
+    let mut tracks = Vec::with_capacity(listed.len());
+    let mut start = 0u64;
+    for (index, track) in listed.into_iter().enumerate() {
+        if start >= audio_frames {
+            return Err(format!(
+                "track {} would start at {}, after the end of the audio ({})",
+                index + 1,
+                format_msf(start),
+                format_msf(audio_frames)
+            ));
+        }
+        tracks.push(CueTrack {
+            number: index as u32 + 1,
+            title: Some(track.title),
+            performer: track.performer,
+            songwriter: None,
+            composer: None,
+            isrc: None,
+            start_frames: start as i64,
+            length_frames: None,
+            sub_indexes: Vec::new(),
+            pregap_start: None,
+            filename: Some(file_name.to_string()),
+            rem: CueRem::default(),
+        });
+        start += track.seconds.unwrap_or(0) * FRAMES_PER_SECOND;
+    }
+    Ok(tracks)
+}
+
+// Listed durations are rounded to seconds, so only a difference larger than
+// that rounding can add up to is reported.
+fn length_warning(listed: &[ListedTrack], audio_frames: u64) -> Option<String> {
+    if listed.iter().any(|track| track.seconds.is_none()) {
+        return None;
+    }
+    let listed_frames: u64 =
+        listed.iter().filter_map(|track| track.seconds).sum::<u64>() * FRAMES_PER_SECOND;
+    let tolerance = listed.len() as u64 * FRAMES_PER_SECOND;
+    (listed_frames.abs_diff(audio_frames) > tolerance).then(|| {
+        format!(
+            "the tracklist lasts {} but the audio lasts {}; check the plan before splitting",
+            format_msf(listed_frames),
+            format_msf(audio_frames)
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ListedTrack, parse_tracklist, split_args};
+    use std::path::Path;
+
+    #[test]
+    fn tracklists_are_parsed_from_common_layouts() {
+        let text = "Tracklist\nA1\tIntro\t1:05\nA2 Artist – Song - Part 2 (4:02)\n\
+                    Written-By – Someone\nSide B\nB1. Long One 1:02:03\n2-05 Outro\n";
+        let tracks = parse_tracklist(text).unwrap();
+        assert_eq!(
+            tracks,
+            vec![
+                ListedTrack {
+                    performer: None,
+                    title: "Intro".to_string(),
+                    seconds: Some(65),
+                },
+                ListedTrack {
+                    performer: Some("Artist".to_string()),
+                    title: "Song - Part 2".to_string(),
+                    seconds: Some(242),
+                },
+                ListedTrack {
+                    performer: None,
+                    title: "Long One".to_string(),
+                    seconds: Some(3723),
+                },
+                ListedTrack {
+                    performer: None,
+                    title: "Outro".to_string(),
+                    seconds: None,
+                },
+            ]
+        );
+
+        assert!(parse_tracklist("1 First\n2 Second 3:00\n").is_err());
+        assert!(parse_tracklist("1 3:00\n").is_err());
+        assert!(parse_tracklist("Credits only\n").is_err());
+    }
+
+    #[test]
+    fn split_options_are_checked_without_exiting() {
+        let (audio, cue) = (Path::new("/music/Album.wav"), Path::new("/music/Album.cue"));
+        let split = split_args(audio, cue, vec!["-y".into(), "-c".into(), "8".into()]).unwrap();
+        assert!(split.yes);
+        assert_eq!(split.compression_level, Some(8));
+        assert_eq!(split.cue.as_deref(), Some(cue));
+        let error = split_args(audio, cue, vec!["--no-such-option".into()]).unwrap_err();
+        assert!(error.contains("--no-such-option"), "{}", error);
+    }
+}
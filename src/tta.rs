@@ -0,0 +1,763 @@
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+
+use crate::Result;
+use crate::apetag::read_ape_tags;
+use crate::decoder::{AudioBlock, Decoder, DecoderMetadata};
+use crate::id3::{merge_id3_tags, strip_id3v2};
+use crate::metadata::find_tag;
+use crate::types::InputMetadata;
+
+const TTA_HEADER_LEN: usize = 22;
+const TTA_FORMAT_PCM: u16 = 1;
+// Hybrid filter shifts for 8, 16, 24 and 32-bit samples.
+const FILTER_SHIFTS: [u32; 4] = [10, 9, 10, 12];
+// Larger Rice parameters only come out of corrupt frames.
+const MAX_RICE_K: u32 = 25;
+const CRC_TABLE: [u32; 256] = crc_table();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TtaHeader {
+    channels: u32,
+    bits_per_sample: u32,
+    sample_rate: u32,
+    total_samples: u64,
+}
+
+impl TtaHeader {
+    fn parse(bytes: &[u8; TTA_HEADER_LEN]) -> Result<Self> {
+        if &bytes[..4] != b"TTA1" {
+            return Err("not a TTA1 stream".to_string());
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at = |offset: usize| {
+            u32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        if crc32(&bytes[..18]) != u32_at(18) {
+            return Err("TTA header is corrupt (CRC mismatch)".to_string());
+        }
+        match u16_at(4) {
+            TTA_FORMAT_PCM => {}
+            2 => return Err("encrypted TTA images are not supported".to_string()),
+            format => return Err(format!("unsupported TTA format {}", format)),
+        }
+        let header = Self {
+            channels: u32::from(u16_at(6)),
+            bits_per_sample: u32::from(u16_at(8)),
+            sample_rate: u32_at(10),
+            total_samples: u64::from(u32_at(14)),
+        };
+        if header.channels == 0 {
+            return Err("TTA channel count is zero".to_string());
+        }
+        if !(8..=32).contains(&header.bits_per_sample) {
+            return Err(format!(
+                "unsupported TTA sample size of {} bits",
+                header.bits_per_sample
+            ));
+        }
+        if header.sample_rate == 0 {
+            return Err("TTA sample rate is zero".to_string());
+        }
+        Ok(header)
+    }
+
+    fn bytes_per_sample(&self) -> usize {
+        self.bits_per_sample.div_ceil(8) as usize
+    }
+
+    // Frames last 256/245 of a second.
+    fn frame_length(&self) -> u64 {
+        u64::from(self.sample_rate) * 256 / 245
+    }
+
+    fn frame_count(&self) -> usize {
+        self.total_samples.div_ceil(self.frame_length()) as usize
+    }
+
+    fn frame_samples(&self, frame: usize) -> usize {
+        let start = frame as u64 * self.frame_length();
+        self.frame_length()
+            .min(self.total_samples.saturating_sub(start)) as usize
+    }
+}
+
+pub(crate) struct TtaDecoder {
+    path: PathBuf,
+}
+
+struct TtaStream {
+    reader: Box<dyn Read + Send>,
+    id3_tags: Vec<(String, String)>,
+    header: TtaHeader,
+}
+
+impl TtaDecoder {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn open(&self) -> Result<TtaStream> {
+        let error =
+            |err: std::io::Error| format!("failed to read {}: {}", self.path.display(), err);
+        let file = File::open(&self.path).map_err(error)?;
+        // TTA images often start with an ID3v2 tag.
+        let (mut reader, id3_tags) = strip_id3v2(Box::new(BufReader::new(file))).map_err(error)?;
+        let mut header = [0u8; TTA_HEADER_LEN];
+        reader.read_exact(&mut header).map_err(error)?;
+        let header =
+            TtaHeader::parse(&header).map_err(|err| format!("{}: {}", self.path.display(), err))?;
+        Ok(TtaStream {
+            reader,
+            id3_tags,
+            header,
+        })
+    }
+}
+
+impl Decoder for TtaDecoder {
+    fn read_metadata(&mut self) -> Result<DecoderMetadata> {
+        let TtaStream {
+            id3_tags, header, ..
+        } = self.open()?;
+        let mut input_meta = InputMetadata::new();
+        input_meta.sample_rate = header.sample_rate;
+        input_meta.channels = header.channels;
+        input_meta.bits_per_sample = header.bits_per_sample;
+        input_meta.total_samples = header.total_samples;
+
+        let mut picture_names = Vec::new();
+        read_ape_tags(&self.path, &mut input_meta, &mut picture_names)?;
+        merge_id3_tags(&mut input_meta.comments, &id3_tags);
+        input_meta.embedded_cue = find_tag(&input_meta.comments, "CUESHEET").map(str::to_string);
+
+        Ok(DecoderMetadata {
+            input_meta,
+            picture_names,
+        })
+    }
+
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        let TtaStream {
+            mut reader, header, ..
+        } = self.open()?;
+        // The seek table and every frame are read into memory, so sizes from
+        // a damaged file must not exceed the file itself.
+        let file_len = fs::metadata(&self.path)
+            .map_err(|err| format!("failed to read {}: {}", self.path.display(), err))?
+            .len();
+        let table_len = header.frame_count() as u64 * 4 + 4;
+        if table_len > file_len {
+            return Err(format!(
+                "TTA seek table of {} is larger than the file ({} frames)",
+                self.path.display(),
+                header.frame_count()
+            ));
+        }
+        let mut table = vec![0u8; table_len as usize];
+        reader
+            .read_exact(&mut table)
+            .map_err(|err| format!("failed to read the TTA seek table: {}", err))?;
+        let (sizes, crc) = table.split_at(table.len() - 4);
+        if crc32(sizes) != u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+            return Err(format!(
+                "TTA seek table of {} is corrupt (CRC mismatch)",
+                self.path.display()
+            ));
+        }
+        let frame_sizes: Vec<usize> = sizes
+            .chunks_exact(4)
+            .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+            .collect();
+        if frame_sizes.iter().map(|size| *size as u64).sum::<u64>() > file_len - table_len {
+            return Err(format!(
+                "TTA seek table of {} lists frames larger than the file",
+                self.path.display()
+            ));
+        }
+
+        Ok(Box::new(TtaBlockIter {
+            reader,
+            header,
+            frame_sizes,
+            next_frame: 0,
+            pending: Vec::new(),
+            offset: 0,
+            sample_index: 0,
+            block_len: block_size.max(1) * header.channels as usize,
+            done: false,
+        }))
+    }
+}
+
+struct TtaBlockIter {
+    reader: Box<dyn Read + Send>,
+    header: TtaHeader,
+    frame_sizes: Vec<usize>,
+    next_frame: usize,
+    pending: Vec<i32>,
+    offset: usize,
+    sample_index: u64,
+    block_len: usize,
+    done: bool,
+}
+
+impl TtaBlockIter {
+    fn read_frame(&mut self) -> Result<Vec<i32>> {
+        let index = self.next_frame;
+        let mut data = vec![0u8; self.frame_sizes[index]];
+        self.reader
+            .read_exact(&mut data)
+            .map_err(|err| format!("failed to read TTA frame {}: {}", index + 1, err))?;
+        self.next_frame += 1;
+        decode_frame(&data, &self.header, self.header.frame_samples(index))
+            .map_err(|err| format!("TTA frame {}: {}", index + 1, err))
+    }
+}
+
+impl Iterator for TtaBlockIter {
+    type Item = Result<AudioBlock>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        if self.offset >= self.pending.len() {
+            if self.next_frame >= self.frame_sizes.len() {
+                self.done = true;
+                return None;
+            }
+            match self.read_frame() {
+                Ok(samples) => {
+                    self.pending = samples;
+                    self.offset = 0;
+                }
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            }
+        }
+
+        let end = (self.offset + self.block_len).min(self.pending.len());
+        let block = AudioBlock {
+            sample_index: self.sample_index,
+            channels: self.header.channels,
+            interleaved: self.pending[self.offset..end].to_vec(),
+        };
+        self.offset = end;
+        self.sample_index += block.sample_count() as u64;
+        Some(Ok(block))
+    }
+}
+
+fn decode_frame(data: &[u8], header: &TtaHeader, samples: usize) -> Result<Vec<i32>> {
+    if data.len() < 4 {
+        return Err("frame is truncated".to_string());
+    }
+    let (payload, crc) = data.split_at(data.len() - 4);
+    if crc32(payload) != u32::from_le_bytes([crc[0], crc[1], crc[2], crc[3]]) {
+        return Err("frame is corrupt (CRC mismatch)".to_string());
+    }
+
+    let channels = header.channels as usize;
+    let bytes_per_sample = header.bytes_per_sample();
+    let mut states: Vec<ChannelState> = (0..channels)
+        .map(|_| ChannelState::new(bytes_per_sample))
+        .collect();
+    let mut reader = BitReader::new(payload);
+    let mut interleaved = vec![0i32; samples * channels];
+    for frame in interleaved.chunks_exact_mut(channels) {
+        for (sample, state) in frame.iter_mut().zip(states.iter_mut()) {
+            *sample = state
+                .decode(&mut reader)
+                .ok_or_else(|| "frame is truncated".to_string())?;
+        }
+        // Every channel but the last was stored as the difference to the
+        // next one, the last one relative to the mean.
+        if channels > 1 {
+            let last = channels - 1;
+            frame[last] = frame[last].wrapping_add(frame[last - 1] / 2);
+            for channel in (0..last).rev() {
+                frame[channel] = frame[channel + 1].wrapping_sub(frame[channel]);
+            }
+        }
+    }
+    Ok(interleaved)
+}
+
+struct ChannelState {
+    bytes_per_sample: usize,
+    predictor: i32,
+    filter: Filter,
+    k0: u32,
+    k1: u32,
+    sum0: u32,
+    sum1: u32,
+}
+
+impl ChannelState {
+    fn new(bytes_per_sample: usize) -> Self {
+        Self {
+            bytes_per_sample,
+            predictor: 0,
+            filter: Filter::new(FILTER_SHIFTS[bytes_per_sample.clamp(1, 4) - 1]),
+            k0: 10,
+            k1: 10,
+            sum0: rice_threshold(10),
+            sum1: rice_threshold(10),
+        }
+    }
+
+    fn decode(&mut self, reader: &mut BitReader) -> Option<i32> {
+        let mut unary = reader.read_unary()?;
+        let escaped = unary > 0;
+        let k = if escaped {
+            unary -= 1;
+            self.k1
+        } else {
+            self.k0
+        };
+        if k > MAX_RICE_K {
+            return None;
+        }
+        let mut value = (unary << k).wrapping_add(reader.read_bits(k)?);
+        if escaped {
+            adapt(&mut self.k1, &mut self.sum1, value);
+            value = value.wrapping_add(1 << self.k0);
+        }
+        adapt(&mut self.k0, &mut self.sum0, value);
+
+        let value = value as i32;
+        let mut sample = 1i32.wrapping_add((value >> 1) ^ ((value & 1) - 1));
+        self.filter.decode(&mut sample);
+        sample = sample.wrapping_add(self.prediction());
+        self.predictor = sample;
+        Some(sample)
+    }
+
+    fn prediction(&self) -> i32 {
+        match self.bytes_per_sample {
+            1 => predict(self.predictor, 4),
+            2 | 3 => predict(self.predictor, 5),
+            _ => self.predictor,
+        }
+    }
+}
+
+fn rice_threshold(k: u32) -> u32 {
+    1u32.checked_shl(k + 4).unwrap_or(u32::MAX)
+}
+
+fn adapt(k: &mut u32, sum: &mut u32, value: u32) {
+    *sum = sum.wrapping_add(value.wrapping_sub(*sum >> 4));
+    if *k > 0 && *sum < rice_threshold(*k) {
+        *k -= 1;
+    } else if *sum > rice_threshold(*k + 1) {
+        *k += 1;
+    }
+}
+
+// x * (2^k - 1) / 2^k, truncated the way the reference implementation does.
+fn predict(x: i32, k: u32) -> i32 {
+    let x = x as i64 as u64;
+    ((x << k).wrapping_sub(x) >> k) as i32
+}
+
+struct Filter {
+    shift: u32,
+    round: i32,
+    error: i32,
+    qm: [i32; 8],
+    dx: [i32; 8],
+    dl: [i32; 8],
+}
+
+impl Filter {
+    fn new(shift: u32) -> Self {
+        Self {
+            shift,
+            round: 1 << (shift - 1),
+            error: 0,
+            qm: [0; 8],
+            dx: [0; 8],
+            dl: [0; 8],
+        }
+    }
+
+    fn predict(&mut self) -> i32 {
+        if self.error < 0 {
+            for (qm, dx) in self.qm.iter_mut().zip(self.dx) {
+                *qm = qm.wrapping_sub(dx);
+            }
+        } else if self.error > 0 {
+            for (qm, dx) in self.qm.iter_mut().zip(self.dx) {
+                *qm = qm.wrapping_add(dx);
+            }
+        }
+        let round = self
+            .dl
+            .iter()
+            .zip(self.qm)
+            .fold(self.round, |sum, (dl, qm)| {
+                sum.wrapping_add(dl.wrapping_mul(qm))
+            });
+
+        self.dx.copy_within(1..5, 0);
+        self.dl.copy_within(1..5, 0);
+        self.dx[4] = (self.dl[4] >> 30) | 1;
+        self.dx[5] = ((self.dl[5] >> 30) | 2) & !1;
+        self.dx[6] = ((self.dl[6] >> 30) | 2) & !1;
+        self.dx[7] = ((self.dl[7] >> 30) | 4) & !3;
+        round >> self.shift
+    }
+
+    fn update(&mut self, value: i32) {
+        self.dl[4] = self.dl[5].wrapping_neg();
+        self.dl[5] = self.dl[6].wrapping_neg();
+        self.dl[6] = value.wrapping_sub(self.dl[7]);
+        self.dl[7] = value;
+        self.dl[5] = self.dl[5].wrapping_add(self.dl[6]);
+        self.dl[4] = self.dl[4].wrapping_add(self.dl[5]);
+    }
+
+    fn decode(&mut self, value: &mut i32) {
+        let prediction = self.predict();
+        self.error = *value;
+        *value = value.wrapping_add(prediction);
+        self.update(*value);
+    }
+}
+
+// Bits are stored least significant first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+    cache: u64,
+    bits: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            cache: 0,
+            bits: 0,
+        }
+    }
+
+    fn refill(&mut self) {
+        while self.bits <= 56 {
+            let Some(&byte) = self.data.get(self.position) else {
+                break;
+            };
+            self.cache |= u64::from(byte) << self.bits;
+            self.bits += 8;
+            self.position += 1;
+        }
+    }
+
+    fn read_bits(&mut self, count: u32) -> Option<u32> {
+        if count == 0 {
+            return Some(0);
+        }
+        if self.bits < count {
+            self.refill();
+            if self.bits < count {
+                return None;
+            }
+        }
+        let value = (self.cache & ((1u64 << count) - 1)) as u32;
+        self.cache >>= count;
+        self.bits -= count;
+        Some(value)
+    }
+
+    // Counts the one bits in front of the next zero bit.
+    fn read_unary(&mut self) -> Option<u32> {
+        let mut count = 0u32;
+        loop {
+            if self.bits == 0 {
+                self.refill();
+                if self.bits == 0 {
+                    return None;
+                }
+            }
+            let ones = (!self.cache).trailing_zeros().min(self.bits);
+            count = count.saturating_add(ones);
+            self.cache = self.cache.checked_shr(ones).unwrap_or(0);
+            self.bits -= ones;
+            if self.bits > 0 {
+                self.cache >>= 1;
+                self.bits -= 1;
+                return Some(count);
+            }
+        }
+    }
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(u32::MAX, |crc, byte| {
+        CRC_TABLE[((crc ^ u32::from(*byte)) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ChannelState, Filter, TTA_HEADER_LEN, TtaDecoder, TtaHeader, crc32, decode_frame, predict,
+        rice_threshold,
+    };
+    use crate::decoder::Decoder;
+    use std::fs;
+
+    // The encoder side of the reference implementation, to build fixtures.
+    struct BitWriter {
+        bytes: Vec<u8>,
+        cache: u64,
+        bits: u32,
+    }
+
+    impl BitWriter {
+        fn put(&mut self, count: u32, value: u32) {
+            self.cache |= u64::from(value) << self.bits;
+            self.bits += count;
+            while self.bits >= 8 {
+                self.bytes.push(self.cache as u8);
+                self.cache >>= 8;
+                self.bits -= 8;
+            }
+        }
+
+        fn finish(mut self) -> Vec<u8> {
+            if self.bits > 0 {
+                self.bytes.push(self.cache as u8);
+            }
+            let crc = crc32(&self.bytes);
+            self.bytes.extend(crc.to_le_bytes());
+            self.bytes
+        }
+    }
+
+    fn encode_sample(state: &mut ChannelState, filter_input: i32, writer: &mut BitWriter) {
+        let temp = filter_input;
+        let mut value = filter_input.wrapping_sub(state.prediction());
+        state.predictor = temp;
+
+        let filter: &mut Filter = &mut state.filter;
+        let prediction = filter.predict();
+        filter.update(value);
+        value = value.wrapping_sub(prediction);
+        filter.error = value;
+
+        let mut outval = if value > 0 {
+            (value as u32) * 2 - 1
+        } else {
+            value.unsigned_abs() * 2
+        };
+        let k0 = state.k0;
+        super::adapt(&mut state.k0, &mut state.sum0, outval);
+        if outval >= 1 << k0 {
+            outval -= 1 << k0;
+            let k1 = state.k1;
+            super::adapt(&mut state.k1, &mut state.sum1, outval);
+            for _ in 0..1 + (outval >> k1) {
+                writer.put(1, 1);
+            }
+            writer.put(1, 0);
+            writer.put(k1, outval & ((1 << k1) - 1));
+        } else {
+            writer.put(1, 0);
+            writer.put(k0, outval);
+        }
+    }
+
+    fn encode_frame(interleaved: &[i32], header: &TtaHeader) -> Vec<u8> {
+        let channels = header.channels as usize;
+        let mut states: Vec<ChannelState> = (0..channels)
+            .map(|_| ChannelState::new(header.bytes_per_sample()))
+            .collect();
+        let mut writer = BitWriter {
+            bytes: Vec::new(),
+            cache: 0,
+            bits: 0,
+        };
+        for frame in interleaved.chunks_exact(channels) {
+            let mut difference = 0;
+            for (channel, state) in states.iter_mut().enumerate() {
+                let value = if channels == 1 {
+                    frame[channel]
+                } else if channel < channels - 1 {
+                    difference = frame[channel + 1] - frame[channel];
+                    difference
+                } else {
+                    frame[channel] - difference / 2
+                };
+                encode_sample(state, value, &mut writer);
+            }
+        }
+        writer.finish()
+    }
+
+    fn encode_image(interleaved: &[i32], header: &TtaHeader) -> Vec<u8> {
+        let mut image = b"TTA1".to_vec();
+        image.extend(1u16.to_le_bytes());
+        image.extend((header.channels as u16).to_le_bytes());
+        image.extend((header.bits_per_sample as u16).to_le_bytes());
+        image.extend(header.sample_rate.to_le_bytes());
+        image.extend((header.total_samples as u32).to_le_bytes());
+        image.extend(crc32(&image).to_le_bytes());
+        let frame_len = header.frame_length() as usize * header.channels as usize;
+        let frames: Vec<Vec<u8>> = interleaved
+            .chunks(frame_len)
+            .map(|frame| encode_frame(frame, header))
+            .collect();
+        let table: Vec<u8> = frames
+            .iter()
+            .flat_map(|frame| (frame.len() as u32).to_le_bytes())
+            .collect();
+        image.extend(&table);
+        image.extend(crc32(&table).to_le_bytes());
+        image.extend(frames.concat());
+        image
+    }
+
+    fn test_signal(samples: usize, channels: usize, amplitude: f64) -> Vec<i32> {
+        let mut noise = 12345u32;
+        (0..samples * channels)
+            .map(|index| {
+                noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let phase = (index / channels) as f64 * 0.05 * (1 + index % channels) as f64;
+                (phase.sin() * amplitude) as i32 + (noise >> 24) as i32 - 128
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tta_helpers_match_the_reference_arithmetic() {
+        assert_eq!(predict(32, 5), 31);
+        assert_eq!(predict(-32, 5), -31);
+        assert_eq!(predict(-1, 4), -1);
+        assert_eq!(rice_threshold(10), 1 << 14);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn tta_frames_round_trip() {
+        for (channels, bits_per_sample, amplitude) in [(3, 24, 4_000_000.0), (1, 8, 100.0)] {
+            let header = TtaHeader {
+                channels,
+                bits_per_sample,
+                sample_rate: 8000,
+                total_samples: 700,
+            };
+            let signal = test_signal(700, channels as usize, amplitude);
+            let frame = encode_frame(&signal, &header);
+            assert_eq!(decode_frame(&frame, &header, 700).unwrap(), signal);
+
+            let mut corrupt = frame.clone();
+            corrupt[0] ^= 1;
+            assert!(decode_frame(&corrupt, &header, 700).is_err());
+        }
+    }
+
+    #[test]
+    fn tta_images_decode_across_frames() {
+        // 8 kHz gives 8359-sample frames, so the image has a short last frame.
+        let header = TtaHeader {
+            channels: 2,
+            bits_per_sample: 16,
+            sample_rate: 8000,
+            total_samples: 10_000,
+        };
+        let signal = test_signal(10_000, 2, 20_000.0);
+        let image = encode_image(&signal, &header);
+        assert_eq!(
+            TtaHeader::parse(image[..TTA_HEADER_LEN].try_into().unwrap()).unwrap(),
+            header
+        );
+
+        let path =
+            std::env::temp_dir().join(format!("flac-cue-split-tta-{}.tta", std::process::id()));
+        fs::write(&path, &image).unwrap();
+        let mut decoder = Box::new(TtaDecoder::new(path.clone()));
+        let meta = decoder.read_metadata().unwrap().input_meta;
+        assert_eq!(
+            (
+                meta.sample_rate,
+                meta.channels,
+                meta.bits_per_sample,
+                meta.total_samples
+            ),
+            (8000, 2, 16, 10_000)
+        );
+        let blocks: Vec<_> = decoder
+            .into_blocks(4096)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(blocks[2].sample_index, 8192);
+        assert_eq!(blocks[3].sample_index, 8359);
+        let decoded: Vec<i32> = blocks
+            .into_iter()
+            .flat_map(|block| block.interleaved)
+            .collect();
+        assert_eq!(decoded, signal);
+    }
+
+    #[test]
+    fn tta_frame_sizes_past_the_file_are_rejected() {
+        let header = TtaHeader {
+            channels: 1,
+            bits_per_sample: 16,
+            sample_rate: 8000,
+            total_samples: 10_000,
+        };
+        let mut image = encode_image(&test_signal(10_000, 1, 1000.0), &header);
+        // Claim a 4 GB first frame and keep the table CRC valid.
+        let table = TTA_HEADER_LEN..TTA_HEADER_LEN + 8;
+        image[table.start..table.start + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let crc = crc32(&image[table.clone()]);
+        image[table.end..table.end + 4].copy_from_slice(&crc.to_le_bytes());
+
+        let path = std::env::temp_dir().join(format!(
+            "flac-cue-split-tta-sizes-{}.tta",
+            std::process::id()
+        ));
+        fs::write(&path, &image).unwrap();
+        let result = Box::new(TtaDecoder::new(path.clone())).into_blocks(4096);
+        fs::remove_file(&path).unwrap();
+        assert!(result.is_err_and(|err| err.contains("larger than the file")));
+    }
+}
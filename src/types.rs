@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use crate::flac::FlacMetadata;
+use crate::rules::TagRules;
 
 #[derive(Debug, Clone, Default)]
 pub(crate) struct CueRem {
@@ -9,10 +10,11 @@ pub(crate) struct CueRem {
     pub(crate) replaygain_album_peak: Option<String>,
     pub(crate) replaygain_track_gain: Option<String>,
     pub(crate) replaygain_track_peak: Option<String>,
+    pub(crate) sample_offset: Option<u64>,
 }
 
-#[derive(Debug, Clone)]
-pub(crate) struct CueDisc {
+#[derive(Debug, Clone, Default)]
+pub struct CueDisc {
     pub(crate) title: Option<String>,
     pub(crate) performer: Option<String>,
     pub(crate) songwriter: Option<String>,
@@ -22,6 +24,10 @@ pub(crate) struct CueDisc {
     pub(crate) disc_id: Option<String>,
     pub(crate) rem: CueRem,
     pub(crate) tracks: Vec<CueTrack>,
+    // The disc of every TRACK line for sheets that merge several discs, empty
+    // otherwise.
+    pub(crate) discs: Vec<u32>,
+    pub(crate) mode_issues: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -34,19 +40,34 @@ pub(crate) struct CueTrack {
     pub(crate) isrc: Option<String>,
     pub(crate) start_frames: i64,
     pub(crate) length_frames: Option<i64>,
+    pub(crate) sub_indexes: Vec<(u32, i64)>,
+    pub(crate) pregap_start: Option<i64>,
     pub(crate) filename: Option<String>,
     pub(crate) rem: CueRem,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LayoutBlock {
+    VorbisComment,
+    Picture,
+    SeekTable,
+    Padding(u32),
+    Other,
+}
+
 #[derive(Debug)]
 pub(crate) struct InputMetadata {
     pub(crate) sample_rate: u32,
     pub(crate) channels: u32,
+    pub(crate) channel_mask: Option<u32>,
     pub(crate) bits_per_sample: u32,
     pub(crate) total_samples: u64,
+    pub(crate) md5: Option<[u8; 16]>,
     pub(crate) vendor: Option<String>,
     pub(crate) comments: Vec<(String, String)>,
     pub(crate) pictures: Vec<FlacMetadata>,
+    pub(crate) embedded_cue: Option<String>,
+    pub(crate) block_layout: Vec<LayoutBlock>,
 }
 
 impl InputMetadata {
@@ -54,11 +75,15 @@ impl InputMetadata {
         Self {
             sample_rate: 0,
             channels: 0,
+            channel_mask: None,
             bits_per_sample: 0,
             total_samples: 0,
+            md5: None,
             vendor: None,
             comments: Vec::new(),
             pictures: Vec::new(),
+            embedded_cue: None,
+            block_layout: Vec::new(),
         }
     }
 }
@@ -74,5 +99,9 @@ pub(crate) struct TrackSpan {
     pub(crate) composer: Option<String>,
     pub(crate) isrc: Option<String>,
     pub(crate) rem: CueRem,
+    pub(crate) tag_overrides: Vec<(String, String)>,
+    pub(crate) tag_rules: TagRules,
+    pub(crate) fixed_tags: Option<Vec<(String, String)>>,
+    pub(crate) compression_level: Option<u8>,
     pub(crate) output_path: PathBuf,
 }
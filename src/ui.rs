@@ -1,31 +1,56 @@
-use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use clap::ValueEnum;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use owo_colors::OwoColorize;
+use std::collections::HashSet;
 use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use crate::Result;
 use crate::cli::display_path;
-use crate::metadata::{compute_common_metadata, compute_unique_metadata_pairs};
-use crate::split::{Plan, processed_flac_path};
+use crate::decoder::create_decoder;
+use crate::gain::format_gain;
+use crate::i18n::{Msg, tr, tr_with};
+use crate::metadata::{
+    build_track_tags, compute_common_metadata, compute_unique_metadata_pairs, diff_tags,
+};
+use crate::split::{Plan, SourceAction, archived_path, pregaps};
+use crate::template::render_rename_template;
 use crate::types::{CueDisc, InputMetadata, TrackSpan};
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub(crate) enum TimeFormat {
+    #[default]
+    Msf,
+    Hms,
+    Seconds,
+    Samples,
+}
+
+static TIME_FORMAT: OnceLock<TimeFormat> = OnceLock::new();
+
+pub(crate) fn set_time_format(format: TimeFormat) {
+    let _ = TIME_FORMAT.set(format);
+}
+
 pub(crate) enum ConfirmAction {
     Proceed,
     Cancel,
     EditSubdirs,
+    PerPair,
 }
 
 pub(crate) fn print_plan(plan: &Plan) -> Result<()> {
     let cue: &CueDisc = plan.cue();
     let meta: &InputMetadata = plan.input_meta();
     let tracks: &[TrackSpan] = plan.tracks();
-    let compression_level = plan.compression_level();
+    let compression_level = plan.encoder_options().compression_level;
     let display_base_abs = plan.display_base_abs();
     let picture_names = plan.picture_names();
     let input_path = plan.flac_display();
     let cue_path = plan.cue_display();
     let (cue_encoding, cue_encoding_autodetected) = plan.cue_encoding();
-    let (delete_original, rename_original) = plan.source_actions();
     if meta.sample_rate == 0 {
         return Err("invalid sample rate in metadata".to_string());
     }
@@ -36,33 +61,97 @@ pub(crate) fn print_plan(plan: &Plan) -> Result<()> {
         ));
     }
 
-    let samples_per_frame = (meta.sample_rate / 75) as u64;
-
-    println!("{}", "Plan".bold());
-    println!("  {} {}", "Input:".cyan(), input_path.display());
-    if delete_original {
+    println!("{}", tr(Msg::Plan).bold());
+    println!("  {} {}", tr(Msg::Input).cyan(), input_path.display());
+    match plan.source_action() {
+        SourceAction::Keep => {}
+        SourceAction::Delete => println!(
+            "  {} {}",
+            tr(Msg::SourceAction).cyan(),
+            tr(Msg::WillBeDeleted).red().bold()
+        ),
+        SourceAction::Trash => println!(
+            "  {} {}",
+            tr(Msg::SourceAction).cyan(),
+            tr(Msg::WillBeTrashed).yellow()
+        ),
+        SourceAction::Archive { dir, base } => {
+            let target = archived_path(plan.flac_path(), dir, base);
+            let note = tr_with(
+                Msg::WillBeMovedTo,
+                &[&display_path(plan.display_base_abs(), &target).display()],
+            );
+            println!("  {} {}", tr(Msg::SourceAction).cyan(), note.yellow());
+        }
+        SourceAction::Rename { template } => {
+            let renamed = render_rename_template(template, input_path)?;
+            let rename_note = tr_with(Msg::WillBeRenamedTo, &[&renamed.display()]);
+            println!(
+                "  {} {}",
+                tr(Msg::SourceAction).cyan(),
+                rename_note.yellow()
+            );
+        }
+    }
+    if plan.source_files().len() > 1 {
+        let sidecars: Vec<String> = plan.source_files()[1..]
+            .iter()
+            .map(|path| {
+                display_path(plan.display_base_abs(), path)
+                    .display()
+                    .to_string()
+            })
+            .collect();
         println!(
             "  {} {}",
-            "Source action:".cyan(),
-            "will be deleted after successful split".red().bold()
+            tr(Msg::Sidecars).cyan(),
+            format!("{} {}", sidecars.join(", "), tr(Msg::SameAction)).yellow()
         );
-    } else if rename_original {
-        let rename_note = match processed_flac_path(input_path) {
-            Some(renamed) => format!("will be renamed to {}", renamed.display()),
-            None => "will be renamed after successful split".to_string(),
-        };
-        println!("  {} {}", "Source action:".cyan(), rename_note.yellow());
     }
     println!("  {} {}", "CUE:".cyan(), cue_path.display());
+    if let Some(target) = plan.cue_target() {
+        println!(
+            "  {} {}",
+            tr(Msg::CueSheet).cyan(),
+            display_path(display_base_abs, &target).display()
+        );
+    }
+    if let Some(target) = plan.cover_target() {
+        println!(
+            "  {} {}",
+            tr(Msg::CoverFile).cyan(),
+            display_path(display_base_abs, &target).display()
+        );
+    }
+    if let Some((format, dir)) = plan.also_encode_target() {
+        println!(
+            "  {} {} -> {}",
+            tr(Msg::AlsoEncode).cyan(),
+            format.name(),
+            display_path(display_base_abs, &dir).display()
+        );
+    }
+    if !plan.inferred_tags().is_empty() {
+        println!(
+            "  {} {} {}",
+            tr(Msg::FromFolders).cyan(),
+            format_tag_pairs(plan.inferred_tags()),
+            tr(Msg::CheckBeforeConfirming).yellow()
+        );
+    }
     let encoding_label = if cue_encoding_autodetected {
-        format!("{} {}", cue_encoding.name(), "(autodetected)".dimmed())
+        format!("{} {}", cue_encoding.name(), tr(Msg::Autodetected).dimmed())
     } else {
         cue_encoding.name().to_string()
     };
-    println!("  {} {}", "CUE encoding:".cyan(), encoding_label.green());
+    println!(
+        "  {} {}",
+        tr(Msg::CueEncoding).cyan(),
+        encoding_label.green()
+    );
     println!(
         "  {} {} ({} Hz, {} ch, {} bits, compression {})",
-        "Tracks:".cyan(),
+        tr(Msg::Tracks).cyan(),
         tracks.len(),
         meta.sample_rate,
         meta.channels,
@@ -70,22 +159,166 @@ pub(crate) fn print_plan(plan: &Plan) -> Result<()> {
         compression_level
     );
 
+    let audio_length = format_time(meta.total_samples, meta.sample_rate);
+    let length_line = match plan.cue_length() {
+        Some(cue_length) if cue_length == meta.total_samples => {
+            format!("{} {}", audio_length, tr(Msg::MatchesCue).dimmed())
+        }
+        Some(cue_length) => format!(
+            "{} audio, {} cue ({})",
+            audio_length,
+            format_time(cue_length, meta.sample_rate),
+            format_length_difference(
+                meta.total_samples as i64 - cue_length as i64,
+                meta.sample_rate
+            )
+            .yellow()
+        ),
+        None => format!("{} {}", audio_length, tr(Msg::CueHasNoEnd).dimmed()),
+    };
+    println!("  {} {}", tr(Msg::Length).cyan(), length_line);
+    if !plan.to_stdout()
+        && let Some(size) = plan.estimated_output_size()
+    {
+        println!("  {} ~{}", tr(Msg::EstimatedSize).cyan(), format_size(size));
+    }
+    if let Some((mode, threshold)) = plan.trim_silence() {
+        println!(
+            "  {} {} {}",
+            tr(Msg::TrimSilence).cyan(),
+            mode.label().yellow(),
+            format!("(below {} dBFS)", threshold).dimmed()
+        );
+    }
+    match plan.downmix() {
+        Some(target) => println!(
+            "  {} {} -> {}",
+            tr(Msg::Channels).cyan(),
+            plan.channel_layout(),
+            format!("downmix to {}", target.label()).yellow()
+        ),
+        None if meta.channels > 2 => {
+            println!("  {} {}", tr(Msg::Channels).cyan(), plan.channel_layout())
+        }
+        None => {}
+    }
+    let gains: Vec<(u32, f64)> = tracks
+        .iter()
+        .filter_map(|track| plan.track_gain(track.number).map(|db| (track.number, db)))
+        .collect();
+    if gains.len() == tracks.len() && gains.iter().all(|(_, db)| *db == gains[0].1) {
+        println!(
+            "  {} {} {}",
+            tr(Msg::Gain).cyan(),
+            format_gain(gains[0].1).yellow(),
+            "(all tracks, clipped samples are limited)".dimmed()
+        );
+    } else if !gains.is_empty() {
+        let list: Vec<String> = gains
+            .iter()
+            .map(|(number, db)| format!("{:02}: {}", number, format_gain(*db)))
+            .collect();
+        println!(
+            "  {} {} {}",
+            tr(Msg::Gain).cyan(),
+            list.join(", ").yellow(),
+            "(clipped samples are limited)".dimmed()
+        );
+    }
+    let fade = plan.fade();
+    if !fade.is_none() {
+        println!(
+            "  {} {} {}",
+            tr(Msg::Fade).cyan(),
+            fade.describe().yellow(),
+            "(every track)".dimmed()
+        );
+    }
+
+    let pregaps = pregaps(cue, tracks, meta.sample_rate);
+    if !pregaps.is_empty() {
+        let total = |kept: bool| {
+            pregaps
+                .iter()
+                .filter(|pregap| pregap.kept == kept)
+                .map(|pregap| pregap.samples)
+                .sum::<u64>()
+        };
+        let mut parts = Vec::new();
+        if total(true) > 0 {
+            let length = format_time(total(true), meta.sample_rate);
+            parts.push(tr_with(Msg::PregapsAppended, &[&length]));
+        }
+        if total(false) > 0 {
+            let length = format_time(total(false), meta.sample_rate);
+            parts.push(
+                tr_with(Msg::PregapsDiscarded, &[&length])
+                    .yellow()
+                    .to_string(),
+            );
+        }
+        println!("  {} {}", tr(Msg::Pregaps).cyan(), parts.join(", "));
+    }
+
     let common_metadata = compute_common_metadata(meta, cue, tracks);
     let picture_count = meta.pictures.len();
     print_shared_metadata(&common_metadata, picture_count, picture_names);
 
+    let mut pregap_shown = HashSet::new();
     for track in tracks {
-        let start_frames = track.start / samples_per_frame;
-        let end_frames = track.end / samples_per_frame;
-        let length_frames = end_frames.saturating_sub(start_frames);
-
         let output_display = display_path(display_base_abs, &track.output_path);
         let output_target = format_output_target(&output_display);
-        let length = format_msf(length_frames);
-        let range = format!("({}-{})", format_msf(start_frames), format_msf(end_frames));
+        let time = |samples: u64| format_time(samples, meta.sample_rate);
+        let length = time(track.end.saturating_sub(track.start));
+        let range = format!("({}-{})", time(track.start), time(track.end));
         let unique_metadata =
             compute_unique_metadata_pairs(meta, cue, tracks, track, &common_metadata);
-        let tags = format_tag_pairs(&unique_metadata);
+        let mut tags = format_tag_pairs(&unique_metadata);
+        if let Some(pregap) = pregaps.iter().find(|pregap| pregap.track == track.number)
+            && pregap_shown.insert(track.number)
+        {
+            let length = format_time(pregap.samples, meta.sample_rate);
+            let note = tr_with(Msg::Pregap, &[&length]).dimmed().to_string();
+            tags = if tags.is_empty() {
+                note
+            } else {
+                format!("{} {}", tags, note)
+            };
+        }
+        if plan.track_selected(track.number)
+            && !plan.track_complete(track.number)
+            && let Some(size) = plan.estimated_track_size(track)
+        {
+            let note = format!("~{}", format_size(size)).dimmed().to_string();
+            tags = if tags.is_empty() {
+                note
+            } else {
+                format!("{} {}", tags, note)
+            };
+        }
+        if let Some(level) = track.compression_level {
+            let note = format!("(compression {})", level).yellow().to_string();
+            tags = if tags.is_empty() {
+                note
+            } else {
+                format!("{} {}", tags, note)
+            };
+        }
+        if !plan.track_selected(track.number) {
+            let skipped = tr(Msg::Skipped).dimmed().to_string();
+            tags = if tags.is_empty() {
+                skipped
+            } else {
+                format!("{} {}", tags, skipped)
+            };
+        } else if plan.track_complete(track.number) {
+            let kept = tr(Msg::CompleteKept).green().to_string();
+            tags = if tags.is_empty() {
+                kept
+            } else {
+                format!("{} {}", tags, kept)
+            };
+        }
         if tags.is_empty() {
             println!(
                 "{} {}",
@@ -100,11 +333,66 @@ pub(crate) fn print_plan(plan: &Plan) -> Result<()> {
                 tags
             );
         }
+
+        if plan.overwrite()
+            && plan.track_selected(track.number)
+            && !plan.track_complete(track.number)
+            && track.output_path.is_file()
+        {
+            let new_tags = build_track_tags(meta, cue, tracks.len(), track);
+            print_overwrite_diff(track, &new_tags, meta.sample_rate);
+        }
     }
 
     Ok(())
 }
 
+fn print_overwrite_diff(track: &TrackSpan, new_tags: &[(String, String)], sample_rate: u32) {
+    let existing =
+        match create_decoder(&track.output_path).and_then(|mut decoder| decoder.read_metadata()) {
+            Ok(decoded) => decoded.input_meta,
+            Err(err) => {
+                println!(
+                    "  {} {}",
+                    "overwrites existing file".red().bold(),
+                    format!("(unreadable: {})", err).dimmed()
+                );
+                return;
+            }
+        };
+
+    let new_samples = track.end - track.start;
+    let new_length = format_time(new_samples, sample_rate);
+    let length = if existing.total_samples == new_samples {
+        format!("length unchanged ({})", new_length)
+            .dimmed()
+            .to_string()
+    } else {
+        format!(
+            "length {} -> {}",
+            format_time(existing.total_samples, existing.sample_rate),
+            new_length.yellow()
+        )
+    };
+    println!("  {} {}", "overwrites existing file:".red().bold(), length);
+
+    let (removed, added) = diff_tags(&existing.comments, new_tags);
+    if removed.is_empty() && added.is_empty() {
+        println!("    {}", "tags unchanged".dimmed());
+    }
+    for (key, value) in &removed {
+        println!("    {} {}={}", "-".red().bold(), key.cyan(), value.red());
+    }
+    for (key, value) in &added {
+        println!(
+            "    {} {}={}",
+            "+".green().bold(),
+            key.cyan(),
+            value.green()
+        );
+    }
+}
+
 fn format_output_target(path: &Path) -> String {
     let file_name = path
         .file_name()
@@ -195,22 +483,158 @@ pub(crate) fn make_progress_bar(total_samples: u64) -> ProgressBar {
     }
 }
 
+pub(crate) struct BatchProgress {
+    multi: MultiProgress,
+    overall: ProgressBar,
+    albums: usize,
+    done: AtomicUsize,
+}
+
+impl BatchProgress {
+    pub(crate) fn new(albums: usize, total_samples: u64) -> Self {
+        let multi = MultiProgress::with_draw_target(ProgressDrawTarget::stderr_with_hz(10));
+        let overall = multi.add(ProgressBar::new(total_samples));
+        let style = ProgressStyle::with_template(
+            "{bar:60.green/white} {percent:>3}% {pos:>10}/{len:<10} {msg}",
+        )
+        .unwrap()
+        .progress_chars("=>-");
+        overall.set_style(style);
+        let batch = Self {
+            multi,
+            overall,
+            albums,
+            done: AtomicUsize::new(0),
+        };
+        batch.update_message();
+        batch
+    }
+
+    pub(crate) fn add_album_bar(&self, total_samples: u64) -> ProgressBar {
+        self.multi.add(make_progress_bar(total_samples))
+    }
+
+    pub(crate) fn advance(&self, samples: u64) {
+        self.overall.inc(samples);
+    }
+
+    pub(crate) fn finish_album(&self) {
+        self.done.fetch_add(1, Ordering::Relaxed);
+        self.update_message();
+    }
+
+    pub(crate) fn finish(&self) {
+        self.overall.finish();
+    }
+
+    pub(crate) fn abandon(&self) {
+        self.overall.abandon();
+    }
+
+    fn update_message(&self) {
+        self.overall.set_message(format!(
+            "albums {}/{}",
+            self.done.load(Ordering::Relaxed),
+            self.albums
+        ));
+    }
+}
+
+// Messages about one album. Albums split in parallel print theirs in one
+// piece when the album ends, so lines of different albums do not interleave.
+pub(crate) struct AlbumReport<'a> {
+    batch: Option<&'a BatchProgress>,
+    source: &'a str,
+    status_to_stderr: bool,
+    // Buffered lines and whether each goes to stderr.
+    lines: Vec<(bool, String)>,
+}
+
+impl<'a> AlbumReport<'a> {
+    pub(crate) fn new(
+        batch: Option<&'a BatchProgress>,
+        source: &'a str,
+        status_to_stderr: bool,
+    ) -> Self {
+        Self {
+            batch,
+            source,
+            status_to_stderr,
+            lines: Vec::new(),
+        }
+    }
+
+    // Warnings and notes, always on stderr.
+    pub(crate) fn note(&mut self, line: String) {
+        self.push(true, line);
+    }
+
+    // Results such as moved sources, on stdout unless the audio goes there.
+    pub(crate) fn status(&mut self, line: String) {
+        self.push(self.status_to_stderr, line);
+    }
+
+    fn push(&mut self, to_stderr: bool, line: String) {
+        if self.batch.is_some() {
+            self.lines.push((to_stderr, line));
+        } else if to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
+        }
+    }
+}
+
+impl Drop for AlbumReport<'_> {
+    fn drop(&mut self) {
+        let Some(batch) = self.batch else {
+            return;
+        };
+        if self.lines.is_empty() {
+            return;
+        }
+        batch.multi.suspend(|| {
+            let mut stdout = io::stdout().lock();
+            let mut stderr = io::stderr().lock();
+            let _ = writeln!(stderr, "{}", format!("{}:", self.source).bold());
+            for (to_stderr, line) in &self.lines {
+                let _ = if *to_stderr {
+                    writeln!(stderr, "  {}", line)
+                } else {
+                    writeln!(stdout, "  {}", line)
+                };
+            }
+        });
+    }
+}
+
 pub(crate) fn finish_progress(progress: &mut Option<ProgressBar>, message: &str) {
     if let Some(pb) = progress.take() {
         pb.finish_with_message(message.to_string());
     }
 }
 
-pub(crate) fn confirm_or_exit(yes: bool, allow_subdirs_edit: bool) -> Result<ConfirmAction> {
+pub(crate) fn confirm_or_exit(yes: bool, batch: bool) -> Result<ConfirmAction> {
     if yes {
         return Ok(ConfirmAction::Proceed);
     }
 
-    if allow_subdirs_edit {
-        print!("Proceed? [y/{}ubdirs/N]: ", "s".yellow().bold());
+    if batch {
+        print!(
+            "{}",
+            tr_with(
+                Msg::ProceedBatch,
+                &[&"s".yellow().bold(), &"p".yellow().bold()]
+            )
+        );
     } else {
-        print!("Proceed? [y/N]: ");
+        print!("{}", tr(Msg::Proceed));
     }
+    let input = read_answer()?;
+    Ok(parse_confirm_action(&input, batch))
+}
+
+fn read_answer() -> Result<String> {
     io::stdout()
         .flush()
         .map_err(|err| format!("failed to flush stdout: {}", err))?;
@@ -219,21 +643,78 @@ pub(crate) fn confirm_or_exit(yes: bool, allow_subdirs_edit: bool) -> Result<Con
     io::stdin()
         .read_line(&mut input)
         .map_err(|err| format!("failed to read confirmation: {}", err))?;
-
-    Ok(parse_confirm_action(&input, allow_subdirs_edit))
+    Ok(input)
 }
 
-fn parse_confirm_action(input: &str, allow_subdirs_edit: bool) -> ConfirmAction {
-    let answer = input.trim().to_ascii_lowercase();
-    if answer == "y" || answer == "yes" {
+fn parse_confirm_action(input: &str, batch: bool) -> ConfirmAction {
+    let answer = input.trim().to_lowercase();
+    if matches!(answer.as_str(), "y" | "yes" | "д" | "да") {
         return ConfirmAction::Proceed;
     }
-    if allow_subdirs_edit && (answer == "s" || answer == "subdirs") {
+    if batch && (answer == "s" || answer == "subdirs") {
         return ConfirmAction::EditSubdirs;
     }
+    if batch && (answer == "p" || answer == "per pair") {
+        return ConfirmAction::PerPair;
+    }
     ConfirmAction::Cancel
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum PairChoice {
+    Yes,
+    No,
+    All,
+    Skip,
+    Quit,
+}
+
+pub(crate) fn prompt_pair_choice(index: usize, total: usize, source: &Path) -> Result<PairChoice> {
+    loop {
+        print!(
+            "{} {}",
+            format!("[{}/{}]", index, total).bold().blue(),
+            tr_with(Msg::PairPrompt, &[&source.display()])
+        );
+        let input = read_answer()?;
+        if input.is_empty() {
+            return Ok(PairChoice::Quit);
+        }
+        if let Some(choice) = parse_pair_choice(&input) {
+            return Ok(choice);
+        }
+    }
+}
+
+fn parse_pair_choice(input: &str) -> Option<PairChoice> {
+    match input.trim().to_lowercase().as_str() {
+        "y" | "yes" | "д" | "да" => Some(PairChoice::Yes),
+        "n" | "no" | "н" | "нет" => Some(PairChoice::No),
+        "a" | "all" => Some(PairChoice::All),
+        "s" | "skip" => Some(PairChoice::Skip),
+        "q" | "quit" => Some(PairChoice::Quit),
+        _ => None,
+    }
+}
+
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if value < 1024.0 {
+            break;
+        }
+        value /= 1024.0;
+        unit = next;
+    }
+    if unit == "B" {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", value, unit)
+    }
+}
+
 pub(crate) fn format_msf(frames: u64) -> String {
     let total_seconds = frames / 75;
     let minutes = total_seconds / 60;
@@ -242,9 +723,167 @@ pub(crate) fn format_msf(frames: u64) -> String {
     format!("{:02}:{:02}:{:02}", minutes, seconds, frames)
 }
 
+pub(crate) fn format_time(samples: u64, sample_rate: u32) -> String {
+    format_time_as(
+        TIME_FORMAT.get().copied().unwrap_or_default(),
+        samples,
+        sample_rate,
+    )
+}
+
+fn format_time_as(format: TimeFormat, samples: u64, sample_rate: u32) -> String {
+    let rate = u64::from(sample_rate.max(1));
+    match format {
+        // Rates that are not a whole number of samples per CD frame cannot be
+        // shown as MSF without rounding, so they fall back to clock time.
+        TimeFormat::Msf if rate.is_multiple_of(75) => format_msf(samples / (rate / 75)),
+        TimeFormat::Msf | TimeFormat::Hms => {
+            let millis = samples * 1000 / rate;
+            let (hours, minutes) = (millis / 3_600_000, millis / 60_000 % 60);
+            let (seconds, millis) = (millis / 1000 % 60, millis % 1000);
+            if hours > 0 {
+                format!("{}:{:02}:{:02}.{:03}", hours, minutes, seconds, millis)
+            } else {
+                format!("{:02}:{:02}.{:03}", minutes, seconds, millis)
+            }
+        }
+        TimeFormat::Seconds => format!("{:.3} s", samples as f64 / rate as f64),
+        TimeFormat::Samples => samples.to_string(),
+    }
+}
+
+pub(crate) fn format_length_difference(samples: i64, sample_rate: u32) -> String {
+    let samples_per_frame = (sample_rate / 75).max(1) as i64;
+    let seconds = samples as f64 / sample_rate.max(1) as f64;
+    format!(
+        "{:+} frames, {:+.2} s",
+        samples / samples_per_frame,
+        seconds
+    )
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct AlbumWarnings {
+    pub(crate) source: PathBuf,
+    pub(crate) warnings: Vec<String>,
+}
+
+pub(crate) fn collect_warnings(plans: &[Plan]) -> Vec<AlbumWarnings> {
+    plans
+        .iter()
+        .filter(|plan| !plan.warnings().is_empty())
+        .map(|plan| AlbumWarnings {
+            source: plan.flac_display().to_path_buf(),
+            warnings: plan.warnings().to_vec(),
+        })
+        .collect()
+}
+
+fn warning_recap_lines(recap: &[AlbumWarnings]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for album in recap {
+        lines.push(format!("  {}", album.source.display()));
+        for warning in &album.warnings {
+            // Multi-line warnings (cue parser context) stay under their album.
+            for (index, line) in warning.lines().enumerate() {
+                let marker = if index == 0 { "-" } else { " " };
+                lines.push(format!("    {} {}", marker, line));
+            }
+        }
+    }
+    lines
+}
+
+pub(crate) fn print_warning_recap(recap: &[AlbumWarnings]) {
+    // The warnings printed with each plan have usually scrolled away by the
+    // end of a batch, so they are repeated once more on stderr.
+    if recap.is_empty() {
+        return;
+    }
+    eprintln!();
+    eprintln!(
+        "{}",
+        tr_with(Msg::WarningRecap, &[&recap.len()]).yellow().bold()
+    );
+    for line in warning_recap_lines(recap) {
+        eprintln!("{}", line.yellow());
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{ConfirmAction, parse_confirm_action};
+    use super::{
+        AlbumWarnings, ConfirmAction, PairChoice, TimeFormat, format_length_difference,
+        format_size, format_time_as, parse_confirm_action, parse_pair_choice, warning_recap_lines,
+    };
+    use std::path::PathBuf;
+
+    #[test]
+    fn warning_recap_groups_warnings_by_album() {
+        let recap = vec![
+            AlbumWarnings {
+                source: PathBuf::from("CD1/Album.flac"),
+                warnings: vec![
+                    "cue line 4: unknown command\n  FOO bar".to_string(),
+                    "track 2: TITLE is empty".to_string(),
+                ],
+            },
+            AlbumWarnings {
+                source: PathBuf::from("CD2/Album.flac"),
+                warnings: vec!["no front cover".to_string()],
+            },
+        ];
+        assert_eq!(
+            warning_recap_lines(&recap),
+            vec![
+                "  CD1/Album.flac",
+                "    - cue line 4: unknown command",
+                "        FOO bar",
+                "    - track 2: TITLE is empty",
+                "  CD2/Album.flac",
+                "    - no front cover",
+            ]
+        );
+    }
+
+    #[test]
+    fn format_time_as_supports_every_format() {
+        let samples = 3_723 * 44100 + 22050;
+        assert_eq!(format_time_as(TimeFormat::Msf, samples, 44100), "62:03:37");
+        assert_eq!(
+            format_time_as(TimeFormat::Hms, samples, 44100),
+            "1:02:03.500"
+        );
+        assert_eq!(format_time_as(TimeFormat::Hms, 44100, 44100), "00:01.000");
+        assert_eq!(
+            format_time_as(TimeFormat::Seconds, samples, 44100),
+            "3723.500 s"
+        );
+        assert_eq!(
+            format_time_as(TimeFormat::Samples, samples, 44100),
+            "164206350"
+        );
+        assert_eq!(format_time_as(TimeFormat::Msf, 11025, 22050), "00:00.500");
+    }
+
+    #[test]
+    fn format_size_uses_binary_units() {
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KiB");
+        assert_eq!(format_size(31 * 1024 * 1024 + 314_573), "31.3 MiB");
+    }
+
+    #[test]
+    fn format_length_difference_reports_frames_and_seconds() {
+        assert_eq!(
+            format_length_difference(-13230, 44100),
+            "-22 frames, -0.30 s"
+        );
+        assert_eq!(
+            format_length_difference(88200, 44100),
+            "+150 frames, +2.00 s"
+        );
+    }
 
     #[test]
     fn parse_confirm_action_accepts_yes() {
@@ -256,6 +895,10 @@ mod tests {
             parse_confirm_action("YES", true),
             ConfirmAction::Proceed
         ));
+        assert!(matches!(
+            parse_confirm_action("Да", false),
+            ConfirmAction::Proceed
+        ));
     }
 
     #[test]
@@ -274,6 +917,29 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn parse_confirm_action_offers_per_pair_only_in_batches() {
+        assert!(matches!(
+            parse_confirm_action("p", true),
+            ConfirmAction::PerPair
+        ));
+        assert!(matches!(
+            parse_confirm_action("p", false),
+            ConfirmAction::Cancel
+        ));
+    }
+
+    #[test]
+    fn parse_pair_choice_accepts_all_answers() {
+        assert_eq!(parse_pair_choice("y\n"), Some(PairChoice::Yes));
+        assert_eq!(parse_pair_choice("N"), Some(PairChoice::No));
+        assert_eq!(parse_pair_choice("all"), Some(PairChoice::All));
+        assert_eq!(parse_pair_choice("s"), Some(PairChoice::Skip));
+        assert_eq!(parse_pair_choice("q"), Some(PairChoice::Quit));
+        assert_eq!(parse_pair_choice(""), None);
+        assert_eq!(parse_pair_choice("maybe"), None);
+    }
+
     #[test]
     fn parse_confirm_action_defaults_to_cancel() {
         assert!(matches!(
@@ -0,0 +1,280 @@
+use owo_colors::OwoColorize;
+use std::path::{Path, PathBuf};
+
+use crate::Result;
+use crate::checksum::{AUDIO_MD5_TAG, PcmHasher, format_md5};
+use crate::cli::{VerifyArgs, display_path, find_track_files, resolve_input_path};
+use crate::cue::{cue_file_name, parse_cue_file, report_cue_warnings, resolve_encoding};
+use crate::decoder::{DEFAULT_BLOCK_SIZE, create_decoder};
+use crate::jobs::run_parallel;
+use crate::metadata::{build_override_tags, find_tag};
+use crate::priority::lower_priority;
+use crate::rules::TagRules;
+use crate::split::compute_track_spans;
+use crate::types::{CueDisc, InputMetadata, TrackSpan};
+
+struct VerifyFile {
+    path: PathBuf,
+    meta: InputMetadata,
+}
+
+pub(crate) fn run_verify(args: VerifyArgs) -> Result<()> {
+    lower_priority(&args.priority)?;
+    let cwd = std::env::current_dir()
+        .map_err(|err| format!("failed to get current directory: {}", err))?;
+    let dir_abs = absolute(&cwd, &args.dir);
+    if !dir_abs.is_dir() {
+        return Err(format!("directory not found: {}", dir_abs.display()));
+    }
+
+    let encoding = match args.cue_encoding.as_deref() {
+        Some(label) => Some(resolve_encoding(label)?),
+        None => None,
+    };
+    let cue_path = args.cue.as_ref().map(|path| absolute(&cwd, path));
+    let cue_input = resolve_input_path(&dir_abs, Some(cwd.as_path()), cue_path.as_ref(), "cue")?;
+    let (cue, warnings, _, _) = parse_cue_file(&cue_input.abs, encoding)?;
+    report_cue_warnings(&warnings);
+
+    let image = match args.flac.as_ref() {
+        Some(path) => {
+            let path = absolute(&cwd, path);
+            if !path.is_file() {
+                return Err(format!("file not found: {}", path.display()));
+            }
+            let mut decoder = create_decoder(&path)?;
+            let meta = decoder.read_metadata()?.input_meta;
+            Some(VerifyFile { path, meta })
+        }
+        None => None,
+    };
+
+    let files = read_split_tracks(&dir_abs, &cue, image.as_ref())?;
+
+    println!("{}", "Verify".bold());
+    println!("  {} {}", "CUE:".cyan(), cue_input.display.display());
+    println!(
+        "  {} {}",
+        "Tracks:".cyan(),
+        display_path(Some(cwd.as_path()), &dir_abs).display()
+    );
+    if let Some(image) = image.as_ref() {
+        println!(
+            "  {} {}",
+            "Image:".cyan(),
+            display_path(Some(cwd.as_path()), &image.path).display()
+        );
+    }
+
+    if files.len() != cue.tracks.len() {
+        return Err(format!(
+            "verification failed: found {} .flac files in {} but the cue sheet has {} tracks",
+            files.len(),
+            dir_abs.display(),
+            cue.tracks.len()
+        ));
+    }
+
+    let sample_rate = files[0].meta.sample_rate;
+    let total_samples = match image.as_ref() {
+        Some(image) => image.meta.total_samples,
+        None => files.iter().map(|file| file.meta.total_samples).sum(),
+    };
+    let spans = compute_track_spans(&cue, sample_rate, total_samples)?;
+    let covers_image = spans.first().is_some_and(|span| span.start == 0)
+        && spans.last().is_some_and(|span| span.end == total_samples);
+    let tolerance = args.tolerance * (sample_rate / 75) as u64;
+
+    let track_md5s = if args.md5 {
+        run_parallel(files.iter().collect(), args.jobs, |file: &VerifyFile| {
+            find_tag(&file.meta.comments, AUDIO_MD5_TAG)
+                .map(|_| hash_file(&file.path, file.meta.bits_per_sample))
+        })
+    } else {
+        Vec::new()
+    };
+
+    let mut problems = 0usize;
+    for (index, (file, span)) in files.iter().zip(spans.into_iter()).enumerate() {
+        let expected_length = span.end - span.start;
+        let track = TrackSpan {
+            number: span.number,
+            start: span.start,
+            end: span.end,
+            title: span.title,
+            performer: span.performer,
+            songwriter: span.songwriter,
+            composer: span.composer,
+            isrc: span.isrc,
+            rem: span.rem,
+            tag_overrides: Vec::new(),
+            tag_rules: TagRules::default(),
+            fixed_tags: None,
+            compression_level: None,
+            output_path: file.path.clone(),
+        };
+
+        let mut issues = Vec::new();
+        if let Some(image) = image.as_ref()
+            && (file.meta.sample_rate != image.meta.sample_rate
+                || file.meta.channels != image.meta.channels
+                || file.meta.bits_per_sample != image.meta.bits_per_sample)
+        {
+            issues.push(format!(
+                "audio format {} Hz, {} ch, {} bits does not match the image",
+                file.meta.sample_rate, file.meta.channels, file.meta.bits_per_sample
+            ));
+        }
+        if file.meta.total_samples.abs_diff(expected_length) > tolerance {
+            issues.push(format!(
+                "length is {} samples, expected {} ({:+})",
+                file.meta.total_samples,
+                expected_length,
+                file.meta.total_samples as i64 - expected_length as i64
+            ));
+        }
+        if let Some(Some(hashed)) = track_md5s.get(index)
+            && let Some(expected) = find_tag(&file.meta.comments, AUDIO_MD5_TAG)
+        {
+            let actual = format_md5(hashed.as_ref().map_err(Clone::clone)?);
+            if !actual.eq_ignore_ascii_case(expected) {
+                issues.push(format!(
+                    "audio MD5 is {}, {} tag says {}",
+                    actual, AUDIO_MD5_TAG, expected
+                ));
+            }
+        }
+        for (key, expected) in build_override_tags(&cue, cue.tracks.len(), &track) {
+            match find_tag(&file.meta.comments, &key) {
+                Some(actual) if actual == expected => {}
+                Some(actual) => {
+                    issues.push(format!("{} is {:?}, expected {:?}", key, actual, expected))
+                }
+                None => issues.push(format!("{} is missing, expected {:?}", key, expected)),
+            }
+        }
+
+        let name = display_path(Some(dir_abs.as_path()), &file.path)
+            .display()
+            .to_string();
+        if issues.is_empty() {
+            println!("{} {}", "ok".green().bold(), name);
+        } else {
+            println!("{} {}", "FAIL".red().bold(), name.bold());
+            for issue in &issues {
+                println!("  {}", issue.red());
+            }
+            problems += issues.len();
+        }
+    }
+
+    if args.md5
+        && let Some(image) = image.as_ref()
+    {
+        if covers_image {
+            problems += verify_md5(image, &files)?;
+        } else {
+            println!(
+                "{} {}",
+                "FAIL".red().bold(),
+                "tracks do not cover the whole image; MD5 cannot be compared".red()
+            );
+            problems += 1;
+        }
+    }
+
+    if problems > 0 {
+        return Err(format!("verification failed with {} problem(s)", problems));
+    }
+    println!("{}", "Verification passed".green().bold());
+    Ok(())
+}
+
+fn verify_md5(image: &VerifyFile, files: &[VerifyFile]) -> Result<usize> {
+    let Some(expected) = image.meta.md5 else {
+        println!(
+            "{} {}",
+            "FAIL".red().bold(),
+            "image has no MD5 signature to compare against".red()
+        );
+        return Ok(1);
+    };
+
+    let mut hasher = PcmHasher::new(image.meta.bits_per_sample);
+    for file in files {
+        let decoder = create_decoder(&file.path)?;
+        for block in decoder.into_blocks(DEFAULT_BLOCK_SIZE)? {
+            let block = block?;
+            hasher.update(&block.interleaved);
+        }
+    }
+    let actual = hasher.finish();
+
+    if actual == expected {
+        println!("{} MD5 {}", "ok".green().bold(), format_md5(&actual));
+        Ok(0)
+    } else {
+        println!(
+            "{} MD5 of joined tracks is {}, image has {}",
+            "FAIL".red().bold(),
+            format_md5(&actual).red(),
+            format_md5(&expected)
+        );
+        Ok(1)
+    }
+}
+
+pub(crate) fn hash_file(path: &Path, bits_per_sample: u32) -> Result<[u8; 16]> {
+    let mut hasher = PcmHasher::new(bits_per_sample);
+    let decoder = create_decoder(path)?;
+    for block in decoder.into_blocks(DEFAULT_BLOCK_SIZE)? {
+        hasher.update(&block?.interleaved);
+    }
+    Ok(hasher.finish())
+}
+
+fn read_split_tracks(
+    dir: &Path,
+    cue: &CueDisc,
+    image: Option<&VerifyFile>,
+) -> Result<Vec<VerifyFile>> {
+    let referenced: Vec<&str> = cue
+        .tracks
+        .iter()
+        .filter_map(|track| track.filename.as_deref())
+        .map(cue_file_name)
+        .collect();
+
+    let mut files = Vec::new();
+    for path in find_track_files(dir)? {
+        if image.is_some_and(|image| image.path == path) {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if referenced
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(&name))
+        {
+            continue;
+        }
+        let mut decoder = create_decoder(&path)?;
+        let meta = decoder.read_metadata()?.input_meta;
+        files.push(VerifyFile { path, meta });
+    }
+
+    if files.is_empty() {
+        return Err(format!("no .flac files found in {}", dir.display()));
+    }
+    Ok(files)
+}
+
+fn absolute(cwd: &Path, path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        cwd.join(path)
+    }
+}
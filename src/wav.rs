@@ -0,0 +1,334 @@
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+use crate::Result;
+use crate::decoder::{AudioBlock, ByteOrder, Decoder, DecoderMetadata, PcmBlockIter, PcmFormat};
+use crate::id3::merge_id3_tags;
+use crate::iff::{MAX_TAG_CHUNK, decode_text, read_body, read_id3_chunk, walk_chunks};
+use crate::metadata::find_tag;
+use crate::types::InputMetadata;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+const WAVE_FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+// RF64 files keep their sizes in the ds64 chunk and put this in the 32-bit
+// fields; streaming writers use it for a data chunk of unknown length.
+const SIZE_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct WavLayout {
+    format: PcmFormat,
+    sample_rate: u32,
+    channel_mask: Option<u32>,
+    data_offset: u64,
+    data_len: u64,
+    info_tags: Vec<(String, String)>,
+    id3_tags: Vec<(String, String)>,
+}
+
+impl WavLayout {
+    fn total_samples(&self) -> u64 {
+        self.data_len / self.format.block_align() as u64
+    }
+}
+
+pub(crate) struct WavDecoder {
+    path: PathBuf,
+}
+
+impl WavDecoder {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    fn open(&self) -> Result<(BufReader<File>, WavLayout)> {
+        let file = File::open(&self.path)
+            .map_err(|err| format!("failed to open {}: {}", self.path.display(), err))?;
+        let file_len = file
+            .metadata()
+            .map_err(|err| format!("failed to read {}: {}", self.path.display(), err))?
+            .len();
+        let mut reader = BufReader::new(file);
+        let layout = read_layout(&mut reader, file_len)
+            .map_err(|err| format!("{}: {}", self.path.display(), err))?;
+        Ok((reader, layout))
+    }
+}
+
+impl Decoder for WavDecoder {
+    fn read_metadata(&mut self) -> Result<DecoderMetadata> {
+        let (_, layout) = self.open()?;
+        let mut input_meta = InputMetadata::new();
+        input_meta.sample_rate = layout.sample_rate;
+        input_meta.channels = layout.format.channels;
+        input_meta.channel_mask = layout.channel_mask;
+        input_meta.bits_per_sample = layout.format.bits_per_sample;
+        input_meta.total_samples = layout.total_samples();
+        // An ID3 chunk is usually the richer tag, so LIST/INFO only fills gaps.
+        input_meta.comments = layout.id3_tags.clone();
+        merge_id3_tags(&mut input_meta.comments, &layout.info_tags);
+        input_meta.embedded_cue = find_tag(&input_meta.comments, "CUESHEET").map(str::to_string);
+
+        Ok(DecoderMetadata {
+            input_meta,
+            picture_names: Vec::new(),
+        })
+    }
+
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        let (mut reader, layout) = self.open()?;
+        reader
+            .seek(SeekFrom::Start(layout.data_offset))
+            .map_err(|err| format!("failed to read {}: {}", self.path.display(), err))?;
+        Ok(Box::new(PcmBlockIter::new(
+            reader,
+            layout.format,
+            layout.total_samples(),
+            block_size,
+        )))
+    }
+}
+
+fn read_layout<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<WavLayout> {
+    let io_error = |err: std::io::Error| err.to_string();
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header).map_err(io_error)?;
+    let rf64 = match &header[..4] {
+        b"RIFF" => false,
+        b"RF64" | b"BW64" => true,
+        _ => return Err("not a RIFF/WAVE file".to_string()),
+    };
+    if &header[8..] != b"WAVE" {
+        return Err("not a RIFF/WAVE file".to_string());
+    }
+
+    let mut layout = WavLayout::default();
+    let mut ds64_data_len = None;
+    let mut found_fmt = false;
+    let mut found_data = false;
+    walk_chunks(reader, file_len, ByteOrder::Little, |reader, chunk| {
+        let remaining = file_len - chunk.start;
+        let size = match (&chunk.id, chunk.size) {
+            (b"data", SIZE_UNKNOWN) if rf64 => ds64_data_len
+                .ok_or_else(|| "RF64 file has no ds64 chunk in front of its data".to_string())?,
+            (b"data", SIZE_UNKNOWN) => remaining,
+            (_, size) => u64::from(size),
+        };
+        match &chunk.id {
+            b"ds64" => {
+                let body = read_body(reader, size)?;
+                if body.len() < 16 {
+                    return Err("RF64 ds64 chunk is too short".to_string());
+                }
+                ds64_data_len = Some(u64::from_le_bytes(body[8..16].try_into().unwrap()));
+            }
+            b"fmt " => {
+                parse_fmt(&read_body(reader, size)?, &mut layout)?;
+                found_fmt = true;
+            }
+            b"data" => {
+                // A data chunk cut short by a failed copy still holds audio.
+                let size = size.min(remaining);
+                layout.data_offset = chunk.start;
+                layout.data_len = size;
+                found_data = true;
+                return Ok(size);
+            }
+            b"LIST" if size <= MAX_TAG_CHUNK => {
+                layout
+                    .info_tags
+                    .extend(parse_info(&read_body(reader, size)?));
+            }
+            b"id3 " | b"ID3 " if size <= MAX_TAG_CHUNK => {
+                layout.id3_tags.extend(read_id3_chunk(reader, size)?);
+            }
+            _ => {}
+        }
+        Ok(size)
+    })?;
+
+    if !found_fmt {
+        return Err("WAV file has no fmt chunk".to_string());
+    }
+    if !found_data {
+        return Err("WAV file has no data chunk".to_string());
+    }
+    Ok(layout)
+}
+
+fn parse_fmt(body: &[u8], layout: &mut WavLayout) -> Result<()> {
+    if body.len() < 16 {
+        return Err("WAV fmt chunk is too short".to_string());
+    }
+    let u16_at = |offset: usize| u16::from_le_bytes([body[offset], body[offset + 1]]);
+    let mut format = u16_at(0);
+    let channels = u32::from(u16_at(2));
+    let block_align = usize::from(u16_at(12));
+    let container_bits = u32::from(u16_at(14));
+    let mut valid_bits = container_bits;
+    layout.sample_rate = u32::from_le_bytes(body[4..8].try_into().unwrap());
+    if format == WAVE_FORMAT_EXTENSIBLE {
+        if body.len() < 26 {
+            return Err("WAV fmt chunk is too short for WAVE_FORMAT_EXTENSIBLE".to_string());
+        }
+        if u16_at(18) != 0 {
+            valid_bits = u32::from(u16_at(18));
+        }
+        let mask = u32::from_le_bytes(body[20..24].try_into().unwrap());
+        layout.channel_mask = (mask != 0).then_some(mask);
+        // The sub-format GUID starts with the plain format tag.
+        format = u16_at(24);
+    }
+
+    match format {
+        WAVE_FORMAT_PCM => {}
+        WAVE_FORMAT_IEEE_FLOAT => {
+            return Err("floating-point WAV files are not supported".to_string());
+        }
+        format => return Err(format!("unsupported WAV format tag 0x{:04X}", format)),
+    }
+    if channels == 0 {
+        return Err("WAV channel count is zero".to_string());
+    }
+    if layout.sample_rate == 0 {
+        return Err("WAV sample rate is zero".to_string());
+    }
+    let bytes_per_sample = block_align / channels as usize;
+    if !(1..=4).contains(&bytes_per_sample)
+        || bytes_per_sample * channels as usize != block_align
+        || container_bits.div_ceil(8) as usize != bytes_per_sample
+        || valid_bits == 0
+        || valid_bits > container_bits
+    {
+        return Err(format!(
+            "unsupported WAV sample layout ({} bits in {}-byte frames of {} channels)",
+            valid_bits, block_align, channels
+        ));
+    }
+    layout.format = PcmFormat {
+        channels,
+        bits_per_sample: valid_bits,
+        bytes_per_sample,
+        byte_order: ByteOrder::Little,
+    };
+    Ok(())
+}
+
+fn parse_info(body: &[u8]) -> Vec<(String, String)> {
+    let mut tags = Vec::new();
+    if body.get(..4) != Some(b"INFO".as_slice()) {
+        return tags;
+    }
+    let mut pos = 4;
+    while pos + 8 <= body.len() {
+        let id = &body[pos..pos + 4];
+        let size = u32::from_le_bytes(body[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let start = pos + 8;
+        let Some(text) = body.get(start..start + size) else {
+            break;
+        };
+        if let Some(key) = info_key(id)
+            && let Some(value) = decode_text(text)
+        {
+            tags.push((key.to_string(), value));
+        }
+        pos = start + size + (size & 1);
+    }
+    tags
+}
+
+fn info_key(id: &[u8]) -> Option<&'static str> {
+    match id {
+        b"INAM" => Some("TITLE"),
+        b"IART" => Some("ARTIST"),
+        b"IPRD" => Some("ALBUM"),
+        b"ICRD" => Some("DATE"),
+        b"IGNR" => Some("GENRE"),
+        b"ICMT" => Some("COMMENT"),
+        b"ICOP" => Some("COPYRIGHT"),
+        b"ITRK" | b"IPRT" => Some("TRACKNUMBER"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_layout;
+    use crate::decoder::{ByteOrder, PcmBlockIter};
+    use crate::iff::chunk;
+    use std::io::{Cursor, Seek, SeekFrom};
+
+    #[test]
+    fn rf64_24_bit_extensible_files_are_read() {
+        let samples: [i32; 6] = [1, -1, 0x7F_FFFF, -0x80_0000, 4660, -4660];
+        let data: Vec<u8> = samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes()[..3].to_vec())
+            .collect();
+
+        let mut ds64 = Vec::new();
+        ds64.extend_from_slice(&0u64.to_le_bytes());
+        ds64.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        ds64.extend_from_slice(&3u64.to_le_bytes());
+        ds64.extend_from_slice(&0u32.to_le_bytes());
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&0xFFFEu16.to_le_bytes());
+        fmt.extend_from_slice(&2u16.to_le_bytes());
+        fmt.extend_from_slice(&96_000u32.to_le_bytes());
+        fmt.extend_from_slice(&(96_000u32 * 6).to_le_bytes());
+        fmt.extend_from_slice(&6u16.to_le_bytes());
+        fmt.extend_from_slice(&24u16.to_le_bytes());
+        fmt.extend_from_slice(&22u16.to_le_bytes());
+        fmt.extend_from_slice(&24u16.to_le_bytes());
+        fmt.extend_from_slice(&3u32.to_le_bytes());
+        fmt.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0x10, 0]);
+        fmt.extend_from_slice(&[0x80, 0, 0, 0xAA, 0, 0x38, 0x9B, 0x71]);
+        let mut info = b"INFO".to_vec();
+        info.extend(chunk(b"INAM", b"Caf\xe9\0", ByteOrder::Little));
+        info.extend(chunk(b"IART", b"Artist\0", ByteOrder::Little));
+
+        let mut file = b"RF64\xff\xff\xff\xffWAVE".to_vec();
+        file.extend(chunk(b"ds64", &ds64, ByteOrder::Little));
+        file.extend(chunk(b"fmt ", &fmt, ByteOrder::Little));
+        file.extend_from_slice(b"data\xff\xff\xff\xff");
+        file.extend_from_slice(&data);
+        file.extend(chunk(b"LIST", &info, ByteOrder::Little));
+
+        let mut reader = Cursor::new(file);
+        let len = reader.get_ref().len() as u64;
+        let layout = read_layout(&mut reader, len).unwrap();
+        assert_eq!(
+            (
+                layout.format.channels,
+                layout.sample_rate,
+                layout.format.bits_per_sample
+            ),
+            (2, 96_000, 24)
+        );
+        assert_eq!(layout.channel_mask, Some(3));
+        assert_eq!(layout.total_samples(), 3);
+        assert_eq!(
+            layout.info_tags,
+            vec![
+                ("TITLE".to_string(), "Café".to_string()),
+                ("ARTIST".to_string(), "Artist".to_string()),
+            ]
+        );
+
+        reader.seek(SeekFrom::Start(layout.data_offset)).unwrap();
+        let blocks: Vec<_> = PcmBlockIter::new(reader, layout.format, layout.total_samples(), 2)
+            .map(|block| block.unwrap())
+            .collect();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[1].sample_index, 2);
+        let decoded: Vec<i32> = blocks
+            .into_iter()
+            .flat_map(|block| block.interleaved)
+            .collect();
+        assert_eq!(decoded, samples);
+    }
+}
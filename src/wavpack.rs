@@ -3,8 +3,10 @@ use std::os::raw::c_char;
 use std::path::{Path, PathBuf};
 
 use crate::Result;
+use crate::channels::default_channel_mask;
 use crate::decoder::{AudioBlock, Decoder, DecoderMetadata};
-use crate::flac::FlacMetadata;
+use crate::flac::{FlacMetadata, path_to_cstring};
+use crate::metadata::find_tag;
 use crate::picture::build_picture_metadata_from_data;
 use crate::types::InputMetadata;
 
@@ -18,6 +20,16 @@ mod wavpack_bindings {
     include!(concat!(env!("OUT_DIR"), "/wavpack_bindings.rs"));
 }
 
+pub(crate) fn wavpack_library_version() -> String {
+    let version = unsafe { wavpack_bindings::WavpackGetLibraryVersionString() };
+    if version.is_null() {
+        return "unknown".to_string();
+    }
+    unsafe { CStr::from_ptr(version) }
+        .to_string_lossy()
+        .into_owned()
+}
+
 pub(crate) struct WavPackDecoder {
     path: PathBuf,
 }
@@ -35,6 +47,7 @@ impl WavPackDecoder {
 
         let mut picture_names = Vec::new();
         self.fill_pictures(&handle, &mut input_meta, &mut picture_names)?;
+        input_meta.embedded_cue = find_tag(&input_meta.comments, "CUESHEET").map(str::to_string);
 
         Ok(DecoderMetadata {
             input_meta,
@@ -62,8 +75,12 @@ impl WavPackDecoder {
 
         input_meta.sample_rate = sample_rate;
         input_meta.channels = channels;
+        input_meta.channel_mask = handle
+            .channel_mask()
+            .filter(|mask| Some(*mask) != default_channel_mask(channels));
         input_meta.bits_per_sample = bits_per_sample;
         input_meta.total_samples = handle.total_samples();
+        input_meta.md5 = handle.md5();
         Ok(())
     }
 
@@ -95,8 +112,11 @@ impl Decoder for WavPackDecoder {
         self.read_metadata_internal()
     }
 
-    fn into_blocks(self: Box<Self>) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
-        Ok(Box::new(WavPackBlockIter::new(&self.path)?))
+    fn into_blocks(
+        self: Box<Self>,
+        block_size: usize,
+    ) -> Result<Box<dyn Iterator<Item = Result<AudioBlock>>>> {
+        Ok(Box::new(WavPackBlockIter::new(&self.path, block_size)?))
     }
 }
 
@@ -111,8 +131,7 @@ struct WavPackHandle {
 
 impl WavPackHandle {
     fn open(path: &Path, with_tags: bool) -> Result<Self> {
-        let path_c = CString::new(path.to_string_lossy().as_bytes())
-            .map_err(|_| format!("path contains NUL byte: {}", path.display()))?;
+        let path_c = path_to_cstring(path)?;
 
         let mut error = [0i8; 81];
         let mut flags = 0i32;
@@ -149,12 +168,29 @@ impl WavPackHandle {
         unsafe { wavpack_bindings::WavpackGetNumChannels(self.context) as u32 }
     }
 
+    fn channel_mask(&self) -> Option<u32> {
+        let mask = unsafe { wavpack_bindings::WavpackGetChannelMask(self.context) };
+        if mask > 0 { Some(mask as u32) } else { None }
+    }
+
     fn bits_per_sample(&self) -> u32 {
         unsafe { wavpack_bindings::WavpackGetBitsPerSample(self.context) as u32 }
     }
 
     fn total_samples(&self) -> u64 {
-        unsafe { wavpack_bindings::WavpackGetNumSamples64(self.context) as u64 }
+        // -1 means the length is unknown; 0 makes the caller count the samples.
+        let samples = unsafe { wavpack_bindings::WavpackGetNumSamples64(self.context) };
+        u64::try_from(samples).unwrap_or(0)
+    }
+
+    fn md5(&self) -> Option<[u8; 16]> {
+        let mut sum = [0u8; 16];
+        let ok = unsafe { wavpack_bindings::WavpackGetMD5Sum(self.context, sum.as_mut_ptr()) };
+        if ok != 0 && sum.iter().any(|byte| *byte != 0) {
+            Some(sum)
+        } else {
+            None
+        }
     }
 
     fn sample_index(&self) -> u64 {
@@ -174,7 +210,7 @@ impl WavPackHandle {
             wavpack_bindings::WavpackUnpackSamples(
                 self.context,
                 interleaved.as_mut_ptr(),
-                max_samples as u32,
+                u32::try_from(max_samples).unwrap_or(u32::MAX),
             )
         };
         Ok(samples as usize)
@@ -360,7 +396,7 @@ struct WavPackBlockIter {
 }
 
 impl WavPackBlockIter {
-    fn new(path: &Path) -> Result<Self> {
+    fn new(path: &Path, block_size: usize) -> Result<Self> {
         let handle = WavPackHandle::open(path, false)?;
         let channels = handle.channels() as usize;
         if channels == 0 {
@@ -370,7 +406,7 @@ impl WavPackBlockIter {
         Ok(Self {
             handle,
             channels,
-            buffer: vec![0i32; 4096 * channels],
+            buffer: vec![0i32; block_size.max(1) * channels],
             done: false,
         })
     }
@@ -407,7 +443,7 @@ impl Iterator for WavPackBlockIter {
     }
 }
 
-fn split_picture_blob(bytes: &[u8]) -> (Option<String>, &[u8]) {
+pub(crate) fn split_picture_blob(bytes: &[u8]) -> (Option<String>, &[u8]) {
     if let Some(pos) = bytes.iter().position(|byte| *byte == 0) {
         let name = decode_lossy_bytes(&bytes[..pos]);
         let data = if pos + 1 < bytes.len() {
@@ -421,7 +457,7 @@ fn split_picture_blob(bytes: &[u8]) -> (Option<String>, &[u8]) {
     (None, bytes)
 }
 
-fn decode_lossy_bytes(bytes: &[u8]) -> Option<String> {
+pub(crate) fn decode_lossy_bytes(bytes: &[u8]) -> Option<String> {
     let end = bytes
         .iter()
         .position(|byte| *byte == 0)
@@ -436,3 +472,128 @@ fn decode_lossy_bytes(bytes: &[u8]) -> Option<String> {
         Some(decoded)
     }
 }
+
+#[cfg(feature = "test-utils")]
+pub(crate) fn write_wavpack_image(
+    path: &Path,
+    meta: &InputMetadata,
+    interleaved: &[i32],
+    tags: &[(String, String)],
+    picture: Option<&[u8]>,
+) -> Result<()> {
+    use std::io::Write;
+    use std::os::raw::{c_int, c_void};
+
+    unsafe extern "C" fn write_block(id: *mut c_void, data: *mut c_void, bcount: i32) -> c_int {
+        let file = unsafe { &mut *(id as *mut std::fs::File) };
+        let bytes = unsafe { std::slice::from_raw_parts(data as *const u8, bcount as usize) };
+        c_int::from(file.write_all(bytes).is_ok())
+    }
+
+    let mut file = std::fs::File::create(path)
+        .map_err(|err| format!("failed to create {}: {}", path.display(), err))?;
+    let context = unsafe {
+        wavpack_bindings::WavpackOpenFileOutput(
+            Some(write_block),
+            &mut file as *mut std::fs::File as *mut c_void,
+            std::ptr::null_mut(),
+        )
+    };
+    if context.is_null() {
+        return Err("failed to create WavPack encoder".to_string());
+    }
+    let handle = WavPackHandle { context };
+
+    let mut config: wavpack_bindings::WavpackConfig = unsafe { std::mem::zeroed() };
+    config.bits_per_sample = meta.bits_per_sample as i32;
+    config.bytes_per_sample = meta.bits_per_sample.div_ceil(8) as i32;
+    config.num_channels = meta.channels as i32;
+    config.sample_rate = meta.sample_rate as i32;
+    config.channel_mask = meta
+        .channel_mask
+        .or_else(|| default_channel_mask(meta.channels))
+        .unwrap_or(0) as i32;
+    let ok = unsafe {
+        wavpack_bindings::WavpackSetConfiguration64(
+            handle.context,
+            &mut config,
+            meta.total_samples as i64,
+            std::ptr::null(),
+        ) != 0
+            && wavpack_bindings::WavpackPackInit(handle.context) != 0
+    };
+    if !ok {
+        return Err(format!(
+            "failed to configure WavPack encoder for {}",
+            path.display()
+        ));
+    }
+
+    let mut samples = interleaved.to_vec();
+    let frames = samples.len() / meta.channels.max(1) as usize;
+    let ok = unsafe {
+        wavpack_bindings::WavpackPackSamples(handle.context, samples.as_mut_ptr(), frames as u32)
+            != 0
+            && wavpack_bindings::WavpackFlushSamples(handle.context) != 0
+    };
+    if !ok {
+        return Err(format!(
+            "failed to write WavPack samples to {}",
+            path.display()
+        ));
+    }
+
+    for (key, value) in tags {
+        let key_c =
+            CString::new(key.as_str()).map_err(|_| format!("tag {:?} contains NUL", key))?;
+        let ok = unsafe {
+            wavpack_bindings::WavpackAppendTagItem(
+                handle.context,
+                key_c.as_ptr(),
+                value.as_ptr() as *const c_char,
+                value.len() as i32,
+            ) != 0
+        };
+        if !ok {
+            return Err(format!(
+                "failed to add WavPack tag {} to {}",
+                key,
+                path.display()
+            ));
+        }
+    }
+    if let Some(data) = picture {
+        let name: &[u8] = if data.starts_with(b"\x89PNG") {
+            b"cover.png\0"
+        } else {
+            b"cover.jpg\0"
+        };
+        let mut blob = name.to_vec();
+        blob.extend_from_slice(data);
+        let ok = unsafe {
+            wavpack_bindings::WavpackAppendBinaryTagItem(
+                handle.context,
+                c"Cover Art (Front)".as_ptr(),
+                blob.as_ptr() as *const c_char,
+                blob.len() as i32,
+            ) != 0
+        };
+        if !ok {
+            return Err(format!(
+                "failed to add WavPack cover art to {}",
+                path.display()
+            ));
+        }
+    }
+    if (!tags.is_empty() || picture.is_some())
+        && unsafe { wavpack_bindings::WavpackWriteTag(handle.context) } == 0
+    {
+        return Err(format!(
+            "failed to write WavPack tags to {}",
+            path.display()
+        ));
+    }
+    drop(handle);
+    file.flush()
+        .map_err(|err| format!("failed to write {}: {}", path.display(), err))
+}
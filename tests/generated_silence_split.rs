@@ -1,144 +1,338 @@
+use flac_cue_split::test_utils::{Fixture, ImageFormat, TINY_PNG, Tone};
 use libflac_sys as flac;
-use std::ffi::{CStr, CString};
-use std::fs;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::Command;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
 
 #[test]
 fn splits_generated_silent_flac_with_generated_cue() {
     let dir = unique_test_dir("generated-silence-split");
     fs::create_dir_all(&dir).expect("failed to create test directory");
 
-    let input_flac = dir.join("album.flac");
-    let input_cue = dir.join("album.cue");
+    Fixture::default()
+        .write(&dir, "album")
+        .expect("failed to generate fixture");
 
-    write_silent_flac(&input_flac, 44_100, 2, 44_100 * 3).expect("failed to generate FLAC");
-    write_cue(&input_cue, "album.flac");
+    let output = run_split(&dir, &["--flac", "album.flac", "--cue", "album.cue"]);
+    assert_success(&output);
+
+    assert!(dir.join("1 - One.flac").is_file());
+    assert!(dir.join("2 - Two.flac").is_file());
+    assert!(dir.join("3 - Three.flac").is_file());
+
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+#[test]
+fn splits_generated_wavpack_with_tags_and_picture() {
+    let dir = unique_test_dir("generated-wavpack-split");
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+
+    let mut fixture = Fixture {
+        format: ImageFormat::WavPack,
+        tags: vec![("DATE".to_string(), "1999".to_string())],
+        picture: Some(TINY_PNG.to_vec()),
+        ..Fixture::default()
+    };
+    fixture.tracks[1].tone = Tone::Sine {
+        frequency: 440.0,
+        amplitude: 0.25,
+    };
+    fixture
+        .write(&dir, "album")
+        .expect("failed to generate fixture");
+
+    let output = run_split(&dir, &[]);
+    assert_success(&output);
+
+    let track = dir.join("2 - Two.flac");
+    let (total_samples, channels) = read_streaminfo(&track);
+    assert_eq!(total_samples, fixture.tracks[1].samples);
+    assert_eq!(channels, 2);
+    let tags = fs::read(&track).expect("failed to read track");
+    assert!(contains(&tags, b"DATE=1999"));
+    assert!(contains(&tags, b"image/png"));
+
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+#[test]
+fn plan_does_not_create_output_directories() {
+    let dir = unique_test_dir("generated-plan-no-dirs");
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+
+    Fixture::default()
+        .write(&dir, "album")
+        .expect("failed to generate fixture");
 
     let output = Command::new(env!("CARGO_BIN_EXE_flac-cue-split"))
         .current_dir(&dir)
-        .arg("-y")
-        .arg("--flac")
-        .arg("album.flac")
-        .arg("--cue")
-        .arg("album.cue")
+        .args(["plan", "--mirror-root", "mirror", "."])
         .output()
         .expect("failed to run flac-cue-split");
+    assert_success(&output);
+    assert!(!dir.join("mirror").exists());
 
-    assert!(
-        output.status.success(),
-        "split command failed\nstatus: {:?}\nstdout:\n{}\nstderr:\n{}",
-        output.status.code(),
-        String::from_utf8_lossy(&output.stdout),
-        String::from_utf8_lossy(&output.stderr)
-    );
-
-    assert!(dir.join("1 - One.flac").is_file());
-    assert!(dir.join("2 - Two.flac").is_file());
-    assert!(dir.join("3 - Three.flac").is_file());
+    let output = run_split(&dir, &["--mirror-root", "mirror"]);
+    assert_success(&output);
+    assert!(dir.join("mirror").join("1 - One.flac").is_file());
 
     fs::remove_dir_all(&dir).expect("failed to remove test directory");
 }
 
-fn write_cue(path: &Path, flac_name: &str) {
-    let cue = format!(
-        r#"PERFORMER "Test Artist"
-TITLE "Test Album"
-FILE "{}" WAVE
-  TRACK 01 AUDIO
-    TITLE "One"
-    INDEX 01 00:00:00
-  TRACK 02 AUDIO
-    TITLE "Two"
-    INDEX 01 00:01:00
-  TRACK 03 AUDIO
-    TITLE "Three"
-    INDEX 01 00:02:00
-"#,
-        flac_name
-    );
-    fs::write(path, cue).expect("failed to write cue file");
+#[test]
+fn saved_plan_creates_directories_only_when_executed() {
+    let dir = unique_test_dir("generated-saved-plan-dirs");
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+
+    Fixture::default()
+        .write(&dir, "album")
+        .expect("failed to generate fixture");
+
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_flac-cue-split"))
+            .current_dir(&dir)
+            .args(args)
+            .stdin(Stdio::null())
+            .output()
+            .expect("failed to run flac-cue-split")
+    };
+    assert_success(&run(&[
+        "plan",
+        "--mirror-root",
+        "mirror",
+        "--out",
+        "plan.json",
+        ".",
+    ]));
+    // Without confirmation nothing is split.
+    run(&["execute", "plan.json"]);
+    assert!(!dir.join("mirror").exists());
+
+    assert_success(&run(&["execute", "-y", "plan.json"]));
+    assert!(dir.join("mirror").join("2 - Two.flac").is_file());
+
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
 }
 
-fn write_silent_flac(path: &Path, sample_rate: u32, channels: u32, samples_per_channel: u32) -> Result<(), String> {
-    let encoder = unsafe { flac::FLAC__stream_encoder_new() };
-    if encoder.is_null() {
-        return Err("failed to allocate FLAC encoder".to_string());
+#[test]
+fn splits_generated_album_from_zip_archive() {
+    let dir = unique_test_dir("generated-zip-split");
+    let source = dir.join("source");
+    fs::create_dir_all(&source).expect("failed to create test directory");
+
+    let (image, cue) = Fixture::default()
+        .write(&source, "album")
+        .expect("failed to generate fixture");
+    let archive = File::create(dir.join("Album.zip")).expect("failed to create archive");
+    let mut zip = ZipWriter::new(archive);
+    for path in [&image, &cue] {
+        let name = format!("Album/{}", path.file_name().unwrap().to_string_lossy());
+        zip.start_file(name, SimpleFileOptions::default())
+            .expect("failed to add archive member");
+        zip.write_all(&fs::read(path).expect("failed to read fixture"))
+            .expect("failed to write archive member");
     }
+    zip.finish().expect("failed to finish archive");
 
-    let configured = unsafe {
-        flac::FLAC__stream_encoder_set_channels(encoder, channels) != 0
-            && flac::FLAC__stream_encoder_set_bits_per_sample(encoder, 16) != 0
-            && flac::FLAC__stream_encoder_set_sample_rate(encoder, sample_rate) != 0
-            && flac::FLAC__stream_encoder_set_compression_level(encoder, 5) != 0
-            && flac::FLAC__stream_encoder_set_total_samples_estimate(
-                encoder,
-                samples_per_channel as u64,
-            ) != 0
-    };
-    if !configured {
-        unsafe {
-            flac::FLAC__stream_encoder_delete(encoder);
-        }
-        return Err("failed to configure FLAC encoder".to_string());
+    let output = run_split(&dir, &["Album.zip"]);
+    assert_success(&output);
+
+    let tracks = dir.join("Album");
+    assert!(tracks.join("1 - One.flac").is_file());
+    assert!(tracks.join("3 - Three.flac").is_file());
+    assert!(!tracks.join("album.flac").exists());
+
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+#[test]
+fn resume_reencodes_only_truncated_tracks() {
+    let dir = unique_test_dir("generated-resume-truncated");
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+
+    let mut fixture = Fixture::default();
+    for track in &mut fixture.tracks {
+        track.tone = Tone::Sine {
+            frequency: 440.0,
+            amplitude: 0.25,
+        };
     }
+    fixture
+        .write(&dir, "album")
+        .expect("failed to generate fixture");
+    let args = ["--flac", "album.flac", "--cue", "album.cue"];
+    assert_success(&run_split(&dir, &args));
 
-    let path_c = CString::new(path.to_string_lossy().as_bytes())
-        .map_err(|_| format!("path contains NUL byte: {}", path.display()))?;
-    let init_status = unsafe {
-        flac::FLAC__stream_encoder_init_file(encoder, path_c.as_ptr(), None, std::ptr::null_mut())
-    };
-    if init_status != flac::FLAC__STREAM_ENCODER_INIT_STATUS_OK {
-        unsafe {
-            flac::FLAC__stream_encoder_delete(encoder);
-        }
-        return Err(format!(
-            "failed to init FLAC encoder for {}: status {}",
-            path.display(),
-            init_status
-        ));
+    let partial = dir.join("2 - Two.flac");
+    let encoded = fs::read(&partial).expect("failed to read track");
+    // Cutting off the last frames keeps STREAMINFO intact, so only a full
+    // decode notices the missing audio.
+    File::options()
+        .write(true)
+        .open(&partial)
+        .and_then(|file| file.set_len(encoded.len() as u64 * 3 / 4))
+        .expect("failed to truncate track");
+    let kept = [dir.join("1 - One.flac"), dir.join("3 - Three.flac")];
+    for path in &kept {
+        set_old_mtime(path);
     }
 
-    let interleaved_len = samples_per_channel as usize * channels as usize;
-    let interleaved = vec![0i32; interleaved_len];
-    let processed = unsafe {
-        flac::FLAC__stream_encoder_process_interleaved(
-            encoder,
-            interleaved.as_ptr(),
-            samples_per_channel,
-        )
-    };
+    assert_success(&run_split(&dir, &[&args[..], &["--resume"]].concat()));
 
-    if processed == 0 {
-        let state = unsafe { flac::FLAC__stream_encoder_get_state(encoder) };
-        let state_msg = unsafe {
-            let ptr = flac::FLAC__stream_encoder_get_resolved_state_string(encoder);
-            if ptr.is_null() {
-                "unknown".to_string()
-            } else {
-                CStr::from_ptr(ptr).to_string_lossy().into_owned()
-            }
-        };
-        unsafe {
-            flac::FLAC__stream_encoder_finish(encoder);
-            flac::FLAC__stream_encoder_delete(encoder);
-        }
-        return Err(format!(
-            "failed to write FLAC samples (state {}: {})",
-            state, state_msg
-        ));
+    assert_eq!(fs::read(&partial).expect("failed to read track"), encoded);
+    for path in &kept {
+        assert_eq!(mtime(path), old_mtime());
     }
 
-    let finished = unsafe { flac::FLAC__stream_encoder_finish(encoder) };
-    unsafe {
-        flac::FLAC__stream_encoder_delete(encoder);
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+#[test]
+fn update_skips_complete_albums_and_resplits_incomplete_ones() {
+    let dir = unique_test_dir("generated-update");
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+
+    Fixture::default()
+        .write(&dir, "album")
+        .expect("failed to generate fixture");
+    let args = ["--update", "--flac", "album.flac", "--cue", "album.cue"];
+    assert_success(&run_split(&dir, &args));
+
+    let first = dir.join("1 - One.flac");
+    set_old_mtime(&first);
+    assert_success(&run_split(&dir, &args));
+    assert_eq!(mtime(&first), old_mtime());
+    assert!(contains(
+        &fs::read(&first).expect("failed to read track"),
+        b"SOURCEMD5="
+    ));
+
+    fs::remove_file(dir.join("2 - Two.flac")).expect("failed to remove track");
+    assert_success(&run_split(&dir, &args));
+    assert!(dir.join("2 - Two.flac").is_file());
+    assert_ne!(mtime(&first), old_mtime());
+
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+#[test]
+fn audio_md5_tag_matches_streaminfo() {
+    let dir = unique_test_dir("generated-audio-md5");
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+
+    let mut fixture = Fixture::default();
+    fixture.tracks[0].tone = Tone::Constant(1000);
+    fixture
+        .write(&dir, "album")
+        .expect("failed to generate fixture");
+    assert_success(&run_split(&dir, &["--audio-md5"]));
+
+    for name in ["1 - One.flac", "2 - Two.flac"] {
+        let track = dir.join(name);
+        let md5: String = read_stream_info(&track)
+            .md5sum
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let tag = format!("AUDIOMD5={}", md5);
+        let data = fs::read(&track).expect("failed to read track");
+        assert!(contains(&data, tag.as_bytes()), "{} has no {}", name, tag);
     }
-    if finished == 0 {
-        return Err("failed to finish FLAC encoder".to_string());
+
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+#[test]
+fn splits_image_read_from_stdin() {
+    let dir = unique_test_dir("generated-stdin-split");
+    let source = dir.join("source");
+    fs::create_dir_all(&source).expect("failed to create test directory");
+
+    let fixture = Fixture::default();
+    let (image, cue) = fixture
+        .write(&source, "album")
+        .expect("failed to generate fixture");
+    fs::copy(&cue, dir.join("album.cue")).expect("failed to copy cue sheet");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_flac-cue-split"))
+        .current_dir(&dir)
+        .args(["--flac", "-", "--cue", "album.cue", "-y"])
+        .stdin(File::open(&image).expect("failed to open image"))
+        .output()
+        .expect("failed to run flac-cue-split");
+    assert_success(&output);
+
+    for (index, name) in ["1 - One.flac", "2 - Two.flac", "3 - Three.flac"]
+        .iter()
+        .enumerate()
+    {
+        let (total_samples, _) = read_streaminfo(&dir.join(name));
+        assert_eq!(total_samples, fixture.tracks[index].samples);
     }
 
-    Ok(())
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+fn run_split(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_flac-cue-split"))
+        .current_dir(dir)
+        .arg("-y")
+        .args(args)
+        .output()
+        .expect("failed to run flac-cue-split")
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "split command failed\nstatus: {:?}\nstdout:\n{}\nstderr:\n{}",
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+fn read_streaminfo(path: &Path) -> (u64, u32) {
+    let info = read_stream_info(path);
+    (info.total_samples, info.channels)
+}
+
+fn read_stream_info(path: &Path) -> flac::FLAC__StreamMetadata_StreamInfo {
+    let path_c = CString::new(path.to_string_lossy().as_bytes()).expect("path contains NUL byte");
+    let mut metadata: flac::FLAC__StreamMetadata = unsafe { std::mem::zeroed() };
+    let ok = unsafe { flac::FLAC__metadata_get_streaminfo(path_c.as_ptr(), &mut metadata) };
+    assert!(ok != 0, "failed to read STREAMINFO from {}", path.display());
+    unsafe { metadata.data.stream_info }
+}
+
+fn old_mtime() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_000_000_000)
+}
+
+fn set_old_mtime(path: &Path) {
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|file| file.set_modified(old_mtime()))
+        .expect("failed to set modification time");
+}
+
+fn mtime(path: &Path) -> SystemTime {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .expect("failed to read modification time")
 }
 
 fn unique_test_dir(label: &str) -> PathBuf {
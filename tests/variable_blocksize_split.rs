@@ -0,0 +1,195 @@
+use libflac_sys as flac;
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SAMPLE_RATE: u32 = 44_100;
+const TOTAL_SAMPLES: usize = 44_100 * 3;
+// libFLAC's encoder only writes fixed-blocksize streams, so the fixture is
+// assembled by hand from constant subframes of varying sizes.
+const BLOCK_SIZES: [usize; 6] = [4096, 1152, 576, 4608, 2304, 192];
+
+#[test]
+fn splits_variable_blocksize_flac_at_sample_positions() {
+    let dir = unique_test_dir("variable-blocksize-split");
+    fs::create_dir_all(&dir).expect("failed to create test directory");
+
+    let samples = fixture_samples();
+    fs::write(dir.join("album.flac"), variable_blocksize_flac(&samples))
+        .expect("failed to write FLAC fixture");
+    fs::write(
+        dir.join("album.cue"),
+        r#"PERFORMER "Test Artist"
+TITLE "Test Album"
+FILE "album.flac" WAVE
+  TRACK 01 AUDIO
+    TITLE "One"
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Two"
+    INDEX 01 00:01:00
+  TRACK 03 AUDIO
+    TITLE "Three"
+    INDEX 01 00:02:00
+"#,
+    )
+    .expect("failed to write cue file");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_flac-cue-split"))
+        .current_dir(&dir)
+        .arg("-y")
+        .arg("--flac")
+        .arg("album.flac")
+        .arg("--cue")
+        .arg("album.cue")
+        .output()
+        .expect("failed to run flac-cue-split");
+
+    assert!(
+        output.status.success(),
+        "split command failed\nstatus: {:?}\nstdout:\n{}\nstderr:\n{}",
+        output.status.code(),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let second = SAMPLE_RATE as usize;
+    for (index, name) in ["1 - One.flac", "2 - Two.flac", "3 - Three.flac"]
+        .iter()
+        .enumerate()
+    {
+        let expected = &samples[index * second..(index + 1) * second];
+        let (total_samples, md5) = read_streaminfo(&dir.join(name));
+        assert_eq!(total_samples, second as u64, "{}", name);
+        assert_eq!(md5, pcm_md5(expected), "{} has misplaced audio", name);
+    }
+
+    fs::remove_dir_all(&dir).expect("failed to remove test directory");
+}
+
+fn fixture_samples() -> Vec<i16> {
+    // Every frame holds a different constant, so audio taken from the wrong
+    // position changes the track checksums.
+    let mut samples = Vec::with_capacity(TOTAL_SAMPLES);
+    for (frame, size) in BLOCK_SIZES.iter().cycle().enumerate() {
+        if samples.len() >= TOTAL_SAMPLES {
+            break;
+        }
+        let count = (*size).min(TOTAL_SAMPLES - samples.len());
+        samples.extend(std::iter::repeat_n(frame as i16 * 7 - 300, count));
+    }
+    samples
+}
+
+fn variable_blocksize_flac(samples: &[i16]) -> Vec<u8> {
+    let mut out = b"fLaC".to_vec();
+    out.extend([0x80, 0, 0, 34]);
+    let min_block = *BLOCK_SIZES.iter().min().unwrap() as u16;
+    let max_block = *BLOCK_SIZES.iter().max().unwrap() as u16;
+    out.extend(min_block.to_be_bytes());
+    out.extend(max_block.to_be_bytes());
+    out.extend([0; 6]);
+    let packed = (u64::from(SAMPLE_RATE) << 44) | (15 << 36) | TOTAL_SAMPLES as u64;
+    out.extend(packed.to_be_bytes());
+    out.extend([0; 16]);
+
+    let mut position = 0;
+    for size in BLOCK_SIZES.iter().cycle() {
+        if position >= samples.len() {
+            break;
+        }
+        let count = (*size).min(samples.len() - position);
+        let frame_start = out.len();
+        out.extend([0xFF, 0xF9, 0x79, 0x08]);
+        out.extend(utf8_number(position as u64));
+        out.extend(((count - 1) as u16).to_be_bytes());
+        out.push(crc8(&out[frame_start..]));
+        out.push(0x00);
+        out.extend(samples[position].to_be_bytes());
+        out.extend(crc16(&out[frame_start..]).to_be_bytes());
+        position += count;
+    }
+    out
+}
+
+fn utf8_number(value: u64) -> Vec<u8> {
+    if value < 0x80 {
+        return vec![value as u8];
+    }
+    let continuation = match value {
+        0..0x800 => 1,
+        0x800..0x10000 => 2,
+        _ => 3,
+    };
+    let mut bytes = vec![0u8; continuation + 1];
+    let mut rest = value;
+    for byte in bytes[1..].iter_mut().rev() {
+        *byte = 0x80 | (rest & 0x3F) as u8;
+        rest >>= 6;
+    }
+    let marker = !(0xFFu8 >> (continuation + 1));
+    bytes[0] = marker | rest as u8;
+    bytes
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in data {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0u16;
+    for byte in data {
+        crc ^= u16::from(*byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+fn pcm_md5(samples: &[i16]) -> [u8; 16] {
+    let bytes: Vec<u8> = samples
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect();
+    md5::compute(bytes).0
+}
+
+fn read_streaminfo(path: &Path) -> (u64, [u8; 16]) {
+    let path_c = CString::new(path.to_string_lossy().as_bytes()).expect("path contains NUL byte");
+    let mut metadata: flac::FLAC__StreamMetadata = unsafe { std::mem::zeroed() };
+    let ok = unsafe { flac::FLAC__metadata_get_streaminfo(path_c.as_ptr(), &mut metadata) };
+    assert!(ok != 0, "failed to read STREAMINFO from {}", path.display());
+    let info = unsafe { metadata.data.stream_info };
+    (info.total_samples, info.md5sum)
+}
+
+fn unique_test_dir(label: &str) -> PathBuf {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time before unix epoch")
+        .as_nanos();
+    std::env::temp_dir().join(format!(
+        "flac-cue-split-{}-{}-{}",
+        label,
+        std::process::id(),
+        stamp
+    ))
+}